@@ -0,0 +1,62 @@
+//! Analyzing 20,000 messages with word statistics against
+//! `StatsSettings::skip_words`, which leaves messages unsplit.
+
+use std::hint::black_box;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use serde_json::{Value, json};
+use texport::{Chat, ChatStats, StatsSettings};
+
+const MESSAGES: u64 = 20_000;
+
+/// A chat of 7 participants whose messages are a dozen words each.
+fn chat() -> Chat {
+    let start = 1_704_067_200;
+    let messages: Vec<Value> = (1..=MESSAGES)
+        .map(|id| {
+            let user = id % 7;
+            let text = format!(
+                "Message {id} says hello to everyone here and asks how the day went, word{}",
+                id % 1_000
+            );
+            json!({
+                "id": id,
+                "type": "message",
+                "date": "2024-01-01T00:00:00",
+                "date_unixtime": (start + 60 * id).to_string(),
+                "from": format!("User {user}"),
+                "from_id": format!("user{user}"),
+                "text": text,
+                "text_entities": [{ "type": "plain", "text": text }],
+            })
+        })
+        .collect();
+    let chat = json!({
+        "name": "Benchmark",
+        "type": "private_group",
+        "id": 1,
+        "messages": messages,
+    });
+    serde_json::from_value(chat).unwrap()
+}
+
+fn analyze(c: &mut Criterion) {
+    let chat = chat();
+    let run = |skip_words| {
+        let mut stats = ChatStats::new(StatsSettings {
+            skip_words,
+            ..StatsSettings::default()
+        });
+        stats.analyze_ref(black_box(&chat.messages));
+        stats.messages
+    };
+
+    let mut group = c.benchmark_group("analyze");
+    group.sample_size(10);
+    group.bench_function("words", |b| b.iter(|| run(false)));
+    group.bench_function("skip_words", |b| b.iter(|| run(true)));
+    group.finish();
+}
+
+criterion_group!(benches, analyze);
+criterion_main!(benches);
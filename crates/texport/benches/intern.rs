@@ -0,0 +1,72 @@
+//! Word maps keyed by `String`, as before interning, against maps keyed by
+//! [`Word`]: the time to build them. The memory they hold is measured by
+//! `tests/intern.rs`.
+
+use std::{collections::HashMap, hint::black_box};
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use texport::Word;
+
+const USERS: usize = 200;
+const WORDS_PER_USER: usize = 5_000;
+const VOCABULARY: usize = 20_000;
+
+/// The words of each user, drawn from a shared vocabulary.
+fn corpus() -> Vec<Vec<String>> {
+    let vocabulary: Vec<_> = (0..VOCABULARY).map(|i| format!("word{i}")).collect();
+    // A fixed linear congruential generator, so that every run counts the
+    // same words.
+    let mut state = 1u64;
+    let mut next = move || {
+        state = state
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1);
+        (state >> 33) as usize
+    };
+    (0..USERS)
+        .map(|_| {
+            (0..WORDS_PER_USER)
+                .map(|_| vocabulary[next() % VOCABULARY].clone())
+                .collect()
+        })
+        .collect()
+}
+
+fn string_maps(corpus: &[Vec<String>]) -> Vec<HashMap<String, usize>> {
+    corpus
+        .iter()
+        .map(|words| {
+            let mut map = HashMap::new();
+            for word in words {
+                *map.entry(word.clone()).or_default() += 1;
+            }
+            map
+        })
+        .collect()
+}
+
+fn word_maps(corpus: &[Vec<String>]) -> Vec<HashMap<Word, usize>> {
+    corpus
+        .iter()
+        .map(|words| {
+            let mut map = HashMap::new();
+            for word in words {
+                *map.entry(Word::new(word)).or_default() += 1;
+            }
+            map
+        })
+        .collect()
+}
+
+fn intern(c: &mut Criterion) {
+    let corpus = corpus();
+
+    let mut group = c.benchmark_group("word maps");
+    group.sample_size(10);
+    group.bench_function("String", |b| b.iter(|| string_maps(black_box(&corpus))));
+    group.bench_function("Word", |b| b.iter(|| word_maps(black_box(&corpus))));
+    group.finish();
+}
+
+criterion_group!(benches, intern);
+criterion_main!(benches);
@@ -0,0 +1,79 @@
+//! Parsing a generated export of 50,000 messages with serde_json against
+//! simd-json, which `Chat::from_slice` uses with the `simd` feature.
+
+use std::hint::black_box;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use serde_json::{Value, json};
+use texport::Chat;
+
+const MESSAGES: u64 = 50_000;
+
+/// A `result.json` of a group chat, with replies, formatted text and
+/// reactions.
+fn export() -> Vec<u8> {
+    let start = 1_704_067_200;
+    let messages: Vec<Value> = (1..=MESSAGES)
+        .map(|id| {
+            let user = id % 7;
+            let text = format!("message number {id}, with a few more words to count");
+            let mut message = json!({
+                "id": id,
+                "type": "message",
+                "date": "2024-01-01T00:00:00",
+                "date_unixtime": (start + 60 * id).to_string(),
+                "from": format!("User {user}"),
+                "from_id": format!("user{user}"),
+                "text": [text.clone(), { "type": "bold", "text": "bold" }],
+                "text_entities": [
+                    { "type": "plain", "text": text },
+                    { "type": "bold", "text": "bold" },
+                ],
+            });
+            if id % 3 == 0 {
+                message["reply_to_message_id"] = json!(id - 1);
+            }
+            if id % 5 == 0 {
+                message["reactions"] = json!([{
+                    "type": "emoji",
+                    "count": 1,
+                    "emoji": "👍",
+                    "recent": [{
+                        "from": "User 1",
+                        "from_id": "user1",
+                        "date": "2024-01-01T00:00:00",
+                    }],
+                }]);
+            }
+            message
+        })
+        .collect();
+    let chat = json!({
+        "name": "Benchmark",
+        "type": "private_supergroup",
+        "id": 1,
+        "messages": messages,
+    });
+    serde_json::to_vec(&chat).unwrap()
+}
+
+fn parse(c: &mut Criterion) {
+    let bytes = export();
+
+    let mut group = c.benchmark_group("parse");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(bytes.len() as u64));
+    group.bench_function("serde_json", |b| {
+        b.iter(|| {
+            let chat: Chat = serde_json::from_slice(black_box(&bytes)).unwrap();
+            chat.messages.len()
+        })
+    });
+    group.bench_function("simd-json", |b| {
+        b.iter(|| Chat::from_slice(black_box(&bytes)).unwrap().messages.len())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -0,0 +1,40 @@
+//! Picking the 10 most frequent of 300,000 words with
+//! `UserStats::top_words`, which sorts only the words it returns, against
+//! sorting them all, as it did before.
+
+use std::hint::black_box;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use texport::{ChatStats, StatsSettings, Word};
+
+const WORDS: usize = 300_000;
+const TOP: usize = 10;
+
+fn top_n(c: &mut Criterion) {
+    let mut stats = ChatStats::new(StatsSettings::default());
+    let participant = stats.participants.entry("user1".to_string()).or_default();
+    for i in 0..WORDS {
+        // Many ties, as in real chats, where most words are used a few times.
+        participant
+            .words
+            .insert(Word::new(&format!("word{i}")), i * 7_919 % 1_000);
+    }
+
+    let mut group = c.benchmark_group("top 10 words");
+    group.sample_size(20);
+    group.bench_function("top_words", |b| {
+        b.iter(|| black_box(participant.top_words(TOP)).len())
+    });
+    group.bench_function("full sort", |b| {
+        b.iter(|| {
+            let mut words: Vec<_> = participant.words.iter().collect();
+            words.sort_unstable_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+            words.truncate(TOP);
+            black_box(words).len()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, top_n);
+criterion_main!(benches);
@@ -1,13 +1,40 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, time::SystemTime};
 
+use chrono::{DateTime, Utc};
 use clap::Parser;
-use texport::Storage;
+use texport::{ChatType, Storage};
 
 #[derive(Debug, Parser)]
 struct Cli {
     /// A directory containing Telegram chat exports
     #[arg(long, short)]
     input: Option<PathBuf>,
+
+    /// Only list chats of this type, e.g. `private_group`
+    #[arg(long)]
+    chat_type: Option<ChatType>,
+
+    /// Only list chats whose name contains this, ignoring case
+    #[arg(long)]
+    name: Option<String>,
+
+    /// Check that the media files of each chat are on disk
+    #[arg(long)]
+    verify_media: bool,
+
+    /// List how much disk space each chat takes instead, largest first
+    #[arg(long)]
+    disk_usage: bool,
+
+    /// Keep running and report chats as they are exported
+    #[cfg(feature = "watch")]
+    #[arg(long)]
+    watch: bool,
+}
+
+/// `bytes` in mebibytes.
+fn mib(bytes: u64) -> String {
+    format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
 }
 
 fn main() -> anyhow::Result<()> {
@@ -15,10 +42,69 @@ fn main() -> anyhow::Result<()> {
     let storage = cli
         .input
         .map(Storage::from_path)
-        .unwrap_or_else(|| Storage::new())?;
+        .unwrap_or_else(Storage::new)?;
+
+    if cli.disk_usage {
+        for usage in storage.disk_usage() {
+            println!("{} ({}): {}", usage.name, usage.id, mib(usage.total()));
+            println!("  result.json: {}", mib(usage.manifest));
+            for (dir, bytes) in &usage.media {
+                println!("  {dir}: {}", mib(*bytes));
+            }
+        }
+        return Ok(());
+    }
+
+    let name = cli.name.map(|name| name.to_lowercase());
+    let chats = storage.filter(|info| {
+        cli.chat_type.is_none_or(|t| info.chat_type == t)
+            && name
+                .as_ref()
+                .is_none_or(|name| info.name.to_lowercase().contains(name))
+    });
+    for chat in chats {
+        let summary = chat.summary()?;
+        let date = |time: Option<SystemTime>| {
+            time.map_or_else(
+                || "?".to_string(),
+                |time| DateTime::<Utc>::from(time).format("%Y-%m-%d").to_string(),
+            )
+        };
+        println!(
+            "{} → {} ({:?}): {} messages, {} – {}",
+            chat.info.id,
+            chat.info.name,
+            chat.info.chat_type,
+            summary.messages,
+            date(summary.first),
+            date(summary.last),
+        );
+
+        if cli.verify_media {
+            let report = chat.verify_media()?;
+            println!(
+                "  media: {} files, {} bytes, {} not included in the export",
+                report.present, report.bytes, report.not_included
+            );
+            for file in &report.missing {
+                println!("  missing: {}", file.display());
+            }
+            for file in &report.extra {
+                println!("  unreferenced: {}", file.display());
+            }
+        }
+    }
 
-    for (id, info) in storage.chats {
-        println!("{id} → {info:?}");
+    #[cfg(feature = "watch")]
+    if cli.watch {
+        storage.watch(|event| match event {
+            texport::StorageEvent::ChatAdded(chat) => {
+                println!("added: {} ({})", chat.info.name, chat.info.id);
+            }
+            texport::StorageEvent::ChatUpdated(chat) => {
+                println!("updated: {} ({})", chat.info.name, chat.info.id);
+            }
+        })?;
     }
 
     Ok(())
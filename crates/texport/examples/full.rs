@@ -1,8 +1,12 @@
-use std::{fs, path::PathBuf};
+use std::{collections::HashSet, io, path::PathBuf};
 
 use clap::Parser;
 
-use texport::{Chat, ChatStats, StatsSettings, Storage};
+use texport::{
+    CharCount, ChatFile, ChatStats, ChatType, DateBound, DateRange, DuplicatePolicy, EntityWords,
+    JsonDetail, Language, LoadError, RankBy, ScanOptions, Section, Sections, SenderNames,
+    StatsSettings, Storage, TimeZone,
+};
 
 #[derive(Debug, Parser)]
 struct Cli {
@@ -10,45 +14,433 @@ struct Cli {
     #[arg(long, short)]
     input: Option<PathBuf>,
 
+    /// How many directory levels below the input to search for exports
+    #[arg(long, default_value_t = 3)]
+    max_depth: usize,
+
+    /// What to do with a chat found in several directories: `keep_all`
+    /// (merge them), `keep_newest` or `error`
+    #[arg(long, default_value = "keep_all")]
+    duplicates: DuplicatePolicy,
+
+    /// Also search folders that symbolic links point to
+    #[arg(long)]
+    follow_symlinks: bool,
+
+    /// Fail instead of skipping chat exports that can't be read
+    #[arg(long)]
+    strict: bool,
+
+    /// Only analyze chats of this type, e.g. `private_group` or `personal_chat`
+    #[arg(long)]
+    chat_type: Option<ChatType>,
+
+    /// Only analyze the chat with this id
+    #[arg(long, conflicts_with_all = ["chat_type", "chat_name"])]
+    chat: Option<i64>,
+
+    /// Only analyze the chat whose name contains this, ignoring case
+    #[arg(long, conflicts_with = "chat_type")]
+    chat_name: Option<String>,
+
+    /// Reuse statistics cached next to each export by an earlier run with
+    /// the same settings, and cache them if there are none
+    #[arg(long)]
+    cache: bool,
+
+    /// Analyze each chat in chunks on all cores; faster for very large
+    /// chats, but reply chains and runs are cut where chunks meet
+    #[cfg(feature = "rayon")]
+    #[arg(long)]
+    chunked: bool,
+
     #[arg(long, short, default_value = "text")]
     output: OutputFormat,
 
+    /// Where to write the files of `--output csv`
+    #[arg(long, required_if_eq("output", "csv"))]
+    out_dir: Option<PathBuf>,
+
+    /// Also write SVG charts into this directory
+    #[cfg(feature = "charts")]
+    #[arg(long)]
+    charts_dir: Option<PathBuf>,
+
+    /// Start CSV files with a byte order mark so Excel reads them as UTF-8
+    #[arg(long)]
+    bom: bool,
+
+    /// How much of the word and reaction maps to include in JSON output:
+    /// `summary`, `top` or `full`
+    #[arg(long, default_value = "summary")]
+    json_detail: JsonDetail,
+
+    /// Leave the analysis settings out of JSON output
+    #[arg(long)]
+    omit_settings: bool,
+
+    /// Write JSON in the shape of the internal structures, as before the
+    /// versioned schema; to be removed in the next release
+    #[arg(long)]
+    legacy_json: bool,
+
     #[arg(long, short, default_value_t = 10)]
     max_words: usize,
 
     #[arg(long, short, default_value_t = 5)]
     participants: usize,
+
+    /// Width of the bar charts in text output (0 shows numbers only)
+    #[arg(long, default_value_t = 40)]
+    chart_width: usize,
+
+    /// Rank participants by `messages`, `characters`, `reactions` or `words`
+    #[arg(long, default_value = "messages")]
+    rank_by: RankBy,
+
+    /// Language of the text output, e.g. `en` or `ru`
+    #[arg(long, default_value = "en")]
+    language: Language,
+
+    /// Comma-separated sections to leave out of text output, e.g. `words,reactions`
+    #[arg(long, value_delimiter = ',')]
+    hide: Vec<Section>,
+
+    /// Comma-separated sections to add to text output, e.g. `entities`
+    #[arg(long, value_delimiter = ',')]
+    show: Vec<Section>,
+
+    /// How many distinctive words to show per participant (0 disables)
+    #[arg(long, default_value_t = 0)]
+    distinctive: usize,
+
+    /// How many common phrases to show per participant (0 disables)
+    #[arg(long, default_value_t = 0)]
+    max_phrases: usize,
+
+    /// Comma-separated language codes whose stop words are ignored (e.g. `en,ru`)
+    #[arg(long, value_delimiter = ',')]
+    stop_words: Vec<Language>,
+
+    /// A file with additional stop words, one per line
+    #[arg(long)]
+    stop_words_file: Option<PathBuf>,
+
+    /// Count lengths in `scalars` (Unicode code points) or `graphemes`
+    /// (characters as displayed, slower)
+    #[arg(long, default_value = "scalars")]
+    char_count: CharCount,
+
+    /// Don't split messages into words, for much faster statistics
+    /// without word counts, top words or phrases
+    #[arg(long)]
+    skip_words: bool,
+
+    /// Ignore words shorter than this many characters
+    #[arg(long, default_value_t = 0)]
+    min_word_length: usize,
+
+    /// Ignore numbers, links and @mentions in word statistics
+    #[arg(long)]
+    skip_noise: bool,
+
+    /// What to do with words from links, mentions, hashtags, commands and
+    /// code: `count`, `skip` or `separate`
+    #[arg(long, default_value = "count")]
+    entity_words: EntityWords,
+
+    /// Comma-separated language codes whose stemmers are applied to words
+    /// (requires the `stemming` feature)
+    #[arg(long, value_delimiter = ',')]
+    stem: Vec<Language>,
+
+    /// Report the languages each participant writes in
+    /// (requires the `lang-detect` feature)
+    #[arg(long)]
+    detect_languages: bool,
+
+    /// Filter each message with the stop words of its detected language
+    /// (requires the `lang-detect` feature)
+    #[arg(long)]
+    detected_stop_words: bool,
+
+    /// Break forum chats down by topic
+    #[arg(long)]
+    group_by_topic: bool,
+
+    /// Count how often each participant uses this word (repeatable)
+    #[arg(long)]
+    track: Vec<String>,
+
+    /// Count how often this word is used each month (repeatable)
+    #[arg(long)]
+    trend: Vec<String>,
+
+    /// Count matches of this regular expression per participant
+    /// (repeatable, requires the `regex` feature)
+    #[arg(long)]
+    track_pattern: Vec<String>,
+
+    /// Label a custom emoji reaction, e.g. `5312790123456789012=:party:` (repeatable)
+    #[arg(long, value_parser = parse_pair)]
+    emoji_label: Vec<(String, String)>,
+
+    /// Count one sender id as another, e.g. `user1=user2` (repeatable)
+    #[arg(long, value_parser = parse_pair)]
+    alias: Vec<(String, String)>,
+
+    /// Leave a sender out of the statistics, by name or id (repeatable)
+    #[arg(long)]
+    exclude: Vec<String>,
+
+    /// Leave out senders whose name or id ends in "bot"
+    #[arg(long)]
+    exclude_bots: bool,
+
+    /// Only analyze this sender, by display name ignoring case (repeatable)
+    #[arg(long = "user")]
+    users: Vec<String>,
+
+    /// Only analyze this sender, by id such as `user123456` (repeatable)
+    #[arg(long = "user-id")]
+    user_ids: Vec<String>,
+
+    /// List people who never posted but reacted at least this many times
+    #[arg(long, default_value_t = 1)]
+    min_lurker_reactions: u64,
+
+    /// Comma-separated message numbers to report the author of, e.g. the
+    /// 1000th message of each chat
+    #[arg(long, value_delimiter = ',', default_value = "1000,10000,100000")]
+    milestones: Vec<u64>,
+
+    /// Minutes of silence after which a message starts a new conversation
+    #[arg(long, default_value_t = 60)]
+    conversation_gap: u64,
+
+    /// Only analyze messages sent on or after this date (YYYY-MM-DD, in
+    /// --time-zone) or moment (RFC 3339)
+    #[arg(long)]
+    since: Option<DateBound>,
+
+    /// Only analyze messages sent on or before this date (YYYY-MM-DD, in
+    /// --time-zone) or before this moment (RFC 3339)
+    #[arg(long)]
+    until: Option<DateBound>,
+
+    /// Time zone for time-of-day statistics: `local`, `utc` or an offset like `+03:00`
+    #[arg(long, default_value = "local")]
+    time_zone: TimeZone,
+}
+
+/// The ids of the senders picked with `--user` and `--user-id`, telling
+/// whose names the names are from `known`.
+fn senders(
+    known: &SenderNames,
+    names: &[String],
+    ids: Vec<String>,
+) -> anyhow::Result<HashSet<String>> {
+    let mut senders: HashSet<_> = ids.into_iter().collect();
+    senders.extend(known.resolve(names.iter().map(String::as_str))?);
+    Ok(senders)
+}
+
+/// The names of the participants of `files`, read from their statistics
+/// cached with `settings` for every sender, so that a chat needn't be parsed
+/// just to look names up. Only senders of analyzed messages are known, so a
+/// name used outside `--since`/`--until` or by an excluded sender isn't.
+/// Participants are keyed by [`StatsSettings::aliases`] targets, so the ids
+/// aliased to each are recorded under its names too.
+fn cached_names(files: &[&ChatFile], settings: &StatsSettings) -> anyhow::Result<SenderNames> {
+    let settings = StatsSettings {
+        senders: HashSet::new(),
+        ..settings.clone()
+    };
+    let mut known = SenderNames::default();
+    for file in files {
+        let stats = ChatStats::load_cached(file, settings.clone())?;
+        for (id, user) in &stats.participants {
+            let aliased = settings.aliases.iter().filter(|(_, to)| *to == id);
+            let ids = aliased.map(|(from, _)| from).chain([id]);
+            for id in ids {
+                user.names
+                    .iter()
+                    .for_each(|name| known.add_sender(name, id));
+            }
+        }
+    }
+    Ok(known)
+}
+
+/// Load the chats under `root`, or in it if it is a zip archive.
+fn open(root: PathBuf, options: ScanOptions) -> Result<Storage, texport::OpenError> {
+    #[cfg(feature = "zip")]
+    if root.extension().is_some_and(|ext| ext == "zip") {
+        return Storage::from_zip_with(root, options);
+    }
+    // Progress is only reported by the sequential scan.
+    #[cfg(feature = "rayon")]
+    return Storage::from_path_with(root, options);
+    #[cfg(not(feature = "rayon"))]
+    Storage::from_path_with_progress(root, options, |event| {
+        if let texport::ScanEvent::Skipped(path, reason) = event {
+            eprintln!("skipping {}: {reason}", path.display());
+        }
+    })
+}
+
+fn parse_pair(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(from, to)| (from.trim().to_string(), to.trim().to_string()))
+        .ok_or_else(|| format!("expected KEY=VALUE, got `{s}`"))
 }
 
 #[derive(Debug, Clone, clap::ValueEnum)]
 enum OutputFormat {
     Text,
     Json,
+    Csv,
+    Html,
+    Markdown,
 }
 
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
-    let mut stats = ChatStats {
-        settings: StatsSettings {
-            max_words: cli.max_words,
-            max_participants: cli.participants,
-            ..Default::default()
-        },
+    let mut sections = Sections::default();
+    cli.show
+        .iter()
+        .for_each(|&section| sections.insert(section));
+    cli.hide
+        .iter()
+        .for_each(|&section| sections.remove(section));
+    let DateRange { since, until } = cli.time_zone.date_range(cli.since, cli.until)?;
+    let mut stats = ChatStats::new(StatsSettings {
+        max_words: cli.max_words,
+        max_participants: cli.participants,
+        chart_width: cli.chart_width,
+        sections,
+        language: cli.language,
+        json_detail: cli.json_detail,
+        rank_by: cli.rank_by,
+        max_phrases: cli.max_phrases,
+        max_distinctive_words: cli.distinctive,
+        stop_word_languages: cli.stop_words,
+        extra_stop_words: cli
+            .stop_words_file
+            .map(StatsSettings::read_stop_words)
+            .transpose()?
+            .unwrap_or_default(),
+        skip_words: cli.skip_words,
+        min_word_length: cli.min_word_length,
+        char_count: cli.char_count,
+        skip_numbers: cli.skip_noise,
+        skip_links: cli.skip_noise,
+        skip_mentions: cli.skip_noise,
+        entity_words: cli.entity_words,
+        stem_languages: cli.stem,
+        detect_languages: cli.detect_languages,
+        track_words: cli.track,
+        track_patterns: cli.track_pattern,
+        trend_words: cli.trend,
+        detected_stop_words: cli.detected_stop_words,
+        group_by_topic: cli.group_by_topic,
+        milestones: cli.milestones,
+        aliases: cli.alias.into_iter().collect(),
+        emoji_labels: cli.emoji_label.into_iter().collect(),
+        exclude: cli.exclude.into_iter().collect(),
+        exclude_bots: cli.exclude_bots,
+        min_lurker_reactions: cli.min_lurker_reactions,
+        time_zone: cli.time_zone,
+        conversation_gap_minutes: Some(cli.conversation_gap),
+        since,
+        until,
         ..Default::default()
+    });
+    let root = cli.input.map_or_else(Storage::default_root, Ok)?;
+    let options = ScanOptions {
+        max_depth: cli.max_depth,
+        duplicates: cli.duplicates,
+        follow_symlinks: cli.follow_symlinks,
+    };
+    let storage = open(root, options)?;
+    if cli.strict && !storage.errors.is_empty() {
+        return Err(texport::OpenError::Unreadable(storage.errors).into());
+    }
+    for (id, paths) in &storage.duplicates {
+        eprintln!("chat {id} was found in several places: {paths:?}");
+    }
+    let selected: Vec<_> = match (cli.chat, &cli.chat_name) {
+        (Some(id), _) => vec![storage.chat(id).ok_or(LoadError::UnknownChat(id))?],
+        (None, Some(name)) => vec![storage.find_by_name(name)?],
+        (None, None) => storage
+            .filter(|info| cli.chat_type.is_none_or(|t| info.chat_type == t))
+            .collect(),
     };
-    for input in Storage::new()?.chats.into_values().map(|v| v.path) {
-        let json_data = fs::read(input)?;
-        let chat: Chat = serde_json::from_slice(&json_data)?;
-        stats.analyze(chat.messages);
+    if cli.cache {
+        let known = if cli.users.is_empty() {
+            SenderNames::default()
+        } else {
+            cached_names(&selected, &stats.settings)?
+        };
+        stats.settings.senders = senders(&known, &cli.users, cli.user_ids)?;
+        for file in selected {
+            stats.merge(ChatStats::load_cached(file, stats.settings.clone())?);
+        }
+    } else {
+        // Names must be resolved before any chat is analyzed, so with
+        // `--user` the chats are kept once parsed rather than parsed twice.
+        let mut known = SenderNames::default();
+        let mut chats = Vec::new();
+        if !cli.users.is_empty() {
+            for file in &selected {
+                let chat = storage.load_chat(file.info.id)?;
+                chat.messages.iter().for_each(|message| known.add(message));
+                chats.push(chat);
+            }
+        }
+        stats.settings.senders = senders(&known, &cli.users, cli.user_ids)?;
+        let rest = selected[chats.len()..]
+            .iter()
+            .map(|file| storage.load_chat(file.info.id));
+        for chat in chats.into_iter().map(Ok).chain(rest) {
+            let chat = chat?;
+            #[cfg(feature = "rayon")]
+            if cli.chunked {
+                stats.analyze_parallel(&chat.name, &chat.messages);
+                continue;
+            }
+            stats.analyze_labeled(&chat.name, &chat.messages);
+        }
     }
 
-    println!(
-        "{}",
-        match cli.output {
-            OutputFormat::Text => stats.to_string(),
-            OutputFormat::Json => serde_json::to_string_pretty(&stats)?,
+    #[cfg(feature = "charts")]
+    if let Some(dir) = &cli.charts_dir {
+        for path in stats.render_charts(dir, texport::ChartOptions::default())? {
+            eprintln!("{}", path.display());
         }
-    );
+    }
+
+    match cli.output {
+        OutputFormat::Text => println!("{stats}"),
+        OutputFormat::Json if cli.legacy_json => {
+            println!("{}", serde_json::to_string_pretty(&stats)?)
+        }
+        OutputFormat::Json => {
+            let mut report = stats.to_report();
+            if cli.omit_settings {
+                report.analysis_settings = None;
+            }
+            println!("{}", serde_json::to_string_pretty(&report)?)
+        }
+        OutputFormat::Csv => {
+            let dir = cli.out_dir.expect("clap requires --out-dir for csv");
+            for path in stats.write_csv(dir, cli.bom)? {
+                println!("{}", path.display());
+            }
+        }
+        OutputFormat::Html => stats.write_html_report(&mut io::stdout().lock())?,
+        OutputFormat::Markdown => stats.write_markdown_report(&mut io::stdout().lock())?,
+    }
 
     Ok(())
 }
@@ -1,4 +1,4 @@
-use std::{fs, path::PathBuf};
+use std::path::PathBuf;
 
 use clap::Parser;
 
@@ -23,8 +23,7 @@ fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
     let mut stats = ChatStats::default();
     for input in cli.input {
-        let json_data = fs::read(input)?;
-        let chat: Chat = serde_json::from_slice(&json_data)?;
+        let chat = Chat::from_path(input)?;
         stats.analyze(chat.messages);
     }
 
@@ -32,7 +31,7 @@ fn main() -> anyhow::Result<()> {
         "{}",
         match cli.output {
             OutputFormat::Text => stats.to_string(),
-            OutputFormat::Json => serde_json::to_string_pretty(&stats)?,
+            OutputFormat::Json => serde_json::to_string_pretty(&stats.to_report())?,
         }
     );
 
@@ -1,8 +1,10 @@
-use std::{fs, path::PathBuf};
+use std::{collections::HashSet, fs, io, path::PathBuf};
 
 use clap::Parser;
 
-use texport::Chat;
+use texport::{
+    ChatReader, DateBound, DateRange, ExportOptions, ExportWriter, SenderNames, TimeZone,
+};
 
 #[derive(Debug, Parser)]
 struct Cli {
@@ -14,18 +16,69 @@ struct Cli {
 
     #[arg(long, short)]
     output: Option<PathBuf>,
+
+    /// Time zone of the dates written: `local`, `utc` or an offset like `+03:00`
+    #[arg(long, default_value = "local")]
+    time_zone: TimeZone,
+
+    /// Format of the dates written, e.g. `%d.%m.%Y %H:%M`
+    #[arg(long, default_value = "%Y-%m-%d %H:%M:%S")]
+    date_format: String,
+
+    /// Write every message on one line
+    #[arg(long)]
+    single_line: bool,
+
+    /// Only write messages sent on or after this date (YYYY-MM-DD, in
+    /// --time-zone) or moment (RFC 3339)
+    #[arg(long)]
+    since: Option<DateBound>,
+
+    /// Only write messages sent on or before this date (YYYY-MM-DD, in
+    /// --time-zone) or before this moment (RFC 3339)
+    #[arg(long)]
+    until: Option<DateBound>,
+
+    /// Only write messages by this sender, by display name ignoring case
+    /// (repeatable)
+    #[arg(long = "user")]
+    users: Vec<String>,
+
+    /// Only write messages by this sender, by id such as `user123456`
+    /// (repeatable)
+    #[arg(long = "user-id")]
+    user_ids: Vec<String>,
 }
 
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
-    let json_data = fs::read(cli.input)?;
-    let export: Chat = serde_json::from_slice(&json_data)?;
-
-    if let Some(out) = cli.output {
-        let mut file = fs::File::create(out)?;
-        export.write_export(&mut file, cli.max)?;
-    } else {
-        export.write_export(&mut std::io::stdout(), cli.max)?;
+    let DateRange { since, until } = cli.time_zone.date_range(cli.since, cli.until)?;
+    let mut senders: HashSet<_> = cli.user_ids.into_iter().collect();
+    if !cli.users.is_empty() {
+        // Names are only told apart after reading the whole export.
+        let mut known = SenderNames::default();
+        for message in ChatReader::open(&cli.input)? {
+            known.add(&message?);
+        }
+        senders.extend(known.resolve(cli.users.iter().map(String::as_str))?);
+    }
+    // Read one message at a time, so that huge exports fit in memory.
+    let messages = ChatReader::open(cli.input)?.take(cli.max.unwrap_or(usize::MAX));
+
+    let out: Box<dyn io::Write> = match cli.output {
+        Some(out) => Box::new(fs::File::create(out)?),
+        None => Box::new(io::stdout().lock()),
+    };
+    let mut export = ExportWriter::new(out).with_options(ExportOptions {
+        time_zone: cli.time_zone,
+        date_format: cli.date_format,
+        single_line: cli.single_line,
+        since,
+        until,
+        senders,
+    });
+    for message in messages {
+        export.write_message(&message?)?;
     }
 
     Ok(())
@@ -0,0 +1,33 @@
+use std::{path::PathBuf, process::ExitCode};
+
+use clap::Parser;
+
+use texport::Chat;
+
+#[derive(Debug, Parser)]
+struct Cli {
+    #[arg(long, short)]
+    input: PathBuf,
+
+    /// Print the anomalies as JSON.
+    #[arg(long)]
+    json: bool,
+}
+
+fn main() -> anyhow::Result<ExitCode> {
+    let cli = Cli::parse();
+    let chat = Chat::from_path(cli.input)?;
+    let anomalies = chat.validate();
+    if cli.json {
+        println!("{}", serde_json::to_string_pretty(&anomalies)?);
+    } else {
+        for anomaly in &anomalies {
+            println!("{anomaly}");
+        }
+    }
+    Ok(if anomalies.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
@@ -0,0 +1,180 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufReader, Read, Seek},
+    path::{Component, Path, PathBuf},
+};
+
+use log::warn;
+use zip::ZipArchive;
+
+use crate::{
+    Chat,
+    storage::{
+        ChatFile, ExportSummary, HTML_FILE, LoadError, OpenError, RESULT_FILE, ScanOptions, Source,
+        Storage, summarize,
+    },
+};
+
+impl Storage {
+    /// Load all chats found in the zip archive at `path`, with the default
+    /// [`ScanOptions`].
+    pub fn from_zip(path: impl AsRef<Path>) -> Result<Self, OpenError> {
+        Self::from_zip_with(path, ScanOptions::default())
+    }
+
+    /// Load all chats found in the zip archive at `path`, without
+    /// extracting it. The archive is searched as a directory is by
+    /// [`Storage::from_path_with`], and a `result.json` that can't be read
+    /// is skipped and recorded in [`Storage::errors`] the same way.
+    ///
+    /// The [`ChatFile::paths`] of the chats are the path of the archive
+    /// followed by the name of the entry, e.g.
+    /// `exports.zip/ChatExport_A/result.json`. Media are not looked at:
+    /// [`ChatFile::verify_media`] fails, and [`Storage::disk_usage`]
+    /// leaves them out. Under [`DuplicatePolicy::KeepNewest`] the last
+    /// export in name order is kept.
+    ///
+    /// [`DuplicatePolicy::KeepNewest`]: crate::DuplicatePolicy::KeepNewest
+    pub fn from_zip_with(path: impl AsRef<Path>, options: ScanOptions) -> Result<Self, OpenError> {
+        let path = path.as_ref();
+        let mut archive = open(path).map_err(|source| OpenError::Archive {
+            path: path.into(),
+            source,
+        })?;
+
+        let mut found = Vec::new();
+        let mut errors = Vec::new();
+        for (dir, is_chat) in chat_dirs(&archive, options.max_depth) {
+            let result = if is_chat {
+                let name = entry_name(&dir.join(RESULT_FILE));
+                let manifest = path.join(&name);
+                read_entry(&mut archive, &name, &manifest)
+                    .and_then(|bytes| ChatFile::from_manifest(manifest, &bytes))
+            } else {
+                Err(LoadError::Html {
+                    path: path.join(dir),
+                })
+            };
+            match result {
+                Ok(mut file) => {
+                    file.source = Source::Zip(path.into());
+                    found.push((file.info.id, file));
+                }
+                Err(e) => {
+                    warn!("skipping: {e}");
+                    errors.push(e);
+                }
+            }
+        }
+        Self::from_found(path, found, errors, options)
+    }
+}
+
+impl Chat {
+    /// Parse the `result.json` named `name` in `archive`, e.g.
+    /// `ChatExport_A/result.json`.
+    pub fn from_zip_entry<R: Read + Seek>(
+        archive: &mut ZipArchive<R>,
+        name: &str,
+    ) -> Result<Self, LoadError> {
+        let path = Path::new(name);
+        Chat::from_slice(&read_entry(archive, name, path)?).map_err(|e| e.with_path(path))
+    }
+}
+
+/// Parse the `result.json` at `path`, inside the zip archive at `archive`.
+pub(crate) fn read_chat(archive: &Path, path: &Path) -> Result<Chat, LoadError> {
+    let name = archived_name(archive, path);
+    let mut zip = open(archive).map_err(|source| LoadError::Zip {
+        path: path.into(),
+        source,
+    })?;
+    Chat::from_slice(&read_entry(&mut zip, &name, path)?).map_err(|e| e.with_path(path))
+}
+
+/// Summarize the `result.json` at `path`, inside the zip archive at
+/// `archive`, see [`ChatFile::summary`].
+pub(crate) fn summarize_entry(archive: &Path, path: &Path) -> Result<ExportSummary, LoadError> {
+    let zip_error = |source| LoadError::Zip {
+        path: path.into(),
+        source,
+    };
+    let mut zip = open(archive).map_err(zip_error)?;
+    let entry = zip
+        .by_name(&archived_name(archive, path))
+        .map_err(zip_error)?;
+    summarize(BufReader::new(entry), path)
+}
+
+fn open(path: &Path) -> zip::result::ZipResult<ZipArchive<BufReader<File>>> {
+    ZipArchive::new(BufReader::new(File::open(path)?))
+}
+
+/// The directories in `archive` that hold a chat, with `true`, or an HTML
+/// export, with `false`, in name order. As on disk, hidden directories,
+/// directories more than `max_depth` levels deep and the subdirectories of
+/// exports are left out.
+fn chat_dirs<R: Read + Seek>(archive: &ZipArchive<R>, max_depth: usize) -> BTreeMap<PathBuf, bool> {
+    let mut dirs = BTreeMap::new();
+    for name in archive.file_names() {
+        let path = Path::new(name);
+        let Some(dir) = path.parent() else {
+            continue;
+        };
+        let is_chat = match path.file_name() {
+            Some(file) if file == RESULT_FILE => true,
+            Some(file) if file == HTML_FILE => false,
+            _ => continue,
+        };
+        let hidden = dir
+            .components()
+            .any(|c| matches!(c, Component::Normal(c) if c.to_string_lossy().starts_with('.')));
+        if hidden || dir.components().count() > max_depth {
+            continue;
+        }
+        // A `result.json` wins over a `messages.html` next to it.
+        *dirs.entry(dir.to_path_buf()).or_default() |= is_chat;
+    }
+    let exports: Vec<_> = dirs.keys().cloned().collect();
+    dirs.retain(|dir, _| {
+        !exports
+            .iter()
+            .any(|export| export != dir && dir.starts_with(export))
+    });
+    dirs
+}
+
+/// The name of the entry at `path`, the path of an archive followed by
+/// the name of one of its entries.
+fn archived_name(archive: &Path, path: &Path) -> String {
+    entry_name(path.strip_prefix(archive).unwrap_or(path))
+}
+
+/// The name of the entry at `path` in an archive, which uses `/` whatever
+/// the platform.
+fn entry_name(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn read_entry<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    name: &str,
+    path: &Path,
+) -> Result<Vec<u8>, LoadError> {
+    let mut entry = archive.by_name(name).map_err(|source| LoadError::Zip {
+        path: path.into(),
+        source,
+    })?;
+    let mut bytes = Vec::new();
+    entry
+        .read_to_end(&mut bytes)
+        .map_err(|source| LoadError::Io {
+            path: path.into(),
+            source,
+        })?;
+    Ok(bytes)
+}
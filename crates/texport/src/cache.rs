@@ -0,0 +1,402 @@
+use std::{
+    collections::{BTreeMap, HashMap, hash_map::DefaultHasher},
+    hash::{Hash, Hasher},
+    io, mem,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use chrono::{Datelike, Local, NaiveDate, Offset, TimeZone as _, Utc};
+use fs_err as fs;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ChatStats, StatsMap, StatsSet, StatsSettings, TimeZone, Word,
+    stats::{
+        ChatSummary, DayActivity, IdGaps, Membership, Milestone, Pin, PushState, Reactor,
+        TitleChange, TopicStats, UserStats,
+    },
+    storage::{ChatFile, LoadError},
+};
+
+/// Directory next to `result.json` that cached statistics are kept in.
+const CACHE_DIR: &str = ".tg-export-cache";
+
+/// Bumped whenever the cached format changes, or what the statistics mean.
+const CACHE_VERSION: u32 = 10;
+
+impl ChatStats {
+    /// Analyze the chat of `chat_file` as [`ChatStats::analyze_labeled`]
+    /// does, labeled with its name, reusing the statistics cached by an
+    /// earlier call if neither its `result.json` nor `settings` have changed
+    /// since. Add the result to a running total with [`ChatStats::merge`].
+    ///
+    /// The cache is kept in a `.tg-export-cache` directory next to the first
+    /// `result.json`, keyed by the size and modification time of every
+    /// part and by the settings that change the statistics: those that only
+    /// change how they are shown, such as [`StatsSettings::max_words`] or
+    /// [`StatsSettings::language`], reuse the same entry. Entries that are
+    /// missing, corrupt or from another version are recomputed and
+    /// rewritten; failing to write one is only a warning.
+    pub fn load_cached(chat_file: &ChatFile, settings: StatsSettings) -> Result<Self, LoadError> {
+        let key = CacheKey::new(chat_file, &settings);
+        let cache = key.as_ref().map(|key| key.path(chat_file));
+        if let (Some(key), Some(cache)) = (&key, &cache) {
+            if let Some(cached) = read_cache(cache, key) {
+                return Ok(cached.into_stats(settings));
+            }
+        }
+
+        let chat = chat_file.load()?;
+        let mut stats = ChatStats::new(settings);
+        stats.analyze_labeled(&chat.name, &chat.messages);
+        let (Some(key), Some(cache)) = (key, cache) else {
+            return Ok(stats);
+        };
+        let (cached, settings) = CachedStats::from_stats(stats);
+        let entry = CacheEntry { key, stats: cached };
+        if let Err(e) = write_cache(&cache, &entry) {
+            warn!("couldn't cache statistics in {cache:?}: {e}");
+        }
+        Ok(entry.stats.into_stats(settings))
+    }
+}
+
+/// What cached statistics were computed from.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct CacheKey {
+    version: u32,
+    crate_version: String,
+    /// Size and modification time (seconds and nanoseconds since the Unix
+    /// epoch) of every `result.json` of the chat.
+    manifests: Vec<(u64, u64, u32)>,
+    /// The settings that change the statistics, as JSON.
+    settings: String,
+    /// The offsets from UTC, in seconds, of [`TimeZone::Local`] in January
+    /// and July, as the settings only name it.
+    local_offsets: Option<(i32, i32)>,
+}
+
+impl CacheKey {
+    /// The key of `chat_file` analyzed with `settings`, or `None` if its
+    /// files can't be looked at, in which case nothing is cached.
+    fn new(chat_file: &ChatFile, settings: &StatsSettings) -> Option<Self> {
+        let manifests = chat_file
+            .paths
+            .iter()
+            .map(|path| {
+                let metadata = fs::metadata(path).ok()?;
+                let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+                Some((metadata.len(), modified.as_secs(), modified.subsec_nanos()))
+            })
+            .collect::<Option<_>>()?;
+        Some(Self {
+            version: CACHE_VERSION,
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            manifests,
+            settings: serde_json::to_string(&analysis_settings(settings)).ok()?,
+            local_offsets: (settings.time_zone == TimeZone::Local).then(local_offsets),
+        })
+    }
+
+    /// Where statistics with this key are cached: one file per settings,
+    /// next to the first `result.json`.
+    fn path(&self, chat_file: &ChatFile) -> PathBuf {
+        let mut hasher = DefaultHasher::new();
+        self.settings.hash(&mut hasher);
+        self.local_offsets.hash(&mut hasher);
+        let dir = chat_file.paths[0].parent().unwrap_or(Path::new(""));
+        dir.join(CACHE_DIR)
+            .join(format!("stats-{:016x}.json", hasher.finish()))
+    }
+}
+
+/// `settings` with those that only change how statistics are shown left at
+/// their defaults.
+fn analysis_settings(settings: &StatsSettings) -> StatsSettings {
+    let defaults = StatsSettings::default();
+    StatsSettings {
+        max_words: defaults.max_words,
+        sections: defaults.sections,
+        language: defaults.language,
+        max_participants: defaults.max_participants,
+        chart_width: defaults.chart_width,
+        json_detail: defaults.json_detail,
+        rank_by: defaults.rank_by,
+        min_lurker_reactions: defaults.min_lurker_reactions,
+        emoji_labels: defaults.emoji_labels,
+        max_distinctive_words: defaults.max_distinctive_words,
+        // Phrases are only counted when some are shown.
+        max_phrases: settings.max_phrases.min(1),
+        ..settings.clone()
+    }
+}
+
+/// The offsets from UTC, in seconds, of the machine's time zone at the
+/// start of January and of July this year, which differ where it observes
+/// daylight saving time.
+fn local_offsets() -> (i32, i32) {
+    let offset = |month| {
+        let start = NaiveDate::from_ymd_opt(Utc::now().year(), month, 1).unwrap_or_default();
+        let start = start.and_hms_opt(0, 0, 0).unwrap_or_default();
+        Local
+            .offset_from_utc_datetime(&start)
+            .fix()
+            .local_minus_utc()
+    };
+    (offset(1), offset(7))
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    key: CacheKey,
+    stats: CachedStats,
+}
+
+/// The cached statistics at `path`, if they were computed for `key`.
+fn read_cache(path: &Path, key: &CacheKey) -> Option<CachedStats> {
+    /// Just the key of an entry, to tell a stale entry from a corrupt one
+    /// without parsing the rest.
+    #[derive(Deserialize)]
+    struct KeyOnly {
+        key: CacheKey,
+    }
+
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            warn!("ignoring cached statistics in {path:?}: {e}");
+            return None;
+        }
+    };
+    let stale = serde_json::from_slice::<KeyOnly>(&bytes).is_ok_and(|entry| entry.key != *key);
+    if stale {
+        return None;
+    }
+    serde_json::from_slice::<CacheEntry>(&bytes)
+        .inspect_err(|e| warn!("ignoring cached statistics in {path:?}: {e}"))
+        .ok()
+        .map(|entry| entry.stats)
+}
+
+fn write_cache(path: &Path, entry: &CacheEntry) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_vec(entry)?)
+}
+
+/// Everything [`ChatStats::merge`] reads, without the settings.
+#[derive(Serialize, Deserialize)]
+struct CachedStats {
+    messages: u64,
+    service_messages: u64,
+    service_actions: StatsMap<String, u64>,
+    edited: u64,
+    excluded: u64,
+    duplicate_ids: u64,
+    undated: u64,
+    messages_in_runs: u64,
+    participants: HashMap<String, CachedUser>,
+    text_entity_types: StatsMap<String, u64>,
+    longest_chain: String,
+    longest_chain_length: usize,
+    chats: BTreeMap<String, ChatSummary>,
+    weekday_hours: [[u64; 24]; 7],
+    timeline: BTreeMap<String, u64>,
+    membership: Membership,
+    forward_sources: StatsMap<String, u64>,
+    id_gaps: IdGaps,
+    pins: Vec<Pin>,
+    title_history: Vec<TitleChange>,
+    tracked: StatsMap<String, StatsMap<String, u64>>,
+    mentions: StatsMap<String, StatsMap<String, u64>>,
+    trends: StatsMap<String, BTreeMap<String, u64>>,
+    reactors: StatsMap<String, Reactor>,
+    milestones: Vec<Milestone>,
+    topics: HashMap<String, CachedTopic>,
+    days: BTreeMap<NaiveDate, DayActivity>,
+}
+
+impl CachedStats {
+    fn from_stats(stats: ChatStats) -> (Self, StatsSettings) {
+        let ChatStats {
+            messages,
+            service_messages,
+            service_actions,
+            edited,
+            excluded,
+            duplicate_ids,
+            undated,
+            messages_in_runs,
+            participants,
+            text_entity_types,
+            settings,
+            longest_chain,
+            chats,
+            weekday_hours,
+            timeline,
+            membership,
+            forward_sources,
+            id_gaps,
+            pins,
+            title_history,
+            tracked,
+            mentions,
+            trends,
+            reactors,
+            milestones,
+            topics,
+            state,
+        } = stats;
+        let cached = Self {
+            messages,
+            service_messages,
+            service_actions,
+            edited,
+            excluded,
+            duplicate_ids,
+            undated,
+            messages_in_runs,
+            participants: participants
+                .into_iter()
+                .map(|(id, user)| (id, CachedUser::from(user)))
+                .collect(),
+            text_entity_types,
+            longest_chain,
+            longest_chain_length: state.longest_chain,
+            chats,
+            weekday_hours,
+            timeline,
+            membership,
+            forward_sources,
+            id_gaps,
+            pins,
+            title_history,
+            tracked,
+            mentions,
+            trends,
+            reactors,
+            milestones,
+            topics: topics
+                .into_iter()
+                .map(|(title, topic)| (title, CachedTopic::from(topic)))
+                .collect(),
+            days: state.days,
+        };
+        (cached, settings)
+    }
+
+    fn into_stats(self, settings: StatsSettings) -> ChatStats {
+        let mut state = PushState::default();
+        state.longest_chain = self.longest_chain_length;
+        state.days = self.days;
+        ChatStats {
+            messages: self.messages,
+            service_messages: self.service_messages,
+            service_actions: self.service_actions,
+            edited: self.edited,
+            excluded: self.excluded,
+            duplicate_ids: self.duplicate_ids,
+            undated: self.undated,
+            messages_in_runs: self.messages_in_runs,
+            participants: self
+                .participants
+                .into_iter()
+                .map(|(id, user)| (id, user.into()))
+                .collect(),
+            text_entity_types: self.text_entity_types,
+            settings,
+            longest_chain: self.longest_chain,
+            chats: self.chats,
+            weekday_hours: self.weekday_hours,
+            timeline: self.timeline,
+            membership: self.membership,
+            forward_sources: self.forward_sources,
+            id_gaps: self.id_gaps,
+            pins: self.pins,
+            title_history: self.title_history,
+            tracked: self.tracked,
+            mentions: self.mentions,
+            trends: self.trends,
+            reactors: self.reactors,
+            milestones: self.milestones,
+            topics: self
+                .topics
+                .into_iter()
+                .map(|(title, topic)| (title, topic.into()))
+                .collect(),
+            state,
+        }
+    }
+}
+
+/// A [`UserStats`] with the fields its JSON output leaves out.
+#[derive(Serialize, Deserialize)]
+struct CachedUser {
+    stats: UserStats,
+    gaps: BTreeMap<u32, u64>,
+    words: StatsMap<Word, usize>,
+    received_reactions: StatsMap<String, usize>,
+    surface_forms: StatsMap<Word, StatsMap<Word, usize>>,
+    entity_words: StatsMap<Word, usize>,
+    phrases: StatsMap<String, usize>,
+    vocabulary: StatsSet<Word>,
+}
+
+impl From<UserStats> for CachedUser {
+    fn from(mut stats: UserStats) -> Self {
+        Self {
+            gaps: mem::take(&mut stats.gaps),
+            words: mem::take(&mut stats.words),
+            received_reactions: mem::take(&mut stats.received_reactions),
+            surface_forms: mem::take(&mut stats.surface_forms),
+            entity_words: mem::take(&mut stats.entity_words),
+            phrases: mem::take(&mut stats.phrases),
+            vocabulary: mem::take(&mut stats.vocabulary),
+            stats,
+        }
+    }
+}
+
+impl From<CachedUser> for UserStats {
+    fn from(cached: CachedUser) -> Self {
+        UserStats {
+            gaps: cached.gaps,
+            words: cached.words,
+            received_reactions: cached.received_reactions,
+            surface_forms: cached.surface_forms,
+            entity_words: cached.entity_words,
+            phrases: cached.phrases,
+            vocabulary: cached.vocabulary,
+            ..cached.stats
+        }
+    }
+}
+
+/// A [`TopicStats`] with the fields its JSON output leaves out.
+#[derive(Serialize, Deserialize)]
+struct CachedTopic {
+    stats: TopicStats,
+    words: StatsMap<Word, usize>,
+}
+
+impl From<TopicStats> for CachedTopic {
+    fn from(mut stats: TopicStats) -> Self {
+        Self {
+            words: mem::take(&mut stats.words),
+            stats,
+        }
+    }
+}
+
+impl From<CachedTopic> for TopicStats {
+    fn from(cached: CachedTopic) -> Self {
+        TopicStats {
+            words: cached.words,
+            ..cached.stats
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use fs_err as fs;
+use plotters::{coord::Shift, prelude::*};
+
+use crate::ChatStats;
+
+/// Size of the charts written by [`ChatStats::render_charts`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChartOptions {
+    /// Width of each chart, in pixels.
+    pub width: u32,
+    /// Height of each chart, in pixels.
+    pub height: u32,
+}
+
+impl Default for ChartOptions {
+    fn default() -> Self {
+        Self {
+            width: 800,
+            height: 480,
+        }
+    }
+}
+
+impl ChatStats {
+    /// Write SVG bar charts into `dir`, creating it if needed, and return
+    /// the paths written:
+    ///
+    /// - `timeline.svg`: messages per month,
+    /// - `hours.svg`: messages per hour of day,
+    /// - `participants.svg`: messages of the most active participants.
+    ///
+    /// Charts without any data are skipped.
+    pub fn render_charts(&self, dir: &Path, options: ChartOptions) -> io::Result<Vec<PathBuf>> {
+        fs::create_dir_all(dir)?;
+        let mut paths = Vec::new();
+        let mut render = |name: &str, caption: &str, bars: &[(String, u64)]| -> io::Result<()> {
+            if bars.iter().all(|(_, count)| *count == 0) {
+                return Ok(());
+            }
+            let path = dir.join(name);
+            {
+                let root =
+                    SVGBackend::new(&path, (options.width, options.height)).into_drawing_area();
+                draw_bars(&root, caption, bars).map_err(io::Error::other)?;
+            }
+            paths.push(path);
+            Ok(())
+        };
+
+        let months: Vec<_> = self
+            .timeline
+            .iter()
+            .map(|(month, &count)| (month.clone(), count))
+            .collect();
+        render("timeline.svg", "Messages per month", &months)?;
+
+        let hours: Vec<_> = self
+            .hour_totals()
+            .into_iter()
+            .enumerate()
+            .map(|(hour, count)| (hour.to_string(), count))
+            .collect();
+        render("hours.svg", "Messages by hour of day", &hours)?;
+
+        let participants: Vec<_> = self
+            .top_by_messages()
+            .into_iter()
+            .map(|(name, count)| (name.to_string(), count))
+            .collect();
+        render("participants.svg", "Messages by participant", &participants)?;
+
+        Ok(paths)
+    }
+}
+
+/// Draw `bars` as a vertical bar chart labeled with their names.
+fn draw_bars<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    caption: &str,
+    bars: &[(String, u64)],
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    let max = bars.iter().map(|(_, count)| *count).max().unwrap_or(0);
+
+    area.fill(&WHITE)?;
+    let mut chart = ChartBuilder::on(area)
+        .caption(caption, ("sans-serif", 20))
+        .margin(10)
+        .x_label_area_size(30)
+        .y_label_area_size(50)
+        .build_cartesian_2d((0..bars.len()).into_segmented(), 0..max + max / 10 + 1)?;
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_labels(bars.len())
+        .x_label_formatter(&|value| match value {
+            SegmentValue::CenterOf(i) => bars.get(*i).map_or_else(String::new, |(l, _)| l.clone()),
+            _ => String::new(),
+        })
+        .draw()?;
+    chart.draw_series(
+        Histogram::vertical(&chart)
+            .style(BLUE.filled())
+            .margin(4)
+            .data(bars.iter().enumerate().map(|(i, (_, count))| (i, *count))),
+    )?;
+    area.present()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        StatsSettings,
+        testing::{analyze, message},
+    };
+
+    /// The bars of `svg`, which are the only shapes filled in blue.
+    fn bars(svg: &str) -> usize {
+        svg.matches(r##"fill="#0000FF""##).count()
+    }
+
+    #[test]
+    fn draws_a_bar_per_entry_at_the_given_size() {
+        let entries = [
+            ("a".to_string(), 3),
+            ("b".to_string(), 0),
+            ("c".to_string(), 1),
+        ];
+        let mut svg = String::new();
+        {
+            let root = SVGBackend::with_string(&mut svg, (400, 300)).into_drawing_area();
+            draw_bars(&root, "Test", &entries).unwrap();
+        }
+        assert!(svg.contains(r#"width="400" height="300""#), "{svg}");
+        assert_eq!(bars(&svg), 3);
+    }
+
+    #[test]
+    fn renders_charts_with_data() {
+        let settings = StatsSettings {
+            max_participants: 10,
+            ..StatsSettings::default()
+        };
+        let stats = analyze(
+            settings,
+            [message(1, "Alice", "hi"), message(2, "Bob", "hello")],
+        );
+        let dir = std::env::temp_dir().join(format!("texport-charts-{}", std::process::id()));
+        let options = ChartOptions {
+            width: 640,
+            height: 360,
+        };
+        let paths = stats.render_charts(&dir, options).unwrap();
+        let names: Vec<_> = paths.iter().map(|p| p.file_name().unwrap()).collect();
+        assert_eq!(names, ["timeline.svg", "hours.svg", "participants.svg"]);
+        for path in &paths {
+            let svg = fs::read_to_string(path).unwrap();
+            assert!(svg.contains(r#"width="640" height="360""#), "{path:?}");
+        }
+        assert_eq!(
+            bars(&fs::read_to_string(dir.join("participants.svg")).unwrap()),
+            2
+        );
+        fs::remove_dir_all(&dir).unwrap();
+
+        let empty = ChatStats::new(StatsSettings::default());
+        let dir = dir.with_extension("empty");
+        assert!(empty.render_charts(&dir, options).unwrap().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,175 @@
+use std::{
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use fs_err as fs;
+
+use crate::{
+    ChatStats,
+    stats::{UserStats, by_count},
+    time,
+};
+
+/// Byte order mark that makes Excel read a CSV file as UTF-8.
+const BOM: &str = "\u{feff}";
+
+impl ChatStats {
+    /// Write the statistics as CSV files into `dir`, creating it if needed,
+    /// and return the paths written:
+    ///
+    /// - `participants.csv`: one row per participant with every numeric field,
+    /// - `words.csv`: `user_id, user, word, count`,
+    /// - `reactions.csv`: `user_id, user, reaction, count`,
+    /// - `timeline.csv`: `month, messages`.
+    ///
+    /// Participants are in ranking order and words and reactions are sorted
+    /// by count, then alphabetically, so the output is the same on every
+    /// run. With `bom`, each file starts with a UTF-8 byte order mark for
+    /// the benefit of Excel.
+    pub fn write_csv(&self, dir: impl AsRef<Path>, bom: bool) -> io::Result<Vec<PathBuf>> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let participants = self.ranked_participants();
+
+        let mut paths = Vec::new();
+        let mut create = |name: &str| -> io::Result<CsvWriter<_>> {
+            let path = dir.join(name);
+            let mut file = BufWriter::new(fs::File::create(&path)?);
+            if bom {
+                file.write_all(BOM.as_bytes())?;
+            }
+            paths.push(path);
+            Ok(CsvWriter(file))
+        };
+
+        let mut csv = create("participants.csv")?;
+        csv.row(PARTICIPANT_COLUMNS)?;
+        for (id, stats) in &participants {
+            csv.row(participant_row(id, stats))?;
+        }
+        csv.finish()?;
+
+        let mut csv = create("words.csv")?;
+        csv.row(["user_id", "user", "word", "count"])?;
+        for (id, stats) in &participants {
+            let mut words: Vec<_> = stats.words.iter().collect();
+            words.sort_unstable_by(by_count);
+            for (word, count) in words {
+                let word = stats.word_label(word);
+                csv.row([id.as_str(), &stats.name, word, &count.to_string()])?;
+            }
+        }
+        csv.finish()?;
+
+        let mut csv = create("reactions.csv")?;
+        csv.row(["user_id", "user", "reaction", "count"])?;
+        for (id, stats) in &participants {
+            let reactions = self.settings.labeled_reactions(&stats.received_reactions);
+            for (reaction, count) in reactions {
+                csv.row([id.as_str(), &stats.name, &reaction, &count.to_string()])?;
+            }
+        }
+        csv.finish()?;
+
+        let mut csv = create("timeline.csv")?;
+        csv.row(["month", "messages"])?;
+        for (month, count) in &self.timeline {
+            csv.row([month.as_str(), &count.to_string()])?;
+        }
+        csv.finish()?;
+
+        Ok(paths)
+    }
+}
+
+const PARTICIPANT_COLUMNS: [&str; 25] = [
+    "user_id",
+    "user",
+    "messages",
+    "characters",
+    "longest_message",
+    "words",
+    "unique_words",
+    "avg_chars",
+    "avg_words",
+    "avg_word_length",
+    "caps_messages",
+    "exclamations",
+    "ellipsis_messages",
+    "sentences",
+    "single_word_messages",
+    "replies",
+    "forwards",
+    "conversations_started",
+    "runs",
+    "longest_run",
+    "pinned",
+    "reactions_received",
+    "first_message",
+    "last_message",
+    "longest_word",
+];
+
+fn participant_row(id: &str, stats: &UserStats) -> [String; 25] {
+    let reactions: usize = stats.received_reactions.values().sum();
+    [
+        id.to_string(),
+        stats.name.clone(),
+        stats.count.to_string(),
+        stats.total_chars.to_string(),
+        stats.max_chars.to_string(),
+        stats.total_words.to_string(),
+        stats.unique_words.to_string(),
+        stats.avg_chars().to_string(),
+        format!("{:.2}", stats.avg_words()),
+        format!("{:.2}", stats.avg_word_length()),
+        stats.caps_messages.to_string(),
+        stats.exclamations.to_string(),
+        stats.ellipsis_messages.to_string(),
+        stats.sentences.to_string(),
+        stats.single_word_messages.to_string(),
+        stats.replies.to_string(),
+        stats.forwards.to_string(),
+        stats.conversations_started.to_string(),
+        stats.runs.to_string(),
+        stats.longest_run.to_string(),
+        stats.pinned.to_string(),
+        reactions.to_string(),
+        stats
+            .first_message
+            .map(time::to_rfc3339)
+            .unwrap_or_default(),
+        stats.last_message.map(time::to_rfc3339).unwrap_or_default(),
+        stats.longest_word.clone(),
+    ]
+}
+
+/// Writes RFC 4180 rows: comma-separated, CRLF-terminated, with fields
+/// quoted when they contain a comma, quote or line break.
+struct CsvWriter<W>(W);
+
+impl<W: Write> CsvWriter<W> {
+    fn row<I>(&mut self, fields: I) -> io::Result<()>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        for (i, field) in fields.into_iter().enumerate() {
+            if i > 0 {
+                self.0.write_all(b",")?;
+            }
+            let field = field.as_ref();
+            if field.contains([',', '"', '\n', '\r']) {
+                write!(self.0, "\"{}\"", field.replace('"', "\"\""))?;
+            } else {
+                self.0.write_all(field.as_bytes())?;
+            }
+        }
+        self.0.write_all(b"\r\n")
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
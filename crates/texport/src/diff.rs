@@ -0,0 +1,371 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::{
+    ChatStats, Labels, Language,
+    labels::fill,
+    stats::{UserStats, by_count},
+};
+
+/// How two [`ChatStats`] differ, as computed by [`ChatStats::diff`].
+#[derive(Clone, Debug, Serialize)]
+pub struct StatsDiff {
+    pub messages: Change,
+    /// Reactions received by all participants.
+    pub reactions: Change,
+    /// Participants who posted in both, the biggest change in messages first.
+    pub participants: Vec<ParticipantChange>,
+    /// Display names of participants who posted only in the newer stats.
+    pub new_participants: Vec<String>,
+    /// Display names of participants who posted only in the older stats.
+    pub gone_quiet: Vec<String>,
+    /// Language of the text output, taken from the newer stats.
+    #[serde(skip)]
+    pub language: Language,
+}
+
+/// A value in the older and the newer stats.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Change {
+    pub before: u64,
+    pub after: u64,
+}
+
+/// How one participant's activity changed.
+#[derive(Clone, Debug, Serialize)]
+pub struct ParticipantChange {
+    /// Sender id, as in [`ChatStats::participants`].
+    pub id: String,
+    /// The display name in the newer stats.
+    pub name: String,
+    pub messages: Change,
+    pub reactions: Change,
+}
+
+impl Change {
+    pub fn delta(self) -> i64 {
+        self.after as i64 - self.before as i64
+    }
+
+    /// The change in percent of the older value, or `None` if that is zero
+    /// and no percentage makes sense.
+    pub fn percent(self) -> Option<f64> {
+        (self.before > 0).then(|| 100.0 * self.delta() as f64 / self.before as f64)
+    }
+}
+
+impl ChatStats {
+    /// Compare these statistics with `newer`, typically the same chats
+    /// analyzed for a later period.
+    ///
+    /// Participants are matched by sender id. Those who posted on one side
+    /// only are listed as new or gone quiet instead of as a change from or
+    /// to zero.
+    pub fn diff(&self, newer: &ChatStats) -> StatsDiff {
+        let posted = |stats: &ChatStats, id: &str| {
+            stats
+                .participants
+                .get(id)
+                .filter(|user| user.count > 0)
+                .is_some()
+        };
+
+        let mut participants: Vec<_> = newer
+            .participants
+            .iter()
+            .filter_map(|(id, after)| {
+                let before = self.participants.get(id).filter(|user| user.count > 0)?;
+                (after.count > 0).then(|| ParticipantChange {
+                    id: id.clone(),
+                    name: after.name.clone(),
+                    messages: Change {
+                        before: before.count,
+                        after: after.count,
+                    },
+                    reactions: Change {
+                        before: received_reactions(before),
+                        after: received_reactions(after),
+                    },
+                })
+            })
+            .collect();
+        participants.sort_by(|a, b| {
+            by_count(
+                &(&a.id, a.messages.delta().unsigned_abs()),
+                &(&b.id, b.messages.delta().unsigned_abs()),
+            )
+        });
+
+        let only_in = |stats: &ChatStats, other: &ChatStats| {
+            let mut names: Vec<_> = stats
+                .participants
+                .iter()
+                .filter(|(id, user)| user.count > 0 && !posted(other, id))
+                .map(|(_, user)| (user.name.clone(), user.count))
+                .collect();
+            names.sort_by(by_count);
+            names.into_iter().map(|(name, _)| name).collect()
+        };
+
+        StatsDiff {
+            messages: Change {
+                before: self.messages,
+                after: newer.messages,
+            },
+            reactions: Change {
+                before: self.participants.values().map(received_reactions).sum(),
+                after: newer.participants.values().map(received_reactions).sum(),
+            },
+            participants,
+            new_participants: only_in(newer, self),
+            gone_quiet: only_in(self, newer),
+            language: newer.settings.language,
+        }
+    }
+}
+
+fn received_reactions(user: &UserStats) -> u64 {
+    user.received_reactions.values().sum::<usize>() as u64
+}
+
+/// `value` with an explicit sign, using a proper minus sign.
+fn signed(value: impl fmt::Display + PartialOrd + Default) -> String {
+    if value > Default::default() {
+        format!("+{value}")
+    } else {
+        value.to_string().replace('-', "−")
+    }
+}
+
+/// A change as " (+18%)", or nothing if there is no percentage.
+fn percent_suffix(change: Change) -> String {
+    change
+        .percent()
+        .map(|percent| format!(" ({}%)", signed(percent.round() as i64)))
+        .unwrap_or_default()
+}
+
+impl fmt::Display for StatsDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let labels = Labels::for_language(self.language);
+        for (template, change) in [
+            (labels.diff_messages, self.messages),
+            (labels.diff_reactions, self.reactions),
+        ] {
+            writeln!(
+                f,
+                "{}{}",
+                fill(template, &[&signed(change.delta())]),
+                percent_suffix(change)
+            )?;
+        }
+
+        for user in &self.participants {
+            let percent = user.messages.percent().unwrap_or_default();
+            writeln!(f, "{} {}%", user.name, signed(percent.round() as i64))?;
+        }
+
+        for (template, names) in [
+            (labels.diff_new, &self.new_participants),
+            (labels.diff_gone_quiet, &self.gone_quiet),
+        ] {
+            if !names.is_empty() {
+                writeln!(f, "{}", fill(template, &[&names.join(", ")]))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::{
+        StatsSettings,
+        testing::{analyze, message},
+    };
+
+    /// `message` with `count` 👍 reactions.
+    fn liked(message: Value, count: u64) -> Value {
+        let mut message = message;
+        message["reactions"] = json!([{"type": "emoji", "count": count, "emoji": "👍"}]);
+        message
+    }
+
+    /// A month with Alice, Bob and Carol, and the next one, where Alice has
+    /// a new display name, Carol is gone and Erin has joined.
+    fn months(language: Language) -> (ChatStats, ChatStats) {
+        let settings = || StatsSettings {
+            language,
+            ..Default::default()
+        };
+        let january = analyze(
+            settings(),
+            [
+                liked(message(1, "Alice", "hi"), 2),
+                message(2, "Alice", "anyone?"),
+                message(3, "Bob", "yes"),
+                message(4, "Alice", "great"),
+                message(5, "Carol", "bye"),
+                message(6, "Bob", "bye"),
+                message(7, "Alice", "ok"),
+            ],
+        );
+        let mut renamed = message(11, "Alice B.", "back");
+        renamed["from_id"] = json!("useralice");
+        let mut february = analyze(
+            settings(),
+            [
+                renamed,
+                liked(message(12, "Bob", "news"), 1),
+                liked(message(13, "Bob", "more news"), 2),
+                message(14, "Bob", "and more"),
+                message(15, "Erin", "hello"),
+                message(16, "Bob", "welcome"),
+                message(17, "Bob", "sure"),
+                message(18, "Alice", "thanks"),
+            ],
+        );
+        // Listed without having posted, as a reactor might be.
+        february.participants.entry("userzed".into()).or_default();
+        (january, february)
+    }
+
+    #[test]
+    fn participants_are_matched_by_id_and_sorted_by_change() {
+        let (january, february) = months(Language::English);
+        let diff = january.diff(&february);
+
+        assert_eq!(
+            diff.messages,
+            Change {
+                before: 7,
+                after: 8
+            }
+        );
+        assert_eq!(
+            diff.reactions,
+            Change {
+                before: 2,
+                after: 3
+            }
+        );
+        let changes: Vec<_> = diff
+            .participants
+            .iter()
+            .map(|user| {
+                (
+                    user.id.as_str(),
+                    user.name.as_str(),
+                    user.messages,
+                    user.reactions,
+                )
+            })
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                (
+                    "userbob",
+                    "Bob",
+                    Change {
+                        before: 2,
+                        after: 5
+                    },
+                    Change {
+                        before: 0,
+                        after: 3
+                    }
+                ),
+                (
+                    "useralice",
+                    "Alice",
+                    Change {
+                        before: 4,
+                        after: 2
+                    },
+                    Change {
+                        before: 2,
+                        after: 0
+                    }
+                ),
+            ]
+        );
+        assert_eq!(diff.new_participants, ["Erin"]);
+        assert_eq!(diff.gone_quiet, ["Carol"]);
+        // Nothing changed the other way round but the direction.
+        let back = february.diff(&january);
+        assert_eq!(back.new_participants, ["Carol"]);
+        assert_eq!(back.gone_quiet, ["Erin"]);
+    }
+
+    #[test]
+    fn diffs_are_written_with_signs_and_percentages() {
+        let (january, february) = months(Language::English);
+        assert_eq!(
+            january.diff(&february).to_string(),
+            "+1 messages (+14%)\n\
+             +1 reactions (+50%)\n\
+             Bob +150%\n\
+             Alice −50%\n\
+             New: Erin\n\
+             Gone quiet: Carol\n"
+        );
+        let (january, february) = months(Language::Russian);
+        assert_eq!(
+            february.diff(&january).to_string(),
+            "Сообщений: −1 (−13%)\n\
+             Реакций: −1 (−33%)\n\
+             Bob −60%\n\
+             Alice +100%\n\
+             Новые: Carol\n\
+             Замолчали: Erin\n"
+        );
+
+        let nothing = ChatStats::new(StatsSettings::default());
+        assert_eq!(
+            nothing.diff(&nothing).to_string(),
+            "0 messages\n0 reactions\n"
+        );
+    }
+
+    #[test]
+    fn changes_from_zero_have_no_percentage() {
+        let from_zero = Change {
+            before: 0,
+            after: 5,
+        };
+        assert_eq!(from_zero.delta(), 5);
+        assert_eq!(from_zero.percent(), None);
+        assert_eq!(percent_suffix(from_zero), "");
+        assert_eq!(Change::default().percent(), None);
+
+        let halved = Change {
+            before: 10,
+            after: 5,
+        };
+        assert_eq!(halved.delta(), -5);
+        assert_eq!(halved.percent(), Some(-50.0));
+        assert_eq!(percent_suffix(halved), " (−50%)");
+        assert_eq!(
+            Change {
+                before: 3,
+                after: 3
+            }
+            .percent(),
+            Some(0.0)
+        );
+    }
+
+    #[test]
+    fn signs_are_explicit_and_minus_is_a_minus_sign() {
+        assert_eq!(signed(3), "+3");
+        assert_eq!(signed(0), "0");
+        assert_eq!(signed(-3), "−3");
+        assert_eq!(signed(-2.5), "−2.5");
+        assert_eq!(signed(i64::MIN), "−9223372036854775808");
+    }
+}
@@ -0,0 +1,188 @@
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fmt,
+    ops::Deref,
+    sync::{LazyLock, OnceLock, PoisonError, RwLock},
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// The symbol of every word interned so far, shared by all statistics in
+/// the process. Only interning takes the lock; the text of a symbol is read
+/// from [`TEXTS`].
+static SYMBOLS: LazyLock<RwLock<HashMap<&'static str, u32>>> = LazyLock::new(Default::default);
+
+/// The text of every symbol, in buckets that are never moved or shrunk, so
+/// that it can be read without a lock: bucket `b` holds the `2^b` symbols
+/// from `2^b - 1`. A slot is filled, and its bucket allocated, before the
+/// symbol is handed out.
+static TEXTS: [OnceLock<Box<[OnceLock<&'static str>]>>; 32] = [const { OnceLock::new() }; 32];
+
+/// The bucket of `symbol` in [`TEXTS`] and its index there.
+fn slot(symbol: u32) -> (usize, usize) {
+    let n = u64::from(symbol) + 1;
+    let bucket = n.ilog2();
+    (bucket as usize, (n - (1 << bucket)) as usize)
+}
+
+fn bucket(bucket: usize) -> &'static [OnceLock<&'static str>] {
+    TEXTS[bucket].get_or_init(|| (0..1usize << bucket).map(|_| OnceLock::new()).collect())
+}
+
+/// A word counted in statistics such as [`UserStats::words`], stored once
+/// per process however many participants and chats use it.
+///
+/// A word is a 32-bit symbol, so maps keyed by words hash and compare
+/// integers; the text is looked up with [`Word::as_str`] or through
+/// `Deref`, neither of which locks. Words are ordered by their text and
+/// serialized as it.
+///
+/// Interned text is never freed, even once no statistics use it: a process
+/// holds every distinct word it has seen until it exits. That is bounded by
+/// the vocabulary rather than the number of messages, but a long-running
+/// process such as `watch` grows with every word it hasn't seen before.
+///
+/// [`UserStats::words`]: crate::UserStats::words
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Word(u32);
+
+impl Word {
+    /// The word with text `word`, interning it if it is new.
+    pub fn new(word: &str) -> Self {
+        if let Some(found) = Self::get(word) {
+            return found;
+        }
+        let mut symbols = SYMBOLS.write().unwrap_or_else(PoisonError::into_inner);
+        // Another thread may have interned it in the meantime.
+        if let Some(&symbol) = symbols.get(word) {
+            return Word(symbol);
+        }
+        let symbol = u32::try_from(symbols.len())
+            .ok()
+            .filter(|&symbol| symbol < u32::MAX)
+            .expect("more than 2^32 - 1 distinct words");
+        let word: &'static str = Box::leak(word.into());
+        let (b, i) = slot(symbol);
+        // Symbols are only handed out under the lock, so the slot is empty.
+        let _ = bucket(b)[i].set(word);
+        symbols.insert(word, symbol);
+        Word(symbol)
+    }
+
+    /// The word with text `word`, if it has been interned, e.g. to look it
+    /// up in a map without interning it.
+    pub fn get(word: &str) -> Option<Self> {
+        let symbols = SYMBOLS.read().unwrap_or_else(PoisonError::into_inner);
+        symbols.get(word).map(|&symbol| Word(symbol))
+    }
+
+    pub fn as_str(self) -> &'static str {
+        let (b, i) = slot(self.0);
+        TEXTS[b]
+            .get()
+            .and_then(|bucket| bucket[i].get())
+            .expect("symbols are only made by interning")
+    }
+}
+
+impl Deref for Word {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&str> for Word {
+    fn from(word: &str) -> Self {
+        Word::new(word)
+    }
+}
+
+impl Ord for Word {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self == other {
+            return Ordering::Equal;
+        }
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl PartialOrd for Word {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Word {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for Word {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl Serialize for Word {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Word {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let word = String::deserialize(deserializer)?;
+        Ok(Word::new(&word))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn symbols_fill_each_bucket_in_turn() {
+        assert_eq!(slot(0), (0, 0));
+        assert_eq!(slot(1), (1, 0));
+        assert_eq!(slot(2), (1, 1));
+        assert_eq!(slot(3), (2, 0));
+        assert_eq!(slot(6), (2, 3));
+        assert_eq!(slot(7), (3, 0));
+        assert_eq!(slot(u32::MAX - 1), (31, (1 << 31) - 1));
+    }
+
+    #[test]
+    fn words_read_back_while_others_are_interned() {
+        let threads: Vec<_> = (0..4)
+            .map(|t| {
+                thread::spawn(move || {
+                    (0..2_000)
+                        .map(|i| {
+                            let word = Word::new(&format!("intern-{t}-{i}"));
+                            // Shared by every thread, so sometimes interned
+                            // by another one first.
+                            let shared = Word::new(&format!("shared-{i}"));
+                            assert_eq!(shared.as_str(), format!("shared-{i}"));
+                            word
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        for (t, thread) in threads.into_iter().enumerate() {
+            for (i, word) in thread.join().unwrap().into_iter().enumerate() {
+                assert_eq!(word.as_str(), format!("intern-{t}-{i}"));
+                assert_eq!(Word::get(&format!("intern-{t}-{i}")), Some(word));
+            }
+        }
+        assert_eq!(Word::new("shared-7"), Word::get("shared-7").unwrap());
+        // Ordered by text, not by which was interned first.
+        assert!(Word::new("pear") > Word::new("fig"));
+    }
+}
@@ -0,0 +1,645 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::SystemTime,
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+
+use crate::{
+    ChatStats, JsonDetail, StatsSettings, Word,
+    stats::{BUSIEST_DAYS, Cadence, UserStats, by_count},
+};
+
+/// Version of the [`StatsReport`] schema. It changes whenever a field is
+/// renamed, removed or changes meaning; adding a field doesn't change it.
+///
+/// The surface forms of stemmed words are not in the report: as in the text
+/// output, each word is named by its most frequent form.
+pub const STATS_REPORT_VERSION: u32 = 1;
+
+/// The statistics in the shape of the JSON output, made by
+/// [`ChatStats::to_report`].
+///
+/// Unlike the [`Serialize`] implementation of [`ChatStats`], which follows
+/// its internal structures, this is a schema of its own: names are stable
+/// and in snake_case, times are ISO 8601 strings in UTC, and every map is a
+/// list ordered by count, or by time where that makes more sense. Word,
+/// reaction and entity word lists hold as much as
+/// [`StatsSettings::json_detail`] asks for.
+#[derive(Clone, Debug, Serialize)]
+pub struct StatsReport {
+    /// [`STATS_REPORT_VERSION`] of the code that made the report.
+    pub schema_version: u32,
+    pub messages: u64,
+    pub service_messages: u64,
+    pub edited_messages: u64,
+    /// Messages from excluded senders, see [`StatsSettings::exclude`].
+    pub excluded_messages: u64,
+    /// Messages skipped for repeating the id of an earlier message.
+    pub duplicate_messages: u64,
+    /// Messages without a timestamp that can be parsed.
+    pub undated_messages: u64,
+    /// Messages in runs of at least three by the same sender.
+    pub messages_in_runs: u64,
+    pub first_message: Option<DateTime<Utc>>,
+    pub last_message: Option<DateTime<Utc>>,
+    /// Participants ordered by [`StatsSettings::rank_by`].
+    pub participants: Vec<ParticipantReport>,
+    /// Chats in the order of their labels.
+    pub chats: Vec<ChatReport>,
+    /// Forum topics, the most active first.
+    pub topics: Vec<TopicReport>,
+    /// Service messages per action, e.g. `pin_message`.
+    pub service_actions: Vec<Count>,
+    pub text_entity_types: Vec<Count>,
+    /// Forwarded messages per original sender or channel.
+    pub forward_sources: Vec<Count>,
+    pub longest_chain: String,
+    /// Messages by day of week (Monday first) and hour of day, in
+    /// [`StatsSettings::time_zone`].
+    pub weekday_hours: [[u64; 24]; 7],
+    /// Messages per month, oldest first.
+    pub timeline: Vec<MonthCount>,
+    pub longest_streak: Option<StreakReport>,
+    pub busiest_days: Vec<DayReport>,
+    pub membership: MembershipReport,
+    pub deletions: DeletionsReport,
+    /// Pinned messages, in the order they were pinned.
+    pub pins: Vec<PinReport>,
+    pub milestones: Vec<MilestoneReport>,
+    /// Title and photo changes, oldest first.
+    pub title_changes: Vec<TitleChangeReport>,
+    /// Hits of [`StatsSettings::track_words`] and
+    /// [`StatsSettings::track_patterns`], in the order of the keywords.
+    pub tracked: Vec<TrackedReport>,
+    /// Who mentions whom, the most frequent first, see
+    /// [`ChatStats::mention_pairs`].
+    pub mentions: Vec<MentionReport>,
+    /// Uses of [`StatsSettings::trend_words`] per month, in the order the
+    /// words were given.
+    pub trends: Vec<TrendReport>,
+    /// Everyone listed as a recent reactor, the most reactions first.
+    pub reactors: Vec<ParticipantCount>,
+    /// Reactors who never posted, see [`ChatStats::lurkers`].
+    pub lurkers: Vec<ParticipantCount>,
+    /// The settings of the analysis, left out when `None`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub analysis_settings: Option<StatsSettings>,
+}
+
+/// Something and how many times it occurred.
+#[derive(Clone, Debug, Serialize)]
+pub struct Count {
+    pub name: String,
+    pub count: u64,
+}
+
+/// A word and its TF-IDF score.
+#[derive(Clone, Debug, Serialize)]
+pub struct Score {
+    pub name: String,
+    pub score: f64,
+}
+
+/// A count for one month (`YYYY-MM`, in [`StatsSettings::time_zone`]).
+#[derive(Clone, Debug, Serialize)]
+pub struct MonthCount {
+    pub month: String,
+    pub count: u64,
+}
+
+/// A count for one sender.
+#[derive(Clone, Debug, Serialize)]
+pub struct ParticipantCount {
+    /// Sender id, e.g. `user123`.
+    pub id: String,
+    /// The display name last seen.
+    pub name: String,
+    pub count: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ParticipantReport {
+    /// Sender id, e.g. `user123`.
+    pub id: String,
+    /// The display name last seen.
+    pub name: String,
+    /// Other display names used, in alphabetical order.
+    pub other_names: Vec<String>,
+    pub messages: u64,
+    pub characters: u64,
+    pub longest_message_characters: u64,
+    pub words: u64,
+    pub unique_words: u64,
+    pub longest_word: String,
+    pub first_message: Option<DateTime<Utc>>,
+    pub last_message: Option<DateTime<Utc>>,
+    pub caps_messages: u64,
+    /// Messages with at least 5 letters, out of which `caps_messages` are.
+    pub caps_eligible_messages: u64,
+    pub exclamations: u64,
+    pub ellipsis_messages: u64,
+    pub sentences: u64,
+    pub single_word_messages: u64,
+    pub runs: u64,
+    pub longest_run: u64,
+    pub longest_run_start: Option<DateTime<Utc>>,
+    pub replies: u64,
+    pub forwards: u64,
+    pub conversations_started: u64,
+    pub pinned: u64,
+    pub median_gap_seconds: Option<u64>,
+    pub cadence: Option<Cadence>,
+    pub night_percent: f64,
+    /// Messages by hour of day, in [`StatsSettings::time_zone`].
+    pub hours: [u64; 24],
+    /// Messages per detected language code.
+    pub languages: Vec<Count>,
+    pub top_words: Vec<Count>,
+    pub top_reactions: Vec<Count>,
+    pub top_entity_words: Vec<Count>,
+    /// The most common phrases of two or more words, see
+    /// [`StatsSettings::max_phrases`].
+    pub top_phrases: Vec<Count>,
+    /// Words that set this participant apart, the highest score first, see
+    /// [`ChatStats::distinctive_words`].
+    pub distinctive_words: Vec<Score>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ChatReport {
+    /// Label of the chat, as passed to [`ChatStats::analyze_labeled`].
+    pub label: String,
+    pub messages: u64,
+    pub first_message: Option<DateTime<Utc>>,
+    pub last_message: Option<DateTime<Utc>>,
+    /// Messages per sender display name.
+    pub senders: Vec<Count>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TopicReport {
+    pub title: String,
+    pub messages: u64,
+    /// Messages per sender display name.
+    pub senders: Vec<Count>,
+    pub top_words: Vec<Count>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct StreakReport {
+    pub first_day: NaiveDate,
+    pub last_day: NaiveDate,
+    pub days: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DayReport {
+    pub date: NaiveDate,
+    pub messages: u64,
+    /// Display name of whoever posted the most that day.
+    pub top_poster: String,
+    pub top_poster_messages: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct MembershipReport {
+    pub joined: u64,
+    pub left: u64,
+    /// Times each member joined, by display name.
+    pub joins: Vec<Count>,
+    /// Times each member left or was removed, by display name.
+    pub leaves: Vec<Count>,
+    /// Members brought in by each inviter, by display name.
+    pub inviters: Vec<Count>,
+    pub monthly: Vec<MembershipMonth>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct MembershipMonth {
+    pub month: String,
+    pub joined: u64,
+    pub left: u64,
+}
+
+/// Gaps in message ids, an estimate of deleted messages.
+#[derive(Clone, Debug, Serialize)]
+pub struct DeletionsReport {
+    pub id_range: u64,
+    pub present: u64,
+    pub estimated_deletions: u64,
+    /// Missing ids per month of the message after each gap.
+    pub monthly: Vec<MonthCount>,
+    /// The largest gaps, largest first.
+    pub largest_gaps: Vec<GapReport>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct GapReport {
+    pub chat: String,
+    /// The last id present before the gap.
+    pub after_id: u64,
+    /// The first id present after the gap.
+    pub before_id: u64,
+    pub after_date: Option<DateTime<Utc>>,
+    pub before_date: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PinReport {
+    pub message_id: u64,
+    pub date: Option<DateTime<Utc>>,
+    pub pinned_by: String,
+    /// `None` when the pinned message is not part of the export.
+    pub author: Option<String>,
+    /// `None` when the pinned message is not part of the export.
+    pub preview: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct MilestoneReport {
+    pub chat: String,
+    pub number: u64,
+    pub message_id: u64,
+    pub date: Option<DateTime<Utc>>,
+    pub author: String,
+    pub preview: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TitleChangeReport {
+    pub chat: String,
+    pub date: Option<DateTime<Utc>>,
+    pub changed_by: String,
+    /// The new title, or `None` if the photo was changed.
+    pub title: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TrackedReport {
+    pub keyword: String,
+    pub total: u64,
+    pub participants: Vec<ParticipantCount>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct MentionReport {
+    /// Sender id of the author.
+    pub from: String,
+    /// Sender id of the mentioned participant, or `@username` if they
+    /// can't be told.
+    pub to: String,
+    pub count: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TrendReport {
+    pub word: String,
+    /// Every month from the first to the last of the timeline.
+    pub months: Vec<MonthCount>,
+}
+
+impl ChatStats {
+    /// The statistics as a [`StatsReport`], the schema of the JSON output.
+    /// The settings are included; set
+    /// [`StatsReport::analysis_settings`] to `None` to leave them out.
+    pub fn to_report(&self) -> StatsReport {
+        let max_words = self.json_limit(self.settings.max_words);
+        let combined = self.participants.values().sum::<UserStats>();
+        let name = |id: &str| {
+            self.participants
+                .get(id)
+                .map_or(id, |u| &u.name)
+                .to_string()
+        };
+
+        let max_distinctive = self.json_limit(self.settings.max_distinctive_words);
+        let df = if max_distinctive > 0 {
+            self.document_frequencies()
+        } else {
+            HashMap::new()
+        };
+        let participants = self
+            .ranked_participants()
+            .into_iter()
+            .map(|(id, stats)| self.participant_report(id, stats, &df))
+            .collect();
+        let topics = self
+            .ranked_topics()
+            .into_iter()
+            .map(|(title, topic)| TopicReport {
+                title: title.clone(),
+                messages: topic.messages,
+                senders: counts(&topic.senders),
+                top_words: topic
+                    .top_words(max_words)
+                    .into_iter()
+                    .map(|(word, &count)| count_of(word.as_str(), count))
+                    .collect(),
+            })
+            .collect();
+        let months = self.months();
+        let trends = self
+            .trend_series(&months)
+            .into_iter()
+            .map(|(word, series)| TrendReport {
+                word: word.to_string(),
+                months: months
+                    .iter()
+                    .zip(series)
+                    .map(|(month, count)| MonthCount {
+                        month: month.clone(),
+                        count,
+                    })
+                    .collect(),
+            })
+            .collect();
+        let mut reactors: Vec<_> = self
+            .reactors
+            .iter()
+            .map(|(id, reactor)| ParticipantCount {
+                id: id.clone(),
+                name: reactor.name.clone(),
+                count: reactor.reactions,
+            })
+            .collect();
+        reactors.sort_unstable_by(|a, b| b.count.cmp(&a.count).then_with(|| a.id.cmp(&b.id)));
+
+        StatsReport {
+            schema_version: STATS_REPORT_VERSION,
+            messages: self.messages,
+            service_messages: self.service_messages,
+            edited_messages: self.edited,
+            excluded_messages: self.excluded,
+            duplicate_messages: self.duplicate_ids,
+            undated_messages: self.undated,
+            messages_in_runs: self.messages_in_runs,
+            first_message: utc(combined.first_message),
+            last_message: utc(combined.last_message),
+            participants,
+            chats: self
+                .chats
+                .iter()
+                .map(|(label, chat)| ChatReport {
+                    label: label.clone(),
+                    messages: chat.messages,
+                    first_message: utc(chat.first_message),
+                    last_message: utc(chat.last_message),
+                    senders: counts(&chat.senders),
+                })
+                .collect(),
+            topics,
+            service_actions: counts(&self.service_actions),
+            text_entity_types: counts(&self.text_entity_types),
+            forward_sources: counts(&self.forward_sources),
+            longest_chain: self.longest_chain.clone(),
+            weekday_hours: self.weekday_hours,
+            timeline: month_counts(&self.timeline),
+            longest_streak: self.longest_streak().map(|streak| StreakReport {
+                first_day: streak.first_day,
+                last_day: streak.last_day,
+                days: streak.days,
+            }),
+            busiest_days: self
+                .busiest_days(BUSIEST_DAYS)
+                .into_iter()
+                .map(|day| DayReport {
+                    date: day.date,
+                    messages: day.messages,
+                    top_poster: day.top_poster,
+                    top_poster_messages: day.top_poster_messages,
+                })
+                .collect(),
+            membership: MembershipReport {
+                joined: self.membership.joined,
+                left: self.membership.left,
+                joins: counts(&self.membership.joins),
+                leaves: counts(&self.membership.leaves),
+                inviters: counts(&self.membership.inviters),
+                monthly: self
+                    .membership
+                    .monthly
+                    .iter()
+                    .map(|(month, change)| MembershipMonth {
+                        month: month.clone(),
+                        joined: change.joined,
+                        left: change.left,
+                    })
+                    .collect(),
+            },
+            deletions: DeletionsReport {
+                id_range: self.id_gaps.id_range,
+                present: self.id_gaps.present,
+                estimated_deletions: self.id_gaps.estimated_deletions,
+                monthly: month_counts(&self.id_gaps.estimated_deletions_monthly),
+                largest_gaps: self
+                    .id_gaps
+                    .largest
+                    .iter()
+                    .map(|gap| GapReport {
+                        chat: gap.chat.clone(),
+                        after_id: gap.after_id,
+                        before_id: gap.before_id,
+                        after_date: utc(gap.after_date),
+                        before_date: utc(gap.before_date),
+                    })
+                    .collect(),
+            },
+            pins: self
+                .pins
+                .iter()
+                .map(|pin| PinReport {
+                    message_id: pin.message_id,
+                    date: utc(pin.date),
+                    pinned_by: pin.pinned_by.clone(),
+                    author: pin.author.clone(),
+                    preview: pin.preview.clone(),
+                })
+                .collect(),
+            milestones: self
+                .milestones
+                .iter()
+                .map(|milestone| MilestoneReport {
+                    chat: milestone.chat.clone(),
+                    number: milestone.number,
+                    message_id: milestone.message_id,
+                    date: utc(milestone.date),
+                    author: milestone.author.clone(),
+                    preview: milestone.preview.clone(),
+                })
+                .collect(),
+            title_changes: self
+                .title_history
+                .iter()
+                .map(|change| TitleChangeReport {
+                    chat: change.chat.clone(),
+                    date: utc(change.date),
+                    changed_by: change.changed_by.clone(),
+                    title: change.title.clone(),
+                })
+                .collect(),
+            tracked: self
+                .tracked
+                .iter()
+                .collect::<BTreeMap<_, _>>()
+                .into_iter()
+                .map(|(keyword, users)| {
+                    let mut participants: Vec<_> = users.iter().collect();
+                    participants.sort_unstable_by(by_count);
+                    TrackedReport {
+                        keyword: keyword.clone(),
+                        total: users.values().sum(),
+                        participants: participants
+                            .into_iter()
+                            .map(|(id, &count)| ParticipantCount {
+                                id: id.clone(),
+                                name: name(id),
+                                count,
+                            })
+                            .collect(),
+                    }
+                })
+                .collect(),
+            mentions: self
+                .mention_pairs()
+                .into_iter()
+                .map(|(from, to, count)| MentionReport {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                    count,
+                })
+                .collect(),
+            trends,
+            reactors,
+            lurkers: self
+                .lurkers()
+                .into_iter()
+                .map(|(id, reactor)| ParticipantCount {
+                    id: id.clone(),
+                    name: reactor.name.clone(),
+                    count: reactor.reactions,
+                })
+                .collect(),
+            analysis_settings: Some(self.settings.clone()),
+        }
+    }
+
+    /// How many entries of a list that the text output cuts to `max` the
+    /// JSON output keeps, by [`StatsSettings::json_detail`].
+    fn json_limit(&self, max: usize) -> usize {
+        match self.settings.json_detail {
+            JsonDetail::Summary => 0,
+            JsonDetail::Top => max,
+            JsonDetail::Full => usize::MAX,
+        }
+    }
+
+    fn participant_report(
+        &self,
+        id: &str,
+        stats: &UserStats,
+        df: &HashMap<Word, usize>,
+    ) -> ParticipantReport {
+        let max_words = self.json_limit(self.settings.max_words);
+        ParticipantReport {
+            id: id.to_string(),
+            name: stats.name.clone(),
+            other_names: stats.other_names().cloned().collect(),
+            messages: stats.count,
+            characters: stats.total_chars,
+            longest_message_characters: stats.max_chars,
+            words: stats.total_words,
+            unique_words: stats.unique_words,
+            longest_word: stats.longest_word.clone(),
+            first_message: utc(stats.first_message),
+            last_message: utc(stats.last_message),
+            caps_messages: stats.caps_messages,
+            caps_eligible_messages: stats.caps_eligible,
+            exclamations: stats.exclamations,
+            ellipsis_messages: stats.ellipsis_messages,
+            sentences: stats.sentences,
+            single_word_messages: stats.single_word_messages,
+            runs: stats.runs,
+            longest_run: stats.longest_run,
+            longest_run_start: utc(stats.longest_run_date),
+            replies: stats.replies,
+            forwards: stats.forwards,
+            conversations_started: stats.conversations_started,
+            pinned: stats.pinned,
+            median_gap_seconds: stats.median_gap().map(|gap| gap.as_secs()),
+            cadence: stats.cadence(),
+            night_percent: stats.night_percent(),
+            hours: stats.hours,
+            languages: counts(&stats.languages),
+            top_words: stats
+                .top_words(max_words)
+                .into_iter()
+                .map(|(word, &count)| count_of(stats.word_label(word), count))
+                .collect(),
+            top_reactions: self
+                .settings
+                .labeled_reactions(&stats.received_reactions)
+                .into_iter()
+                .take(max_words)
+                .map(|(label, count)| count_of(&label, count))
+                .collect(),
+            top_entity_words: stats
+                .top_entity_words(max_words)
+                .into_iter()
+                .map(|(word, &count)| count_of(word.as_str(), count))
+                .collect(),
+            top_phrases: stats
+                .top_phrases(self.json_limit(self.settings.max_phrases))
+                .into_iter()
+                .map(|(phrase, &count)| count_of(phrase, count))
+                .collect(),
+            distinctive_words: self
+                .score_distinctive(
+                    stats,
+                    df,
+                    self.json_limit(self.settings.max_distinctive_words),
+                )
+                .into_iter()
+                .map(|(word, score)| Score {
+                    name: word.to_string(),
+                    score,
+                })
+                .collect(),
+        }
+    }
+}
+
+fn utc(time: Option<SystemTime>) -> Option<DateTime<Utc>> {
+    time.map(DateTime::from)
+}
+
+fn count_of(name: &str, count: usize) -> Count {
+    Count {
+        name: name.to_string(),
+        count: count as u64,
+    }
+}
+
+/// The entries of `map` as [`Count`]s, ordered by count.
+fn counts<H>(map: &HashMap<String, u64, H>) -> Vec<Count> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_unstable_by(by_count);
+    entries
+        .into_iter()
+        .map(|(name, &count)| Count {
+            name: name.clone(),
+            count,
+        })
+        .collect()
+}
+
+fn month_counts<'a>(months: impl IntoIterator<Item = (&'a String, &'a u64)>) -> Vec<MonthCount> {
+    months
+        .into_iter()
+        .map(|(month, &count)| MonthCount {
+            month: month.clone(),
+            count,
+        })
+        .collect()
+}
@@ -0,0 +1,531 @@
+use std::{
+    fmt::{self, Write},
+    time::Duration,
+};
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::{Cadence, Language, RankBy};
+
+/// Every string the text output prints, so that it can be translated.
+///
+/// Fields holding `{}` are templates: each `{}` is replaced by a value, in
+/// order. Row labels are padded to a common display width, so they don't
+/// have to be aligned by hand.
+#[derive(Clone, Debug)]
+pub struct Labels {
+    /// Heading of the whole report, underline included.
+    pub title: &'static str,
+    pub total_messages: &'static str,
+    pub edited_messages: &'static str,
+    pub excluded_messages: &'static str,
+    pub duplicate_ids: &'static str,
+    pub undated_messages: &'static str,
+    pub total_reactions: &'static str,
+    /// Followed by the minimum run length, e.g. `3+`.
+    pub in_runs: &'static str,
+    pub in_runs_value: &'static str,
+    pub longest_streak: &'static str,
+    pub longest_streak_value: &'static str,
+    pub busiest_day: &'static str,
+    pub busiest_day_value: &'static str,
+
+    pub participants_chart: &'static str,
+    pub timeline_chart: &'static str,
+
+    pub combined: &'static str,
+    pub longest_chain: &'static str,
+    pub top_participants: &'static str,
+    pub share: &'static str,
+    pub also_known_as: &'static str,
+    pub and_more: &'static str,
+    pub per_chat: &'static str,
+    pub chat_messages: &'static str,
+    pub most_active: &'static str,
+    pub entity_types: &'static str,
+    pub topics: &'static str,
+    pub top_posters: &'static str,
+    pub topic_words: &'static str,
+
+    pub no_messages: &'static str,
+    pub messages: &'static str,
+    pub avg_length: &'static str,
+    pub longest_message: &'static str,
+    pub chars: &'static str,
+    pub vocabulary: &'static str,
+    pub vocabulary_value: &'static str,
+    pub words: &'static str,
+    pub words_value: &'static str,
+    pub longest_word: &'static str,
+    pub single_word: &'static str,
+    pub double_texting: &'static str,
+    pub double_texting_value: &'static str,
+    pub night: &'static str,
+    pub night_value: &'static str,
+    pub cadence: &'static str,
+    /// Followed by the median gap and the name of the cadence.
+    pub cadence_value: &'static str,
+    /// Names of [`Cadence`]s, in declaration order.
+    pub cadences: [&'static str; 4],
+    pub seconds: &'static str,
+    pub minutes: &'static str,
+    pub hours: &'static str,
+    pub replies: &'static str,
+    pub replies_value: &'static str,
+    pub forwards: &'static str,
+    pub forwards_value: &'static str,
+    pub languages: &'static str,
+    pub style: &'static str,
+    pub style_value: &'static str,
+    pub first_message: &'static str,
+    pub last_message: &'static str,
+    pub reactions: &'static str,
+    pub pinned: &'static str,
+    pub pinned_value: &'static str,
+    pub top_words: &'static str,
+    pub entity_words: &'static str,
+    pub top_phrases: &'static str,
+    pub distinctive: &'static str,
+
+    pub heatmap: &'static str,
+    pub night_owls: &'static str,
+    /// Weekday names, Monday first.
+    pub weekdays: [&'static str; 7],
+    pub mentions: &'static str,
+    pub most_mentioned: &'static str,
+    pub tracked: &'static str,
+    pub trends: &'static str,
+    /// Heading of the month column.
+    pub month: &'static str,
+    pub lurkers: &'static str,
+    pub lurker_value: &'static str,
+    pub service_messages: &'static str,
+    /// Names of service message actions, by their name in the export.
+    /// Actions missing here are shown as they are.
+    pub actions: &'static [(&'static str, &'static str)],
+    pub membership: &'static str,
+    pub top_inviter: &'static str,
+    pub forward_sources: &'static str,
+    pub id_gaps: &'static str,
+    pub ids_missing: &'static str,
+    pub gap: &'static str,
+    pub gap_chat: &'static str,
+    pub by_month: &'static str,
+    pub pins: &'static str,
+    pub pinned_by: &'static str,
+    pub pin_not_exported: &'static str,
+    pub milestones: &'static str,
+    pub milestone: &'static str,
+    pub the_chat: &'static str,
+    /// For one name and for several.
+    pub title_names: [&'static str; 2],
+    pub current_title: &'static str,
+    /// For one photo change and for several.
+    pub photo_changes: [&'static str; 2],
+
+    /// Lines of a [`StatsDiff`](crate::StatsDiff), each followed by the
+    /// percent change when there is one.
+    pub diff_messages: &'static str,
+    pub diff_reactions: &'static str,
+    pub diff_new: &'static str,
+    pub diff_gone_quiet: &'static str,
+
+    pub rank_messages: &'static str,
+    pub rank_characters: &'static str,
+    pub rank_reactions: &'static str,
+    pub rank_words: &'static str,
+}
+
+static ENGLISH: Labels = Labels::english();
+static RUSSIAN: Labels = Labels::russian();
+
+impl Labels {
+    pub const fn english() -> Self {
+        Labels {
+            title: "📊 Chat Statistics Summary\n=========================",
+            total_messages: "💬 Total messages",
+            edited_messages: "✏️ Edited messages",
+            excluded_messages: "🚫 Excluded messages",
+            duplicate_ids: "👯 Duplicate ids",
+            undated_messages: "⏳ Undated messages",
+            total_reactions: "❤️ Total reactions",
+            in_runs: "🔁 In runs of",
+            in_runs_value: "{}% of messages",
+            longest_streak: "🔥 Longest streak",
+            longest_streak_value: "{} days ({} – {})",
+            busiest_day: "📅 Busiest day",
+            busiest_day_value: "{} with {} messages, most by {} ({})",
+
+            participants_chart: "📈 Messages by participant:",
+            timeline_chart: "📆 Messages per month:",
+
+            combined: "📏 Combined Participant Stats:",
+            longest_chain: "Longest chain: {}",
+            top_participants: "👥 Top Participants ({}):",
+            share: "{}% of {}",
+            also_known_as: "also known as {}",
+            and_more: "... and {} more",
+            per_chat: "📁 Per-chat ({}):",
+            chat_messages: "{} messages",
+            most_active: "most active: {} ({})",
+            entity_types: "🔤 Text Entity Types ({}):",
+            topics: "🗂 Topics ({}):",
+            top_posters: "top posters: {}",
+            topic_words: "top words: {}",
+
+            no_messages: "No messages",
+            messages: "Messages",
+            avg_length: "Avg. length",
+            longest_message: "Longest message",
+            chars: "{} chars",
+            vocabulary: "Vocabulary",
+            vocabulary_value: "{} unique of {} words ({} per message, TTR {})",
+            words: "Words",
+            words_value: "{} chars on average",
+            longest_word: "longest \"{}\"",
+            single_word: "{}% single-word messages",
+            double_texting: "Double-texting",
+            double_texting_value: "{} messages per run, longest {} in a row",
+            night: "Night posts",
+            night_value: "{}% of messages between 00:00 and 06:00",
+            cadence: "Cadence",
+            cadence_value: "every {} (median), {}",
+            cadences: ["several times a day", "daily", "weekly", "rarely"],
+            seconds: "{} s",
+            minutes: "{} min",
+            hours: "{} h",
+            replies: "Replies",
+            replies_value: "{}% of messages, started {} conversations",
+            forwards: "Forwards",
+            forwards_value: "{} ({}% of messages)",
+            languages: "Languages",
+            style: "Style",
+            style_value: "{}% caps, {} '!' per message, {}% with ellipsis, {} sentences per message",
+            first_message: "First message",
+            last_message: "Last message",
+            reactions: "Reactions",
+            pinned: "Pinned",
+            pinned_value: "{} messages",
+            top_words: "Top words",
+            entity_words: "Links and tags",
+            top_phrases: "Top phrases",
+            distinctive: "Distinctive",
+
+            heatmap: "🗓️ Activity by weekday and hour:",
+            night_owls: "🦉 Night owls:",
+            weekdays: ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+            mentions: "📣 Mentions:",
+            most_mentioned: "Mentions the most: {}",
+            tracked: "🔎 Tracked words:",
+            trends: "📉 Word trends:",
+            month: "Month",
+            lurkers: "👀 Lurkers ({}, react but never post):",
+            lurker_value: "at least {} reactions",
+            service_messages: "⚙️ Service messages ({}):",
+            actions: &[
+                ("create_group", "Chat created"),
+                ("create_channel", "Chat created"),
+                ("edit_group_title", "Title changes"),
+                ("edit_group_photo", "Photo changes"),
+                ("delete_group_photo", "Photo removals"),
+                ("invite_members", "Members added"),
+                ("join_group_by_link", "Joins by link"),
+                ("join_group_by_request", "Joins by request"),
+                ("remove_members", "Members removed"),
+                ("pin_message", "Pins"),
+                ("phone_call", "Calls"),
+                ("group_call", "Video chats"),
+                ("invite_to_group_call", "Video chat invites"),
+                ("topic_created", "Topics created"),
+                ("migrate_to_supergroup", "Supergroup upgrades"),
+                ("migrate_from_group", "Supergroup upgrades"),
+            ],
+            membership: "👋 Members: {} joined, {} left (net {})",
+            top_inviter: "Top inviter: {} ({})",
+            forward_sources: "📤 Forwards ({}):",
+            id_gaps: "🕳️ Missing message ids (an estimate of deleted messages):",
+            ids_missing: "{} of {} ids missing ({}%)",
+            gap: "{} missing between #{} and #{}",
+            gap_chat: "in {}",
+            by_month: "By month: {}",
+            pins: "📌 Pins ({}):",
+            pinned_by: "by {}",
+            pin_not_exported: "message #{} (not in export)",
+            milestones: "🏁 Milestones ({}):",
+            milestone: "message #{} by {}",
+            the_chat: "The chat",
+            title_names: ["🏷️ {} has had {} name", "🏷️ {} has had {} names"],
+            current_title: "current: {}",
+            photo_changes: ["{} photo change", "{} photo changes"],
+
+            diff_messages: "{} messages",
+            diff_reactions: "{} reactions",
+            diff_new: "New: {}",
+            diff_gone_quiet: "Gone quiet: {}",
+
+            rank_messages: "messages",
+            rank_characters: "characters",
+            rank_reactions: "reactions",
+            rank_words: "words",
+        }
+    }
+
+    pub const fn russian() -> Self {
+        Labels {
+            title: "📊 Статистика чата\n=================",
+            total_messages: "💬 Всего сообщений",
+            edited_messages: "✏️ Отредактировано",
+            excluded_messages: "🚫 Исключено",
+            duplicate_ids: "👯 Повторные id",
+            undated_messages: "⏳ Без даты",
+            total_reactions: "❤️ Всего реакций",
+            in_runs: "🔁 Сериями от",
+            in_runs_value: "{}% сообщений",
+            longest_streak: "🔥 Самая долгая серия",
+            longest_streak_value: "{} дн. подряд ({} – {})",
+            busiest_day: "📅 Самый активный день",
+            busiest_day_value: "{}: {} сообщ., больше всех {} ({})",
+
+            participants_chart: "📈 Сообщения по участникам:",
+            timeline_chart: "📆 Сообщения по месяцам:",
+
+            combined: "📏 Все участники вместе:",
+            longest_chain: "Самая длинная цепочка ответов: {}",
+            top_participants: "👥 Самые активные участники ({}):",
+            share: "{}% {}",
+            also_known_as: "другие имена: {}",
+            and_more: "... и ещё {}",
+            per_chat: "📁 По чатам ({}):",
+            chat_messages: "{} сообщ.",
+            most_active: "активнее всех: {} ({})",
+            entity_types: "🔤 Типы разметки текста ({}):",
+            topics: "🗂 Темы ({}):",
+            top_posters: "чаще всех пишут: {}",
+            topic_words: "частые слова: {}",
+
+            no_messages: "Нет сообщений",
+            messages: "Сообщений",
+            avg_length: "Средняя длина",
+            longest_message: "Самое длинное",
+            chars: "{} симв.",
+            vocabulary: "Словарь",
+            vocabulary_value: "{} разных из {} слов ({} на сообщение, TTR {})",
+            words: "Слова",
+            words_value: "в среднем {} симв.",
+            longest_word: "самое длинное «{}»",
+            single_word: "{}% сообщений из одного слова",
+            double_texting: "Подряд",
+            double_texting_value: "{} сообщ. за раз, максимум {} подряд",
+            night: "Ночью",
+            night_value: "{}% сообщений с 00:00 до 06:00",
+            cadence: "Частота",
+            cadence_value: "раз в {} (медиана), {}",
+            cadences: ["несколько раз в день", "ежедневно", "еженедельно", "редко"],
+            seconds: "{} с",
+            minutes: "{} мин",
+            hours: "{} ч",
+            replies: "Ответы",
+            replies_value: "{}% сообщений, начато разговоров: {}",
+            forwards: "Пересылки",
+            forwards_value: "{} ({}% сообщений)",
+            languages: "Языки",
+            style: "Стиль",
+            style_value: "{}% капсом, {} «!» на сообщение, {}% с многоточием, {} предложений на сообщение",
+            first_message: "Первое сообщение",
+            last_message: "Последнее",
+            reactions: "Реакции",
+            pinned: "Закреплено",
+            pinned_value: "{} сообщ.",
+            top_words: "Частые слова",
+            entity_words: "Ссылки и теги",
+            top_phrases: "Частые фразы",
+            distinctive: "Характерные",
+
+            heatmap: "🗓️ Активность по дням недели и часам:",
+            night_owls: "🦉 Совы:",
+            weekdays: ["Пн", "Вт", "Ср", "Чт", "Пт", "Сб", "Вс"],
+            mentions: "📣 Упоминания:",
+            most_mentioned: "Чаще всего упоминают: {}",
+            tracked: "🔎 Отслеживаемые слова:",
+            trends: "📉 Слова по месяцам:",
+            month: "Месяц",
+            lurkers: "👀 Читатели ({}, ставят реакции, но не пишут):",
+            lurker_value: "реакций: не меньше {}",
+            service_messages: "⚙️ Служебные сообщения ({}):",
+            actions: &[
+                ("create_group", "Создание чата"),
+                ("create_channel", "Создание чата"),
+                ("edit_group_title", "Смена названия"),
+                ("edit_group_photo", "Смена фото"),
+                ("delete_group_photo", "Удаление фото"),
+                ("invite_members", "Добавление участников"),
+                ("join_group_by_link", "Вступление по ссылке"),
+                ("join_group_by_request", "Вступление по заявке"),
+                ("remove_members", "Удаление участников"),
+                ("pin_message", "Закрепы"),
+                ("phone_call", "Звонки"),
+                ("group_call", "Видеочаты"),
+                ("invite_to_group_call", "Приглашения в видеочат"),
+                ("topic_created", "Новые темы"),
+                ("migrate_to_supergroup", "Переход в супергруппу"),
+                ("migrate_from_group", "Переход в супергруппу"),
+            ],
+            membership: "👋 Участники: вступили {}, вышли {} (итого {})",
+            top_inviter: "Чаще всех приглашал(а): {} ({})",
+            forward_sources: "📤 Пересылки ({}):",
+            id_gaps: "🕳️ Пропущенные номера сообщений (оценка удалённых):",
+            ids_missing: "пропущено {} из {} номеров ({}%)",
+            gap: "пропущено {} между #{} и #{}",
+            gap_chat: "в чате {}",
+            by_month: "По месяцам: {}",
+            pins: "📌 Закрепы ({}):",
+            pinned_by: "закрепил(а) {}",
+            pin_not_exported: "сообщение #{} (нет в выгрузке)",
+            milestones: "🏁 Юбилейные сообщения ({}):",
+            milestone: "сообщение #{} от {}",
+            the_chat: "Чат",
+            title_names: ["🏷️ {}: названий {}", "🏷️ {}: названий {}"],
+            current_title: "сейчас: {}",
+            photo_changes: ["смен фото: {}", "смен фото: {}"],
+
+            diff_messages: "Сообщений: {}",
+            diff_reactions: "Реакций: {}",
+            diff_new: "Новые: {}",
+            diff_gone_quiet: "Замолчали: {}",
+
+            rank_messages: "сообщений",
+            rank_characters: "символов",
+            rank_reactions: "реакций",
+            rank_words: "слов",
+        }
+    }
+
+    /// The labels for `language`, or English if there are none for it.
+    pub fn for_language(language: Language) -> &'static Labels {
+        match language {
+            Language::Russian => &RUSSIAN,
+            _ => &ENGLISH,
+        }
+    }
+
+    /// The name of a service message action.
+    pub fn action<'a>(&self, action: &'a str) -> &'a str {
+        self.actions
+            .iter()
+            .find(|(name, _)| *name == action)
+            .map_or(action, |(_, label)| label)
+    }
+
+    /// What participants are ranked by, as in "28% of `messages`".
+    pub fn rank(&self, rank_by: RankBy) -> &'static str {
+        match rank_by {
+            RankBy::Messages => self.rank_messages,
+            RankBy::Characters => self.rank_characters,
+            RankBy::ReactionsReceived => self.rank_reactions,
+            RankBy::WordsTyped => self.rank_words,
+        }
+    }
+
+    /// The name of a [`Cadence`].
+    pub fn cadence_name(&self, cadence: Cadence) -> &'static str {
+        self.cadences[cadence as usize]
+    }
+
+    /// A short duration in the largest unit that fits, e.g. `25 min`.
+    pub fn duration(&self, duration: Duration) -> String {
+        let secs = duration.as_secs();
+        match secs {
+            0..60 => fill(self.seconds, &[&secs]),
+            60..3600 => fill(self.minutes, &[&(secs / 60)]),
+            _ => fill(self.hours, &[&format!("{:.1}", secs as f64 / 3600.0)]),
+        }
+    }
+
+    /// Display width of the widest per-participant row label.
+    pub(crate) fn row_width(&self) -> usize {
+        [
+            self.messages,
+            self.avg_length,
+            self.longest_message,
+            self.vocabulary,
+            self.words,
+            self.double_texting,
+            self.cadence,
+            self.night,
+            self.replies,
+            self.forwards,
+            self.languages,
+            self.style,
+            self.first_message,
+            self.last_message,
+            self.reactions,
+            self.pinned,
+            self.top_words,
+            self.entity_words,
+            self.top_phrases,
+            self.distinctive,
+        ]
+        .into_iter()
+        .map(UnicodeWidthStr::width)
+        .max()
+        .unwrap_or(0)
+    }
+
+    /// Display width of the widest weekday name.
+    pub(crate) fn weekday_width(&self) -> usize {
+        self.weekdays
+            .iter()
+            .map(|day| day.width())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Display width of the widest summary label.
+    pub(crate) fn summary_width(&self, run_length: &str) -> usize {
+        [
+            self.total_messages,
+            self.edited_messages,
+            self.excluded_messages,
+            self.duplicate_ids,
+            self.undated_messages,
+            self.total_reactions,
+            self.longest_streak,
+            self.busiest_day,
+        ]
+        .into_iter()
+        .map(UnicodeWidthStr::width)
+        .chain([self.in_runs.width() + 1 + run_length.width()])
+        .max()
+        .unwrap_or(0)
+    }
+}
+
+impl Default for Labels {
+    fn default() -> Self {
+        Labels::english()
+    }
+}
+
+/// Replace the `{}` placeholders in `template` with `args`, in order.
+/// Placeholders without an argument are left empty.
+pub(crate) fn fill(template: &str, args: &[&dyn fmt::Display]) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut args = args.iter();
+    let mut parts = template.split("{}");
+    if let Some(first) = parts.next() {
+        filled.push_str(first);
+    }
+    for part in parts {
+        if let Some(arg) = args.next() {
+            let _ = write!(filled, "{arg}");
+        }
+        filled.push_str(part);
+    }
+    filled
+}
+
+/// `label` followed by enough spaces to take `width` columns on a terminal.
+pub(crate) fn pad(label: &str, width: usize) -> String {
+    let padding = width.saturating_sub(label.width());
+    format!("{label}{}", " ".repeat(padding))
+}
@@ -0,0 +1,267 @@
+#[cfg(feature = "stemming")]
+use std::sync::Arc;
+use std::{fmt, str::FromStr};
+
+use serde::Serialize;
+
+/// A natural language with a built-in stop-word list.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    Arabic,
+    Azerbaijani,
+    Danish,
+    Dutch,
+    #[default]
+    English,
+    Finnish,
+    French,
+    German,
+    Greek,
+    Hungarian,
+    Indonesian,
+    Italian,
+    Kazakh,
+    Nepali,
+    Norwegian,
+    Portuguese,
+    Romanian,
+    Russian,
+    Slovenian,
+    Spanish,
+    Swedish,
+    Tajik,
+    Turkish,
+}
+
+impl Language {
+    /// Every supported language, in alphabetical order.
+    pub const ALL: [Language; 23] = [
+        Language::Arabic,
+        Language::Azerbaijani,
+        Language::Danish,
+        Language::Dutch,
+        Language::English,
+        Language::Finnish,
+        Language::French,
+        Language::German,
+        Language::Greek,
+        Language::Hungarian,
+        Language::Indonesian,
+        Language::Italian,
+        Language::Kazakh,
+        Language::Nepali,
+        Language::Norwegian,
+        Language::Portuguese,
+        Language::Romanian,
+        Language::Russian,
+        Language::Slovenian,
+        Language::Spanish,
+        Language::Swedish,
+        Language::Tajik,
+        Language::Turkish,
+    ];
+
+    /// The two-letter ISO 639-1 code of this language.
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::Arabic => "ar",
+            Language::Azerbaijani => "az",
+            Language::Danish => "da",
+            Language::Dutch => "nl",
+            Language::English => "en",
+            Language::Finnish => "fi",
+            Language::French => "fr",
+            Language::German => "de",
+            Language::Greek => "el",
+            Language::Hungarian => "hu",
+            Language::Indonesian => "id",
+            Language::Italian => "it",
+            Language::Kazakh => "kk",
+            Language::Nepali => "ne",
+            Language::Norwegian => "no",
+            Language::Portuguese => "pt",
+            Language::Romanian => "ro",
+            Language::Russian => "ru",
+            Language::Slovenian => "sl",
+            Language::Spanish => "es",
+            Language::Swedish => "sv",
+            Language::Tajik => "tg",
+            Language::Turkish => "tr",
+        }
+    }
+
+    /// The built-in stop-word list for this language.
+    pub fn stop_words(self) -> Vec<String> {
+        stop_words::get(self.code())
+    }
+
+    /// The writing system this language is usually written in.
+    pub fn script(self) -> Script {
+        match self {
+            Language::Kazakh | Language::Russian | Language::Tajik => Script::Cyrillic,
+            Language::Greek => Script::Greek,
+            Language::Arabic => Script::Arabic,
+            Language::Nepali => Script::Devanagari,
+            _ => Script::Latin,
+        }
+    }
+
+    /// Reduce `word` to its stem, if a stemmer exists for this language.
+    /// This builds a stemmer on every call, so [`WordFilter`] keeps its own.
+    ///
+    /// [`WordFilter`]: crate::WordFilter
+    #[cfg(feature = "stemming")]
+    pub fn stem(self, word: &str) -> Option<String> {
+        Some(self.stemmer()?.stem(word))
+    }
+
+    /// The stemmer of this language, if there is one.
+    #[cfg(feature = "stemming")]
+    pub(crate) fn stemmer(self) -> Option<LanguageStemmer> {
+        use rust_stemmers::{Algorithm, Stemmer};
+
+        let algorithm = match self {
+            Language::Arabic => Algorithm::Arabic,
+            Language::Danish => Algorithm::Danish,
+            Language::Dutch => Algorithm::Dutch,
+            Language::English => Algorithm::English,
+            Language::Finnish => Algorithm::Finnish,
+            Language::French => Algorithm::French,
+            Language::German => Algorithm::German,
+            Language::Greek => Algorithm::Greek,
+            Language::Hungarian => Algorithm::Hungarian,
+            Language::Italian => Algorithm::Italian,
+            Language::Norwegian => Algorithm::Norwegian,
+            Language::Portuguese => Algorithm::Portuguese,
+            Language::Romanian => Algorithm::Romanian,
+            Language::Russian => Algorithm::Russian,
+            Language::Spanish => Algorithm::Spanish,
+            Language::Swedish => Algorithm::Swedish,
+            Language::Turkish => Algorithm::Turkish,
+            _ => return None,
+        };
+        Some(LanguageStemmer {
+            language: self,
+            stemmer: Arc::new(Stemmer::create(algorithm)),
+        })
+    }
+}
+
+/// A stemmer built once for a language, to stem many words with.
+#[cfg(feature = "stemming")]
+#[derive(Clone)]
+pub(crate) struct LanguageStemmer {
+    language: Language,
+    stemmer: Arc<rust_stemmers::Stemmer>,
+}
+
+#[cfg(feature = "stemming")]
+impl LanguageStemmer {
+    pub(crate) fn language(&self) -> Language {
+        self.language
+    }
+
+    pub(crate) fn stem(&self, word: &str) -> String {
+        self.stemmer.stem(word).into_owned()
+    }
+}
+
+#[cfg(feature = "stemming")]
+impl fmt::Debug for LanguageStemmer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("LanguageStemmer")
+            .field(&self.language)
+            .finish()
+    }
+}
+
+/// Guess the language of `text`: the code of a supported [`Language`] where
+/// there is one, whatlang's ISO 639-3 code otherwise. `None` when the guess
+/// isn't reliable.
+#[cfg(feature = "lang-detect")]
+pub(crate) fn detect_language(text: &str) -> Option<&'static str> {
+    use whatlang::Lang;
+
+    let info = whatlang::detect(text).filter(whatlang::Info::is_reliable)?;
+    let language = match info.lang() {
+        Lang::Ara => Language::Arabic,
+        Lang::Aze => Language::Azerbaijani,
+        Lang::Dan => Language::Danish,
+        Lang::Nld => Language::Dutch,
+        Lang::Eng => Language::English,
+        Lang::Fin => Language::Finnish,
+        Lang::Fra => Language::French,
+        Lang::Deu => Language::German,
+        Lang::Ell => Language::Greek,
+        Lang::Hun => Language::Hungarian,
+        Lang::Ind => Language::Indonesian,
+        Lang::Ita => Language::Italian,
+        Lang::Nep => Language::Nepali,
+        Lang::Nob => Language::Norwegian,
+        Lang::Por => Language::Portuguese,
+        Lang::Ron => Language::Romanian,
+        Lang::Rus => Language::Russian,
+        Lang::Slv => Language::Slovenian,
+        Lang::Spa => Language::Spanish,
+        Lang::Swe => Language::Swedish,
+        Lang::Tur => Language::Turkish,
+        other => return Some(other.code()),
+    };
+    Some(language.code())
+}
+
+/// Guess the language of `text`. Always `None` without the `lang-detect`
+/// feature.
+#[cfg(not(feature = "lang-detect"))]
+pub(crate) fn detect_language(_text: &str) -> Option<&'static str> {
+    None
+}
+
+/// A writing system, used to route words to the right language.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Script {
+    Latin,
+    Cyrillic,
+    Greek,
+    Arabic,
+    Devanagari,
+}
+
+impl Script {
+    /// Guess the script of `word` from its first letter.
+    pub fn of(word: &str) -> Option<Script> {
+        let c = word.chars().find(|c| c.is_alphabetic())?;
+        Some(match c {
+            '\u{0370}'..='\u{03FF}' | '\u{1F00}'..='\u{1FFF}' => Script::Greek,
+            '\u{0400}'..='\u{052F}' => Script::Cyrillic,
+            '\u{0600}'..='\u{06FF}' | '\u{0750}'..='\u{077F}' => Script::Arabic,
+            '\u{0900}'..='\u{097F}' => Script::Devanagari,
+            _ => Script::Latin,
+        })
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+impl FromStr for Language {
+    type Err = UnknownLanguage;
+
+    /// Parse an ISO 639-1 code such as `en` or `ru`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code = s.trim().to_lowercase();
+        Language::ALL
+            .into_iter()
+            .find(|l| l.code() == code)
+            .ok_or(UnknownLanguage(code))
+    }
+}
+
+/// The given string is not the code of a supported language.
+#[derive(thiserror::Error, Debug)]
+#[error("unknown language code: {0}")]
+pub struct UnknownLanguage(pub String);
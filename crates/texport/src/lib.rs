@@ -1,26 +1,147 @@
+#[cfg(feature = "zip")]
+mod archive;
+mod cache;
+#[cfg(feature = "charts")]
+mod charts;
+mod csv;
+mod diff;
+mod intern;
+mod json;
+mod labels;
+mod language;
+mod locate;
+mod media;
 mod messages;
+mod reader;
+mod report;
+mod sections;
+mod senders;
 mod stats;
 mod storage;
+#[cfg(test)]
+mod testing;
+mod time;
+mod tokenize;
+mod validate;
+#[cfg(feature = "watch")]
+mod watch;
 
-use std::{collections::HashMap, fmt::Display, io};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    io,
+    str::FromStr,
+    time::SystemTime,
+};
 
+use chrono::{DateTime, Utc};
 use messages::RawMessage;
+pub use messages::{ExportMessage, RawMessageRef};
 use serde::Deserialize;
 
+#[cfg(feature = "charts")]
+pub use self::charts::ChartOptions;
+#[cfg(feature = "watch")]
+pub use self::watch::StorageEvent;
 pub use self::{
-    stats::{ChatStats, StatsSettings},
-    storage::Storage,
+    diff::{Change, ParticipantChange, StatsDiff},
+    intern::Word,
+    json::{
+        ChatReport, Count, DayReport, DeletionsReport, GapReport, MembershipMonth,
+        MembershipReport, MentionReport, MilestoneReport, MonthCount, ParticipantCount,
+        ParticipantReport, PinReport, STATS_REPORT_VERSION, Score, StatsReport, StreakReport,
+        TitleChangeReport, TopicReport, TrackedReport, TrendReport,
+    },
+    labels::Labels,
+    language::{Language, Script, UnknownLanguage},
+    media::{DiskUsage, MediaReport},
+    reader::ChatReader,
+    sections::{Section, Sections},
+    senders::{SenderError, SenderNames},
+    stats::{
+        BusyDay, Cadence, ChatStats, ChatSummary, DELETED_ACCOUNT, EntityWords, GENERAL_TOPIC,
+        HIDDEN_FORWARD_SOURCE, IdGap, IdGaps, JsonDetail, Membership, MembershipChange, Milestone,
+        Pin, RankBy, StatsHasher, StatsMap, StatsSet, StatsSettings, TopicStats, UNLABELED,
+        UndatedPolicy, WordFilter,
+    },
+    storage::{
+        ChatFile, ChatFiles, ChatInfo, ChatLoadError, DuplicatePolicy, EXPORT_DIR_VAR,
+        ExportSummary, FindChatError, LoadError, OpenError, ParseError, ScanEvent, ScanOptions,
+        Storage, StorageConfig,
+    },
+    time::{DateBound, DateRange, DateRangeError, TimeZone},
+    tokenize::{CharCount, Tokenizer},
+    validate::{Anomaly, AnomalyKind},
 };
 
 #[derive(Debug, Deserialize)]
 pub struct Chat {
     pub name: String,
     #[serde(rename = "type")]
-    pub chat_type: String,
+    pub chat_type: ChatType,
     pub id: i64,
     pub messages: Vec<RawMessage>,
 }
 
+/// A chat export whose messages borrow their strings from the JSON, see
+/// [`RawMessageRef`].
+#[derive(Debug, Deserialize)]
+pub struct ChatRef<'a> {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub chat_type: ChatType,
+    pub id: i64,
+    #[serde(borrow)]
+    pub messages: Vec<RawMessageRef<'a>>,
+}
+
+impl<'a> ChatRef<'a> {
+    /// Parse a chat export, the contents of a `result.json`, borrowing
+    /// from `bytes`, e.g. a file read into memory or memory-mapped.
+    pub fn from_slice(bytes: &'a [u8]) -> Result<Self, ParseError> {
+        serde_json::from_slice(bytes)
+            .map_err(|e| ParseError::Json(locate::locate::<Self>(bytes, e)))
+    }
+}
+
+/// The kind of a chat, as Telegram Desktop names it in `result.json`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatType {
+    #[serde(rename = "personal_chat")]
+    Personal,
+    #[serde(rename = "bot_chat")]
+    Bot,
+    SavedMessages,
+    PrivateGroup,
+    PrivateSupergroup,
+    PublicSupergroup,
+    PrivateChannel,
+    PublicChannel,
+    /// A type this version doesn't know about.
+    #[serde(other)]
+    Other,
+}
+
+impl FromStr for ChatType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "personal_chat" => Ok(ChatType::Personal),
+            "bot_chat" => Ok(ChatType::Bot),
+            "saved_messages" => Ok(ChatType::SavedMessages),
+            "private_group" => Ok(ChatType::PrivateGroup),
+            "private_supergroup" => Ok(ChatType::PrivateSupergroup),
+            "public_supergroup" => Ok(ChatType::PublicSupergroup),
+            "private_channel" => Ok(ChatType::PrivateChannel),
+            "public_channel" => Ok(ChatType::PublicChannel),
+            _ => Err(format!("unknown chat type: {s}")),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(untagged)]
 pub enum Text {
@@ -40,6 +161,9 @@ pub struct TextEntity {
     #[serde(rename = "type")]
     pub entity_type: String,
     pub text: String,
+    /// The mentioned user of a `mention_name` entity, for people without
+    /// a username.
+    pub user_id: Option<i64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -61,6 +185,15 @@ pub enum Reaction {
     },
 }
 
+impl Reaction {
+    /// The most recent reactors, as far as the export lists them.
+    pub fn recent(&self) -> &[RecentReaction] {
+        match self {
+            Reaction::Emoji { recent, .. } | Reaction::CustomEmoji { recent, .. } => recent,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct RecentReaction {
     pub from: String,
@@ -69,86 +202,351 @@ pub struct RecentReaction {
 }
 
 impl Chat {
+    /// Parse a chat export, the contents of a `result.json`, from `reader`.
+    /// The reader is read to the end first, so it needn't be buffered.
+    pub fn from_reader<R: io::Read>(mut reader: R) -> Result<Self, ParseError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        #[cfg(feature = "simd")]
+        return Self::from_mut_slice(&mut bytes);
+        #[cfg(not(feature = "simd"))]
+        Self::from_slice(&bytes)
+    }
+
+    /// Parse a chat export, the contents of a `result.json`. If it can't
+    /// be, the error has the path to the value that couldn't be parsed,
+    /// and the id and date of its message.
+    ///
+    /// With the `simd` feature the export is parsed with simd-json, which
+    /// is faster than serde_json but needs a copy of `bytes` to work in.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, ParseError> {
+        #[cfg(feature = "simd")]
+        let chat = Self::from_mut_slice(&mut bytes.to_vec());
+        #[cfg(not(feature = "simd"))]
+        let chat = serde_json::from_slice(bytes).map_err(ParseError::Json);
+        chat.map_err(|e| match e {
+            ParseError::Json(e) => ParseError::Json(locate::locate::<Self>(bytes, e)),
+            e => e,
+        })
+    }
+
+    /// Parse a chat export with simd-json, which overwrites `bytes`, so
+    /// errors can't be located as by [`Chat::from_slice`].
+    #[cfg(feature = "simd")]
+    pub(crate) fn from_mut_slice(bytes: &mut [u8]) -> Result<Self, ParseError> {
+        simd_json::serde::from_slice(bytes)
+            .map_err(|e| ParseError::Json(serde::de::Error::custom(e)))
+    }
+
+    /// Read and parse the `result.json` at `path`.
+    ///
+    /// With the `mmap` feature the file is memory-mapped and parsed in
+    /// place instead of first being copied into memory, which lowers the
+    /// peak memory use for large exports. The file must not change while
+    /// it is parsed: another process writing to it then makes the result
+    /// undefined, and truncating it may crash the process.
+    ///
+    /// When the file is not a valid chat export, the error has the index of
+    /// the message that couldn't be parsed, which takes reading the file
+    /// again.
+    pub fn from_path(path: impl AsRef<std::path::Path>) -> Result<Self, ChatLoadError> {
+        let path = path.as_ref();
+        storage::parse_file(path).map_err(|e| ChatLoadError::new(path, e))
+    }
+
     pub fn write_export<W: io::Write>(&self, writer: &mut W, max: Option<usize>) -> io::Result<()> {
-        let mut messages = HashMap::new();
-        let max = max.unwrap_or(self.messages.len());
+        self.write_export_with(writer, max, ExportOptions::default())
+    }
 
-        for msg in self.messages.iter().take(max) {
-            if let RawMessage::Message {
-                id,
-                date,
-                from,
-                text,
-                edited,
-                reactions,
+    /// Like [`Chat::write_export`], writing dates as set in `options`.
+    pub fn write_export_with<W: io::Write>(
+        &self,
+        writer: &mut W,
+        max: Option<usize>,
+        options: ExportOptions,
+    ) -> io::Result<()> {
+        let max = max.unwrap_or(self.messages.len());
+        let messages = &self.messages[..max.min(self.messages.len())];
+        let targets = messages.iter().filter_map(|msg| match msg {
+            RawMessage::Message {
                 reply_to_message_id,
                 ..
-            } = msg
-            {
-                let msg_text = text.to_string().replace('\n', " ");
-                messages.insert(id, (from.as_str(), msg_text.clone()));
+            } => *reply_to_message_id,
+            _ => None,
+        });
+        let mut export = ExportWriter::with_reply_targets(writer, targets).with_options(options);
+        messages
+            .iter()
+            .try_for_each(|msg| export.write_message(msg))
+    }
+}
 
-                writeln!(writer, "[{}] @{}: {}", clean_date(date), from, msg_text)?;
+/// Characters of a message quoted in a reply to it by [`ExportWriter`].
+const QUOTE_CHARS: usize = 80;
 
-                // Handle edit information
-                if let Some(edited_date) = edited {
-                    writeln!(writer, "  ↳ [edited] {}", clean_date(edited_date))?;
-                }
+/// How [`ExportWriter`] writes messages.
+#[derive(Clone, Debug)]
+pub struct ExportOptions {
+    /// The time zone dates are written in. Old exports without Unix
+    /// timestamps only have dates in the time zone of the machine that
+    /// exported them, which are written as they are.
+    pub time_zone: TimeZone,
+    /// The format of dates, as for [`chrono::format::strftime`].
+    pub date_format: String,
+    /// Write every message on one line, with its line breaks as spaces,
+    /// rather than as it was sent.
+    pub single_line: bool,
+    /// Only messages sent at or after this moment are written.
+    pub since: Option<DateTime<Utc>>,
+    /// Only messages sent strictly before this moment are written.
+    /// Messages whose date can't be parsed are written whatever the range.
+    pub until: Option<DateTime<Utc>>,
+    /// Sender ids whose messages are written, e.g. found by
+    /// [`SenderNames::resolve`]. Everyone's are written when empty.
+    pub senders: HashSet<String>,
+}
 
-                // Handle replies
-                if let Some(reply_id) = reply_to_message_id {
-                    if let Some((replied_from, replied_text)) = messages.get(&reply_id) {
-                        writeln!(
-                            writer,
-                            "  ↳ [reply to msg#{}] @{}: {}",
-                            reply_id, replied_from, replied_text
-                        )?;
-                    } else {
-                        writeln!(writer, "  ↳ [reply to unknown msg#{}]", reply_id)?;
-                    }
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            time_zone: TimeZone::Local,
+            date_format: "%Y-%m-%d %H:%M:%S".to_string(),
+            single_line: false,
+            since: None,
+            until: None,
+            senders: HashSet::new(),
+        }
+    }
+}
+
+/// Writes messages as text one at a time, as [`Chat::write_export`] does,
+/// e.g. as they are read by a [`ChatReader`].
+pub struct ExportWriter<W> {
+    writer: W,
+    /// The ids of the messages that are replied to, if known beforehand.
+    targets: Option<HashSet<u64>>,
+    /// The sender and the start of the text of each message written so
+    /// far that may be replied to, to quote in replies.
+    quotes: HashMap<u64, (String, String)>,
+    options: ExportOptions,
+}
+
+impl<W: io::Write> ExportWriter<W> {
+    /// A writer that keeps a quote of every message, as any of them may be
+    /// replied to later.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            targets: None,
+            quotes: HashMap::new(),
+            options: ExportOptions::default(),
+        }
+    }
+
+    /// A writer that only keeps quotes of the messages with ids in
+    /// `targets`, the ones that are replied to.
+    pub fn with_reply_targets(writer: W, targets: impl IntoIterator<Item = u64>) -> Self {
+        Self {
+            targets: Some(targets.into_iter().collect()),
+            ..Self::new(writer)
+        }
+    }
+
+    /// Write dates as set in `options` rather than as by default.
+    pub fn with_options(self, options: ExportOptions) -> Self {
+        Self { options, ..self }
+    }
+
+    /// Write one message. Service messages, messages outside
+    /// [`ExportOptions::since`] and [`ExportOptions::until`], and those of
+    /// senders not in [`ExportOptions::senders`] are left out.
+    pub fn write_message(&mut self, msg: &RawMessage) -> io::Result<()> {
+        if !self.includes(msg) {
+            return Ok(());
+        }
+        let writer = &mut self.writer;
+        if let RawMessage::Message {
+            id,
+            date,
+            date_unixtime,
+            from,
+            text,
+            edited,
+            edited_unixtime,
+            reactions,
+            reply_to_message_id,
+            ..
+        } = msg
+        {
+            let from = from.as_deref().unwrap_or(DELETED_ACCOUNT);
+            if self.targets.as_ref().is_none_or(|t| t.contains(id)) {
+                self.quotes.insert(*id, (from.to_string(), quote(text)));
+            }
+
+            let date = format_date(date, Some(date_unixtime), &self.options);
+            write!(writer, "[{}] @{}: ", date, from)?;
+            if self.options.single_line {
+                text.write_flat(writer)?;
+                writeln!(writer)?;
+            } else {
+                writeln!(writer, "{text}")?;
+            }
+
+            // Handle edit information, which older exports only have as
+            // `edited` and newer ones may only have as `edited_unixtime`
+            if msg.is_edited() {
+                let edited_date = match edited {
+                    Some(edited) => Some(format_date(
+                        edited,
+                        edited_unixtime.as_deref(),
+                        &self.options,
+                    )),
+                    None => msg
+                        .edited()
+                        .and_then(|time| format_time(time, &self.options))
+                        .map(Cow::Owned),
+                };
+                match edited_date {
+                    Some(edited_date) => writeln!(writer, "  ↳ [edited] {}", edited_date)?,
+                    None => writeln!(writer, "  ↳ [edited]")?,
                 }
+            }
 
-                // Handle reactions
-                reactions.iter().try_for_each(|r| {
-                    let (icon, users): (String, String) = match r {
-                        Reaction::Emoji { emoji, recent, .. } => (
-                            emoji.clone(),
-                            recent.iter().map(|u| format!("@{}", u.from)).collect(),
-                        ),
-                        Reaction::CustomEmoji {
-                            document_id,
-                            recent,
-                            ..
-                        } => (
-                            format!("custom_emoji:{}", document_id),
-                            recent.iter().map(|u| format!("@{}", u.from)).collect(),
-                        ),
-                    };
-
-                    writeln!(writer, "  ↳ [reaction: {} by {}]", icon, users)
-                })?;
+            // Handle replies
+            if let Some(reply_id) = reply_to_message_id {
+                if let Some((replied_from, replied_text)) = self.quotes.get(reply_id) {
+                    writeln!(
+                        writer,
+                        "  ↳ [reply to msg#{}] @{}: {}",
+                        reply_id, replied_from, replied_text
+                    )?;
+                } else {
+                    writeln!(writer, "  ↳ [reply to unknown msg#{}]", reply_id)?;
+                }
             }
+
+            // Handle reactions
+            reactions.iter().try_for_each(|r| {
+                let (icon, users): (String, String) = match r {
+                    Reaction::Emoji { emoji, recent, .. } => (
+                        emoji.clone(),
+                        recent.iter().map(|u| format!("@{}", u.from)).collect(),
+                    ),
+                    Reaction::CustomEmoji {
+                        document_id,
+                        recent,
+                        ..
+                    } => (
+                        format!("custom_emoji:{}", document_id),
+                        recent.iter().map(|u| format!("@{}", u.from)).collect(),
+                    ),
+                };
+
+                writeln!(writer, "  ↳ [reaction: {} by {}]", icon, users)
+            })?;
         }
 
         Ok(())
     }
+
+    /// Whether `msg` is by one of the senders of the options and was sent
+    /// within their range, or has no date to tell.
+    fn includes(&self, msg: &RawMessage) -> bool {
+        if let RawMessage::Message { from_id, .. } = msg {
+            let senders = &self.options.senders;
+            if !senders.is_empty() && !senders.contains(from_id) {
+                return false;
+            }
+        }
+        let Some(date) = msg.date().map(DateTime::<Utc>::from) else {
+            return true;
+        };
+        self.options.since.is_none_or(|since| date >= since)
+            && self.options.until.is_none_or(|until| date < until)
+    }
 }
 
+impl Text {
+    /// The text as one string, with structured elements concatenated.
+    /// Plain texts are borrowed rather than copied.
+    pub fn flatten(&self) -> Cow<'_, str> {
+        match self {
+            Text::Plain(s) => Cow::Borrowed(s),
+            Text::Structured(_) => Cow::Owned(self.to_string()),
+        }
+    }
+
+    /// Write the text on one line, with its line breaks as spaces.
+    fn write_flat<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut write = |s: &str| {
+            for (i, line) in s.split('\n').enumerate() {
+                if i > 0 {
+                    writer.write_all(b" ")?;
+                }
+                writer.write_all(line.as_bytes())?;
+            }
+            Ok(())
+        };
+        match self {
+            Text::Plain(s) => write(s),
+            Text::Structured(elements) => elements.iter().try_for_each(|e| match e {
+                TextElement::String(s) => write(s),
+                TextElement::Entity(te) => write(&te.text),
+            }),
+        }
+    }
+}
+
+/// The start of `text` on one line, to quote in a reply to it.
+fn quote(text: &Text) -> String {
+    let flat = text.flatten().replace('\n', " ");
+    match flat.char_indices().nth(QUOTE_CHARS) {
+        Some((end, _)) => format!("{}…", &flat[..end]),
+        None => flat,
+    }
+}
+
+/// The text as it was sent. The elements of a structured text are written
+/// one after the other as they are: Telegram splits the text into them
+/// without dropping anything, line breaks between code blocks or list
+/// items included.
 impl Display for Text {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Text::Plain(s) => write!(f, "{s}"),
-            Text::Structured(elements) => elements
-                .iter()
-                .map(|e| match e {
-                    TextElement::String(s) => write!(f, "{s}"),
-                    TextElement::Entity(te) => write!(f, "{}", te.text),
-                })
-                .collect::<Result<_, _>>(),
+            Text::Plain(s) => f.write_str(s),
+            Text::Structured(elements) => elements.iter().try_for_each(|e| match e {
+                TextElement::String(s) => f.write_str(s),
+                TextElement::Entity(te) => f.write_str(&te.text),
+            }),
         }
     }
 }
 
-fn clean_date(date: &str) -> String {
-    date.replace('T', " ").replace('Z', "")
+/// The date of a message, from its Unix timestamp if it has a valid one,
+/// else from `date`, the local time of the exporting machine. `date` is
+/// kept as it is if it can't be parsed either, or if the date format of
+/// `options` can't be applied.
+fn format_date<'a>(date: &'a str, unixtime: Option<&str>, options: &ExportOptions) -> Cow<'a, str> {
+    use std::fmt::Write as _;
+
+    let formatted = match unixtime.and_then(messages::parse_unixtime) {
+        Some(time) => format_time(time, options),
+        None => messages::parse_local_date(date).and_then(|time| {
+            let mut formatted = String::new();
+            write!(formatted, "{}", time.format(&options.date_format)).ok()?;
+            Some(formatted)
+        }),
+    };
+    formatted.map_or(Cow::Borrowed(date), Cow::Owned)
+}
+
+/// `time` in the time zone and date format of `options`, or `None` if the
+/// date format can't be applied.
+fn format_time(time: SystemTime, options: &ExportOptions) -> Option<String> {
+    use std::fmt::Write as _;
+
+    let mut formatted = String::new();
+    let time = options.time_zone.convert(time);
+    write!(formatted, "{}", time.format(&options.date_format)).ok()?;
+    Some(formatted)
 }
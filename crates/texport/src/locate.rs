@@ -0,0 +1,147 @@
+use std::fmt;
+
+use serde::{
+    Deserialize, Deserializer,
+    de::{DeserializeSeed, Error as _, IgnoredAny, MapAccess, SeqAccess, Visitor},
+};
+use serde_json::Value;
+use serde_path_to_error::{Path, Segment};
+
+/// `error`, from deserializing `bytes` as a `T`, with the path to the
+/// value it is about, e.g. `messages[48211].text_entities[2].type`, and
+/// the id and date of the message it is in. Finding them takes reading
+/// `bytes` again, so it is only done once deserializing failed.
+pub(crate) fn locate<'de, T: Deserialize<'de>>(
+    bytes: &'de [u8],
+    error: serde_json::Error,
+) -> serde_json::Error {
+    let mut deserializer = serde_json::Deserializer::from_slice(bytes);
+    let Err(located) = serde_path_to_error::deserialize::<_, T>(&mut deserializer) else {
+        return error;
+    };
+    let header = message_index(located.path()).and_then(|index| {
+        let mut deserializer = serde_json::Deserializer::from_slice(bytes);
+        Messages(index)
+            .deserialize(&mut deserializer)
+            .ok()
+            .flatten()
+    });
+    with_location(error, &located.path().to_string(), header)
+}
+
+/// `error`, from deserializing `bytes` as the message at `index` of an
+/// export, with the path to the value it is about, as by [`locate`].
+pub(crate) fn locate_message<'de, T: Deserialize<'de>>(
+    index: usize,
+    bytes: &'de [u8],
+    error: serde_json::Error,
+) -> serde_json::Error {
+    let mut deserializer = serde_json::Deserializer::from_slice(bytes);
+    let Err(located) = serde_path_to_error::deserialize::<_, T>(&mut deserializer) else {
+        return error;
+    };
+    let path = match located.path().iter().next() {
+        Some(_) => format!("messages[{index}].{}", located.path()),
+        None => format!("messages[{index}]"),
+    };
+    with_location(error, &path, serde_json::from_slice(bytes).ok())
+}
+
+fn with_location(
+    error: serde_json::Error,
+    path: &str,
+    header: Option<MessageHeader>,
+) -> serde_json::Error {
+    match header {
+        Some(MessageHeader { id, date }) => serde_json::Error::custom(format!(
+            "{error}, at {path} (message {}, sent {})",
+            id.unwrap_or(Value::Null),
+            date.unwrap_or(Value::Null)
+        )),
+        None => serde_json::Error::custom(format!("{error}, at {path}")),
+    }
+}
+
+/// The index of the message `path` leads into, if it does.
+fn message_index(path: &Path) -> Option<usize> {
+    let mut segments = path.iter();
+    match (segments.next(), segments.next()) {
+        (Some(Segment::Map { key }), Some(Segment::Seq { index })) if key == "messages" => {
+            Some(*index)
+        }
+        _ => None,
+    }
+}
+
+/// What is needed of a message to point users to it. The values are kept
+/// as they are, since they may be what can't be deserialized.
+#[derive(Deserialize)]
+struct MessageHeader {
+    id: Option<Value>,
+    date: Option<Value>,
+}
+
+/// Deserializes the header of the message at this index of a chat export,
+/// skipping over everything else.
+struct Messages(usize);
+
+impl<'de> DeserializeSeed<'de> for Messages {
+    type Value = Option<MessageHeader>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Messages {
+    type Value = Option<MessageHeader>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a chat export")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut header = None;
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "messages" {
+                header = map.next_value_seed(Message(self.0))?;
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(header)
+    }
+}
+
+/// Deserializes the header of the message at this index of the messages
+/// of a chat export.
+struct Message(usize);
+
+impl<'de> DeserializeSeed<'de> for Message {
+    type Value = Option<MessageHeader>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Message {
+    type Value = Option<MessageHeader>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an array of messages")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut header = None;
+        let mut index = 0;
+        loop {
+            if index == self.0 {
+                header = seq.next_element()?;
+            } else if seq.next_element::<IgnoredAny>()?.is_none() {
+                return Ok(header);
+            }
+            index += 1;
+        }
+    }
+}
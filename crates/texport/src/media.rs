@@ -0,0 +1,199 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use log::warn;
+
+use crate::{
+    RawMessage, Storage,
+    storage::{ChatFile, LoadError, read_chat},
+};
+
+/// Directories Telegram Desktop puts media in, next to `result.json`.
+const MEDIA_DIRS: &[&str] = &[
+    "photos",
+    "files",
+    "video_files",
+    "voice_messages",
+    "round_video_messages",
+    "stickers",
+];
+
+/// What [`ChatFile::verify_media`] found.
+#[derive(Clone, Debug, Default)]
+pub struct MediaReport {
+    /// Referenced files that are on disk.
+    pub present: usize,
+    /// Total size of the referenced files that are on disk, in bytes.
+    pub bytes: u64,
+    /// Referenced files that are not on disk, in message order.
+    pub missing: Vec<PathBuf>,
+    /// References to files that were left out of the export on purpose,
+    /// e.g. by its size limit.
+    pub not_included: usize,
+    /// Files in the media directories that no message refers to, in path
+    /// order.
+    pub extra: Vec<PathBuf>,
+}
+
+/// How much disk space a chat takes, see [`Storage::disk_usage`].
+#[derive(Clone, Debug)]
+pub struct DiskUsage {
+    pub id: i64,
+    pub name: String,
+    /// Size of `result.json` in bytes, summed over the parts of a chat.
+    pub manifest: u64,
+    /// Size in bytes of each subdirectory of the chat directories, such
+    /// as `photos` or `voice_messages`, by name.
+    pub media: BTreeMap<String, u64>,
+}
+
+impl DiskUsage {
+    /// Size of the manifests and media together, in bytes.
+    pub fn total(&self) -> u64 {
+        self.manifest + self.media.values().sum::<u64>()
+    }
+}
+
+impl Storage {
+    /// How much disk space each chat takes, largest first. Symbolic links
+    /// are not followed, and what can't be read is skipped with a warning.
+    /// Chats that are not on disk are left out.
+    pub fn disk_usage(&self) -> Vec<DiskUsage> {
+        let mut usage: Vec<_> = self
+            .chats
+            .iter()
+            .filter(|(_, chat)| chat.is_on_disk())
+            .map(|(&id, chat)| {
+                let mut usage = DiskUsage {
+                    id,
+                    name: chat.info.name.clone(),
+                    manifest: 0,
+                    media: BTreeMap::new(),
+                };
+                for path in &chat.paths {
+                    usage.manifest += size(path);
+                    let dir = path.parent().unwrap_or(Path::new(""));
+                    for subdir in entries(dir).filter(|path| is_dir(path)) {
+                        let name = subdir.file_name().unwrap_or_default();
+                        *usage
+                            .media
+                            .entry(name.to_string_lossy().into_owned())
+                            .or_default() += dir_size(&subdir);
+                    }
+                }
+                usage
+            })
+            .collect();
+        usage.sort_by(|a, b| b.total().cmp(&a.total()).then(a.id.cmp(&b.id)));
+        usage
+    }
+}
+
+/// The size of a file in bytes, or 0 with a warning if it can't be read.
+fn size(path: &Path) -> u64 {
+    fs::symlink_metadata(path)
+        .inspect_err(|e| warn!("skipping {path:?}: {e}"))
+        .map_or(0, |metadata| metadata.len())
+}
+
+/// Whether `path` is a directory, and not a link to one.
+fn is_dir(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_dir())
+}
+
+/// The total size of the files in `dir` and below it, in bytes.
+fn dir_size(dir: &Path) -> u64 {
+    entries(dir)
+        .map(|path| match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.is_dir() => dir_size(&path),
+            Ok(metadata) if metadata.is_file() => metadata.len(),
+            // Symbolic links and the like
+            Ok(_) => 0,
+            Err(e) => {
+                warn!("skipping {path:?}: {e}");
+                0
+            }
+        })
+        .sum()
+}
+
+/// The paths of the entries of `dir`, warning about those that can't be
+/// read.
+fn entries(dir: &Path) -> impl Iterator<Item = PathBuf> {
+    fs::read_dir(dir)
+        .inspect_err(|e| warn!("skipping directory {dir:?}: {e}"))
+        .into_iter()
+        .flatten()
+        // Skip entries we failed to read, warning on error
+        .filter_map(|e| e.inspect_err(|e| warn!("skipping entry: {e}")).ok())
+        .map(|entry| entry.path())
+}
+
+impl ChatFile {
+    /// Parse the chat and check that the media files its messages refer
+    /// to are on disk, relative to the directory of each `result.json`.
+    /// Files referenced more than once are counted once.
+    pub fn verify_media(&self) -> Result<MediaReport, LoadError> {
+        if !self.is_on_disk() {
+            return Err(LoadError::Io {
+                path: self.paths[0].clone(),
+                source: io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "media can only be verified on disk",
+                ),
+            });
+        }
+        let mut report = MediaReport::default();
+        for path in &self.paths {
+            let chat = read_chat(path)?;
+            let dir = path.parent().unwrap_or(Path::new(""));
+            let mut media_dirs: BTreeSet<_> = MEDIA_DIRS.iter().map(|d| dir.join(d)).collect();
+            let mut referenced = BTreeSet::new();
+            for media in chat.messages.iter().flat_map(RawMessage::media) {
+                // "(File not included. Change data exporting settings to download.)"
+                // and the like
+                if media.starts_with('(') {
+                    report.not_included += 1;
+                    continue;
+                }
+                let file = dir.join(media);
+                if let Some(parent) = file.parent().filter(|&parent| parent != dir) {
+                    media_dirs.insert(parent.to_path_buf());
+                }
+                if !referenced.insert(file.clone()) {
+                    continue;
+                }
+                match fs::metadata(&file) {
+                    Ok(metadata) => {
+                        report.present += 1;
+                        report.bytes += metadata.len();
+                    }
+                    Err(_) => report.missing.push(file),
+                }
+            }
+
+            for media_dir in media_dirs {
+                let entries = match fs::read_dir(&media_dir) {
+                    Ok(entries) => entries,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                    Err(e) => {
+                        warn!("skipping {media_dir:?}: {e}");
+                        continue;
+                    }
+                };
+                report.extra.extend(
+                    entries
+                        // Skip entries we failed to read, warning on error
+                        .filter_map(|e| e.inspect_err(|e| warn!("skipping entry: {e}")).ok())
+                        .map(|entry| entry.path())
+                        .filter(|file| file.is_file() && !referenced.contains(file)),
+                );
+            }
+        }
+        report.extra.sort();
+        Ok(report)
+    }
+}
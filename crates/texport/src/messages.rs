@@ -1,12 +1,15 @@
 use std::{
+    borrow::Cow,
     collections::HashMap,
+    fmt,
     time::{Duration, SystemTime},
 };
 
+use chrono::{DateTime, NaiveDateTime, Utc};
 use indexmap::IndexMap;
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer, de::Visitor};
 
-use crate::{Reaction, Text, TextEntity};
+use crate::{DELETED_ACCOUNT, Reaction, Text, TextEntity};
 
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type")]
@@ -15,72 +18,426 @@ pub enum RawMessage {
     Message {
         id: u64,
         date: String,
+        /// Missing from older exports.
+        #[serde(default, deserialize_with = "unixtime")]
         date_unixtime: String,
-        from: String,
+        /// `None` for deleted accounts, see [`DELETED_ACCOUNT`].
+        #[serde(default)]
+        from: Option<String>,
         from_id: String,
         reply_to_message_id: Option<u64>,
         text: Text,
         text_entities: Vec<TextEntity>,
         edited: Option<String>,
+        #[serde(default, deserialize_with = "opt_unixtime")]
         edited_unixtime: Option<String>,
+        /// `Some(None)` for forwards whose original sender is hidden.
+        #[serde(default, deserialize_with = "deserialize_some")]
+        forwarded_from: Option<Option<String>>,
         #[serde(default)]
         reactions: Vec<Reaction>,
+        /// Media files, relative to the chat directory, or a placeholder
+        /// such as `(File not included. …)` when left out of the export.
+        photo: Option<String>,
+        file: Option<String>,
+        thumbnail: Option<String>,
     },
     #[serde(rename = "service")]
     Service {
         id: u64,
         date: String,
+        /// Missing from older exports.
+        #[serde(default, deserialize_with = "unixtime")]
         date_unixtime: String,
-        actor: String,
+        /// `None` for deleted accounts, see [`DELETED_ACCOUNT`].
+        #[serde(default)]
+        actor: Option<String>,
+        /// Missing from some older exports.
+        #[serde(default)]
         actor_id: String,
         action: String,
         duration_seconds: Option<u32>,
         discard_reason: Option<String>,
+        /// Members added or removed; `None` for deleted accounts.
+        #[serde(default)]
+        members: Vec<Option<String>>,
+        /// Who shared the link of a `join_group_by_link` action.
+        inviter: Option<String>,
+        /// The pinned message of a `pin_message` action.
+        message_id: Option<u64>,
+        /// The new title of an `edit_group_title` or `create_group` action,
+        /// or the title of a `topic_created` one.
+        title: Option<String>,
+        /// The new chat photo of an `edit_group_photo` action.
+        photo: Option<String>,
+        text: Text,
+        text_entities: Vec<TextEntity>,
+    },
+}
+
+/// A message of a chat export that borrows its strings from the JSON it
+/// was parsed from, where they hold no escapes, rather than copying them
+/// as [`RawMessage`] does. Texts, entities and reactions are still owned.
+///
+/// Parse a whole chat of them with [`ChatRef::from_slice`](crate::ChatRef::from_slice),
+/// and analyze them as [`RawMessage`]s, with [`ChatStats::push`](crate::ChatStats::push).
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+pub enum RawMessageRef<'a> {
+    #[serde(rename = "message")]
+    Message {
+        id: u64,
+        #[serde(borrow)]
+        date: Cow<'a, str>,
+        #[serde(borrow, default, deserialize_with = "borrow_unixtime")]
+        date_unixtime: Cow<'a, str>,
+        #[serde(borrow, default, deserialize_with = "borrow_opt_str")]
+        from: Option<Cow<'a, str>>,
+        #[serde(borrow)]
+        from_id: Cow<'a, str>,
+        reply_to_message_id: Option<u64>,
+        text: Text,
+        text_entities: Vec<TextEntity>,
+        #[serde(borrow, default, deserialize_with = "borrow_opt_str")]
+        edited: Option<Cow<'a, str>>,
+        #[serde(borrow, default, deserialize_with = "borrow_opt_unixtime")]
+        edited_unixtime: Option<Cow<'a, str>>,
+        /// `Some(None)` for forwards whose original sender is hidden.
+        #[serde(borrow, default, deserialize_with = "borrow_some_str")]
+        forwarded_from: Option<Option<Cow<'a, str>>>,
+        #[serde(default)]
+        reactions: Vec<Reaction>,
+        /// Media files, as in [`RawMessage`].
+        #[serde(borrow, default, deserialize_with = "borrow_opt_str")]
+        photo: Option<Cow<'a, str>>,
+        #[serde(borrow, default, deserialize_with = "borrow_opt_str")]
+        file: Option<Cow<'a, str>>,
+        #[serde(borrow, default, deserialize_with = "borrow_opt_str")]
+        thumbnail: Option<Cow<'a, str>>,
+    },
+    #[serde(rename = "service")]
+    Service {
+        id: u64,
+        #[serde(borrow)]
+        date: Cow<'a, str>,
+        #[serde(borrow, default, deserialize_with = "borrow_unixtime")]
+        date_unixtime: Cow<'a, str>,
+        #[serde(borrow, default, deserialize_with = "borrow_opt_str")]
+        actor: Option<Cow<'a, str>>,
+        #[serde(borrow, default)]
+        actor_id: Cow<'a, str>,
+        #[serde(borrow)]
+        action: Cow<'a, str>,
+        duration_seconds: Option<u32>,
+        #[serde(borrow, default, deserialize_with = "borrow_opt_str")]
+        discard_reason: Option<Cow<'a, str>>,
+        #[serde(default)]
+        members: Vec<Option<String>>,
+        #[serde(borrow, default, deserialize_with = "borrow_opt_str")]
+        inviter: Option<Cow<'a, str>>,
+        message_id: Option<u64>,
+        #[serde(borrow, default, deserialize_with = "borrow_opt_str")]
+        title: Option<Cow<'a, str>>,
+        #[serde(borrow, default, deserialize_with = "borrow_opt_str")]
+        photo: Option<Cow<'a, str>>,
         text: Text,
         text_entities: Vec<TextEntity>,
     },
 }
 
-#[derive(Default)]
+/// A message that [`ChatStats`](crate::ChatStats) can analyze: a
+/// [`RawMessage`] or a [`RawMessageRef`].
+pub trait ExportMessage: sealed::Sealed {}
+
+impl ExportMessage for RawMessage {}
+
+impl ExportMessage for RawMessageRef<'_> {}
+
+// `Sealed` can't be named outside the crate, so neither can the views it
+// returns be reached.
+#[allow(private_interfaces)]
+mod sealed {
+    use super::{Message, Service};
+    use crate::TextEntity;
+
+    /// What the statistics read of a message, whatever its type.
+    pub trait Sealed {
+        fn id(&self) -> u64;
+        /// The Unix timestamp of the message, as written in the export.
+        fn unixtime(&self) -> &str;
+        fn date(&self) -> Option<std::time::SystemTime>;
+        /// The action and entities of a service message.
+        fn service_action(&self) -> Option<(&str, &[TextEntity])>;
+        fn service(&self) -> Option<Service<'_>>;
+        fn message(&self) -> Option<(u64, Message<'_>)>;
+    }
+}
+
+#[allow(private_interfaces)]
+impl sealed::Sealed for RawMessage {
+    fn id(&self) -> u64 {
+        self.id()
+    }
+
+    fn unixtime(&self) -> &str {
+        let (RawMessage::Message { date_unixtime, .. } | RawMessage::Service { date_unixtime, .. }) =
+            self;
+        date_unixtime
+    }
+
+    fn date(&self) -> Option<SystemTime> {
+        self.date()
+    }
+
+    fn service_action(&self) -> Option<(&str, &[TextEntity])> {
+        match self {
+            RawMessage::Service {
+                action,
+                text_entities,
+                ..
+            } => Some((action, text_entities)),
+            RawMessage::Message { .. } => None,
+        }
+    }
+
+    fn service(&self) -> Option<Service<'_>> {
+        self.service()
+    }
+
+    fn message(&self) -> Option<(u64, Message<'_>)> {
+        self.message()
+    }
+}
+
+#[allow(private_interfaces)]
+impl sealed::Sealed for RawMessageRef<'_> {
+    fn id(&self) -> u64 {
+        match self {
+            RawMessageRef::Message { id, .. } | RawMessageRef::Service { id, .. } => *id,
+        }
+    }
+
+    fn unixtime(&self) -> &str {
+        let (RawMessageRef::Message { date_unixtime, .. }
+        | RawMessageRef::Service { date_unixtime, .. }) = self;
+        date_unixtime
+    }
+
+    fn date(&self) -> Option<SystemTime> {
+        parse_unixtime(self.unixtime())
+    }
+
+    fn service_action(&self) -> Option<(&str, &[TextEntity])> {
+        match self {
+            RawMessageRef::Service {
+                action,
+                text_entities,
+                ..
+            } => Some((action, text_entities)),
+            RawMessageRef::Message { .. } => None,
+        }
+    }
+
+    fn service(&self) -> Option<Service<'_>> {
+        let RawMessageRef::Service {
+            date_unixtime,
+            actor,
+            action,
+            members,
+            inviter,
+            message_id,
+            title,
+            ..
+        } = self
+        else {
+            return None;
+        };
+        Some(Service {
+            date: parse_unixtime(date_unixtime),
+            actor: actor.as_deref().unwrap_or(DELETED_ACCOUNT),
+            action: ServiceAction::new(
+                action,
+                members,
+                inviter.as_deref(),
+                *message_id,
+                title.as_deref(),
+            ),
+        })
+    }
+
+    fn message(&self) -> Option<(u64, Message<'_>)> {
+        let RawMessageRef::Message {
+            id,
+            reply_to_message_id,
+            date_unixtime,
+            from,
+            from_id,
+            text,
+            reactions,
+            date,
+            edited,
+            edited_unixtime,
+            text_entities,
+            forwarded_from,
+            ..
+        } = self
+        else {
+            return None;
+        };
+        let message = Message {
+            date: parse_unixtime(date_unixtime),
+            from: from.as_deref().unwrap_or(DELETED_ACCOUNT),
+            from_id,
+            reply_to_message_id: *reply_to_message_id,
+            text: text.flatten(),
+            reactions,
+            edited: edit_time(
+                date,
+                date_unixtime,
+                edited.as_deref(),
+                edited_unixtime.as_deref(),
+            ),
+            text_entities,
+            forwarded_from: forwarded_from.as_ref().map(Option::as_deref),
+        };
+        Some((*id, message))
+    }
+}
+
+/// Indexes messages by id to follow reply chains.
+#[derive(Debug, Default)]
 pub(crate) struct IndexedMessages {
-    messages: IndexMap<u64, Message>,
+    messages: IndexMap<u64, ChainMessage>,
     // store the length of the reply‐chain ending at each message ID
     chain_lengths: HashMap<u64, usize>,
 }
 
+/// What the reply index keeps of each message.
 #[derive(Debug)]
-pub(crate) struct Message {
+pub(crate) struct ChainMessage {
     pub date: Option<SystemTime>,
     pub from: String,
+    pub from_id: String,
     pub reply_to_message_id: Option<u64>,
     pub text: String,
-    pub reactions: Vec<Reaction>,
-    pub edited: Option<SystemTime>,
-    pub text_entities: Vec<TextEntity>,
+}
+
+/// A regular message borrowed from a [`RawMessage`], with parsed timestamps
+/// and flattened text.
+#[derive(Debug)]
+pub(crate) struct Message<'a> {
+    pub date: Option<SystemTime>,
+    pub from: &'a str,
+    pub from_id: &'a str,
+    pub reply_to_message_id: Option<u64>,
+    pub text: Cow<'a, str>,
+    pub reactions: &'a [Reaction],
+    /// `Some(None)` for edits whose time can't be told.
+    pub edited: Option<Option<SystemTime>>,
+    pub text_entities: &'a [TextEntity],
+    /// `Some(None)` for forwards whose original sender is hidden.
+    pub forwarded_from: Option<Option<&'a str>>,
+}
+
+/// A service message borrowed from a [`RawMessage`].
+#[derive(Debug)]
+pub(crate) struct Service<'a> {
+    pub date: Option<SystemTime>,
+    pub actor: &'a str,
+    pub action: ServiceAction<'a>,
+}
+
+/// The service actions the statistics look into, with their payloads.
+#[derive(Debug)]
+pub(crate) enum ServiceAction<'a> {
+    /// `invite_members`: the actor added `members` to the chat.
+    InviteMembers(&'a [Option<String>]),
+    /// `join_group_by_link`: the actor joined through someone's link.
+    JoinByLink { inviter: Option<&'a str> },
+    /// `remove_members`: `members` left or were removed by the actor.
+    RemoveMembers(&'a [Option<String>]),
+    /// `pin_message`: the actor pinned the message with this id.
+    PinMessage(u64),
+    /// `create_group` or `edit_group_title`: the chat got this title.
+    EditTitle(&'a str),
+    /// `edit_group_photo`: the chat got a new photo.
+    EditPhoto,
+    /// `topic_created`: the actor created a forum topic with this title.
+    CreateTopic(&'a str),
+    /// Any other action.
+    Other,
+}
+
+impl<'a> ServiceAction<'a> {
+    /// The action named `action` in the export, with the payload it uses.
+    fn new(
+        action: &str,
+        members: &'a [Option<String>],
+        inviter: Option<&'a str>,
+        message_id: Option<u64>,
+        title: Option<&'a str>,
+    ) -> Self {
+        match action {
+            "invite_members" => ServiceAction::InviteMembers(members),
+            "join_group_by_link" => ServiceAction::JoinByLink { inviter },
+            "remove_members" => ServiceAction::RemoveMembers(members),
+            "pin_message" => message_id.map_or(ServiceAction::Other, ServiceAction::PinMessage),
+            "create_group" | "edit_group_title" => {
+                title.map_or(ServiceAction::Other, ServiceAction::EditTitle)
+            }
+            "edit_group_photo" => ServiceAction::EditPhoto,
+            "topic_created" => title.map_or(ServiceAction::Other, ServiceAction::CreateTopic),
+            _ => ServiceAction::Other,
+        }
+    }
 }
 
 impl IndexedMessages {
-    pub(crate) fn add_message(&mut self, id: u64, message: Message) {
-        self.messages.insert(id, message);
+    /// Index `message` and return the length of the reply chain ending at it.
+    pub(crate) fn add_message(&mut self, id: u64, message: &Message<'_>) -> usize {
+        self.messages.insert(
+            id,
+            ChainMessage {
+                date: message.date,
+                from: message.from.to_string(),
+                from_id: message.from_id.to_string(),
+                reply_to_message_id: message.reply_to_message_id,
+                text: message.text.to_string(),
+            },
+        );
 
         // 2) Compute this message’s chain length
         let length = if let Some(parent_id) = self.messages[&id].reply_to_message_id {
-            // parent’s chain length + 1, or 1 if parent not seen
-            1 + self.chain_lengths.get(&parent_id).cloned().unwrap_or(1)
+            // parent’s chain length + 1, or 1 if the parent isn't in the
+            // export, e.g. deleted or sent before it
+            1 + self.chain_lengths.get(&parent_id).cloned().unwrap_or(0)
         } else {
             1
         };
         self.chain_lengths.insert(id, length);
+        length
     }
 
-    pub(crate) fn longest_chain(&self) -> Vec<&Message> {
-        let mut chain = Vec::with_capacity(self.chain_lengths.len());
-        let mut current = self.chain_lengths.iter().max_by_key(|e| e.1).map(|e| *e.0);
+    /// The indexed message with this `id`, if it has been seen.
+    pub(crate) fn get(&self, id: u64) -> Option<&ChainMessage> {
+        self.messages.get(&id)
+    }
+
+    /// The reply chain ending at message `id`, oldest message first. The
+    /// chain stops at a parent that isn't in the export, and is never
+    /// longer than computed by [`IndexedMessages::add_message`], so a
+    /// message replying to itself doesn't loop.
+    pub(crate) fn chain_ending_at(&self, id: u64) -> Vec<&ChainMessage> {
+        let length = self.chain_lengths.get(&id).copied().unwrap_or(0);
+        let mut chain = Vec::new();
+        let mut current = Some(id);
 
-        while let Some(msg_id) = current {
-            let msg = self.messages.get(&msg_id).unwrap();
+        while let Some(msg) = current.and_then(|id| self.messages.get(&id)) {
+            if chain.len() == length {
+                break;
+            }
             chain.push(msg);
-            current = self.messages[&msg_id].reply_to_message_id;
+            current = msg.reply_to_message_id;
         }
         chain.reverse();
 
@@ -89,42 +446,374 @@ impl IndexedMessages {
 }
 
 impl RawMessage {
-    pub(crate) fn message(self) -> Option<(u64, Message)> {
+    /// The message id, for regular and service messages alike.
+    pub fn id(&self) -> u64 {
+        match self {
+            RawMessage::Message { id, .. } | RawMessage::Service { id, .. } => *id,
+        }
+    }
+
+    /// When the message was sent, or `None` if the timestamp can't be parsed.
+    pub fn date(&self) -> Option<SystemTime> {
+        let (RawMessage::Message { date_unixtime, .. } | RawMessage::Service { date_unixtime, .. }) =
+            self;
+        parse_unixtime(date_unixtime)
+    }
+
+    /// When the message was last edited, or `None` if it wasn't or the
+    /// time can't be told, see [`RawMessage::is_edited`].
+    pub fn edited(&self) -> Option<SystemTime> {
+        match self {
+            RawMessage::Message {
+                date,
+                date_unixtime,
+                edited,
+                edited_unixtime,
+                ..
+            } => edit_time(
+                date,
+                date_unixtime,
+                edited.as_deref(),
+                edited_unixtime.as_deref(),
+            )
+            .flatten(),
+            RawMessage::Service { .. } => None,
+        }
+    }
+
+    /// Whether the message was edited. Older exports only have the local
+    /// time of the edit, `edited`, and newer ones its Unix timestamp too,
+    /// `edited_unixtime`; either one is enough.
+    pub fn is_edited(&self) -> bool {
+        match self {
+            RawMessage::Message {
+                edited,
+                edited_unixtime,
+                ..
+            } => edited.is_some() || edited_unixtime.is_some(),
+            RawMessage::Service { .. } => false,
+        }
+    }
+
+    /// Whether this message should be kept rather than `kept`, an earlier
+    /// copy of it with the same id: only if it was edited later. Exports of
+    /// a chat only differ where messages were edited, so otherwise the
+    /// first copy is kept, as [`ChatStats::push`](crate::ChatStats::push)
+    /// does.
+    pub(crate) fn supersedes(&self, kept: &RawMessage) -> bool {
+        self.edited() > kept.edited()
+    }
+
+    /// The media files the message refers to, as written in the export.
+    pub fn media(&self) -> impl Iterator<Item = &str> {
+        let files = match self {
+            RawMessage::Message {
+                photo,
+                file,
+                thumbnail,
+                ..
+            } => [photo, file, thumbnail],
+            RawMessage::Service { photo, .. } => [photo, &None, &None],
+        };
+        files.into_iter().flatten().map(String::as_str)
+    }
+
+    /// Borrow this as a [`Service`] message, or `None` for regular messages.
+    pub(crate) fn service(&self) -> Option<Service<'_>> {
+        let RawMessage::Service {
+            date_unixtime,
+            actor,
+            action,
+            members,
+            inviter,
+            message_id,
+            title,
+            ..
+        } = self
+        else {
+            return None;
+        };
+        Some(Service {
+            date: parse_unixtime(date_unixtime),
+            actor: actor.as_deref().unwrap_or(DELETED_ACCOUNT),
+            action: ServiceAction::new(
+                action,
+                members,
+                inviter.as_deref(),
+                *message_id,
+                title.as_deref(),
+            ),
+        })
+    }
+
+    /// Borrow this as a regular [`Message`], or `None` for service messages.
+    pub(crate) fn message(&self) -> Option<(u64, Message<'_>)> {
         if let RawMessage::Message {
             id,
             reply_to_message_id,
             date_unixtime,
             from,
+            from_id,
             text,
             reactions,
+            date,
+            edited,
             edited_unixtime,
             text_entities,
+            forwarded_from,
             ..
         } = self
         {
-            let date = date_unixtime
-                .parse::<u64>()
-                .map(|t| std::time::UNIX_EPOCH + Duration::from_secs(t))
-                .ok();
-            let edited = edited_unixtime
-                .map(|d| {
-                    d.parse::<u64>()
-                        .map(|t| std::time::UNIX_EPOCH + Duration::from_secs(t))
-                        .ok()
-                })
-                .flatten();
-            let bm = Message {
+            let edited = edit_time(
                 date,
-                from: from.clone(),
-                reply_to_message_id: reply_to_message_id,
-                text: format!("{text}"),
+                date_unixtime,
+                edited.as_deref(),
+                edited_unixtime.as_deref(),
+            );
+            let bm = Message {
+                date: parse_unixtime(date_unixtime),
+                from: from.as_deref().unwrap_or(DELETED_ACCOUNT),
+                from_id,
+                reply_to_message_id: *reply_to_message_id,
+                text: text.flatten(),
                 reactions,
                 edited,
                 text_entities,
+                forwarded_from: forwarded_from.as_ref().map(Option::as_deref),
             };
-            Some((id, bm))
+            Some((*id, bm))
         } else {
             None
         }
     }
 }
+
+/// Deserialize a present field as `Some`, so that with `#[serde(default)]`
+/// a missing field (`None`) can be told apart from `null` (`Some(None)`).
+fn deserialize_some<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// A string of the export, borrowed from it unless it holds escapes.
+struct BorrowedStr<'a>(Cow<'a, str>);
+
+impl<'de: 'a, 'a> Deserialize<'de> for BorrowedStr<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct StrVisitor;
+
+        impl<'de> Visitor<'de> for StrVisitor {
+            type Value = BorrowedStr<'de>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a string")
+            }
+
+            fn visit_borrowed_str<E>(self, s: &'de str) -> Result<Self::Value, E> {
+                Ok(BorrowedStr(Cow::Borrowed(s)))
+            }
+
+            fn visit_str<E>(self, s: &str) -> Result<Self::Value, E> {
+                Ok(BorrowedStr(Cow::Owned(s.to_string())))
+            }
+
+            fn visit_string<E>(self, s: String) -> Result<Self::Value, E> {
+                Ok(BorrowedStr(Cow::Owned(s)))
+            }
+        }
+
+        deserializer.deserialize_str(StrVisitor)
+    }
+}
+
+/// A Unix timestamp of the export, which newer exports write as a number
+/// rather than a string. Numbers are kept as their decimal digits, like
+/// the strings of older exports.
+struct UnixTime<'a>(Cow<'a, str>);
+
+impl<'de: 'a, 'a> Deserialize<'de> for UnixTime<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct UnixTimeVisitor;
+
+        impl<'de> Visitor<'de> for UnixTimeVisitor {
+            type Value = UnixTime<'de>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a Unix timestamp, as a string or a number")
+            }
+
+            fn visit_borrowed_str<E>(self, s: &'de str) -> Result<Self::Value, E> {
+                Ok(UnixTime(Cow::Borrowed(s)))
+            }
+
+            fn visit_str<E>(self, s: &str) -> Result<Self::Value, E> {
+                Ok(UnixTime(Cow::Owned(s.to_string())))
+            }
+
+            fn visit_string<E>(self, s: String) -> Result<Self::Value, E> {
+                Ok(UnixTime(Cow::Owned(s)))
+            }
+
+            fn visit_u64<E>(self, t: u64) -> Result<Self::Value, E> {
+                Ok(UnixTime(Cow::Owned(t.to_string())))
+            }
+
+            fn visit_i64<E>(self, t: i64) -> Result<Self::Value, E> {
+                Ok(UnixTime(Cow::Owned(t.to_string())))
+            }
+        }
+
+        deserializer.deserialize_any(UnixTimeVisitor)
+    }
+}
+
+/// Deserialize a Unix timestamp given as a string or a number.
+pub(crate) fn unixtime<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    UnixTime::deserialize(deserializer).map(|t| t.0.into_owned())
+}
+
+/// Deserialize an optional Unix timestamp given as a string or a number.
+fn opt_unixtime<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    Option::<UnixTime>::deserialize(deserializer).map(|t| t.map(|t| t.0.into_owned()))
+}
+
+/// [`unixtime`], borrowed where possible.
+fn borrow_unixtime<'de: 'a, 'a, D>(deserializer: D) -> Result<Cow<'a, str>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    UnixTime::deserialize(deserializer).map(|t| t.0)
+}
+
+/// [`opt_unixtime`], borrowed where possible.
+fn borrow_opt_unixtime<'de: 'a, 'a, D>(deserializer: D) -> Result<Option<Cow<'a, str>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<UnixTime>::deserialize(deserializer).map(|t| t.map(|t| t.0))
+}
+
+/// Deserialize an optional string, borrowed where possible.
+fn borrow_opt_str<'de: 'a, 'a, D>(deserializer: D) -> Result<Option<Cow<'a, str>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<BorrowedStr>::deserialize(deserializer).map(|s| s.map(|s| s.0))
+}
+
+/// [`borrow_opt_str`], as `Some` like [`deserialize_some`].
+fn borrow_some_str<'de: 'a, 'a, D>(
+    deserializer: D,
+) -> Result<Option<Option<Cow<'a, str>>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    borrow_opt_str(deserializer).map(Some)
+}
+
+/// When a message sent at `date`, or `unixtime`, was edited, if it was:
+/// `edited_unixtime`, or else `edited`, the local time of the edit on the
+/// exporting machine, shifted into UTC by as much as `date` is from
+/// `unixtime`. `Some(None)` if the message was edited, but when can't be
+/// told.
+fn edit_time(
+    date: &str,
+    unixtime: &str,
+    edited: Option<&str>,
+    edited_unixtime: Option<&str>,
+) -> Option<Option<SystemTime>> {
+    if let Some(time) = edited_unixtime.and_then(parse_unixtime) {
+        return Some(Some(time));
+    }
+    match edited {
+        Some(edited) => Some(local_edit_time(date, unixtime, edited)),
+        None => edited_unixtime.map(|_| None),
+    }
+}
+
+/// The time of an edit at local time `edited`, shifted into UTC as `date`
+/// is into `unixtime`.
+fn local_edit_time(date: &str, unixtime: &str, edited: &str) -> Option<SystemTime> {
+    let sent = DateTime::<Utc>::from(parse_unixtime(unixtime)?);
+    let offset = parse_local_date(date)?.and_utc() - sent;
+    let edited = parse_local_date(edited)?.and_utc() - offset;
+    Some(edited.into())
+}
+
+/// Parse a date as Telegram Desktop writes it, in the local time of the
+/// exporting machine, e.g. `2024-05-01T12:34:56`.
+pub(crate) fn parse_local_date(date: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S%.f").ok()
+}
+
+pub(crate) fn parse_unixtime(unixtime: &str) -> Option<SystemTime> {
+    unixtime
+        .parse::<u64>()
+        .map(|t| std::time::UNIX_EPOCH + Duration::from_secs(t))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Message `id`, with its id as text, replying to `reply_to`.
+    fn reply(id: u64, reply_to: Option<u64>) -> (u64, Message<'static>) {
+        let message = Message {
+            date: None,
+            from: "Alice",
+            from_id: "useralice",
+            reply_to_message_id: reply_to,
+            text: Cow::Owned(id.to_string()),
+            reactions: &[],
+            edited: None,
+            text_entities: &[],
+            forwarded_from: None,
+        };
+        (id, message)
+    }
+
+    /// Index `messages` and return the chain length of each.
+    fn index(messages: &[(u64, Option<u64>)]) -> (IndexedMessages, Vec<usize>) {
+        let mut indexed = IndexedMessages::default();
+        let lengths = messages
+            .iter()
+            .map(|&(id, reply_to)| {
+                let (id, message) = reply(id, reply_to);
+                indexed.add_message(id, &message)
+            })
+            .collect();
+        (indexed, lengths)
+    }
+
+    fn texts(chain: Vec<&ChainMessage>) -> Vec<&str> {
+        chain.into_iter().map(|m| m.text.as_str()).collect()
+    }
+
+    #[test]
+    fn chain_stops_at_a_reply_to_a_missing_message() {
+        let (indexed, lengths) = index(&[(1, None), (3, Some(2)), (4, Some(3))]);
+        assert_eq!(lengths, [1, 1, 2]);
+        assert_eq!(texts(indexed.chain_ending_at(4)), ["3", "4"]);
+    }
+
+    #[test]
+    fn message_replying_to_itself_is_a_chain_of_one() {
+        let (indexed, lengths) = index(&[(5, Some(5)), (6, Some(5))]);
+        assert_eq!(lengths, [1, 2]);
+        assert_eq!(texts(indexed.chain_ending_at(5)), ["5"]);
+        assert_eq!(texts(indexed.chain_ending_at(6)), ["5", "6"]);
+    }
+
+    #[test]
+    fn chain_follows_replies_to_the_first_message() {
+        let (indexed, lengths) = index(&[(1, None), (2, Some(1)), (3, Some(2)), (4, Some(1))]);
+        assert_eq!(lengths, [1, 2, 3, 2]);
+        assert_eq!(texts(indexed.chain_ending_at(3)), ["1", "2", "3"]);
+        assert_eq!(texts(indexed.chain_ending_at(4)), ["1", "4"]);
+        assert!(indexed.chain_ending_at(7).is_empty());
+    }
+}
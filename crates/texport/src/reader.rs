@@ -0,0 +1,391 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+};
+
+use serde::de::Error as _;
+
+use crate::{
+    ChatType, RawMessage, locate::locate_message, storage::LoadError, storage::ParseError,
+};
+
+/// Reads a chat export one message at a time, so that only one message is
+/// in memory at once rather than the whole chat, as with [`Chat`].
+///
+/// The fields before `messages` in `result.json` are read when the reader
+/// is created; Telegram Desktop writes the name, type and id of the chat
+/// there. The messages are then yielded as they are parsed. Reading stops
+/// at the first message that can't be parsed.
+///
+/// Unlike [`Chat::from_slice`], the reader only looks at what comes before
+/// the end of the `messages` array:
+///
+/// - an export with `name`, `type` or `id` after `messages` is an error
+///   saying that field is missing before `messages`, even though it is
+///   there;
+/// - an export without `messages` has no messages rather than being an
+///   error;
+/// - whatever follows the array, even nothing at all, isn't checked.
+///
+/// [`Chat`]: crate::Chat
+/// [`Chat::from_slice`]: crate::Chat::from_slice
+pub struct ChatReader<R> {
+    /// The display name of the chat.
+    pub name: String,
+    pub chat_type: ChatType,
+    pub id: i64,
+    reader: R,
+    /// The JSON of the message being read.
+    buf: Vec<u8>,
+    /// How many bytes have been read, for error messages.
+    offset: u64,
+    /// The index of the next message in the array.
+    index: usize,
+    done: bool,
+}
+
+impl ChatReader<BufReader<File>> {
+    /// Open the `result.json` at `path` and read the chat's name, type and
+    /// id.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, LoadError> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|source| LoadError::Io {
+            path: path.into(),
+            source,
+        })?;
+        Self::new(BufReader::new(file)).map_err(|e| e.with_path(path))
+    }
+}
+
+impl<R: BufRead> ChatReader<R> {
+    /// Read a chat export from `reader`, up to the first message.
+    pub fn new(reader: R) -> Result<Self, ParseError> {
+        let mut chat = Self {
+            name: String::new(),
+            chat_type: ChatType::Other,
+            id: 0,
+            reader,
+            buf: Vec::new(),
+            offset: 0,
+            index: 0,
+            done: false,
+        };
+        let (mut name, mut chat_type, mut id) = (None, None, None);
+        chat.expect(b'{')?;
+        loop {
+            if chat.peek()? == Some(b'}') {
+                // No messages at all.
+                chat.done = true;
+                break;
+            }
+            let key: String = chat.parse_value()?;
+            chat.expect(b':')?;
+            match key.as_str() {
+                "messages" => {
+                    chat.expect(b'[')?;
+                    break;
+                }
+                "name" => name = Some(chat.parse_value()?),
+                "type" => chat_type = Some(chat.parse_value()?),
+                "id" => id = Some(chat.parse_value()?),
+                _ => chat.read_value()?,
+            }
+            match chat.next_byte()? {
+                Some(b',') => {}
+                Some(b'}') => {
+                    chat.done = true;
+                    break;
+                }
+                _ => return Err(chat.syntax_error("expected `,` or `}`")),
+            }
+        }
+        let missing = |field| {
+            let e = format!("missing field `{field}` before `messages`");
+            ParseError::Json(serde_json::Error::custom(e))
+        };
+        chat.name = name.ok_or_else(|| missing("name"))?;
+        chat.chat_type = chat_type.ok_or_else(|| missing("type"))?;
+        chat.id = id.ok_or_else(|| missing("id"))?;
+        Ok(chat)
+    }
+
+    /// The next message, or `None` at the end of the array.
+    fn next_message(&mut self) -> Result<Option<RawMessage>, ParseError> {
+        if self.peek()? == Some(b']') {
+            return Ok(None);
+        }
+        if self.index > 0 {
+            self.expect(b',')?;
+        }
+        let index = self.index;
+        self.index += 1;
+        self.parse_value_with(|bytes, e| locate_message::<RawMessage>(index, bytes, e))
+            .map(Some)
+    }
+
+    /// Read the next JSON value and deserialize it.
+    fn parse_value<T: serde::de::DeserializeOwned>(&mut self) -> Result<T, ParseError> {
+        self.parse_value_with(|_, e| e)
+    }
+
+    /// Read the next JSON value and deserialize it, passing errors through
+    /// `locate` with the JSON of the value.
+    fn parse_value_with<T: serde::de::DeserializeOwned>(
+        &mut self,
+        locate: impl FnOnce(&[u8], serde_json::Error) -> serde_json::Error,
+    ) -> Result<T, ParseError> {
+        self.peek()?;
+        let start = self.offset;
+        self.read_value()?;
+        serde_json::from_slice(&self.buf).map_err(|e| {
+            // `e` has the position in `buf`; give the one in the export.
+            let e = serde_json::Error::custom(format!("{e} of the value at byte {start}"));
+            ParseError::Json(locate(&self.buf, e))
+        })
+    }
+
+    /// Read the next JSON value into `buf`, without parsing it: only
+    /// strings and brackets are followed, to find where it ends.
+    fn read_value(&mut self) -> Result<(), ParseError> {
+        self.buf.clear();
+        let mut depth = 0usize;
+        let mut in_string = false;
+        let mut escaped = false;
+        let Some(first) = self.peek()? else {
+            return Err(self.syntax_error("unexpected end of input"));
+        };
+        if !matches!(first, b'"' | b'{' | b'[') {
+            // A number or a literal, which ends at the next delimiter.
+            while let Some(&byte) = self.reader.fill_buf()?.first() {
+                if matches!(byte, b',' | b'}' | b']') || byte.is_ascii_whitespace() {
+                    break;
+                }
+                self.buf.push(byte);
+                self.consume();
+            }
+            if self.buf.is_empty() {
+                return Err(self.syntax_error("expected a value"));
+            }
+            return Ok(());
+        }
+        loop {
+            let available = self.reader.fill_buf()?;
+            if available.is_empty() {
+                return Err(self.syntax_error("unexpected end of input"));
+            }
+            let mut end = None;
+            for (i, &byte) in available.iter().enumerate() {
+                if in_string {
+                    match byte {
+                        _ if escaped => escaped = false,
+                        b'\\' => escaped = true,
+                        b'"' => in_string = false,
+                        _ => {}
+                    }
+                } else {
+                    match byte {
+                        b'"' => in_string = true,
+                        b'{' | b'[' => depth += 1,
+                        b'}' | b']' => depth -= 1,
+                        _ => {}
+                    }
+                }
+                if depth == 0 && !in_string {
+                    end = Some(i + 1);
+                    break;
+                }
+            }
+            let len = end.unwrap_or(available.len());
+            self.buf.extend_from_slice(&available[..len]);
+            self.reader.consume(len);
+            self.offset += len as u64;
+            if end.is_some() {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Skip whitespace and consume `expected`.
+    fn expect(&mut self, expected: u8) -> Result<(), ParseError> {
+        if self.next_byte()? == Some(expected) {
+            Ok(())
+        } else {
+            Err(self.syntax_error(&format!("expected `{}`", expected as char)))
+        }
+    }
+
+    /// Skip whitespace and consume the next byte.
+    fn next_byte(&mut self) -> io::Result<Option<u8>> {
+        let byte = self.peek()?;
+        if byte.is_some() {
+            self.consume();
+        }
+        Ok(byte)
+    }
+
+    fn consume(&mut self) {
+        self.reader.consume(1);
+        self.offset += 1;
+    }
+
+    /// Skip whitespace and return the next byte without consuming it.
+    fn peek(&mut self) -> io::Result<Option<u8>> {
+        loop {
+            match self.reader.fill_buf()?.first() {
+                Some(byte) if byte.is_ascii_whitespace() => self.consume(),
+                byte => return Ok(byte.copied()),
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for ChatReader<R> {
+    type Item = Result<RawMessage, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let message = self.next_message().transpose();
+        self.done = !matches!(message, Some(Ok(_)));
+        message
+    }
+}
+
+impl<R> ChatReader<R> {
+    fn syntax_error(&self, msg: &str) -> ParseError {
+        ParseError::Json(serde_json::Error::custom(format!(
+            "{msg} at byte {}",
+            self.offset
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chat;
+
+    /// The messages of `json` read by a [`ChatReader`] whose buffer holds
+    /// `capacity` bytes, as debug output to compare them by.
+    fn read(json: &str, capacity: usize) -> Result<Vec<String>, String> {
+        let reader = BufReader::with_capacity(capacity, json.as_bytes());
+        let chat = ChatReader::new(reader).map_err(|e| e.to_string())?;
+        chat.map(|message| message.map(|m| format!("{m:?}")))
+            .collect::<Result<_, _>>()
+            .map_err(|e| e.to_string())
+    }
+
+    /// Read `json` with buffers that split it everywhere and with a large
+    /// one, check they agree, and that [`Chat::from_slice`] parses the same
+    /// messages.
+    fn read_like_chat(json: &str) -> Vec<String> {
+        let messages = read(json, 8 * 1024).unwrap();
+        for capacity in [1, 2, 3, 7] {
+            assert_eq!(
+                read(json, capacity).unwrap(),
+                messages,
+                "capacity {capacity}"
+            );
+        }
+        let chat = Chat::from_slice(json.as_bytes()).unwrap();
+        let expected: Vec<_> = chat.messages.iter().map(|m| format!("{m:?}")).collect();
+        assert_eq!(messages, expected);
+        messages
+    }
+
+    const MESSAGE: &str = r#"{"id": 1, "type": "message", "date": "2024-01-01T10:00:00",
+        "date_unixtime": "1704103200", "from": "Alice", "from_id": "user1",
+        "text": "hi", "text_entities": [{"type": "plain", "text": "hi"}]}"#;
+
+    fn export(header: &str, messages: &[&str]) -> String {
+        format!(
+            r#"{{"name": "Chat", "type": "personal_chat", "id": 7, {header}"messages": [{}]}}"#,
+            messages.join(", ")
+        )
+    }
+
+    #[test]
+    fn strings_may_hold_quotes_backslashes_and_brackets() {
+        let tricky = MESSAGE
+            .replace(r#""text": "hi""#, r#""text": "say \"]}\" \\ [{\\\" }""#)
+            .replace(r#""from": "Alice""#, r#""from": "\\\\A]\"l{ice\\""#);
+        // In an unknown field before the messages, too.
+        let header = r#""about": {"a": ["]", "\"", "\\", {"b": "}{"}], "c": "\\\""}, "#;
+        let messages = read_like_chat(&export(header, &[&tricky, MESSAGE]));
+        assert_eq!(messages.len(), 2);
+        assert!(
+            messages[0].contains(r#"say \"]}\" \\ [{\\\" }"#),
+            "{}",
+            messages[0]
+        );
+        assert!(
+            messages[0].contains(r#"\\\\A]\"l{ice\\"#),
+            "{}",
+            messages[0]
+        );
+    }
+
+    #[test]
+    fn numbers_and_literals_end_at_the_next_delimiter() {
+        let header = r#""count": -1.5e3, "ok": true, "none": null ,"list": [1,2.0,false], "#;
+        let json = export(header, &[MESSAGE]).replace(r#""id": 7,"#, r#""id":7 ,"#);
+        assert_eq!(read_like_chat(&json).len(), 1);
+        // Right before the closing brace.
+        let json = r#"{"name": "Chat", "type": "personal_chat", "id": -7}"#;
+        assert_eq!(ChatReader::new(json.as_bytes()).unwrap().id, -7);
+
+        // A number where a message should be, ended by the array.
+        let error = read(&export("", &[MESSAGE, "42"]), 4).unwrap_err();
+        assert!(Chat::from_slice(export("", &[MESSAGE, "42"]).as_bytes()).is_err());
+        assert!(error.contains("integer `42`"), "{error}");
+        assert!(error.ends_with("at messages[1]"), "{error}");
+        // A key with no value.
+        let error = read(&export(r#""about": , "#, &[]), 4).unwrap_err();
+        assert!(error.contains("expected a value"), "{error}");
+    }
+
+    #[test]
+    fn an_export_without_messages_has_none() {
+        let json = r#"{"name": "Chat", "type": "personal_chat", "id": 7, "about": [1]}"#;
+        let chat = ChatReader::new(json.as_bytes()).unwrap();
+        assert_eq!((chat.name.as_str(), chat.id), ("Chat", 7));
+        assert_eq!(chat.count(), 0);
+        // Which a whole chat must have.
+        assert!(Chat::from_slice(json.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn fields_after_the_messages_are_missed() {
+        let json = format!(
+            r#"{{"messages": [{MESSAGE}], "name": "Chat", "type": "personal_chat", "id": 7}}"#
+        );
+        assert_eq!(Chat::from_slice(json.as_bytes()).unwrap().messages.len(), 1);
+        let Err(error) = ChatReader::new(json.as_bytes()) else {
+            panic!("read a chat whose name comes after its messages");
+        };
+        assert_eq!(
+            error.to_string(),
+            "invalid chat export: missing field `name` before `messages`"
+        );
+    }
+
+    #[test]
+    fn truncated_exports_are_errors_up_to_the_end_of_the_messages() {
+        let json = export(
+            "",
+            &[MESSAGE, MESSAGE.replace("\"id\": 1", "\"id\": 2").as_str()],
+        );
+        let end = json.rfind(']').unwrap();
+        for len in 0..end {
+            let cut = &json[..len];
+            for capacity in [1, 64] {
+                assert!(read(cut, capacity).is_err(), "{cut:?}");
+            }
+            assert!(Chat::from_slice(cut.as_bytes()).is_err());
+        }
+        // Nothing after the array is read.
+        assert_eq!(read(&json[..=end], 64).unwrap().len(), 2);
+    }
+}
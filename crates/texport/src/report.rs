@@ -0,0 +1,314 @@
+use std::io::{self, Write};
+
+use crate::{ChatStats, stats::UserStats};
+
+const STYLE: &str = "
+body { font-family: system-ui, sans-serif; max-width: 960px; margin: 2em auto; padding: 0 1em; color: #222; }
+h1, h2 { font-weight: 600; }
+.cards { display: flex; flex-wrap: wrap; gap: 1em; }
+.card { border: 1px solid #ddd; border-radius: 8px; padding: 0.8em 1.2em; min-width: 8em; }
+.card .value { font-size: 1.6em; font-weight: 600; }
+.card .label { color: #666; }
+table { border-collapse: collapse; width: 100%; }
+th, td { border-bottom: 1px solid #eee; padding: 0.3em 0.6em; text-align: right; }
+th:first-child, td:first-child { text-align: left; }
+svg text { font-size: 10px; fill: #666; }
+details { margin: 0.5em 0; }
+summary { cursor: pointer; font-weight: 600; }
+";
+
+/// Size of the bar charts, in SVG user units.
+const CHART_WIDTH: usize = 900;
+const CHART_HEIGHT: usize = 160;
+/// Room below the bars for their labels.
+const LABEL_HEIGHT: usize = 16;
+
+impl ChatStats {
+    /// Render the statistics as a single self-contained HTML page: summary
+    /// cards, a participants table, the monthly timeline and hour-of-day
+    /// histogram as inline SVG, and each participant's top words and
+    /// reactions in collapsible sections. The page uses no scripts and no
+    /// external assets.
+    pub fn write_html_report<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let combined = self.participants.values().sum::<UserStats>();
+        let reactions: usize = combined.received_reactions.values().sum();
+
+        writeln!(w, "<!DOCTYPE html>\n<html lang=\"en\">\n<head>")?;
+        writeln!(
+            w,
+            "<meta charset=\"utf-8\">\n<title>Chat statistics</title>"
+        )?;
+        writeln!(w, "<style>{STYLE}</style>\n</head>\n<body>")?;
+        writeln!(w, "<h1>Chat statistics</h1>")?;
+
+        writeln!(w, "<div class=\"cards\">")?;
+        card(w, "Messages", self.messages)?;
+        card(w, "Participants", self.participants.len())?;
+        card(w, "Reactions", reactions)?;
+        card(w, "Edited", self.edited)?;
+        card(w, "Service messages", self.service_messages)?;
+        if let Some(streak) = self.longest_streak() {
+            card(w, "Longest streak, days", streak.days)?;
+        }
+        writeln!(w, "</div>")?;
+
+        self.write_participants_table(w, &combined)?;
+
+        if !self.timeline.is_empty() {
+            writeln!(w, "<h2>Messages per month</h2>")?;
+            let bars: Vec<_> = self
+                .timeline
+                .iter()
+                .map(|(month, count)| (month.as_str(), *count))
+                .collect();
+            bar_chart(w, &bars)?;
+        }
+
+        let hours = self.hour_totals();
+        if hours.iter().any(|&count| count > 0) {
+            writeln!(w, "<h2>Messages by hour of day</h2>")?;
+            let labels: Vec<_> = (0..24).map(|hour| hour.to_string()).collect();
+            let bars: Vec<_> = labels.iter().map(String::as_str).zip(hours).collect();
+            bar_chart(w, &bars)?;
+        }
+
+        self.write_participant_details(w)?;
+
+        writeln!(w, "</body>\n</html>")
+    }
+
+    fn write_participants_table<W: Write>(
+        &self,
+        w: &mut W,
+        combined: &UserStats,
+    ) -> io::Result<()> {
+        let participants = self.ranked_participants();
+        if participants.is_empty() {
+            return Ok(());
+        }
+
+        let rank_by = self.settings.rank_by;
+        writeln!(w, "<h2>Participants</h2>\n<table>")?;
+        writeln!(
+            w,
+            "<tr><th>Name</th><th>Messages</th><th>Share of {}</th><th>Avg. length</th>\
+             <th>Words</th><th>Reactions</th></tr>",
+            rank_by.label()
+        )?;
+        let total = combined.rank_value(rank_by);
+        for (_, stats) in participants.iter().take(self.settings.max_participants) {
+            let reactions: usize = stats.received_reactions.values().sum();
+            let share = 100.0 * stats.rank_value(rank_by) as f64 / total.max(1) as f64;
+            writeln!(
+                w,
+                "<tr><td>{}</td><td>{}</td><td>{share:.1}%</td><td>{}</td><td>{}</td><td>{reactions}</td></tr>",
+                escape(&stats.name),
+                stats.count,
+                stats.avg_chars(),
+                stats.total_words,
+            )?;
+        }
+        writeln!(w, "</table>")?;
+        if participants.len() > self.settings.max_participants {
+            writeln!(
+                w,
+                "<p>… and {} more</p>",
+                participants.len() - self.settings.max_participants
+            )?;
+        }
+        Ok(())
+    }
+
+    fn write_participant_details<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let participants = self.ranked_participants();
+        for (_, stats) in participants.iter().take(self.settings.max_participants) {
+            let words = stats.top_words(self.settings.max_words);
+            let reactions = self.settings.labeled_reactions(&stats.received_reactions);
+            if words.is_empty() && reactions.is_empty() {
+                continue;
+            }
+
+            writeln!(w, "<details>\n<summary>{}</summary>", escape(&stats.name))?;
+            if !words.is_empty() {
+                let words = words
+                    .iter()
+                    .map(|(word, count)| format!("{} ({count})", escape(stats.word_label(word))))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                writeln!(w, "<p>Top words: {words}</p>")?;
+            }
+            if !reactions.is_empty() {
+                let reactions = reactions
+                    .iter()
+                    .map(|(reaction, count)| format!("{}×{count}", escape(reaction)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                writeln!(w, "<p>Reactions: {reactions}</p>")?;
+            }
+            writeln!(w, "</details>")?;
+        }
+        Ok(())
+    }
+
+    /// Render the statistics as a GitHub-flavored Markdown document: a
+    /// summary, then tables of participants, their top words and reactions,
+    /// and messages per month.
+    pub fn write_markdown_report<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let combined = self.participants.values().sum::<UserStats>();
+        let reactions: usize = combined.received_reactions.values().sum();
+
+        writeln!(w, "# Chat statistics\n")?;
+        writeln!(w, "- Messages: {}", self.messages)?;
+        writeln!(w, "- Participants: {}", self.participants.len())?;
+        writeln!(w, "- Reactions: {reactions}")?;
+        writeln!(w, "- Edited: {}", self.edited)?;
+        writeln!(w, "- Service messages: {}", self.service_messages)?;
+        if let Some(streak) = self.longest_streak() {
+            writeln!(
+                w,
+                "- Longest streak: {} days ({} – {})",
+                streak.days, streak.first_day, streak.last_day
+            )?;
+        }
+        if let Some(day) = self.busiest_days(1).first() {
+            writeln!(
+                w,
+                "- Busiest day: {} with {} messages",
+                day.date, day.messages
+            )?;
+        }
+
+        let participants = self.ranked_participants();
+        let shown = &participants[..participants.len().min(self.settings.max_participants)];
+        if !shown.is_empty() {
+            let rank_by = self.settings.rank_by;
+            let total = combined.rank_value(rank_by);
+            writeln!(w, "\n## Participants\n")?;
+            writeln!(
+                w,
+                "| Name | Messages | Share of {} | Avg. length | Words | Reactions |",
+                rank_by.label()
+            )?;
+            writeln!(w, "|---|--:|--:|--:|--:|--:|")?;
+            for (_, stats) in shown {
+                let reactions: usize = stats.received_reactions.values().sum();
+                let share = 100.0 * stats.rank_value(rank_by) as f64 / total.max(1) as f64;
+                writeln!(
+                    w,
+                    "| {} | {} | {share:.1}% | {} | {} | {reactions} |",
+                    cell(&stats.name),
+                    stats.count,
+                    stats.avg_chars(),
+                    stats.total_words,
+                )?;
+            }
+            if participants.len() > shown.len() {
+                writeln!(w, "\n… and {} more", participants.len() - shown.len())?;
+            }
+
+            writeln!(w, "\n## Top words\n")?;
+            writeln!(w, "| Name | Words |\n|---|---|")?;
+            for (_, stats) in shown {
+                let words = stats
+                    .top_words(self.settings.max_words)
+                    .iter()
+                    .map(|(word, count)| format!("{} ({count})", stats.word_label(word)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                writeln!(w, "| {} | {} |", cell(&stats.name), cell(&words))?;
+            }
+
+            writeln!(w, "\n## Reactions\n")?;
+            writeln!(w, "| Name | Reactions |\n|---|---|")?;
+            for (_, stats) in shown {
+                let reactions = self
+                    .settings
+                    .labeled_reactions(&stats.received_reactions)
+                    .iter()
+                    .map(|(reaction, count)| format!("{reaction}×{count}"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                writeln!(w, "| {} | {} |", cell(&stats.name), cell(&reactions))?;
+            }
+        }
+
+        if !self.timeline.is_empty() {
+            writeln!(w, "\n## Messages per month\n")?;
+            writeln!(w, "| Month | Messages |\n|---|--:|")?;
+            for (month, count) in &self.timeline {
+                writeln!(w, "| {month} | {count} |")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn card<W: Write>(w: &mut W, label: &str, value: impl std::fmt::Display) -> io::Result<()> {
+    writeln!(
+        w,
+        "<div class=\"card\"><div class=\"value\">{value}</div><div class=\"label\">{label}</div></div>"
+    )
+}
+
+/// Draw `bars` as an SVG bar chart scaled to the largest value, labeling
+/// every bar when they fit and every few bars otherwise.
+fn bar_chart<W: Write>(w: &mut W, bars: &[(&str, u64)]) -> io::Result<()> {
+    let max = bars
+        .iter()
+        .map(|(_, count)| *count)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let slot = CHART_WIDTH as f64 / bars.len().max(1) as f64;
+    let label_every = (40.0 / slot).ceil().max(1.0) as usize;
+
+    writeln!(
+        w,
+        "<svg viewBox=\"0 0 {CHART_WIDTH} {}\" width=\"100%\" role=\"img\">",
+        CHART_HEIGHT + LABEL_HEIGHT
+    )?;
+    for (i, (label, count)) in bars.iter().enumerate() {
+        let height = CHART_HEIGHT as f64 * *count as f64 / max as f64;
+        let x = i as f64 * slot;
+        writeln!(
+            w,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{height:.1}\" fill=\"#4a90d9\">\
+             <title>{}: {count}</title></rect>",
+            x + slot * 0.1,
+            CHART_HEIGHT as f64 - height,
+            slot * 0.8,
+            escape(label),
+        )?;
+        if i % label_every == 0 {
+            writeln!(
+                w,
+                "<text x=\"{:.1}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+                x + slot / 2.0,
+                CHART_HEIGHT + LABEL_HEIGHT - 4,
+                escape(label)
+            )?;
+        }
+    }
+    writeln!(w, "</svg>")
+}
+
+/// Escape text for use in HTML content and attribute values.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Make text safe for a Markdown table cell.
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|").replace(['\n', '\r'], " ")
+}
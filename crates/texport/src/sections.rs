@@ -0,0 +1,185 @@
+use std::str::FromStr;
+
+use serde::{Serialize, Serializer, ser::SerializeSeq};
+
+/// A block of the text output that can be shown or hidden.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Section {
+    /// Totals at the top: messages, reactions, streaks, the busiest day.
+    Summary,
+    /// Bar chart of the most active participants.
+    Charts,
+    /// Bar chart of messages per month.
+    Timeline,
+    /// Statistics of all participants combined, and the longest reply chain.
+    Combined,
+    /// Statistics of each top participant.
+    Participants,
+    /// Top words, phrases and distinctive words of each participant.
+    Words,
+    /// Reactions received by each participant.
+    Reactions,
+    /// Message counts of each chat, when several are analyzed.
+    Chats,
+    /// Activity of each forum topic, when grouping by topic.
+    Topics,
+    /// Who mentions whom with @-mentions.
+    Mentions,
+    /// Counts of tracked words and patterns.
+    Tracked,
+    /// Monthly counts of trend words.
+    Trends,
+    /// People who react but never post.
+    Lurkers,
+    /// Service messages by action.
+    Services,
+    /// Activity by weekday and hour.
+    Heatmap,
+    /// Participants who post the most at night.
+    NightOwls,
+    /// Joins and leaves.
+    Membership,
+    /// Sources of forwarded messages.
+    Forwards,
+    /// Messages estimated to be deleted.
+    Deletions,
+    /// Pinned messages.
+    Pins,
+    /// Milestone messages such as the 1000th.
+    Milestones,
+    /// Chat title changes.
+    Titles,
+    /// Counts of text entity types such as links and mentions.
+    Entities,
+}
+
+impl Section {
+    /// Every section, in the order the text output prints them.
+    pub const ALL: [Section; 23] = [
+        Section::Summary,
+        Section::Charts,
+        Section::Timeline,
+        Section::Combined,
+        Section::Participants,
+        Section::Words,
+        Section::Reactions,
+        Section::Chats,
+        Section::Topics,
+        Section::Mentions,
+        Section::Tracked,
+        Section::Trends,
+        Section::Lurkers,
+        Section::Services,
+        Section::Heatmap,
+        Section::NightOwls,
+        Section::Membership,
+        Section::Forwards,
+        Section::Deletions,
+        Section::Pins,
+        Section::Milestones,
+        Section::Titles,
+        Section::Entities,
+    ];
+
+    fn bit(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+impl FromStr for Section {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "summary" => Ok(Section::Summary),
+            "charts" => Ok(Section::Charts),
+            "timeline" => Ok(Section::Timeline),
+            "combined" => Ok(Section::Combined),
+            "participants" => Ok(Section::Participants),
+            "words" => Ok(Section::Words),
+            "reactions" => Ok(Section::Reactions),
+            "chats" => Ok(Section::Chats),
+            "topics" => Ok(Section::Topics),
+            "mentions" => Ok(Section::Mentions),
+            "tracked" => Ok(Section::Tracked),
+            "trends" => Ok(Section::Trends),
+            "lurkers" => Ok(Section::Lurkers),
+            "services" => Ok(Section::Services),
+            "heatmap" => Ok(Section::Heatmap),
+            "night_owls" => Ok(Section::NightOwls),
+            "membership" => Ok(Section::Membership),
+            "forwards" => Ok(Section::Forwards),
+            "deletions" => Ok(Section::Deletions),
+            "pins" => Ok(Section::Pins),
+            "milestones" => Ok(Section::Milestones),
+            "titles" => Ok(Section::Titles),
+            "entities" => Ok(Section::Entities),
+            _ => Err(format!("unknown section: {s}")),
+        }
+    }
+}
+
+/// A set of [`Section`]s. The default is every section except
+/// [`Section::Entities`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sections(u32);
+
+impl Sections {
+    /// Every section.
+    pub fn all() -> Self {
+        Section::ALL.into_iter().collect()
+    }
+
+    /// No sections at all.
+    pub fn none() -> Self {
+        Sections(0)
+    }
+
+    pub fn contains(self, section: Section) -> bool {
+        self.0 & section.bit() != 0
+    }
+
+    pub fn insert(&mut self, section: Section) {
+        self.0 |= section.bit();
+    }
+
+    pub fn remove(&mut self, section: Section) {
+        self.0 &= !section.bit();
+    }
+
+    /// The sections in the set, in output order.
+    pub fn iter(self) -> impl Iterator<Item = Section> {
+        Section::ALL
+            .into_iter()
+            .filter(move |&section| self.contains(section))
+    }
+}
+
+impl Default for Sections {
+    fn default() -> Self {
+        let mut sections = Sections::all();
+        sections.remove(Section::Entities);
+        sections
+    }
+}
+
+impl FromIterator<Section> for Sections {
+    fn from_iter<I: IntoIterator<Item = Section>>(iter: I) -> Self {
+        let mut sections = Sections::none();
+        for section in iter {
+            sections.insert(section);
+        }
+        sections
+    }
+}
+
+impl Serialize for Sections {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(None)?;
+        for section in self.iter() {
+            seq.serialize_element(&section)?;
+        }
+        seq.end()
+    }
+}
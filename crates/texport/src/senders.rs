@@ -0,0 +1,63 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::ExportMessage;
+
+/// The sender ids that have used each display name, to pick senders by
+/// name, e.g. for [`StatsSettings::senders`] and [`ExportOptions::senders`].
+///
+/// [`StatsSettings::senders`]: crate::StatsSettings::senders
+/// [`ExportOptions::senders`]: crate::ExportOptions::senders
+#[derive(Clone, Debug, Default)]
+pub struct SenderNames {
+    /// Sender ids by lowercased display name.
+    ids: HashMap<String, BTreeSet<String>>,
+}
+
+/// A display name that [`SenderNames::resolve`] can't turn into a sender.
+#[derive(thiserror::Error, Debug)]
+pub enum SenderError {
+    #[error("no sender is named {0:?}")]
+    Unknown(String),
+    #[error("{name:?} is the name of several senders, {}; pick one by id", ids.join(", "))]
+    Ambiguous { name: String, ids: Vec<String> },
+}
+
+impl SenderNames {
+    /// Record the sender of `message`. Service messages are skipped.
+    pub fn add(&mut self, message: &impl ExportMessage) {
+        if let Some((_, msg)) = message.message() {
+            self.add_sender(msg.from, msg.from_id);
+        }
+    }
+
+    /// Record that sender `id` has used the display name `name`.
+    pub fn add_sender(&mut self, name: &str, id: &str) {
+        let ids = self.ids.entry(name.to_lowercase()).or_default();
+        if !ids.contains(id) {
+            ids.insert(id.to_string());
+        }
+    }
+
+    /// The ids of the senders with the given display names, ignoring case.
+    /// Every name must belong to exactly one sender: a name used by several
+    /// is an error listing their ids rather than a guess.
+    pub fn resolve<'a>(
+        &self,
+        names: impl IntoIterator<Item = &'a str>,
+    ) -> Result<HashSet<String>, SenderError> {
+        names
+            .into_iter()
+            .map(|name| match self.ids.get(&name.to_lowercase()) {
+                None => Err(SenderError::Unknown(name.to_string())),
+                Some(ids) if ids.len() > 1 => Err(SenderError::Ambiguous {
+                    name: name.to_string(),
+                    ids: ids.iter().cloned().collect(),
+                }),
+                Some(ids) => Ok(ids.iter().cloned()),
+            })
+            .try_fold(HashSet::new(), |mut found, ids| {
+                found.extend(ids?);
+                Ok(found)
+            })
+    }
+}
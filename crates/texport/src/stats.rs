@@ -1,55 +1,352 @@
 use std::{
-    collections::{HashMap, HashSet},
+    borrow::Cow,
+    cmp::{Ordering, Reverse},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     fmt::{self},
-    time::SystemTime,
+    hash::Hash,
+    io::{self, BufRead},
+    mem,
+    ops::{Add, AddAssign},
+    path::Path,
+    str::FromStr,
+    time::{Duration, SystemTime},
 };
 
-use chrono::{DateTime, Local};
-use serde::Serialize;
+use chrono::{DateTime, Datelike, NaiveDate, Timelike, Utc};
+use fs_err as fs;
+use indexmap::IndexMap;
+use log::warn;
+use serde::{Deserialize, Serialize, Serializer, ser::SerializeStruct};
+use unicode_width::UnicodeWidthStr;
 
-use crate::{RawMessage, Reaction, messages::IndexedMessages};
+use crate::{
+    CharCount, ChatReader, ExportMessage, Labels, Language, ParseError, RawMessage, Reaction,
+    Section, Sections, TextEntity, Tokenizer, Word,
+    labels::{fill, pad},
+    language::detect_language,
+    messages::{ChainMessage, IndexedMessages, Message, Service, ServiceAction},
+    time::{self, TimeZone},
+};
+#[cfg(feature = "stemming")]
+use crate::{Script, language::LanguageStemmer};
 
-#[derive(Clone, Debug, Default, Serialize)]
+/// The hasher of the maps in [`UserStats`] and [`ChatStats`]: FxHash with
+/// the `fast-hash` feature, which is on by default, or the standard
+/// library's SipHash without it, for input that may be crafted to collide.
+#[cfg(feature = "fast-hash")]
+pub type StatsHasher = rustc_hash::FxBuildHasher;
+/// The hasher of the maps in [`UserStats`] and [`ChatStats`]: FxHash with
+/// the `fast-hash` feature, which is on by default, or the standard
+/// library's SipHash without it, for input that may be crafted to collide.
+#[cfg(not(feature = "fast-hash"))]
+pub type StatsHasher = std::hash::RandomState;
+
+/// A map in [`UserStats`] or [`ChatStats`], hashed with [`StatsHasher`].
+pub type StatsMap<K, V> = HashMap<K, V, StatsHasher>;
+
+/// A set in [`UserStats`] or [`ChatStats`], hashed with [`StatsHasher`].
+pub type StatsSet<T> = HashSet<T, StatsHasher>;
+
+/// Words longer than this are keyboard mashing rather than a longest word.
+const MAX_LONGEST_WORD_CHARS: usize = 40;
+
+/// Messages shorter than this are too short to detect their language.
+const MIN_DETECT_CHARS: usize = 20;
+
+/// Messages with fewer letters than this never count as shouting.
+const MIN_CAPS_LETTERS: u64 = 5;
+
+/// Upper bound on distinct phrases tracked per user before rare ones are dropped.
+const MAX_PHRASES: usize = 100_000;
+
+/// Longer silences, such as vacations, are left out of
+/// [`UserStats::median_gap`].
+const MAX_CADENCE_GAP: Duration = Duration::from_secs(48 * 60 * 60);
+
+/// The longest median gap of [`Cadence::SeveralTimesADay`].
+const SEVERAL_TIMES_A_DAY_GAP: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// Hours of the day, in [`StatsSettings::time_zone`], that count as night.
+const NIGHT_HOURS: std::ops::Range<usize> = 0..6;
+
+/// Participants with fewer messages are never listed as night owls, so one
+/// late message doesn't make anyone the top night poster.
+const MIN_NIGHT_OWL_MESSAGES: u64 = 10;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct UserStats {
+    /// The most recently used display name.
+    pub name: String,
+    /// Every display name this participant has used.
+    pub names: BTreeSet<String>,
     pub count: u64,
+    /// Characters written, as counted by [`StatsSettings::char_count`].
     pub total_chars: u64,
+    /// Characters in the longest message, as counted by
+    /// [`StatsSettings::char_count`].
     pub max_chars: u64,
+    /// Serialized as an ISO 8601 string in UTC.
+    #[serde(
+        serialize_with = "time::serialize_opt",
+        deserialize_with = "time::deserialize_opt"
+    )]
     pub first_message: Option<SystemTime>,
+    /// Serialized as an ISO 8601 string in UTC.
+    #[serde(
+        serialize_with = "time::serialize_opt",
+        deserialize_with = "time::deserialize_opt"
+    )]
     pub last_message: Option<SystemTime>,
+    /// Number of words written, counted before stop-word filtering.
+    pub total_words: u64,
+    /// Number of distinct words written, counted before stop-word filtering.
+    pub unique_words: u64,
+    /// Messages written in ALL CAPS (only messages with at least 5 letters count).
+    pub caps_messages: u64,
+    /// Messages with at least 5 letters, the ones that can be written in
+    /// ALL CAPS.
+    #[serde(default)]
+    pub caps_eligible: u64,
+    /// Total number of `!` characters.
+    pub exclamations: u64,
+    /// Messages containing an ellipsis (`...` or `…`).
+    pub ellipsis_messages: u64,
+    /// Total number of sentences.
+    pub sentences: u64,
+    /// Characters in all words written, for the average word length.
+    pub total_word_chars: u64,
+    /// The longest word written, ignoring links and anything over
+    /// 40 characters.
+    pub longest_word: String,
+    /// Messages consisting of exactly one word.
+    pub single_word_messages: u64,
+    /// Runs of consecutive messages by this participant with no one else
+    /// in between; a single message is a run of one.
+    pub runs: u64,
+    /// The most consecutive messages this participant sent.
+    pub longest_run: u64,
+    /// When the longest run started. Serialized as an ISO 8601 string in UTC.
+    #[serde(
+        serialize_with = "time::serialize_opt",
+        deserialize_with = "time::deserialize_opt"
+    )]
+    pub longest_run_date: Option<SystemTime>,
+    /// Messages that reply to another message.
+    pub replies: u64,
+    /// Messages forwarded from elsewhere.
+    pub forwards: u64,
+    /// Messages sent after [`StatsSettings::conversation_gap`] of silence
+    /// in their chat, including the first message of each chat.
+    pub conversations_started: u64,
+    /// Messages per detected language code. Only filled when
+    /// [`StatsSettings::detect_languages`] is set and the `lang-detect`
+    /// feature is enabled.
+    #[serde(serialize_with = "serialize_ranked")]
+    pub languages: StatsMap<String, u64>,
+    /// Messages by this participant that someone pinned.
+    pub pinned: u64,
+    /// Messages by hour of day, in [`StatsSettings::time_zone`].
+    pub hours: [u64; 24],
+    /// How many times each gap between a message and the previous one by
+    /// this participant in the same chat occurred, keyed by its length in
+    /// seconds, for gaps of at most 48 hours.
+    #[serde(skip)]
+    pub gaps: BTreeMap<u32, u64>,
+    /// Gaps between messages over 48 hours, left out of `gaps`.
+    #[serde(default)]
+    pub long_gaps: u64,
     /// Word statistics: (word, count)
     #[serde(skip)]
-    pub words: HashMap<String, usize>,
+    pub words: StatsMap<Word, usize>,
+    #[serde(skip)]
+    pub received_reactions: StatsMap<String, usize>,
+    /// Surface forms seen for each stemmed word: (stem, (word, count)).
+    /// Only filled when stemming is enabled.
+    #[serde(skip)]
+    pub surface_forms: StatsMap<Word, StatsMap<Word, usize>>,
+    /// Words from link, mention, hashtag, command and code entities:
+    /// (word, count). Only filled when [`StatsSettings::entity_words`] is
+    /// [`EntityWords::Separate`].
     #[serde(skip)]
-    pub received_reactions: HashMap<String, usize>,
+    pub entity_words: StatsMap<Word, usize>,
+    /// Two- and three-word phrase statistics: (phrase, count)
+    #[serde(skip)]
+    pub phrases: StatsMap<String, usize>,
+    /// Every distinct word seen, including stop words.
+    #[serde(skip)]
+    pub vocabulary: StatsSet<Word>,
 }
 
 impl UserStats {
+    /// Record that this participant used display name `name` at `timestamp`.
+    pub fn observe_name(&mut self, name: &str, timestamp: Option<SystemTime>) -> &mut Self {
+        let is_latest = match (timestamp, self.last_message) {
+            (Some(ts), Some(last)) => ts >= last,
+            _ => true,
+        };
+        if is_latest || self.name.is_empty() {
+            self.name = name.to_string();
+        }
+        if !self.names.contains(name) {
+            self.names.insert(name.to_string());
+        }
+        self
+    }
+
+    /// Display names used besides the current one.
+    pub fn other_names(&self) -> impl Iterator<Item = &String> {
+        self.names.iter().filter(|n| **n != self.name)
+    }
+
+    /// Count a message with flattened text `message`, split into `entities`
+    /// as in the export.
     pub fn add_message(
         &mut self,
         message: &str,
-        filter: &HashSet<String>,
+        entities: &[TextEntity],
+        filter: &WordFilter,
         timestamp: Option<SystemTime>,
     ) -> &mut Self {
-        let len = message.chars().count() as u64;
+        let len = filter.char_count.count(message) as u64;
         self.count += 1;
         self.total_chars += len;
         self.max_chars = len.max(self.max_chars);
 
+        self.add_style(message);
+
         if let Some(ts) = timestamp {
             self.first_message = Some(self.first_message.map_or(ts, |old| old.min(ts)));
             self.last_message = Some(self.last_message.map_or(ts, |old| old.max(ts)));
         }
 
-        for word in message
-            .to_lowercase()
-            .split_whitespace()
-            .filter(|w| !filter.contains(*w))
-        {
-            *self.words.entry(word.to_string()).or_insert(0) += 1;
+        let language = filter.detect(message);
+        if let Some(code) = language {
+            *self.languages.entry(code.to_string()).or_default() += 1;
+        }
+        if filter.skip_words {
+            return self;
+        }
+
+        let words = filter.words(message, entities);
+        if words.len() == 1 {
+            self.single_word_messages += 1;
+        }
+        for (word, from_entity) in words {
+            self.total_words += 1;
+            let chars = filter.char_count.count(&word);
+            self.total_word_chars += chars as u64;
+            if chars > filter.char_count.count(&self.longest_word)
+                && chars <= MAX_LONGEST_WORD_CHARS
+                && !from_entity
+                && !is_link(&word)
+            {
+                self.longest_word.clone_from(&word);
+            }
+            let interned = Word::new(&word);
+            if self.vocabulary.insert(interned) {
+                self.unique_words += 1;
+            }
+            if from_entity {
+                if filter.entity_words == EntityWords::Separate {
+                    *self.entity_words.entry(interned).or_insert(0) += 1;
+                }
+                continue;
+            }
+            if filter.accepts_in(&word, language) {
+                match filter.stem(&word) {
+                    Some(stem) => {
+                        let stem = Word::new(&stem);
+                        *self.words.entry(stem).or_insert(0) += 1;
+                        let forms = self.surface_forms.entry(stem).or_default();
+                        *forms.entry(interned).or_insert(0) += 1;
+                    }
+                    None => *self.words.entry(interned).or_insert(0) += 1,
+                }
+            }
+        }
+        self
+    }
+
+    /// Count the gap between a message sent at `sent` and this participant's
+    /// previous one in the same chat, sent at `previous`.
+    fn add_gap(&mut self, previous: SystemTime, sent: SystemTime) {
+        match sent.duration_since(previous) {
+            Ok(gap) if gap <= MAX_CADENCE_GAP => {
+                *self.gaps.entry(gap.as_secs() as u32).or_default() += 1;
+            }
+            Ok(_) => self.long_gaps += 1,
+            Err(_) => {}
+        }
+    }
+
+    fn add_style(&mut self, message: &str) {
+        let letters = message.chars().filter(|c| c.is_alphabetic());
+        let (mut total, mut upper) = (0, 0);
+        for c in letters {
+            total += 1;
+            upper += u64::from(c.is_uppercase());
+        }
+        if total >= MIN_CAPS_LETTERS {
+            self.caps_eligible += 1;
+            self.caps_messages += u64::from(upper == total);
+        }
+
+        self.exclamations += message.matches('!').count() as u64;
+        if message.contains("...") || message.contains('…') {
+            self.ellipsis_messages += 1;
+        }
+        self.sentences += count_sentences(message);
+    }
+
+    /// Count bigrams and trigrams of consecutive non-stop-words.
+    pub fn add_phrases(
+        &mut self,
+        message: &str,
+        entities: &[TextEntity],
+        filter: &WordFilter,
+    ) -> &mut Self {
+        if filter.skip_words {
+            return self;
+        }
+        let words = filter.words(message, entities);
+        let language = if filter.language_stop_words.is_empty() {
+            None
+        } else {
+            filter.detect(message)
+        };
+
+        for n in 2..=3 {
+            for window in words.windows(n) {
+                let accepted = window
+                    .iter()
+                    .all(|(w, from_entity)| !from_entity && filter.accepts_in(w, language));
+                if !accepted {
+                    continue;
+                }
+                let phrase = window
+                    .iter()
+                    .map(|(w, _)| w.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                *self.phrases.entry(phrase).or_insert(0) += 1;
+            }
+        }
+
+        if self.phrases.len() > MAX_PHRASES {
+            self.prune_phrases();
         }
         self
     }
 
+    /// Drop the rarest phrases until the map is at most half of `MAX_PHRASES`.
+    fn prune_phrases(&mut self) {
+        let mut min_count = 1;
+        while self.phrases.len() > MAX_PHRASES / 2 {
+            self.phrases.retain(|_, count| *count > min_count);
+            min_count += 1;
+        }
+    }
+
     pub fn add_reactions(&mut self, reactions: &[Reaction]) -> &mut Self {
         for reaction in reactions {
             let (emoji, count) = match reaction {
@@ -66,196 +363,4306 @@ impl UserStats {
         self
     }
 
+    /// The value of the given ranking measure for this user.
+    pub fn rank_value(&self, rank_by: RankBy) -> u64 {
+        match rank_by {
+            RankBy::Messages => self.count,
+            RankBy::Characters => self.total_chars,
+            RankBy::ReactionsReceived => self.received_reactions.values().sum::<usize>() as u64,
+            RankBy::WordsTyped => self.total_words,
+        }
+    }
+
     pub fn avg_chars(&self) -> u64 {
         self.total_chars.checked_div(self.count).unwrap_or(0)
     }
 
-    pub fn top_words(&self, max: usize) -> Vec<(&String, &usize)> {
-        let mut words: Vec<_> = self.words.iter().collect();
-        words.sort_unstable_by_key(|(_, count)| std::cmp::Reverse(*count));
-        words.truncate(max);
-        words
+    pub fn avg_words(&self) -> f64 {
+        ratio(self.total_words, self.count)
+    }
+
+    /// Average number of characters per word.
+    pub fn avg_word_length(&self) -> f64 {
+        ratio(self.total_word_chars, self.total_words)
+    }
+
+    /// Detected languages with their share of this user's messages whose
+    /// language could be detected, in percent, most used first.
+    pub fn language_shares(&self) -> Vec<(&String, f64)> {
+        let detected = self.languages.values().sum();
+        let mut shares: Vec<_> = self
+            .languages
+            .iter()
+            .map(|(code, count)| (code, percent(*count, detected)))
+            .collect();
+        shares.sort_unstable_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        shares
+    }
+
+    fn add_run(&mut self, run: &Run) {
+        if run.length == 1 {
+            self.runs += 1;
+        }
+        // Of equally long runs, the earliest is kept.
+        if (run.length, Reverse(run.started)) > (self.longest_run, Reverse(self.longest_run_date)) {
+            self.longest_run = run.length;
+            self.longest_run_date = run.started;
+        }
+    }
+
+    /// The median time between two consecutive messages, ignoring gaps
+    /// over 48 hours, or `None` without any such gap.
+    pub fn median_gap(&self) -> Option<Duration> {
+        let total: u64 = self.gaps.values().sum();
+        // The upper one of the two middle gaps of an even number.
+        let mut middle = total / 2;
+        for (&gap, &count) in &self.gaps {
+            if middle < count {
+                return Some(Duration::from_secs(u64::from(gap)));
+            }
+            middle -= count;
+        }
+        None
+    }
+
+    /// How often this participant posts, judged by [`UserStats::median_gap`].
+    /// When most of their gaps are over 48 hours, so that the median says
+    /// little, by how many messages they sent per day between their first
+    /// and last one instead. `None` with fewer than two dated messages.
+    pub fn cadence(&self) -> Option<Cadence> {
+        let short_gaps: u64 = self.gaps.values().sum();
+        if short_gaps >= self.long_gaps {
+            return self.median_gap().map(|gap| {
+                if gap <= SEVERAL_TIMES_A_DAY_GAP {
+                    Cadence::SeveralTimesADay
+                } else {
+                    Cadence::Daily
+                }
+            });
+        }
+        let span = self
+            .last_message?
+            .duration_since(self.first_message?)
+            .ok()?;
+        let weeks = span.as_secs_f64() / (7.0 * 86_400.0);
+        Some(if self.count as f64 >= weeks {
+            Cadence::Weekly
+        } else {
+            Cadence::Rarely
+        })
+    }
+
+    /// Share of dated messages sent between midnight and 6 AM, in percent.
+    pub fn night_percent(&self) -> f64 {
+        let night = self.hours[NIGHT_HOURS].iter().sum();
+        percent(night, self.hours.iter().sum())
+    }
+
+    /// Average number of consecutive messages per run.
+    pub fn avg_run_length(&self) -> f64 {
+        ratio(self.count, self.runs)
+    }
+
+    /// Share of messages that are replies, in percent.
+    pub fn reply_percent(&self) -> f64 {
+        percent(self.replies, self.count)
+    }
+
+    /// Share of messages that are forwards, in percent.
+    pub fn forward_percent(&self) -> f64 {
+        percent(self.forwards, self.count)
+    }
+
+    /// Share of messages consisting of a single word, in percent.
+    pub fn single_word_percent(&self) -> f64 {
+        percent(self.single_word_messages, self.count)
+    }
+
+    /// Share of messages with at least 5 letters written in ALL CAPS, in
+    /// percent. Shorter messages, such as `OK` or a sticker, are left out.
+    pub fn caps_percent(&self) -> f64 {
+        percent(self.caps_messages, self.caps_eligible)
+    }
+
+    /// Share of messages containing an ellipsis, in percent.
+    pub fn ellipsis_percent(&self) -> f64 {
+        percent(self.ellipsis_messages, self.count)
+    }
+
+    /// Average number of `!` per message.
+    pub fn exclamations_per_message(&self) -> f64 {
+        ratio(self.exclamations, self.count)
+    }
+
+    /// Average number of sentences per message.
+    pub fn sentences_per_message(&self) -> f64 {
+        ratio(self.sentences, self.count)
+    }
+
+    /// Type–token ratio: distinct words divided by total words.
+    pub fn type_token_ratio(&self) -> f64 {
+        ratio(self.unique_words, self.total_words)
+    }
+
+    pub fn top_words(&self, max: usize) -> Vec<(&Word, &usize)> {
+        top_n_by(self.words.iter().collect(), max, by_count)
+    }
+
+    /// The label to display for a key of `words`: the most frequent
+    /// surface form when stemming is enabled, the word itself otherwise.
+    pub fn word_label(&self, word: &Word) -> &'static str {
+        self.surface_forms
+            .get(word)
+            .and_then(|forms| {
+                // The first form in alphabetical order among the most
+                // frequent, so that ties are labeled the same on every run.
+                forms
+                    .iter()
+                    .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+            })
+            .map_or(word.as_str(), |(form, _)| form.as_str())
+    }
+
+    /// The most frequent words from link, mention, hashtag, command and
+    /// code entities, when [`StatsSettings::entity_words`] is
+    /// [`EntityWords::Separate`].
+    pub fn top_entity_words(&self, max: usize) -> Vec<(&Word, &usize)> {
+        top_n_by(self.entity_words.iter().collect(), max, by_count)
+    }
+
+    pub fn top_phrases(&self, max: usize) -> Vec<(&String, &usize)> {
+        top_n_by(self.phrases.iter().collect(), max, by_count)
+    }
+}
+
+impl UserStats {
+    /// Add the statistics of `other` to this one, as if both had been
+    /// computed from a single stream of messages.
+    pub fn merge(&mut self, other: UserStats) {
+        self.merge_ref(&other);
+    }
+
+    /// Like [`UserStats::merge`], copying what is needed from `other`
+    /// rather than taking it.
+    pub fn merge_ref(&mut self, other: &UserStats) {
+        if self.name.is_empty() || other.last_message >= self.last_message {
+            self.name.clone_from(&other.name);
+        }
+        self.names.extend(other.names.iter().cloned());
+        for (&gap, &count) in &other.gaps {
+            *self.gaps.entry(gap).or_default() += count;
+        }
+        self.long_gaps += other.long_gaps;
+        for (hour, count) in self.hours.iter_mut().zip(other.hours) {
+            *hour += count;
+        }
+        self.count += other.count;
+        self.total_chars += other.total_chars;
+        self.max_chars = self.max_chars.max(other.max_chars);
+        self.first_message = time::earliest(self.first_message, other.first_message);
+        self.last_message = self.last_message.max(other.last_message);
+        self.total_words += other.total_words;
+        self.caps_messages += other.caps_messages;
+        self.caps_eligible += other.caps_eligible;
+        self.exclamations += other.exclamations;
+        self.ellipsis_messages += other.ellipsis_messages;
+        self.sentences += other.sentences;
+        self.total_word_chars += other.total_word_chars;
+        let (chars, other_chars) = (
+            self.longest_word.chars().count(),
+            other.longest_word.chars().count(),
+        );
+        if other_chars > chars || (other_chars == chars && other.longest_word < self.longest_word) {
+            self.longest_word.clone_from(&other.longest_word);
+        }
+        self.single_word_messages += other.single_word_messages;
+        self.runs += other.runs;
+        // Of equally long runs, the earliest is kept.
+        if (other.longest_run, Reverse(other.longest_run_date))
+            > (self.longest_run, Reverse(self.longest_run_date))
+        {
+            self.longest_run = other.longest_run;
+            self.longest_run_date = other.longest_run_date;
+        }
+        self.replies += other.replies;
+        self.forwards += other.forwards;
+        self.conversations_started += other.conversations_started;
+        for (language, count) in &other.languages {
+            *self.languages.entry(language.clone()).or_default() += count;
+        }
+        self.pinned += other.pinned;
+        self.vocabulary.extend(&other.vocabulary);
+        self.unique_words = self.vocabulary.len() as u64;
+        for (&word, count) in &other.words {
+            *self.words.entry(word).or_insert(0) += count;
+        }
+        for (&word, count) in &other.entity_words {
+            *self.entity_words.entry(word).or_insert(0) += count;
+        }
+        for (reaction, count) in &other.received_reactions {
+            *self.received_reactions.entry(reaction.clone()).or_insert(0) += count;
+        }
+        for (&stem, forms) in &other.surface_forms {
+            let self_forms = self.surface_forms.entry(stem).or_default();
+            for (&form, count) in forms {
+                *self_forms.entry(form).or_insert(0) += count;
+            }
+        }
+        for (phrase, count) in &other.phrases {
+            *self.phrases.entry(phrase.clone()).or_insert(0) += count;
+        }
     }
 }
 
 impl std::iter::Sum for UserStats {
     fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
         iter.fold(UserStats::default(), |mut acc, item| {
-            acc.count += item.count;
-            acc.total_chars += item.total_chars;
-            acc.max_chars = acc.max_chars.max(item.max_chars);
-            for (word, count) in item.words {
-                *acc.words.entry(word).or_insert(0) += count;
-            }
-            for (reaction, count) in item.received_reactions {
-                *acc.received_reactions.entry(reaction).or_insert(0) += count;
-            }
+            acc.merge(item);
+            acc
+        })
+    }
+}
+
+/// Sums participants without cloning them, e.g. for the combined
+/// statistics of a [`ChatStats`].
+impl<'a> std::iter::Sum<&'a UserStats> for UserStats {
+    fn sum<I: Iterator<Item = &'a UserStats>>(iter: I) -> Self {
+        iter.fold(UserStats::default(), |mut acc, item| {
+            acc.merge_ref(item);
             acc
         })
     }
 }
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Default)]
 pub struct ChatStats {
     pub messages: u64,
     pub service_messages: u64,
+    /// Service messages per action, keyed by the action name in the export
+    /// (e.g. `pin_message`, `phone_call`).
+    pub service_actions: StatsMap<String, u64>,
     pub edited: u64,
-    pub participants: HashMap<String, UserStats>,
-    pub text_entity_types: HashMap<String, u64>,
+    /// Messages from senders excluded by [`StatsSettings::exclude`] or
+    /// [`StatsSettings::exclude_bots`], or left out by
+    /// [`StatsSettings::senders`]. They count towards `messages` but
+    /// nothing else.
+    pub excluded: u64,
+    /// Messages skipped because an earlier message of the same chat has
+    /// the same id, as in merged or hand-edited exports. The first one is
+    /// kept; the others count towards nothing, `messages` included.
+    pub duplicate_ids: u64,
+    /// Messages without a timestamp that can be parsed, which are left out
+    /// of every statistic over time. Counted whether or not
+    /// [`StatsSettings::undated`] lets them through a date range.
+    pub undated: u64,
+    /// Messages that are part of a run of at least three consecutive
+    /// messages by the same sender.
+    pub messages_in_runs: u64,
+    /// Per-participant statistics, keyed by sender id (`from_id`, e.g. `user123`).
+    pub participants: StatsMap<String, UserStats>,
+    pub text_entity_types: StatsMap<String, u64>,
     pub settings: StatsSettings,
+    /// The longest reply chain, one message per line, oldest first:
+    /// `[date] sender: text`, with the text on one line and cut to 60
+    /// characters, or just `[date] sender` for messages without text.
     pub longest_chain: String,
+    /// Per-chat breakdown, keyed by the label passed to
+    /// [`ChatStats::analyze_labeled`].
+    pub chats: BTreeMap<String, ChatSummary>,
+    /// Messages by day of week (Monday first) and hour of day, in
+    /// [`StatsSettings::time_zone`].
+    pub weekday_hours: [[u64; 24]; 7],
+    /// Messages per month (`YYYY-MM`, in [`StatsSettings::time_zone`]).
+    pub timeline: BTreeMap<String, u64>,
+    /// Joins and leaves recorded by service messages.
+    pub membership: Membership,
+    /// Forwarded messages per original sender or channel name.
+    pub forward_sources: StatsMap<String, u64>,
+    /// Gaps in message ids, an estimate of deleted messages.
+    pub id_gaps: IdGaps,
+    /// Pinned messages, in the order they were pinned.
+    pub pins: Vec<Pin>,
+    /// Title and photo changes, oldest first.
+    pub title_history: Vec<TitleChange>,
+    /// Hits of [`StatsSettings::track_words`] and
+    /// [`StatsSettings::track_patterns`]: (keyword, (sender id, count)).
+    pub tracked: StatsMap<String, StatsMap<String, u64>>,
+    /// @-mentions in message text: (author sender id, (mentioned, count)).
+    /// The mentioned person is a sender id for `mention_name` entities and
+    /// a lowercase `@username` for `mention` ones; see
+    /// [`ChatStats::mention_pairs`] for both resolved to participants.
+    pub mentions: StatsMap<String, StatsMap<String, u64>>,
+    /// Uses of [`StatsSettings::trend_words`] per month (`YYYY-MM`, in
+    /// [`StatsSettings::time_zone`]): (word, (month, count)).
+    pub trends: StatsMap<String, BTreeMap<String, u64>>,
+    /// Everyone listed as a recent reactor, keyed by sender id. The export
+    /// only lists the last few reactors of each message, so counts are a
+    /// lower bound.
+    pub reactors: StatsMap<String, Reactor>,
+    /// Milestone messages of each chat, see [`StatsSettings::milestones`],
+    /// ordered by chat and number.
+    pub milestones: Vec<Milestone>,
+    /// Per-topic breakdown of forum chats, keyed by topic title. Only
+    /// filled when [`StatsSettings::group_by_topic`] is set.
+    pub topics: StatsMap<String, TopicStats>,
+    pub(crate) state: PushState,
 }
 
-#[derive(Clone, Debug, Default, Serialize)]
-pub struct StatsSettings {
-    /// How many most frequent words to display.
-    pub max_words: usize,
-    /// Wheter to show most frequent text entity types.
-    pub show_entities: bool,
-    /// How many top participants to display.
-    pub max_participants: usize,
+/// Bookkeeping carried between [`ChatStats::push`] calls.
+#[derive(Debug, Default)]
+pub(crate) struct PushState {
+    /// Built from the settings on first use.
+    filter: Option<WordFilter>,
+    /// Built from the settings on first use.
+    tracker: Option<Tracker>,
+    /// Position in each chat being pushed, by label.
+    chats: HashMap<String, ChatCursor>,
+    /// Length of the reply chain rendered into `ChatStats::longest_chain`.
+    pub(crate) longest_chain: usize,
+    /// Activity per calendar day in [`StatsSettings::time_zone`], for
+    /// [`ChatStats::longest_streak`] and [`ChatStats::busiest_days`].
+    pub(crate) days: BTreeMap<NaiveDate, DayActivity>,
 }
 
-impl ChatStats {
-    pub fn analyze(&mut self, messages: Vec<RawMessage>) {
-        self.messages += messages.len() as u64;
-        let words: HashSet<_> = stop_words::get(stop_words::LANGUAGE::Russian)
-            .into_iter()
-            .collect();
-
-        let mut im = IndexedMessages::default();
+/// What [`ChatStats::push_labeled`] remembers about one chat between
+/// messages.
+#[derive(Debug, Default)]
+struct ChatCursor {
+    /// Reply index, to follow reply chains.
+    replies: IndexedMessages,
+    /// When the previous message was sent, to spot new conversations.
+    last_date: Option<SystemTime>,
+    /// When each sender's previous message was sent, by sender id, for the
+    /// gaps between their messages.
+    last_sent: HashMap<String, SystemTime>,
+    /// The current run of messages by one sender.
+    run: Run,
+    /// The previous message's id and date, to find gaps in message ids.
+    last_id: Option<(u64, Option<SystemTime>)>,
+    /// Regular messages seen so far, for [`StatsSettings::milestones`].
+    messages: u64,
+    /// Ids of the messages seen so far, to skip duplicates.
+    ids: StatsSet<u64>,
+    /// Titles of the forum topics created so far.
+    topic_titles: Vec<String>,
+    /// Topic of each message outside [`GENERAL_TOPIC`], as an index into
+    /// `topic_titles`. Only filled when [`StatsSettings::group_by_topic`]
+    /// is set.
+    topics: HashMap<u64, usize>,
+}
 
-        for message in messages {
-            match &message {
-                RawMessage::Service { text_entities, .. } => {
-                    self.service_messages += 1;
-                    self.count_entities(text_entities);
-                }
-                _ => {}
-            }
+impl ChatCursor {
+    /// Start the forum topic `title` at the `topic_created` message `id`.
+    fn create_topic(&mut self, id: u64, title: &str) {
+        self.topics.insert(id, self.topic_titles.len());
+        self.topic_titles.push(title.to_string());
+    }
 
-            if let Some((id, msg)) = message.message() {
-                self.participants
-                    .entry(msg.from.clone())
-                    .or_default()
-                    .add_message(&msg.text, &words, msg.date)
-                    .add_reactions(&msg.reactions);
-                if msg.edited.is_some() {
-                    self.edited += 1;
-                }
-                self.count_entities(&msg.text_entities);
-                im.add_message(id, msg);
+    /// The topic of message `id`: the topic of the message it replies to.
+    ///
+    /// Telegram makes every message in a topic a reply to the topic's
+    /// `topic_created` message, unless it replies to another message in
+    /// the same topic.
+    fn enter_topic(&mut self, id: u64, reply_to_message_id: Option<u64>) -> &str {
+        let topic = reply_to_message_id.and_then(|parent| self.topics.get(&parent).copied());
+        match topic {
+            Some(topic) => {
+                self.topics.insert(id, topic);
+                &self.topic_titles[topic]
             }
+            None => GENERAL_TOPIC,
         }
-
-        self.longest_chain = format!("{:#?}", im.longest_chain());
     }
+}
 
-    fn count_entities(&mut self, entities: &[crate::TextEntity]) {
-        for entity in entities {
-            *self
-                .text_entity_types
-                .entry(entity.entity_type.clone())
-                .or_default() += 1;
+/// Consecutive messages by one sender with no one else in between.
+#[derive(Clone, Debug, Default)]
+struct Run {
+    from_id: String,
+    length: u64,
+    started: Option<SystemTime>,
+}
+
+/// Where a message falls in its chat, as seen by [`ChatStats::advance_chat`].
+struct ChatStep {
+    starts_conversation: bool,
+    /// When the sender's previous message in the chat was sent.
+    previous: Option<SystemTime>,
+    /// The run of messages by this sender that the message extends.
+    run: Run,
+    /// The forum topic of the message, when grouping by topic.
+    topic: Option<String>,
+    /// The message's number in its chat, if that is one of
+    /// [`StatsSettings::milestones`].
+    milestone: Option<u64>,
+}
+
+/// The fewest messages [`ChatStats::analyze_parallel`] analyzes on one core.
+#[cfg(feature = "rayon")]
+const MIN_PARALLEL_CHUNK: usize = 10_000;
+
+/// Runs at least this long count towards [`ChatStats::messages_in_runs`].
+const LONG_RUN: u64 = 3;
+
+/// Messages sent on one calendar day.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub(crate) struct DayActivity {
+    messages: u64,
+    /// Messages per sender id.
+    senders: HashMap<String, u64>,
+}
+
+/// How many of [`ChatStats::busiest_days`] the JSON output includes.
+pub(crate) const BUSIEST_DAYS: usize = 10;
+
+/// A run of consecutive days on which someone posted.
+#[derive(Clone, Debug, Serialize)]
+pub struct Streak {
+    pub first_day: NaiveDate,
+    pub last_day: NaiveDate,
+    pub days: u64,
+}
+
+/// One day's activity, as listed by [`ChatStats::busiest_days`].
+#[derive(Clone, Debug, Serialize)]
+pub struct BusyDay {
+    pub date: NaiveDate,
+    pub messages: u64,
+    /// Display name of whoever posted the most that day.
+    pub top_poster: String,
+    pub top_poster_messages: u64,
+}
+
+/// Label of the [`ChatStats::chats`] bucket filled by [`ChatStats::analyze`].
+pub const UNLABELED: &str = "(unlabeled)";
+
+/// Statistics of a single chat within a [`ChatStats`] aggregate.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ChatSummary {
+    pub messages: u64,
+    /// Serialized as an ISO 8601 string in UTC.
+    #[serde(
+        serialize_with = "time::serialize_opt",
+        deserialize_with = "time::deserialize_opt"
+    )]
+    pub first_message: Option<SystemTime>,
+    /// Serialized as an ISO 8601 string in UTC.
+    #[serde(
+        serialize_with = "time::serialize_opt",
+        deserialize_with = "time::deserialize_opt"
+    )]
+    pub last_message: Option<SystemTime>,
+    /// Messages per sender display name.
+    #[serde(serialize_with = "serialize_ranked")]
+    pub senders: HashMap<String, u64>,
+}
+
+impl ChatSummary {
+    fn add_message(&mut self, from: &str, timestamp: Option<SystemTime>) {
+        *self.senders.entry(from.to_string()).or_insert(0) += 1;
+        if let Some(ts) = timestamp {
+            self.first_message = Some(self.first_message.map_or(ts, |old| old.min(ts)));
+            self.last_message = Some(self.last_message.map_or(ts, |old| old.max(ts)));
         }
     }
 
-    fn display_user_stats(&self, stats: &UserStats, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if stats.count == 0 {
-            return write!(f, "- No messages");
+    fn merge(&mut self, other: ChatSummary) {
+        self.messages += other.messages;
+        self.first_message = time::earliest(self.first_message, other.first_message);
+        self.last_message = self.last_message.max(other.last_message);
+        for (sender, count) in other.senders {
+            *self.senders.entry(sender).or_insert(0) += count;
         }
+    }
+
+    /// The sender with the most messages in this chat.
+    pub fn top_sender(&self) -> Option<(&String, u64)> {
+        self.senders
+            .iter()
+            .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|(name, count)| (name, *count))
+    }
+}
 
-        writeln!(f, "- Messages       : {}", stats.count)?;
-        writeln!(f, "- Avg. length    : {} chars", stats.avg_chars())?;
-        writeln!(f, "- Longest message: {} chars", stats.max_chars)?;
+/// Topic of forum messages that belong to no topic created in the export,
+/// such as those in the built-in General topic.
+pub const GENERAL_TOPIC: &str = "General";
 
-        if let Some(first) = stats.first_message {
-            let datetime: DateTime<Local> = first.into();
-            writeln!(
-                f,
-                "- First message  : {}",
-                datetime.format("%Y-%m-%d %H:%M:%S")
-            )?;
+/// How many of each topic's top posters the text output shows.
+const TOPIC_POSTERS: usize = 3;
+
+/// Statistics of one forum topic, a reduced [`ChatStats`].
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TopicStats {
+    pub messages: u64,
+    /// Messages per sender display name.
+    #[serde(serialize_with = "serialize_ranked")]
+    pub senders: HashMap<String, u64>,
+    /// Word statistics, filtered like [`UserStats::words`]: (word, count)
+    #[serde(skip)]
+    pub words: StatsMap<Word, usize>,
+}
+
+impl TopicStats {
+    fn add_message(
+        &mut self,
+        from: &str,
+        message: &str,
+        entities: &[TextEntity],
+        filter: &WordFilter,
+    ) {
+        self.messages += 1;
+        *self.senders.entry(from.to_string()).or_insert(0) += 1;
+        if filter.skip_words {
+            return;
         }
-        if let Some(last) = stats.last_message {
-            let datetime: DateTime<Local> = last.into();
-            writeln!(
-                f,
-                "- Last message   : {}",
-                datetime.format("%Y-%m-%d %H:%M:%S")
-            )?;
+        for (word, from_entity) in filter.words(message, entities) {
+            if from_entity || !filter.accepts(&word) {
+                continue;
+            }
+            let word = filter.stem(&word).unwrap_or(word);
+            *self.words.entry(Word::new(&word)).or_insert(0) += 1;
         }
+    }
 
-        let mut received: Vec<_> = stats.received_reactions.iter().collect();
-        received.sort_unstable_by_key(|(_, count)| std::cmp::Reverse(*count));
-        let received = received
-            .into_iter()
-            .map(|(r, c)| format!("{r}×{c}"))
-            .collect::<Vec<_>>()
-            .join(", ");
-        writeln!(f, "- Reactions      : {}", received)?;
-
-        let top_words = stats.top_words(self.settings.max_words);
-        if !top_words.is_empty() {
-            let words_line = top_words
-                .iter()
-                .map(|(word, count)| format!("{} ({})", word, count))
-                .collect::<Vec<_>>()
-                .join(", ");
-            writeln!(f, "- Top words      : {}", words_line)?;
+    fn merge(&mut self, other: TopicStats) {
+        self.messages += other.messages;
+        for (sender, count) in other.senders {
+            *self.senders.entry(sender).or_insert(0) += count;
         }
-        Ok(())
+        for (word, count) in other.words {
+            *self.words.entry(word).or_insert(0) += count;
+        }
+    }
+
+    /// The senders with the most messages in this topic.
+    pub fn top_senders(&self, max: usize) -> Vec<(&String, &u64)> {
+        top_n_by(self.senders.iter().collect(), max, by_count)
+    }
+
+    pub fn top_words(&self, max: usize) -> Vec<(&Word, &usize)> {
+        top_n_by(self.words.iter().collect(), max, by_count)
     }
 }
 
-impl fmt::Display for ChatStats {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let combined = self.participants.values().cloned().sum::<UserStats>();
-        let reactions: usize = combined.received_reactions.values().sum();
+/// Characters of a pinned message's text kept in [`Pin::preview`].
+const PIN_PREVIEW_CHARS: usize = 60;
 
-        writeln!(f, "📊 Chat Statistics Summary\n=========================")?;
-        writeln!(f, "💬 Total messages     : {}", self.messages)?;
-        writeln!(f, "⚙️ Service messages   : {}", self.service_messages)?;
-        writeln!(f, "✏️ Edited messages    : {}", self.edited)?;
-        writeln!(f, "❤️ Total reactions    : {reactions}",)?;
+/// A `pin_message` service action.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Pin {
+    /// Id of the pinned message.
+    pub message_id: u64,
+    /// When the message was pinned. Serialized as an ISO 8601 string in UTC.
+    #[serde(
+        serialize_with = "time::serialize_opt",
+        deserialize_with = "time::deserialize_opt"
+    )]
+    pub date: Option<SystemTime>,
+    /// Display name of whoever pinned the message.
+    pub pinned_by: String,
+    /// Display name of the pinned message's author, or `None` when the
+    /// message is not part of the export.
+    pub author: Option<String>,
+    /// The start of the pinned message's text, or `None` when the message
+    /// is not part of the export.
+    pub preview: Option<String>,
+}
 
-        if combined.count > 0 {
-            writeln!(f, "\n📏 Combined Participant Stats:")?;
-            self.display_user_stats(&combined, f)?;
-        }
+/// A message whose number in its chat is one of
+/// [`StatsSettings::milestones`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Milestone {
+    /// Label of the chat, as passed to [`ChatStats::analyze_labeled`].
+    pub chat: String,
+    /// The message's number among the regular messages of its chat,
+    /// counting from one.
+    pub number: u64,
+    pub message_id: u64,
+    /// Serialized as an ISO 8601 string in UTC.
+    #[serde(
+        serialize_with = "time::serialize_opt",
+        deserialize_with = "time::deserialize_opt"
+    )]
+    pub date: Option<SystemTime>,
+    /// Display name of whoever sent the message.
+    pub author: String,
+    /// The start of the message's text.
+    pub preview: String,
+}
 
-        writeln!(f, "Longest chain: {}", self.longest_chain)?;
+/// A `create_group`, `edit_group_title` or `edit_group_photo` service action.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TitleChange {
+    /// Label of the chat, as passed to [`ChatStats::analyze_labeled`].
+    pub chat: String,
+    /// Serialized as an ISO 8601 string in UTC.
+    #[serde(
+        serialize_with = "time::serialize_opt",
+        deserialize_with = "time::deserialize_opt"
+    )]
+    pub date: Option<SystemTime>,
+    /// Display name of whoever made the change.
+    pub changed_by: String,
+    /// The new title, or `None` if the photo was changed.
+    pub title: Option<String>,
+}
 
-        if !self.participants.is_empty() {
-            let max = self.settings.max_participants;
-            let mut participants: Vec<_> = self.participants.iter().collect();
-            participants.sort_unstable_by_key(|(_, stats)| std::cmp::Reverse(stats.count));
+/// Reactions given by one person.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Reactor {
+    /// The display name last seen for this reactor.
+    pub name: String,
+    pub reactions: u64,
+}
 
-            writeln!(f, "\n👥 Top Participants ({}):", participants.len())?;
-            for (i, (name, stats)) in participants.iter().take(max).enumerate() {
-                let percent = 100.0 * (stats.total_chars as f64 / combined.total_chars as f64);
-                writeln!(f, "\n{}. {name}  (Character share: {percent:.0}%)", i + 1)?;
-                self.display_user_stats(&stats, f)?;
-            }
-            if participants.len() > max {
-                writeln!(f, "... and {} more", participants.len() - max)?;
-            }
-        }
+/// How many of the largest id gaps [`IdGaps::largest`] keeps.
+const MAX_ID_GAPS: usize = 10;
 
-        if !self.text_entity_types.is_empty() && self.settings.show_entities {
-            writeln!(
-                f,
-                "\n🔤 Text Entity Types ({}):",
-                self.text_entity_types.len()
-            )?;
-            let mut entities: Vec<_> = self.text_entity_types.iter().collect();
-            entities.sort_unstable_by_key(|(_, count)| std::cmp::Reverse(*count));
+/// Gaps in message ids, as an estimate of deleted messages.
+///
+/// Telegram numbers the messages of a chat consecutively, so ids missing
+/// from an export mostly belong to deleted messages. Some belong to
+/// messages that are only hidden from the exporting account, such as
+/// other forum topics, so every count here is an estimate.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct IdGaps {
+    /// Ids from the first to the last message of each chat, summed.
+    pub id_range: u64,
+    /// Messages present in the export.
+    pub present: u64,
+    /// Ids missing between present messages.
+    pub estimated_deletions: u64,
+    /// Missing ids per month (`YYYY-MM`, in [`StatsSettings::time_zone`])
+    /// of the message after each gap.
+    pub estimated_deletions_monthly: BTreeMap<String, u64>,
+    /// The largest gaps, largest first.
+    pub largest: Vec<IdGap>,
+}
 
-            for (entity, &count) in entities {
-                writeln!(f, "- {entity:15}: {count:>4}")?;
-            }
+/// A run of missing message ids.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IdGap {
+    /// Label of the chat, as passed to [`ChatStats::analyze_labeled`].
+    pub chat: String,
+    /// The last id present before the gap.
+    pub after_id: u64,
+    /// The first id present after the gap.
+    pub before_id: u64,
+    /// Serialized as an ISO 8601 string in UTC.
+    #[serde(
+        serialize_with = "time::serialize_opt",
+        deserialize_with = "time::deserialize_opt"
+    )]
+    pub after_date: Option<SystemTime>,
+    /// Serialized as an ISO 8601 string in UTC.
+    #[serde(
+        serialize_with = "time::serialize_opt",
+        deserialize_with = "time::deserialize_opt"
+    )]
+    pub before_date: Option<SystemTime>,
+}
+
+impl IdGap {
+    /// How many ids are missing.
+    pub fn missing(&self) -> u64 {
+        self.before_id - self.after_id - 1
+    }
+}
+
+impl IdGaps {
+    fn add_gap(&mut self, gap: IdGap, month: Option<String>) {
+        let missing = gap.missing();
+        self.estimated_deletions += missing;
+        if let Some(month) = month {
+            *self.estimated_deletions_monthly.entry(month).or_default() += missing;
         }
+        self.largest.push(gap);
+        self.keep_largest();
+    }
 
-        Ok(())
+    fn keep_largest(&mut self) {
+        self.largest.sort_by(|a, b| {
+            b.missing()
+                .cmp(&a.missing())
+                .then_with(|| a.chat.cmp(&b.chat))
+                .then_with(|| a.after_id.cmp(&b.after_id))
+        });
+        self.largest.truncate(MAX_ID_GAPS);
+    }
+
+    fn merge(&mut self, other: IdGaps) {
+        self.id_range += other.id_range;
+        self.present += other.present;
+        self.estimated_deletions += other.estimated_deletions;
+        for (month, missing) in other.estimated_deletions_monthly {
+            *self.estimated_deletions_monthly.entry(month).or_default() += missing;
+        }
+        self.largest.extend(other.largest);
+        self.keep_largest();
+    }
+}
+
+/// Source counted for forwards whose original sender is hidden.
+pub const HIDDEN_FORWARD_SOURCE: &str = "(hidden)";
+
+/// How many messages without a valid timestamp are logged, so that an
+/// export with none doesn't flood the log.
+const UNDATED_WARNINGS: u64 = 5;
+
+/// Name counted for senders and members whose account was deleted before
+/// the export, as Telegram shows them.
+pub const DELETED_ACCOUNT: &str = "Deleted Account";
+
+/// Members joining and leaving, from `invite_members`, `join_group_by_link`
+/// and `remove_members` service messages.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Membership {
+    pub joined: u64,
+    /// Members who left or were removed.
+    pub left: u64,
+    /// Times each member joined, by display name.
+    #[serde(serialize_with = "serialize_ranked")]
+    pub joins: HashMap<String, u64>,
+    /// Times each member left or was removed, by display name.
+    #[serde(serialize_with = "serialize_ranked")]
+    pub leaves: HashMap<String, u64>,
+    /// Members brought in by each inviter, by display name.
+    #[serde(serialize_with = "serialize_ranked")]
+    pub inviters: HashMap<String, u64>,
+    /// Joins and leaves per month (`YYYY-MM`, in [`StatsSettings::time_zone`]).
+    pub monthly: BTreeMap<String, MembershipChange>,
+}
+
+/// Joins and leaves within one period.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct MembershipChange {
+    pub joined: u64,
+    pub left: u64,
+}
+
+impl Membership {
+    fn add_service(&mut self, service: &Service<'_>, month: Option<String>) {
+        let mut change = MembershipChange::default();
+        match service.action {
+            ServiceAction::InviteMembers(members) => {
+                for member in members {
+                    let member = member.as_deref().unwrap_or(DELETED_ACCOUNT);
+                    *self.joins.entry(member.to_string()).or_default() += 1;
+                    // People joining a public group show up as inviting themselves.
+                    if member != service.actor {
+                        *self.inviters.entry(service.actor.to_string()).or_default() += 1;
+                    }
+                    change.joined += 1;
+                }
+            }
+            ServiceAction::JoinByLink { inviter } => {
+                *self.joins.entry(service.actor.to_string()).or_default() += 1;
+                if let Some(inviter) = inviter {
+                    *self.inviters.entry(inviter.to_string()).or_default() += 1;
+                }
+                change.joined += 1;
+            }
+            ServiceAction::RemoveMembers(members) => {
+                for member in members {
+                    let member = member.as_deref().unwrap_or(DELETED_ACCOUNT);
+                    *self.leaves.entry(member.to_string()).or_default() += 1;
+                    change.left += 1;
+                }
+            }
+            _ => return,
+        }
+
+        self.joined += change.joined;
+        self.left += change.left;
+        if let Some(month) = month {
+            self.monthly.entry(month).or_default().merge(change);
+        }
+    }
+
+    fn merge(&mut self, other: Membership) {
+        self.joined += other.joined;
+        self.left += other.left;
+        for (map, other_map) in [
+            (&mut self.joins, other.joins),
+            (&mut self.leaves, other.leaves),
+            (&mut self.inviters, other.inviters),
+        ] {
+            for (name, count) in other_map {
+                *map.entry(name).or_default() += count;
+            }
+        }
+        for (month, change) in other.monthly {
+            self.monthly.entry(month).or_default().merge(change);
+        }
+    }
+
+    /// Joins minus leaves over the whole export.
+    pub fn net_change(&self) -> i64 {
+        self.joined as i64 - self.left as i64
+    }
+
+    /// The inviter who brought in the most members.
+    pub fn top_inviter(&self) -> Option<(&String, u64)> {
+        self.inviters
+            .iter()
+            .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|(name, count)| (name, *count))
+    }
+}
+
+impl MembershipChange {
+    fn merge(&mut self, other: MembershipChange) {
+        self.joined += other.joined;
+        self.left += other.left;
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct StatsSettings {
+    /// How many most frequent words to display.
+    pub max_words: usize,
+    /// Which blocks of the text output to print.
+    pub sections: Sections,
+    /// Language of the text output. Languages without translated
+    /// [`Labels`] fall back to English.
+    pub language: Language,
+    /// How many top participants to display.
+    pub max_participants: usize,
+    /// Width of the bar charts in the text output, in characters.
+    /// Charts are left out when zero.
+    pub chart_width: usize,
+    /// How much of the per-user word and reaction maps to include in JSON.
+    pub json_detail: JsonDetail,
+    /// What participants are ranked by, and what their displayed share is of.
+    pub rank_by: RankBy,
+    /// Senders to leave out of the statistics, matched against both the
+    /// display name and the sender id.
+    #[serde(serialize_with = "serialize_sorted_set")]
+    pub exclude: HashSet<String>,
+    /// Whether to leave out senders that look like bots: those whose display
+    /// name or sender id ends in "bot", ignoring case (e.g. `HelperBot`,
+    /// `@gif_bot`). This is a heuristic; use `exclude` for anything it misses.
+    pub exclude_bots: bool,
+    /// Sender ids to analyze, e.g. found by [`SenderNames::resolve`]. When
+    /// not empty, everyone else is left out as if in `exclude`.
+    ///
+    /// [`SenderNames::resolve`]: crate::SenderNames::resolve
+    #[serde(serialize_with = "serialize_sorted_set")]
+    pub senders: HashSet<String>,
+    /// How many reactions someone who never posted must have given to be
+    /// listed as a lurker.
+    pub min_lurker_reactions: u64,
+    /// Time zone for time-of-day statistics and displayed dates.
+    pub time_zone: TimeZone,
+    /// Labels for custom emoji reactions, by `document_id`. Custom emoji
+    /// without a label are shown as `custom:` and the end of their id.
+    /// Reactions with the same label are counted together, so mapping a
+    /// custom emoji to `👍` merges it with the regular `👍`.
+    #[serde(serialize_with = "serialize_sorted_map")]
+    pub emoji_labels: HashMap<String, String>,
+    /// Silence after which the next message in a chat starts a new
+    /// conversation, in minutes. `None` means one hour.
+    pub conversation_gap_minutes: Option<u64>,
+    /// Only messages sent at or after this moment are analyzed.
+    pub since: Option<DateTime<Utc>>,
+    /// Only messages sent strictly before this moment are analyzed.
+    pub until: Option<DateTime<Utc>>,
+    /// What to do with messages whose date can't be parsed when `since` or
+    /// `until` is set.
+    pub undated: UndatedPolicy,
+    /// Sender ids to count as another sender, e.g. to merge a participant's
+    /// old account into their new one: (from_id, canonical from_id).
+    #[serde(serialize_with = "serialize_sorted_map")]
+    pub aliases: HashMap<String, String>,
+    /// How many distinctive words to display per participant.
+    /// See [`ChatStats::distinctive_words`].
+    pub max_distinctive_words: usize,
+    /// How many most frequent two- and three-word phrases to display.
+    /// Phrase counting is disabled when zero.
+    pub max_phrases: usize,
+    /// Languages whose stop words are excluded from word and phrase statistics.
+    pub stop_word_languages: Vec<Language>,
+    /// Additional words excluded from word and phrase statistics, in lowercase.
+    #[serde(serialize_with = "serialize_sorted_set")]
+    pub extra_stop_words: HashSet<String>,
+    /// Words to count per participant, matched against words the same way
+    /// word statistics see them (lowercased and, with stemming, stemmed).
+    pub track_words: Vec<String>,
+    /// Regular expressions to count matches of per participant, matched
+    /// against the whole message text. Requires the `regex` feature.
+    pub track_patterns: Vec<String>,
+    /// Words to count per month across the whole chat, matched the same
+    /// way as `track_words`. See [`ChatStats::trends`].
+    pub trend_words: Vec<String>,
+    /// Whether to leave messages unsplit into words, which is much faster
+    /// when only message counts and timing matter. Word counts, the
+    /// vocabulary, the longest word, top and distinctive words, phrases
+    /// and the words of topics are then left empty.
+    pub skip_words: bool,
+    /// Words shorter than this many characters are excluded from word statistics.
+    pub min_word_length: usize,
+    /// Whether to exclude purely numeric tokens such as `5` or `1,000`.
+    pub skip_numbers: bool,
+    /// Whether to exclude tokens that look like URLs.
+    pub skip_links: bool,
+    /// Whether to exclude `@mentions`.
+    pub skip_mentions: bool,
+    /// How messages are split into words.
+    pub tokenizer: Tokenizer,
+    /// How message and word lengths are counted: by Unicode scalar values
+    /// (the default) or by grapheme clusters, which match what a reader
+    /// sees but are slower to count. Also applies to pin previews.
+    pub char_count: CharCount,
+    /// What to do with words from links, mentions, hashtags, bot commands
+    /// and code.
+    pub entity_words: EntityWords,
+    /// Languages whose stemmers are applied to counted words, so that
+    /// `run`/`runs`/`running` are counted together. Each word is routed to
+    /// the first language written in the same script. Requires the
+    /// `stemming` feature.
+    pub stem_languages: Vec<Language>,
+    /// Whether to detect the language of each message for
+    /// [`UserStats::languages`]. Requires the `lang-detect` feature.
+    pub detect_languages: bool,
+    /// Whether to filter each message's words with the stop words of its
+    /// detected language (plus `extra_stop_words`) instead of those of
+    /// `stop_word_languages`. Messages whose language can't be detected
+    /// still use `stop_word_languages`. Implies `detect_languages`.
+    pub detected_stop_words: bool,
+    /// Whether to break forum chats down by topic into
+    /// [`ChatStats::topics`]. Topics are told apart by following replies
+    /// to each `topic_created` message, so messages replying to ones
+    /// missing from the export end up in [`GENERAL_TOPIC`].
+    pub group_by_topic: bool,
+    /// Numbers of the messages to record in [`ChatStats::milestones`],
+    /// e.g. 1000 for every chat's 1000th message. Regular messages within
+    /// `since` and `until` are counted in export order, including those of
+    /// excluded senders.
+    pub milestones: Vec<u64>,
+}
+
+/// Decides how messages are split into words and which of them take part
+/// in word and phrase statistics.
+#[derive(Clone, Debug, Default)]
+pub struct WordFilter {
+    tokenizer: Tokenizer,
+    char_count: CharCount,
+    stop_words: HashSet<String>,
+    min_length: usize,
+    skip_numbers: bool,
+    skip_links: bool,
+    skip_mentions: bool,
+    /// Built once from [`StatsSettings::stem_languages`].
+    #[cfg(feature = "stemming")]
+    stemmers: Vec<LanguageStemmer>,
+    entity_words: EntityWords,
+    detect_languages: bool,
+    /// See [`StatsSettings::skip_words`].
+    skip_words: bool,
+    /// Stop words per language code, used for messages in that language.
+    language_stop_words: HashMap<&'static str, HashSet<String>>,
+}
+
+impl WordFilter {
+    /// Reduce `word` to its stem, or `None` when stemming doesn't apply.
+    #[cfg(feature = "stemming")]
+    pub fn stem(&self, word: &str) -> Option<String> {
+        let script = Script::of(word)?;
+        self.stemmers
+            .iter()
+            .find(|stemmer| stemmer.language().script() == script)
+            .map(|stemmer| stemmer.stem(word))
+    }
+
+    /// Reduce `word` to its stem, or `None` when stemming doesn't apply.
+    #[cfg(not(feature = "stemming"))]
+    pub fn stem(&self, _word: &str) -> Option<String> {
+        None
+    }
+
+    /// Split a message into lowercase words, each paired with whether it
+    /// comes from one of the entities [`StatsSettings::entity_words`]
+    /// applies to. Those are never marked with [`EntityWords::Count`].
+    pub fn words(&self, message: &str, entities: &[TextEntity]) -> Vec<(String, bool)> {
+        let tokens = |text: &str, from_entity| {
+            self.tokenizer
+                .tokens(text)
+                .into_iter()
+                .map(move |word| (word, from_entity))
+        };
+        if self.entity_words == EntityWords::Count || entities.is_empty() {
+            return tokens(message, false).collect();
+        }
+
+        // Join the text between entities so formatting inside a word
+        // doesn't split it.
+        let mut words = Vec::new();
+        let mut plain = String::new();
+        for entity in entities {
+            if WORD_ENTITY_TYPES.contains(&entity.entity_type.as_str()) {
+                words.extend(tokens(&plain, false));
+                plain.clear();
+                words.extend(tokens(&entity.text, true));
+            } else {
+                plain.push_str(&entity.text);
+            }
+        }
+        words.extend(tokens(&plain, false));
+        words
+    }
+
+    /// The language of `message`, or `None` if detection is off, the message
+    /// is too short, or the guess isn't reliable.
+    pub fn detect(&self, message: &str) -> Option<&'static str> {
+        if !self.detect_languages || message.chars().count() < MIN_DETECT_CHARS {
+            return None;
+        }
+        detect_language(message)
+    }
+
+    /// Whether `word` (already lowercased) should be counted.
+    pub fn accepts(&self, word: &str) -> bool {
+        self.accepts_in(word, None)
+    }
+
+    /// Like [`WordFilter::accepts`], for a word from a message detected as
+    /// `language`.
+    pub fn accepts_in(&self, word: &str, language: Option<&str>) -> bool {
+        let stop_words = language
+            .and_then(|code| self.language_stop_words.get(code))
+            .unwrap_or(&self.stop_words);
+        let rejected = stop_words.contains(word)
+            || word.chars().count() < self.min_length
+            || (self.skip_numbers && is_number(word))
+            || (self.skip_links && is_link(word))
+            || (self.skip_mentions && is_mention(word));
+        !rejected
+    }
+}
+
+/// Finds the keywords of [`StatsSettings::track_words`] and
+/// [`StatsSettings::track_patterns`] in messages.
+#[derive(Debug, Default)]
+struct Tracker {
+    /// Normalized word to the keyword it was given as.
+    words: HashMap<String, String>,
+    /// Normalized word to the [`StatsSettings::trend_words`] entry it was
+    /// given as.
+    trend_words: HashMap<String, String>,
+    #[cfg(feature = "regex")]
+    patterns: Vec<(String, regex::Regex)>,
+}
+
+impl Tracker {
+    fn new(settings: &StatsSettings, filter: &WordFilter) -> Self {
+        let normalize = |keywords: &[String]| {
+            keywords
+                .iter()
+                .flat_map(|keyword| {
+                    let word = filter.tokenizer.tokens(keyword).into_iter().next()?;
+                    let word = filter.stem(&word).unwrap_or(word);
+                    Some((word, keyword.clone()))
+                })
+                .collect()
+        };
+        let words = normalize(&settings.track_words);
+        let trend_words = normalize(&settings.trend_words);
+
+        #[cfg(feature = "regex")]
+        let patterns = settings
+            .track_patterns
+            .iter()
+            .filter_map(|pattern| match regex::Regex::new(pattern) {
+                Ok(regex) => Some((pattern.clone(), regex)),
+                Err(e) => {
+                    warn!("ignoring tracked pattern `{pattern}`: {e}");
+                    None
+                }
+            })
+            .collect();
+        #[cfg(not(feature = "regex"))]
+        if !settings.track_patterns.is_empty() {
+            warn!("tracked patterns given but the `regex` feature is disabled");
+        }
+
+        Tracker {
+            words,
+            trend_words,
+            #[cfg(feature = "regex")]
+            patterns,
+        }
+    }
+
+    /// Call `hit` with the keyword of every match in `text`.
+    fn find(&self, text: &str, filter: &WordFilter, mut hit: impl FnMut(&str)) {
+        if !self.words.is_empty() {
+            for word in filter.tokenizer.tokens(text) {
+                let word = filter.stem(&word).unwrap_or(word);
+                if let Some(keyword) = self.words.get(&word) {
+                    hit(keyword);
+                }
+            }
+        }
+        #[cfg(feature = "regex")]
+        for (pattern, regex) in &self.patterns {
+            for _ in regex.find_iter(text) {
+                hit(pattern);
+            }
+        }
+    }
+
+    /// Call `hit` with the trend word of every match in `text`.
+    fn find_trends(&self, text: &str, filter: &WordFilter, mut hit: impl FnMut(&str)) {
+        for word in filter.tokenizer.tokens(text) {
+            let word = filter.stem(&word).unwrap_or(word);
+            if let Some(keyword) = self.trend_words.get(&word) {
+                hit(keyword);
+            }
+        }
+    }
+}
+
+/// Count sentences as runs of text ended by `.`, `!`, `?` or `…`.
+fn count_sentences(text: &str) -> u64 {
+    let mut sentences = 0;
+    let mut in_sentence = false;
+    for c in text.chars() {
+        if matches!(c, '.' | '!' | '?' | '…') {
+            if in_sentence {
+                sentences += 1;
+                in_sentence = false;
+            }
+        } else if c.is_alphanumeric() {
+            in_sentence = true;
+        }
+    }
+    sentences + u64::from(in_sentence)
+}
+
+/// Order `(key, count)` pairs by count, highest first, and ties by key, so
+/// that rankings come out the same on every run.
+pub(crate) fn by_count<K: Ord, C: Ord>(a: &(K, C), b: &(K, C)) -> Ordering {
+    b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0))
+}
+
+/// The first `max` of `entries` in the order of `compare`, sorted.
+///
+/// Only the entries returned are sorted, which is much faster than
+/// sorting them all when there are many more of them than `max`.
+pub(crate) fn top_n_by<T>(
+    mut entries: Vec<T>,
+    max: usize,
+    mut compare: impl FnMut(&T, &T) -> Ordering,
+) -> Vec<T> {
+    if max == 0 {
+        entries.clear();
+    } else if max < entries.len() {
+        entries.select_nth_unstable_by(max - 1, &mut compare);
+        entries.truncate(max);
+    }
+    entries.sort_unstable_by(compare);
+    entries
+}
+
+/// The entries of `map` ordered [`by_count`].
+fn ranked<K: Ord + Hash, C: Ord, H>(map: &HashMap<K, C, H>) -> IndexMap<&K, &C> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_unstable_by(by_count);
+    entries.into_iter().collect()
+}
+
+fn serialize_ranked<S, K, C, H>(map: &HashMap<K, C, H>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    K: Ord + Hash + Serialize,
+    C: Ord + Serialize,
+{
+    ranked(map).serialize(serializer)
+}
+
+fn serialize_sorted_map<S, K, V>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    K: Ord + Serialize,
+    V: Serialize,
+{
+    BTreeMap::from_iter(map).serialize(serializer)
+}
+
+fn serialize_sorted_set<S, T>(set: &HashSet<T>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    T: Ord + Serialize,
+{
+    BTreeSet::from_iter(set).serialize(serializer)
+}
+
+fn ratio(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    part as f64 / total as f64
+}
+
+/// Print one horizontal bar per entry, scaled so the largest value fills
+/// `width` characters. Bars are drawn in eighths of a character, and any
+/// nonzero value gets at least an eighth so that it stays visible next to
+/// a much larger one.
+fn display_bars(f: &mut fmt::Formatter<'_>, bars: &[(&str, u64)], width: usize) -> fmt::Result {
+    const EIGHTHS: [char; 8] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+
+    let max = bars.iter().map(|(_, count)| *count).max().unwrap_or(0);
+    let label_width = bars
+        .iter()
+        .map(|(label, _)| label.chars().count())
+        .max()
+        .unwrap_or(0);
+    for (label, count) in bars {
+        let eighths = if max == 0 {
+            0
+        } else {
+            let scaled = (*count as u128 * width as u128 * 8 / max as u128) as usize;
+            if *count > 0 { scaled.max(1) } else { 0 }
+        };
+        let mut bar = "█".repeat(eighths / 8);
+        if eighths % 8 > 0 {
+            bar.push(EIGHTHS[eighths % 8]);
+        }
+        writeln!(f, "{label:<label_width$} {bar} {count}")?;
+    }
+    Ok(())
+}
+
+fn percent(part: u64, total: u64) -> f64 {
+    100.0 * ratio(part, total)
+}
+
+fn is_number(word: &str) -> bool {
+    word.chars().any(char::is_numeric)
+        && word
+            .chars()
+            .all(|c| c.is_numeric() || matches!(c, '.' | ',' | '-' | '+' | '%'))
+}
+
+fn is_link(word: &str) -> bool {
+    word.contains("://") || word.starts_with("www.")
+}
+
+fn is_mention(word: &str) -> bool {
+    word.len() > 1 && word.starts_with('@')
+}
+
+/// Entity types whose words [`StatsSettings::entity_words`] applies to.
+const WORD_ENTITY_TYPES: [&str; 7] = [
+    "link",
+    "text_link",
+    "mention",
+    "hashtag",
+    "bot_command",
+    "code",
+    "pre",
+];
+
+/// What to do with words from link, text link, mention, hashtag, bot
+/// command, code and pre entities.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityWords {
+    /// Count them like any other word.
+    #[default]
+    Count,
+    /// Leave them out of word and phrase statistics.
+    Skip,
+    /// Count them in [`UserStats::entity_words`] instead.
+    Separate,
+}
+
+impl FromStr for EntityWords {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "count" => Ok(EntityWords::Count),
+            "skip" => Ok(EntityWords::Skip),
+            "separate" => Ok(EntityWords::Separate),
+            _ => Err(format!("unknown entity word handling: {s}")),
+        }
+    }
+}
+
+/// What to do with undated messages when filtering by date.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UndatedPolicy {
+    #[default]
+    Include,
+    Exclude,
+}
+
+/// How often someone posts, as told by [`UserStats::cadence`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Cadence {
+    /// A median gap of up to 12 hours.
+    SeveralTimesADay,
+    /// A median gap of 12 to 48 hours.
+    Daily,
+    /// Mostly gaps over 48 hours, but once a week or more on average.
+    Weekly,
+    /// Mostly gaps over 48 hours, and less than once a week on average.
+    Rarely,
+}
+
+/// The measure participants are ranked by.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RankBy {
+    /// Number of messages sent.
+    #[default]
+    Messages,
+    /// Number of characters written.
+    Characters,
+    /// Number of reactions received.
+    ReactionsReceived,
+    /// Number of words written.
+    WordsTyped,
+}
+
+impl RankBy {
+    /// Human-readable name of the measure, used in the participant list.
+    pub fn label(self) -> &'static str {
+        match self {
+            RankBy::Messages => "messages",
+            RankBy::Characters => "characters",
+            RankBy::ReactionsReceived => "reactions",
+            RankBy::WordsTyped => "words",
+        }
+    }
+}
+
+impl FromStr for RankBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "messages" => Ok(RankBy::Messages),
+            "characters" => Ok(RankBy::Characters),
+            "reactions" | "reactions_received" => Ok(RankBy::ReactionsReceived),
+            "words" | "words_typed" => Ok(RankBy::WordsTyped),
+            _ => Err(format!("unknown ranking key: {s}")),
+        }
+    }
+}
+
+/// How much of the per-user word and reaction maps to include in JSON.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonDetail {
+    /// Only scalar counters; no word or reaction maps.
+    #[default]
+    Summary,
+    /// The same top words, phrases, distinctive words and reactions that
+    /// the text output shows.
+    Top,
+    /// The complete word, phrase and reaction maps, and every distinctive
+    /// word.
+    Full,
+}
+
+impl FromStr for JsonDetail {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "summary" => Ok(JsonDetail::Summary),
+            "top" => Ok(JsonDetail::Top),
+            "full" => Ok(JsonDetail::Full),
+            _ => Err(format!("unknown JSON detail level: {s}")),
+        }
+    }
+}
+
+impl StatsSettings {
+    /// Build the set of words excluded from word and phrase statistics.
+    pub fn stop_words(&self) -> HashSet<String> {
+        self.stop_word_languages
+            .iter()
+            .flat_map(|l| l.stop_words())
+            .chain(self.extra_stop_words.iter().cloned())
+            .collect()
+    }
+
+    /// How the reaction counted under `key` in
+    /// [`UserStats::received_reactions`] is displayed and serialized.
+    pub fn reaction_label<'a>(&'a self, key: &'a str) -> Cow<'a, str> {
+        if let Some(label) = self.emoji_labels.get(key) {
+            return Cow::Borrowed(label);
+        }
+        // Custom emoji are counted by their numeric `document_id`.
+        if !key.is_empty() && key.bytes().all(|b| b.is_ascii_digit()) {
+            let short = &key[key.len().saturating_sub(6)..];
+            return Cow::Owned(format!("custom:{short}"));
+        }
+        Cow::Borrowed(key)
+    }
+
+    /// `reactions` under their labels, most frequent first, with reactions
+    /// that share a label added up.
+    pub fn labeled_reactions<'a>(
+        &'a self,
+        reactions: &'a StatsMap<String, usize>,
+    ) -> Vec<(Cow<'a, str>, usize)> {
+        let mut labeled: HashMap<Cow<'a, str>, usize> = HashMap::new();
+        for (key, count) in reactions {
+            *labeled.entry(self.reaction_label(key)).or_default() += count;
+        }
+        let mut labeled: Vec<_> = labeled.into_iter().collect();
+        labeled.sort_unstable_by(by_count);
+        labeled
+    }
+
+    /// Render a reply chain as [`ChatStats::longest_chain`].
+    fn render_chain(&self, chain: &[&ChainMessage]) -> String {
+        let lines: Vec<_> = chain
+            .iter()
+            .map(|message| {
+                let date = message.date.map_or_else(
+                    || "?".to_string(),
+                    |date| {
+                        let date = self.time_zone.convert(date);
+                        date.format("%Y-%m-%d %H:%M:%S").to_string()
+                    },
+                );
+                let text = message
+                    .text
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                if text.is_empty() {
+                    return format!("[{date}] {}", message.from);
+                }
+                let text = self.char_count.truncate(&text, PIN_PREVIEW_CHARS);
+                format!("[{date}] {}: {text}", message.from)
+            })
+            .collect();
+        lines.join("\n")
+    }
+
+    /// Silence after which the next message starts a new conversation.
+    pub fn conversation_gap(&self) -> Duration {
+        Duration::from_secs(60 * self.conversation_gap_minutes.unwrap_or(60))
+    }
+
+    /// Whether a message sent at `date` falls within `since`..`until`.
+    pub fn in_range(&self, date: Option<SystemTime>) -> bool {
+        if self.since.is_none() && self.until.is_none() {
+            return true;
+        }
+        let Some(date) = date.map(DateTime::<Utc>::from) else {
+            return self.undated == UndatedPolicy::Include;
+        };
+        self.since.is_none_or(|since| date >= since) && self.until.is_none_or(|until| date < until)
+    }
+
+    /// Whether messages from this sender are left out of the statistics.
+    pub fn is_excluded(&self, from: &str, from_id: &str) -> bool {
+        let looks_like_bot = |s: &str| {
+            // `get` rather than indexing, as the last 3 bytes of a name in
+            // another script needn't be whole characters.
+            s.len()
+                .checked_sub(3)
+                .and_then(|start| s.get(start..))
+                .is_some_and(|end| end.eq_ignore_ascii_case("bot"))
+        };
+        self.exclude.contains(from)
+            || self.exclude.contains(from_id)
+            || (!self.senders.is_empty() && !self.senders.contains(from_id))
+            || (self.exclude_bots && (looks_like_bot(from) || looks_like_bot(from_id)))
+    }
+
+    /// Build the filter applied to words before they are counted.
+    pub fn word_filter(&self) -> WordFilter {
+        if cfg!(not(feature = "stemming")) && !self.stem_languages.is_empty() {
+            warn!("stemming requested but the `stemming` feature is disabled");
+        }
+        let detect_languages = self.detect_languages || self.detected_stop_words;
+        if cfg!(not(feature = "lang-detect")) && detect_languages {
+            warn!("language detection requested but the `lang-detect` feature is disabled");
+        }
+        let language_stop_words = if self.detected_stop_words && !self.skip_words {
+            Language::ALL
+                .into_iter()
+                .map(|l| {
+                    let words = l.stop_words().into_iter();
+                    (
+                        l.code(),
+                        words.chain(self.extra_stop_words.iter().cloned()).collect(),
+                    )
+                })
+                .collect()
+        } else {
+            HashMap::new()
+        };
+        WordFilter {
+            tokenizer: self.tokenizer,
+            char_count: self.char_count,
+            stop_words: if self.skip_words {
+                HashSet::new()
+            } else {
+                self.stop_words()
+            },
+            min_length: self.min_word_length,
+            skip_numbers: self.skip_numbers,
+            skip_links: self.skip_links,
+            skip_mentions: self.skip_mentions,
+            #[cfg(feature = "stemming")]
+            stemmers: self
+                .stem_languages
+                .iter()
+                .filter_map(|l| l.stemmer())
+                .collect(),
+            entity_words: self.entity_words,
+            detect_languages,
+            skip_words: self.skip_words,
+            language_stop_words,
+        }
+    }
+
+    /// Read a stop-word list with one word per line.
+    ///
+    /// Words are trimmed and lowercased; blank lines and lines starting
+    /// with `#` are ignored.
+    pub fn read_stop_words(path: impl AsRef<Path>) -> io::Result<HashSet<String>> {
+        let contents = fs::read_to_string(path.as_ref())?;
+        Ok(contents
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(str::to_lowercase)
+            .collect())
+    }
+}
+
+impl ChatStats {
+    /// Create empty statistics that will be computed with `settings`.
+    pub fn new(settings: StatsSettings) -> Self {
+        Self {
+            settings,
+            ..Default::default()
+        }
+    }
+
+    pub fn analyze(&mut self, messages: Vec<RawMessage>) {
+        self.analyze_ref(&messages);
+    }
+
+    /// Like [`ChatStats::analyze`], but borrows the messages so they can
+    /// still be used afterwards, e.g. for [`Chat::write_export`](crate::Chat::write_export).
+    pub fn analyze_ref<'a, M: ExportMessage + 'a>(
+        &mut self,
+        messages: impl IntoIterator<Item = &'a M>,
+    ) {
+        self.analyze_labeled(UNLABELED, messages);
+    }
+
+    /// Like [`ChatStats::analyze_ref`], but also keeps a per-chat breakdown
+    /// under `label` in [`ChatStats::chats`].
+    ///
+    /// The messages are treated as one complete chat: reply chains are not
+    /// followed into messages pushed earlier under the same label.
+    pub fn analyze_labeled<'a, M: ExportMessage + 'a>(
+        &mut self,
+        label: &str,
+        messages: impl IntoIterator<Item = &'a M>,
+    ) {
+        self.state.filter = Some(self.settings.word_filter());
+        self.state.chats.remove(label);
+
+        for message in messages {
+            self.push_labeled(label, message);
+        }
+
+        self.state.chats.remove(label);
+    }
+
+    /// Like [`ChatStats::analyze_labeled`], labeled with the name of the
+    /// chat, reading the messages one at a time from `reader` rather than
+    /// keeping them all in memory. Stops at the first message that can't be
+    /// read, keeping the ones before it.
+    pub fn analyze_reader<R: BufRead>(&mut self, reader: ChatReader<R>) -> Result<(), ParseError> {
+        let label = reader.name.clone();
+        self.state.filter = Some(self.settings.word_filter());
+        self.state.chats.remove(&label);
+
+        let result = reader
+            .into_iter()
+            .try_for_each(|message| message.map(|message| self.push_labeled(&label, &message)));
+
+        self.state.chats.remove(&label);
+        result
+    }
+
+    /// Like [`ChatStats::analyze_labeled`], but splits the messages into
+    /// chunks that are analyzed on all cores and then merged, for chats too
+    /// large to analyze quickly on one.
+    ///
+    /// Statistics that only depend on each message, such as message, word
+    /// and character counts, reactions, hours of the day and the timeline,
+    /// are the same as with [`ChatStats::analyze_labeled`]. So are
+    /// milestones, which are found afterwards over all the messages. Those
+    /// that follow the messages in order are computed within each chunk and
+    /// may come out lower where chunks meet:
+    ///
+    /// - reply chains ([`ChatStats::longest_chain`]), though replies are
+    ///   all counted;
+    /// - runs of messages by one sender ([`ChatStats::messages_in_runs`],
+    ///   and each participant's `runs` and `longest_run`);
+    /// - conversation starts (each participant's `conversations_started`);
+    /// - gaps in message ids ([`ChatStats::id_gaps`]) and between a
+    ///   sender's messages (each participant's `gaps`, and so its cadence);
+    /// - the authors of pinned messages (each participant's `pinned`);
+    /// - duplicate ids ([`ChatStats::duplicate_ids`]), only found within a
+    ///   chunk.
+    ///
+    /// Chunks are at least 10,000 messages long, so the difference is
+    /// small; use [`ChatStats::analyze_labeled`] for exact results. With
+    /// [`StatsSettings::group_by_topic`], which needs every message in
+    /// order, and for small chats, the messages are analyzed in one go.
+    #[cfg(feature = "rayon")]
+    pub fn analyze_parallel(&mut self, label: &str, messages: &[RawMessage]) {
+        use rayon::prelude::*;
+
+        let chunk_len = messages
+            .len()
+            .div_ceil(rayon::current_num_threads())
+            .max(MIN_PARALLEL_CHUNK);
+        if self.settings.group_by_topic || messages.len() <= chunk_len {
+            self.analyze_labeled(label, messages);
+            return;
+        }
+        // Milestones are found afterwards, counting from the first chunk.
+        let settings = StatsSettings {
+            milestones: Vec::new(),
+            ..self.settings.clone()
+        };
+        let parts: Vec<_> = messages
+            .par_chunks(chunk_len)
+            .map(|chunk| {
+                let mut part = ChatStats::new(settings.clone());
+                part.analyze_labeled(label, chunk);
+                part
+            })
+            .collect();
+        for part in parts {
+            self.merge(part);
+        }
+
+        let mut ids = StatsSet::default();
+        let regular = messages
+            .iter()
+            .filter(|message| self.settings.in_range(message.date()))
+            .filter(|message| ids.insert(message.id()))
+            .filter_map(RawMessage::message);
+        let mut milestones = Vec::new();
+        for (number, (id, msg)) in (1..).zip(regular) {
+            if self.settings.milestones.contains(&number) {
+                milestones.push((number, id, msg));
+            }
+        }
+        for (number, id, msg) in milestones {
+            self.add_milestone(label, number, id, &msg);
+        }
+    }
+
+    /// Add a single message, exactly as one step of [`ChatStats::analyze`].
+    ///
+    /// Reply chains are only tracked correctly when messages are pushed in
+    /// the order they appear in the export.
+    pub fn push(&mut self, message: &impl ExportMessage) {
+        self.push_labeled(UNLABELED, message);
+    }
+
+    /// Like [`ChatStats::push`], counting the message towards chat `label`
+    /// in [`ChatStats::chats`].
+    pub fn push_labeled(&mut self, label: &str, message: &impl ExportMessage) {
+        let date = message.date();
+        if date.is_none() {
+            self.undated += 1;
+            if self.undated <= UNDATED_WARNINGS {
+                warn!(
+                    "message {} has no valid timestamp: {:?}",
+                    message.id(),
+                    message.unixtime()
+                );
+            }
+        }
+        if !self.settings.in_range(date) {
+            return;
+        }
+        if self.is_duplicate(label, message.id()) {
+            self.duplicate_ids += 1;
+            return;
+        }
+        self.messages += 1;
+        let chat = match self.chats.get_mut(label) {
+            Some(chat) => chat,
+            None => self.chats.entry(label.to_string()).or_default(),
+        };
+        chat.messages += 1;
+        self.observe_id(label, message.id(), message.date());
+
+        if let Some((action, text_entities)) = message.service_action() {
+            self.service_messages += 1;
+            match self.service_actions.get_mut(action) {
+                Some(count) => *count += 1,
+                None => {
+                    self.service_actions.insert(action.to_string(), 1);
+                }
+            }
+            self.count_entities(text_entities);
+        }
+        if let Some(service) = message.service() {
+            let month = service.date.map(|date| {
+                self.settings
+                    .time_zone
+                    .convert(date)
+                    .format("%Y-%m")
+                    .to_string()
+            });
+            self.membership.add_service(&service, month);
+            let title = match service.action {
+                ServiceAction::PinMessage(message_id) => {
+                    self.add_pin(label, &service, message_id);
+                    None
+                }
+                ServiceAction::EditTitle(title) => Some(Some(title.to_string())),
+                ServiceAction::EditPhoto => Some(None),
+                ServiceAction::CreateTopic(title) => {
+                    if self.settings.group_by_topic {
+                        let cursor = self.state.chats.entry(label.to_string()).or_default();
+                        cursor.create_topic(message.id(), title);
+                    }
+                    None
+                }
+                _ => None,
+            };
+            if let Some(title) = title {
+                self.title_history.push(TitleChange {
+                    chat: label.to_string(),
+                    date: service.date,
+                    changed_by: service.actor.to_string(),
+                    title,
+                });
+            }
+        }
+
+        let Some((id, msg)) = message.message() else {
+            return;
+        };
+        // Excluded messages still take part in reply chains and conversations.
+        let step = self.advance_chat(label, id, &msg);
+        if let Some(number) = step.milestone {
+            self.add_milestone(label, number, id, &msg);
+        }
+        if self.settings.is_excluded(msg.from, msg.from_id) {
+            self.excluded += 1;
+            return;
+        }
+
+        let words = self
+            .state
+            .filter
+            .get_or_insert_with(|| self.settings.word_filter());
+        if let Some(topic) = &step.topic {
+            let topic = match self.topics.get_mut(topic) {
+                Some(topic) => topic,
+                None => self.topics.entry(topic.clone()).or_default(),
+            };
+            topic.add_message(msg.from, &msg.text, msg.text_entities, words);
+        }
+        let from_id = self
+            .settings
+            .aliases
+            .get(msg.from_id)
+            .map_or(msg.from_id, String::as_str);
+        let user = self
+            .participants
+            .entry(from_id.to_string())
+            .or_default()
+            .observe_name(msg.from, msg.date)
+            .add_message(&msg.text, msg.text_entities, words, msg.date)
+            .add_reactions(msg.reactions);
+        if let Some(date) = msg.date {
+            let hour = self.settings.time_zone.convert(date).hour();
+            user.hours[hour as usize] += 1;
+            if let Some(previous) = step.previous {
+                user.add_gap(previous, date);
+            }
+        }
+        user.replies += u64::from(msg.reply_to_message_id.is_some());
+        if let Some(source) = msg.forwarded_from {
+            user.forwards += 1;
+            let source = source.unwrap_or(HIDDEN_FORWARD_SOURCE);
+            *self.forward_sources.entry(source.to_string()).or_default() += 1;
+        }
+        user.conversations_started += u64::from(step.starts_conversation);
+        user.add_run(&step.run);
+        match step.run.length {
+            LONG_RUN => self.messages_in_runs += LONG_RUN,
+            length if length > LONG_RUN => self.messages_in_runs += 1,
+            _ => {}
+        }
+        if self.settings.max_phrases > 0 {
+            user.add_phrases(&msg.text, msg.text_entities, words);
+        }
+        if !self.settings.track_words.is_empty() || !self.settings.track_patterns.is_empty() {
+            let tracker = self
+                .state
+                .tracker
+                .get_or_insert_with(|| Tracker::new(&self.settings, words));
+            tracker.find(&msg.text, words, |keyword| {
+                let users = self.tracked.entry(keyword.to_string()).or_default();
+                *users.entry(from_id.to_string()).or_default() += 1;
+            });
+        }
+        if let Some(date) = msg.date.filter(|_| !self.settings.trend_words.is_empty()) {
+            let month = self
+                .settings
+                .time_zone
+                .convert(date)
+                .format("%Y-%m")
+                .to_string();
+            let tracker = self
+                .state
+                .tracker
+                .get_or_insert_with(|| Tracker::new(&self.settings, words));
+            tracker.find_trends(&msg.text, words, |word| {
+                let months = self.trends.entry(word.to_string()).or_default();
+                *months.entry(month.clone()).or_default() += 1;
+            });
+        }
+        if msg.edited.is_some() {
+            self.edited += 1;
+        }
+        for entity in msg.text_entities {
+            let mentioned = match (entity.entity_type.as_str(), entity.user_id) {
+                ("mention", _) => entity.text.to_lowercase(),
+                ("mention_name", Some(user_id)) => format!("user{user_id}"),
+                _ => continue,
+            };
+            let mentions = self.mentions.entry(from_id.to_string()).or_default();
+            *mentions.entry(mentioned).or_default() += 1;
+        }
+        for recent in msg.reactions.iter().flat_map(Reaction::recent) {
+            if self.settings.is_excluded(&recent.from, &recent.from_id) {
+                continue;
+            }
+            let from_id = self
+                .settings
+                .aliases
+                .get(&recent.from_id)
+                .unwrap_or(&recent.from_id);
+            let reactor = self.reactors.entry(from_id.clone()).or_default();
+            reactor.name.clone_from(&recent.from);
+            reactor.reactions += 1;
+        }
+        if let Some(date) = msg.date {
+            let local = self.settings.time_zone.convert(date);
+            let weekday = local.weekday().num_days_from_monday() as usize;
+            self.weekday_hours[weekday][local.hour() as usize] += 1;
+            *self
+                .timeline
+                .entry(local.format("%Y-%m").to_string())
+                .or_default() += 1;
+            let day = self.state.days.entry(local.date_naive()).or_default();
+            day.messages += 1;
+            *day.senders.entry(from_id.to_string()).or_default() += 1;
+        }
+        if let Some(chat) = self.chats.get_mut(label) {
+            chat.add_message(msg.from, msg.date);
+        }
+        self.count_entities(msg.text_entities);
+    }
+
+    /// Record that `msg`, with id `id`, is message number `number` of chat
+    /// `label`.
+    fn add_milestone(&mut self, label: &str, number: u64, id: u64, msg: &Message<'_>) {
+        let preview = self
+            .settings
+            .char_count
+            .truncate(&msg.text, PIN_PREVIEW_CHARS);
+        let milestone = Milestone {
+            chat: label.to_string(),
+            number,
+            message_id: id,
+            date: msg.date,
+            author: msg.from.to_string(),
+            preview: preview.to_string(),
+        };
+        let at = self
+            .milestones
+            .partition_point(|m| (m.chat.as_str(), m.number) <= (label, number));
+        self.milestones.insert(at, milestone);
+    }
+
+    /// Whether a message with this `id` was already pushed to chat `label`.
+    fn is_duplicate(&mut self, label: &str, id: u64) -> bool {
+        let cursor = match self.state.chats.get_mut(label) {
+            Some(cursor) => cursor,
+            None => self.state.chats.entry(label.to_string()).or_default(),
+        };
+        if cursor.ids.insert(id) {
+            return false;
+        }
+        warn!("skipping message {id} of {label:?}: an earlier message has the same id");
+        true
+    }
+
+    /// Count the ids skipped between the previous message of chat `label`
+    /// and this one.
+    fn observe_id(&mut self, label: &str, id: u64, date: Option<SystemTime>) {
+        let cursor = match self.state.chats.get_mut(label) {
+            Some(cursor) => cursor,
+            None => self.state.chats.entry(label.to_string()).or_default(),
+        };
+        let gaps = &mut self.id_gaps;
+        gaps.present += 1;
+        match cursor.last_id {
+            None => gaps.id_range += 1,
+            Some((last_id, _)) if id <= last_id => return,
+            Some((last_id, last_date)) => {
+                gaps.id_range += id - last_id;
+                if id > last_id + 1 {
+                    let month = date.map(|date| {
+                        let date = self.settings.time_zone.convert(date);
+                        date.format("%Y-%m").to_string()
+                    });
+                    let gap = IdGap {
+                        chat: label.to_string(),
+                        after_id: last_id,
+                        before_id: id,
+                        after_date: last_date,
+                        before_date: date,
+                    };
+                    gaps.add_gap(gap, month);
+                }
+            }
+        }
+        cursor.last_id = Some((id, date));
+    }
+
+    /// Move chat `label` past `msg`: index it for reply chains, and work out
+    /// whether it starts a new conversation and which run it extends.
+    fn advance_chat(&mut self, label: &str, id: u64, msg: &Message<'_>) -> ChatStep {
+        let gap = self.settings.conversation_gap();
+        let from_id = self
+            .settings
+            .aliases
+            .get(msg.from_id)
+            .map_or(msg.from_id, String::as_str);
+        let cursor = match self.state.chats.get_mut(label) {
+            Some(cursor) => cursor,
+            None => self.state.chats.entry(label.to_string()).or_default(),
+        };
+        let length = cursor.replies.add_message(id, msg);
+        if length > self.state.longest_chain {
+            self.state.longest_chain = length;
+            let chain = cursor.replies.chain_ending_at(id);
+            self.longest_chain = self.settings.render_chain(&chain);
+        }
+
+        let starts_conversation = match (cursor.last_date, msg.date) {
+            (None, _) => true,
+            (Some(last), Some(date)) => date.duration_since(last).is_ok_and(|d| d > gap),
+            (Some(_), None) => false,
+        };
+        if msg.date.is_some() {
+            cursor.last_date = msg.date;
+        }
+        let previous = match (msg.date, cursor.last_sent.get_mut(from_id)) {
+            (Some(date), Some(last)) => Some(mem::replace(last, date)),
+            (Some(date), None) => {
+                cursor.last_sent.insert(from_id.to_string(), date);
+                None
+            }
+            (None, _) => None,
+        };
+
+        if cursor.run.length > 0 && cursor.run.from_id == from_id {
+            cursor.run.length += 1;
+        } else {
+            cursor.run = Run {
+                from_id: from_id.to_string(),
+                length: 1,
+                started: msg.date,
+            };
+        }
+        cursor.messages += 1;
+        let milestone = self
+            .settings
+            .milestones
+            .contains(&cursor.messages)
+            .then_some(cursor.messages);
+        let topic = self
+            .settings
+            .group_by_topic
+            .then(|| cursor.enter_topic(id, msg.reply_to_message_id).to_string());
+        ChatStep {
+            starts_conversation,
+            previous,
+            run: cursor.run.clone(),
+            topic,
+            milestone,
+        }
+    }
+
+    /// Add the statistics of `other` to this one.
+    ///
+    /// Counters are summed and participants with the same id are merged.
+    /// `self`'s settings are kept; `other`'s are discarded.
+    pub fn merge(&mut self, other: ChatStats) {
+        self.messages += other.messages;
+        self.service_messages += other.service_messages;
+        for (action, count) in other.service_actions {
+            *self.service_actions.entry(action).or_default() += count;
+        }
+        self.edited += other.edited;
+        self.excluded += other.excluded;
+        self.duplicate_ids += other.duplicate_ids;
+        self.undated += other.undated;
+        self.messages_in_runs += other.messages_in_runs;
+        for (date, day) in other.state.days {
+            let entry = self.state.days.entry(date).or_default();
+            entry.messages += day.messages;
+            for (id, count) in day.senders {
+                *entry.senders.entry(id).or_default() += count;
+            }
+        }
+        for (id, stats) in other.participants {
+            self.participants.entry(id).or_default().merge(stats);
+        }
+        for (entity, count) in other.text_entity_types {
+            *self.text_entity_types.entry(entity).or_default() += count;
+        }
+        if other.state.longest_chain > self.state.longest_chain {
+            self.state.longest_chain = other.state.longest_chain;
+            self.longest_chain = other.longest_chain;
+        }
+        for (label, chat) in other.chats {
+            self.chats.entry(label).or_default().merge(chat);
+        }
+        for (title, topic) in other.topics {
+            self.topics.entry(title).or_default().merge(topic);
+        }
+        self.membership.merge(other.membership);
+        for (month, count) in other.timeline {
+            *self.timeline.entry(month).or_default() += count;
+        }
+        self.id_gaps.merge(other.id_gaps);
+        for (source, count) in other.forward_sources {
+            *self.forward_sources.entry(source).or_default() += count;
+        }
+        self.pins.extend(other.pins);
+        self.milestones.extend(other.milestones);
+        self.title_history.extend(other.title_history);
+        for (keyword, users) in other.tracked {
+            let entry = self.tracked.entry(keyword).or_default();
+            for (id, count) in users {
+                *entry.entry(id).or_default() += count;
+            }
+        }
+        for (id, mentions) in other.mentions {
+            let entry = self.mentions.entry(id).or_default();
+            for (mentioned, count) in mentions {
+                *entry.entry(mentioned).or_default() += count;
+            }
+        }
+        for (word, months) in other.trends {
+            let entry = self.trends.entry(word).or_default();
+            for (month, count) in months {
+                *entry.entry(month).or_default() += count;
+            }
+        }
+        for (id, reactor) in other.reactors {
+            let entry = self.reactors.entry(id).or_default();
+            entry.name = reactor.name;
+            entry.reactions += reactor.reactions;
+        }
+        self.title_history
+            .sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.chat.cmp(&b.chat)));
+        self.pins.sort_by_key(|pin| (pin.date, pin.message_id));
+        self.milestones
+            .sort_by(|a, b| a.chat.cmp(&b.chat).then_with(|| a.number.cmp(&b.number)));
+        for (day, other_day) in self.weekday_hours.iter_mut().zip(other.weekday_hours) {
+            for (hour, count) in day.iter_mut().zip(other_day) {
+                *hour += count;
+            }
+        }
+    }
+
+    /// Record a pin of `message_id`, resolving it through the reply index of
+    /// chat `label`.
+    fn add_pin(&mut self, label: &str, service: &Service<'_>, message_id: u64) {
+        let pinned = self
+            .state
+            .chats
+            .get(label)
+            .and_then(|cursor| cursor.replies.get(message_id));
+        if let Some(pinned) = pinned {
+            let from_id = self
+                .settings
+                .aliases
+                .get(&pinned.from_id)
+                .unwrap_or(&pinned.from_id);
+            if let Some(user) = self.participants.get_mut(from_id) {
+                user.pinned += 1;
+            }
+        }
+        // Pins are kept in date order, wherever chats are analyzed from.
+        let date = service.date;
+        let at = self
+            .pins
+            .partition_point(|pin| (pin.date, pin.message_id) <= (date, message_id));
+        self.pins.insert(
+            at,
+            Pin {
+                message_id,
+                date: service.date,
+                pinned_by: service.actor.to_string(),
+                author: pinned.map(|m| m.from.clone()),
+                preview: pinned.map(|m| {
+                    let preview = self
+                        .settings
+                        .char_count
+                        .truncate(&m.text, PIN_PREVIEW_CHARS);
+                    preview.to_string()
+                }),
+            },
+        );
+    }
+
+    /// Every month from the first to the last in [`ChatStats::timeline`],
+    /// including months without messages.
+    pub fn months(&self) -> Vec<String> {
+        let parse = |month: &String| NaiveDate::parse_from_str(&format!("{month}-01"), "%Y-%m-%d");
+        let (Some(Ok(first)), Some(Ok(last))) = (
+            self.timeline.keys().next().map(parse),
+            self.timeline.keys().next_back().map(parse),
+        ) else {
+            return Vec::new();
+        };
+        first
+            .iter_days()
+            .take_while(|day| *day <= last)
+            .filter(|day| day.day() == 1)
+            .map(|day| day.format("%Y-%m").to_string())
+            .collect()
+    }
+
+    /// Uses of each of [`StatsSettings::trend_words`] in each of
+    /// [`ChatStats::months`], in the order the words were given.
+    pub fn trend_series(&self, months: &[String]) -> Vec<(&str, Vec<u64>)> {
+        self.settings
+            .trend_words
+            .iter()
+            .map(|word| {
+                let counts = self.trends.get(word);
+                let series = months
+                    .iter()
+                    .map(|month| counts.and_then(|c| c.get(month)).copied().unwrap_or(0))
+                    .collect();
+                (word.as_str(), series)
+            })
+            .collect()
+    }
+
+    /// Participants who posted at night, the largest share of night
+    /// messages first. Those with fewer than 10 messages are left out.
+    pub fn night_owls(&self) -> Vec<(&String, &UserStats)> {
+        let mut owls: Vec<_> = self
+            .participants
+            .iter()
+            .filter(|(_, stats)| {
+                stats.count >= MIN_NIGHT_OWL_MESSAGES && stats.night_percent() > 0.0
+            })
+            .collect();
+        owls.sort_by(|a, b| {
+            b.1.night_percent()
+                .total_cmp(&a.1.night_percent())
+                .then_with(|| a.0.cmp(b.0))
+        });
+        owls
+    }
+
+    /// Who mentions whom, as (author sender id, mentioned, count), the most
+    /// mentions first. Self-mentions are left out.
+    ///
+    /// Usernames can't be told from the export, so an `@username` counts
+    /// as the participant it is aliased to in [`StatsSettings::aliases`]
+    /// (e.g. `@bob=user123`), or else as the one who has used it as a
+    /// display name, ignoring case. Mentions of unknown usernames are kept
+    /// as `@username`.
+    pub fn mention_pairs(&self) -> Vec<(&str, &str, u64)> {
+        let mut names: HashMap<String, &str> = HashMap::new();
+        for (id, stats) in &self.participants {
+            for name in &stats.names {
+                names.insert(format!("@{}", name.to_lowercase()), id);
+            }
+        }
+        let mut pairs: HashMap<(&str, &str), u64> = HashMap::new();
+        for (from_id, mentions) in &self.mentions {
+            for (mentioned, count) in mentions {
+                let mentioned = self.settings.aliases.get(mentioned).unwrap_or(mentioned);
+                let to = names.get(mentioned).copied().unwrap_or(mentioned);
+                if to != from_id {
+                    *pairs.entry((from_id, to)).or_default() += count;
+                }
+            }
+        }
+        let mut pairs: Vec<_> = pairs.into_iter().collect();
+        pairs.sort_unstable_by(by_count);
+        pairs
+            .into_iter()
+            .map(|((from, to), count)| (from, to, count))
+            .collect()
+    }
+
+    /// The display name of sender `id`, or `id` itself if they never posted.
+    fn display_name<'a>(&'a self, id: &'a str) -> &'a str {
+        self.participants.get(id).map_or(id, |stats| &stats.name)
+    }
+
+    /// Forum topics, the most messages first.
+    pub fn ranked_topics(&self) -> Vec<(&String, &TopicStats)> {
+        let mut topics: Vec<_> = self
+            .topics
+            .iter()
+            .map(|(title, topic)| (title, topic.messages))
+            .collect();
+        topics.sort_unstable_by(by_count);
+        topics
+            .into_iter()
+            .map(|(title, _)| (title, &self.topics[title]))
+            .collect()
+    }
+
+    /// Reactors who never sent a message but gave at least
+    /// [`StatsSettings::min_lurker_reactions`] reactions, most reactions
+    /// first. Reaction counts are a lower bound, see [`ChatStats::reactors`].
+    pub fn lurkers(&self) -> Vec<(&String, &Reactor)> {
+        let mut lurkers: Vec<_> = self
+            .reactors
+            .iter()
+            .filter(|(id, reactor)| {
+                reactor.reactions >= self.settings.min_lurker_reactions
+                    && !self.participants.contains_key(*id)
+            })
+            .collect();
+        lurkers.sort_unstable_by(|a, b| {
+            b.1.reactions
+                .cmp(&a.1.reactions)
+                .then_with(|| a.1.name.cmp(&b.1.name))
+                .then_with(|| a.0.cmp(b.0))
+        });
+        lurkers
+    }
+
+    /// The longest run of consecutive days on which anyone posted, the
+    /// earliest one on ties.
+    pub fn longest_streak(&self) -> Option<Streak> {
+        let mut longest: Option<Streak> = None;
+        let mut current: Option<Streak> = None;
+        for &date in self.state.days.keys() {
+            let streak = match current.take() {
+                Some(mut streak) if streak.last_day.succ_opt() == Some(date) => {
+                    streak.last_day = date;
+                    streak.days += 1;
+                    streak
+                }
+                _ => Streak {
+                    first_day: date,
+                    last_day: date,
+                    days: 1,
+                },
+            };
+            if longest.as_ref().is_none_or(|l| streak.days > l.days) {
+                longest = Some(streak.clone());
+            }
+            current = Some(streak);
+        }
+        longest
+    }
+
+    /// Up to `max` days with the most messages, busiest first, with ties
+    /// going to the earlier day.
+    pub fn busiest_days(&self, max: usize) -> Vec<BusyDay> {
+        let mut days: Vec<_> = self.state.days.iter().collect();
+        days.sort_by_key(|(_, day)| std::cmp::Reverse(day.messages));
+        days.into_iter()
+            .take(max)
+            .map(|(&date, day)| {
+                let (id, count) = day
+                    .senders
+                    .iter()
+                    .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+                    .map_or(("", 0), |(id, count)| (id.as_str(), *count));
+                BusyDay {
+                    date,
+                    messages: day.messages,
+                    top_poster: self
+                        .participants
+                        .get(id)
+                        .map_or(id, |u| &u.name)
+                        .to_string(),
+                    top_poster_messages: count,
+                }
+            })
+            .collect()
+    }
+
+    /// Participants ordered by [`StatsSettings::rank_by`], highest first,
+    /// with ties broken by name and then by id.
+    pub fn ranked_participants(&self) -> Vec<(&String, &UserStats)> {
+        let rank_by = self.settings.rank_by;
+        let mut participants: Vec<_> = self.participants.iter().collect();
+        participants.sort_unstable_by(|(a_id, a), (b_id, b)| {
+            b.rank_value(rank_by)
+                .cmp(&a.rank_value(rank_by))
+                .then_with(|| a.name.cmp(&b.name))
+                .then_with(|| a_id.cmp(b_id))
+        });
+        participants
+    }
+
+    /// Names and message counts of the `max_participants` most active
+    /// participants, for charts.
+    pub(crate) fn top_by_messages(&self) -> Vec<(&str, u64)> {
+        let participants = top_n_by(
+            self.participants.iter().collect(),
+            self.settings.max_participants,
+            |(a_id, a), (b_id, b)| {
+                b.count
+                    .cmp(&a.count)
+                    .then_with(|| a.name.cmp(&b.name))
+                    .then_with(|| a_id.cmp(b_id))
+            },
+        );
+        participants
+            .iter()
+            .map(|(_, stats)| (stats.name.as_str(), stats.count))
+            .collect()
+    }
+
+    /// Messages per hour of day, summed over all weekdays.
+    pub(crate) fn hour_totals(&self) -> [u64; 24] {
+        let mut hours = [0; 24];
+        for day in &self.weekday_hours {
+            for (total, count) in hours.iter_mut().zip(day) {
+                *total += count;
+            }
+        }
+        hours
+    }
+
+    /// Words that characterize participant `from_id` compared to everyone else.
+    ///
+    /// Each participant is treated as a document and words are scored by
+    /// TF-IDF, so words used by every participant score zero no matter how
+    /// frequent they are. Words used only once are ignored as noise.
+    pub fn distinctive_words(&self, from_id: &str, max: usize) -> Vec<(&str, f64)> {
+        match self.participants.get(from_id) {
+            Some(stats) => self.score_distinctive(stats, &self.document_frequencies(), max),
+            None => Vec::new(),
+        }
+    }
+
+    /// How many participants used each word.
+    pub(crate) fn document_frequencies(&self) -> HashMap<Word, usize> {
+        let mut df = HashMap::new();
+        for &word in self.participants.values().flat_map(|p| p.words.keys()) {
+            *df.entry(word).or_insert(0) += 1;
+        }
+        df
+    }
+
+    pub(crate) fn score_distinctive<'a>(
+        &self,
+        stats: &'a UserStats,
+        df: &HashMap<Word, usize>,
+        max: usize,
+    ) -> Vec<(&'a str, f64)> {
+        let documents = self.participants.len() as f64;
+        let total: usize = stats.words.values().sum();
+
+        let scored: Vec<_> = stats
+            .words
+            .iter()
+            .filter(|(_, count)| **count > 1)
+            .map(|(word, &count)| {
+                let tf = count as f64 / total as f64;
+                let idf = (documents / df.get(word).copied().unwrap_or(1) as f64).ln();
+                (stats.word_label(word), tf * idf)
+            })
+            .filter(|(_, score)| *score > 0.0)
+            .collect();
+        top_n_by(scored, max, |a, b| {
+            b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0))
+        })
+    }
+
+    fn count_entities(&mut self, entities: &[crate::TextEntity]) {
+        for entity in entities {
+            *self
+                .text_entity_types
+                .entry(entity.entity_type.clone())
+                .or_default() += 1;
+        }
+    }
+
+    fn display_summary(&self, combined: &UserStats, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let labels = self.labels();
+        let run_length = format!("{LONG_RUN}+");
+        let width = labels.summary_width(&run_length) + 1;
+        let row = |f: &mut fmt::Formatter<'_>, label: &str, value: &dyn fmt::Display| {
+            writeln!(f, "{} : {value}", pad(label, width))
+        };
+        let reactions: usize = combined.received_reactions.values().sum();
+
+        writeln!(f, "{}", labels.title)?;
+        row(f, labels.total_messages, &self.messages)?;
+        row(f, labels.edited_messages, &self.edited)?;
+        if self.excluded > 0 {
+            row(f, labels.excluded_messages, &self.excluded)?;
+        }
+        if self.duplicate_ids > 0 {
+            row(f, labels.duplicate_ids, &self.duplicate_ids)?;
+        }
+        if self.undated > 0 {
+            row(f, labels.undated_messages, &self.undated)?;
+        }
+        row(f, labels.total_reactions, &reactions)?;
+        if combined.count > 0 {
+            let share = format!("{:.0}", percent(self.messages_in_runs, combined.count));
+            row(
+                f,
+                &format!("{} {run_length}", labels.in_runs),
+                &fill(labels.in_runs_value, &[&share]),
+            )?;
+        }
+        if let Some(streak) = self.longest_streak() {
+            let value = fill(
+                labels.longest_streak_value,
+                &[&streak.days, &streak.first_day, &streak.last_day],
+            );
+            row(f, labels.longest_streak, &value)?;
+        }
+        if let Some(day) = self.busiest_days(1).first() {
+            let value = fill(
+                labels.busiest_day_value,
+                &[
+                    &day.date,
+                    &day.messages,
+                    &day.top_poster,
+                    &day.top_poster_messages,
+                ],
+            );
+            row(f, labels.busiest_day, &value)?;
+        }
+        Ok(())
+    }
+
+    fn shows(&self, section: Section) -> bool {
+        self.settings.sections.contains(section)
+    }
+
+    fn labels(&self) -> &'static Labels {
+        Labels::for_language(self.settings.language)
+    }
+
+    /// Print a `... and N more` line if `total` is over `shown`.
+    fn display_more(&self, f: &mut fmt::Formatter<'_>, total: usize, shown: usize) -> fmt::Result {
+        if total > shown {
+            writeln!(f, "{}", fill(self.labels().and_more, &[&(total - shown)]))?;
+        }
+        Ok(())
+    }
+
+    /// Print bar charts of the top participants by message count and of the
+    /// monthly timeline, `chart_width` characters wide at most.
+    fn display_charts(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.settings.chart_width;
+        if width == 0 {
+            return Ok(());
+        }
+
+        let participants = self.top_by_messages();
+        if self.shows(Section::Charts) && !participants.is_empty() {
+            writeln!(f, "\n{}", self.labels().participants_chart)?;
+            display_bars(f, &participants, width)?;
+        }
+
+        if self.shows(Section::Timeline) && !self.timeline.is_empty() {
+            let months: Vec<_> = self
+                .timeline
+                .iter()
+                .map(|(month, &count)| (month.as_str(), count))
+                .collect();
+            writeln!(f, "\n{}", self.labels().timeline_chart)?;
+            display_bars(f, &months, width)?;
+        }
+        Ok(())
+    }
+
+    /// Print `weekday_hours` as a grid, one row per weekday and one
+    /// character per hour, shaded relative to the busiest hour.
+    fn display_heatmap(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const SHADES: [char; 5] = ['·', '░', '▒', '▓', '█'];
+
+        let max = self
+            .weekday_hours
+            .iter()
+            .flatten()
+            .copied()
+            .max()
+            .unwrap_or(0);
+        if max == 0 {
+            return Ok(());
+        }
+
+        let labels = self.labels();
+        let width = labels.weekday_width();
+        writeln!(f, "\n{}", labels.heatmap)?;
+        writeln!(f, "{} 0     6     12    18", pad("", width))?;
+        for (name, hours) in labels.weekdays.iter().zip(&self.weekday_hours) {
+            let row: String = hours
+                .iter()
+                .map(|&count| {
+                    // Any activity gets at least the lightest block.
+                    let level = (count * 4).div_ceil(max) as usize;
+                    SHADES[level.min(4)]
+                })
+                .collect();
+            writeln!(f, "{} {row}", pad(name, width))?;
+        }
+        Ok(())
+    }
+
+    fn display_night_owls(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let owls = self.night_owls();
+        if owls.is_empty() {
+            return Ok(());
+        }
+
+        let labels = self.labels();
+        writeln!(f, "\n{}", labels.night_owls)?;
+        for (_, stats) in owls.iter().take(self.settings.max_participants) {
+            let night = format!("{:.0}", stats.night_percent());
+            writeln!(
+                f,
+                "- {}: {}",
+                stats.name,
+                fill(labels.night_value, &[&night])
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Print the most frequent mention pairs, then whom each top
+    /// participant mentions the most.
+    fn display_mentions(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pairs = self.mention_pairs();
+        if pairs.is_empty() {
+            return Ok(());
+        }
+
+        let labels = self.labels();
+        let max = self.settings.max_participants;
+        writeln!(f, "\n{}", labels.mentions)?;
+        for (from, to, count) in pairs.iter().take(max) {
+            let (from, to) = (self.display_name(from), self.display_name(to));
+            writeln!(f, "- {from} → {to}: {count}")?;
+        }
+
+        let most_mentioned: Vec<_> = self
+            .ranked_participants()
+            .into_iter()
+            .take(max)
+            .filter_map(|(id, stats)| {
+                // Pairs are sorted by count, so the first is the largest.
+                let (_, to, count) = pairs.iter().find(|(from, _, _)| from == id)?;
+                Some(format!(
+                    "{} → {} ({count})",
+                    stats.name,
+                    self.display_name(to)
+                ))
+            })
+            .collect();
+        if !most_mentioned.is_empty() {
+            let line = most_mentioned.join(", ");
+            writeln!(f, "{}", fill(labels.most_mentioned, &[&line]))?;
+        }
+        Ok(())
+    }
+
+    fn display_tracked(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keywords = self
+            .settings
+            .track_words
+            .iter()
+            .chain(&self.settings.track_patterns);
+        let mut totals: Vec<_> = keywords
+            .map(|keyword| {
+                let users = self.tracked.get(keyword);
+                let total: u64 = users.map_or(0, |users| users.values().sum());
+                (keyword, total, users)
+            })
+            .collect();
+        if totals.is_empty() {
+            return Ok(());
+        }
+        totals.sort_by_key(|(_, total, _)| std::cmp::Reverse(*total));
+
+        writeln!(f, "\n{}", self.labels().tracked)?;
+        for (keyword, total, users) in totals {
+            write!(f, "- {keyword}: {total}")?;
+            if let Some(users) = users {
+                let users = top_n_by(
+                    users.iter().collect(),
+                    self.settings.max_participants,
+                    by_count,
+                );
+                let users_line = users
+                    .iter()
+                    .map(|(id, count)| {
+                        let name = self.participants.get(*id).map_or(id.as_str(), |u| &u.name);
+                        format!("{name} {count}")
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, " ({users_line})")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+
+    /// Print a table of [`StatsSettings::trend_words`] by month.
+    fn display_trends(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let months = self.months();
+        let series = self.trend_series(&months);
+        if series.is_empty() || months.is_empty() {
+            return Ok(());
+        }
+
+        let labels = self.labels();
+        let month_width = labels.month.width().max("YYYY-MM".len());
+        writeln!(f, "\n{}", labels.trends)?;
+        write!(f, "{}", pad(labels.month, month_width))?;
+        for (word, _) in &series {
+            write!(f, "  {word:>4}")?;
+        }
+        writeln!(f)?;
+        for (i, month) in months.iter().enumerate() {
+            write!(f, "{}", pad(month, month_width))?;
+            for (word, counts) in &series {
+                let width = word.width().max(4);
+                write!(f, "  {:>width$}", counts[i])?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+
+    fn display_lurkers(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lurkers = self.lurkers();
+        if lurkers.is_empty() {
+            return Ok(());
+        }
+
+        let labels = self.labels();
+        writeln!(f, "\n{}", fill(labels.lurkers, &[&lurkers.len()]))?;
+        let max = self.settings.max_participants;
+        for (_, reactor) in lurkers.iter().take(max) {
+            let value = fill(labels.lurker_value, &[&reactor.reactions]);
+            writeln!(f, "- {}: {value}", reactor.name)?;
+        }
+        self.display_more(f, lurkers.len(), max)
+    }
+
+    fn display_service_actions(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.service_actions.is_empty() {
+            return Ok(());
+        }
+
+        let labels = self.labels();
+        writeln!(
+            f,
+            "\n{}",
+            fill(labels.service_messages, &[&self.service_messages])
+        )?;
+        let mut actions: Vec<_> = self.service_actions.iter().collect();
+        actions.sort_unstable_by(by_count);
+        for (action, count) in actions {
+            writeln!(f, "- {}: {count}", labels.action(action))?;
+        }
+        Ok(())
+    }
+
+    fn display_membership(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let membership = &self.membership;
+        if membership.joined == 0 && membership.left == 0 {
+            return Ok(());
+        }
+
+        let labels = self.labels();
+        let net = format!("{:+}", membership.net_change());
+        writeln!(
+            f,
+            "\n{}",
+            fill(
+                labels.membership,
+                &[&membership.joined, &membership.left, &net]
+            )
+        )?;
+        if let Some((name, count)) = membership.top_inviter() {
+            writeln!(f, "- {}", fill(labels.top_inviter, &[&name, &count]))?;
+        }
+        for (month, change) in &membership.monthly {
+            writeln!(f, "- {month}: +{} / -{}", change.joined, change.left)?;
+        }
+        Ok(())
+    }
+
+    fn display_forwards(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.forward_sources.is_empty() {
+            return Ok(());
+        }
+
+        let total: u64 = self.forward_sources.values().sum();
+        writeln!(f, "\n{}", fill(self.labels().forward_sources, &[&total]))?;
+        let max = self.settings.max_words;
+        let sources = top_n_by(self.forward_sources.iter().collect(), max, by_count);
+        for (source, count) in sources {
+            writeln!(f, "- {source}: {count}")?;
+        }
+        self.display_more(f, self.forward_sources.len(), max)
+    }
+
+    fn display_id_gaps(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let gaps = &self.id_gaps;
+        if gaps.estimated_deletions == 0 {
+            return Ok(());
+        }
+
+        let labels = self.labels();
+        writeln!(f, "\n{}", labels.id_gaps)?;
+        let share = format!("{:.0}", percent(gaps.estimated_deletions, gaps.id_range));
+        writeln!(
+            f,
+            "- {}",
+            fill(
+                labels.ids_missing,
+                &[&gaps.estimated_deletions, &gaps.id_range, &share]
+            )
+        )?;
+        for gap in gaps.largest.iter().take(3) {
+            write!(
+                f,
+                "- {}",
+                fill(labels.gap, &[&gap.missing(), &gap.after_id, &gap.before_id])
+            )?;
+            if gap.chat != UNLABELED {
+                write!(f, " {}", fill(labels.gap_chat, &[&gap.chat]))?;
+            }
+            if let (Some(after), Some(before)) = (gap.after_date, gap.before_date) {
+                let after = self.settings.time_zone.convert(after);
+                let before = self.settings.time_zone.convert(before);
+                write!(
+                    f,
+                    " ({} – {})",
+                    after.format("%Y-%m-%d"),
+                    before.format("%Y-%m-%d")
+                )?;
+            }
+            writeln!(f)?;
+        }
+        let monthly = gaps
+            .estimated_deletions_monthly
+            .iter()
+            .map(|(month, missing)| format!("{month} {missing}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(f, "- {}", fill(labels.by_month, &[&monthly]))?;
+        Ok(())
+    }
+
+    /// Print each forum topic's activity, top posters and top words, the
+    /// most active topic first.
+    fn display_topics(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.topics.is_empty() {
+            return Ok(());
+        }
+
+        let labels = self.labels();
+        let topics = self.ranked_topics();
+        writeln!(f, "\n{}", fill(labels.topics, &[&topics.len()]))?;
+        for (title, topic) in topics {
+            let posters = topic
+                .top_senders(TOPIC_POSTERS)
+                .iter()
+                .map(|(name, count)| format!("{name} ({count})"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            writeln!(
+                f,
+                "- {title}: {}, {}",
+                fill(labels.chat_messages, &[&topic.messages]),
+                fill(labels.top_posters, &[&posters])
+            )?;
+            let words = topic.top_words(self.settings.max_words);
+            if self.shows(Section::Words) && !words.is_empty() {
+                let words = words
+                    .iter()
+                    .map(|(word, count)| format!("{word} ({count})"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                writeln!(f, "  {}", fill(labels.topic_words, &[&words]))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn display_pins(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.pins.is_empty() {
+            return Ok(());
+        }
+
+        let labels = self.labels();
+        writeln!(f, "\n{}", fill(labels.pins, &[&self.pins.len()]))?;
+        for pin in &self.pins {
+            write!(f, "- ")?;
+            if let Some(date) = pin.date {
+                write!(
+                    f,
+                    "{} ",
+                    self.settings.time_zone.convert(date).format("%Y-%m-%d")
+                )?;
+            }
+            write!(f, "{}: ", fill(labels.pinned_by, &[&pin.pinned_by]))?;
+            match (&pin.author, &pin.preview) {
+                (Some(author), Some(preview)) => writeln!(f, "{author}: {preview}")?,
+                _ => writeln!(f, "{}", fill(labels.pin_not_exported, &[&pin.message_id]))?,
+            }
+        }
+        Ok(())
+    }
+
+    fn display_milestones(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.milestones.is_empty() {
+            return Ok(());
+        }
+
+        let labels = self.labels();
+        writeln!(
+            f,
+            "\n{}",
+            fill(labels.milestones, &[&self.milestones.len()])
+        )?;
+        for milestone in &self.milestones {
+            write!(f, "- ")?;
+            if let Some(date) = milestone.date {
+                write!(
+                    f,
+                    "{} ",
+                    self.settings.time_zone.convert(date).format("%Y-%m-%d")
+                )?;
+            }
+            write!(
+                f,
+                "{}",
+                fill(labels.milestone, &[&milestone.number, &milestone.author])
+            )?;
+            if milestone.chat != UNLABELED {
+                write!(f, " {}", fill(labels.gap_chat, &[&milestone.chat]))?;
+            }
+            writeln!(f, ": {}", milestone.preview)?;
+        }
+        Ok(())
+    }
+
+    /// Print how many names each chat has had and what it is called now.
+    fn display_titles(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut chats: BTreeMap<&str, (usize, usize, Option<&str>)> = BTreeMap::new();
+        for change in &self.title_history {
+            let (names, photos, current) = chats.entry(&change.chat).or_default();
+            match &change.title {
+                Some(title) => {
+                    *names += 1;
+                    *current = Some(title);
+                }
+                None => *photos += 1,
+            }
+        }
+        if chats.is_empty() {
+            return Ok(());
+        }
+
+        let labels = self.labels();
+        writeln!(f)?;
+        for (label, (names, photos, current)) in chats {
+            let chat = if label == UNLABELED {
+                labels.the_chat
+            } else {
+                label
+            };
+            let template = labels.title_names[usize::from(names != 1)];
+            write!(f, "{}", fill(template, &[&chat, &names]))?;
+            if let Some(current) = current {
+                write!(f, "; {}", fill(labels.current_title, &[&current]))?;
+            }
+            if photos > 0 {
+                let template = labels.photo_changes[usize::from(photos != 1)];
+                write!(f, " ({})", fill(template, &[&photos]))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+
+    fn display_user_stats(&self, stats: &UserStats, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let labels = self.labels();
+        if stats.count == 0 {
+            return write!(f, "- {}", labels.no_messages);
+        }
+
+        let width = labels.row_width();
+        let row = |f: &mut fmt::Formatter<'_>, label: &str, value: &dyn fmt::Display| {
+            writeln!(f, "- {}: {value}", pad(label, width))
+        };
+        row(f, labels.messages, &stats.count)?;
+        // Media-only messages have no text, so text statistics over them
+        // would be all zeros; such lines are left out.
+        let has_text = stats.total_chars > 0;
+        if has_text {
+            row(
+                f,
+                labels.avg_length,
+                &fill(labels.chars, &[&stats.avg_chars()]),
+            )?;
+            row(
+                f,
+                labels.longest_message,
+                &fill(labels.chars, &[&stats.max_chars]),
+            )?;
+        }
+        if stats.total_words > 0 {
+            let vocabulary = fill(
+                labels.vocabulary_value,
+                &[
+                    &stats.unique_words,
+                    &stats.total_words,
+                    &format!("{:.1}", stats.avg_words()),
+                    &format!("{:.2}", stats.type_token_ratio()),
+                ],
+            );
+            row(f, labels.vocabulary, &vocabulary)?;
+            let mut words = vec![fill(
+                labels.words_value,
+                &[&format!("{:.1}", stats.avg_word_length())],
+            )];
+            if !stats.longest_word.is_empty() {
+                words.push(fill(labels.longest_word, &[&stats.longest_word]));
+            }
+            let single_word = format!("{:.0}", stats.single_word_percent());
+            words.push(fill(labels.single_word, &[&single_word]));
+            row(f, labels.words, &words.join(", "))?;
+        }
+        if stats.longest_run > 1 {
+            let mut runs = fill(
+                labels.double_texting_value,
+                &[
+                    &format!("{:.1}", stats.avg_run_length()),
+                    &stats.longest_run,
+                ],
+            );
+            if let Some(date) = stats.longest_run_date {
+                let date = self.settings.time_zone.convert(date);
+                runs.push_str(&format!(" ({})", date.format("%Y-%m-%d")));
+            }
+            row(f, labels.double_texting, &runs)?;
+        }
+        if let Some(cadence) = stats.cadence() {
+            let value = match (cadence, stats.median_gap()) {
+                (Cadence::SeveralTimesADay | Cadence::Daily, Some(gap)) => fill(
+                    labels.cadence_value,
+                    &[&labels.duration(gap), &labels.cadence_name(cadence)],
+                ),
+                // The median of the gaps under 48 hours isn't typical.
+                _ => labels.cadence_name(cadence).to_string(),
+            };
+            row(f, labels.cadence, &value)?;
+        }
+        let night = stats.night_percent();
+        if night > 0.0 {
+            let night = fill(labels.night_value, &[&format!("{night:.0}")]);
+            row(f, labels.night, &night)?;
+        }
+        let replies = fill(
+            labels.replies_value,
+            &[
+                &format!("{:.0}", stats.reply_percent()),
+                &stats.conversations_started,
+            ],
+        );
+        row(f, labels.replies, &replies)?;
+        if stats.forwards > 0 {
+            let forwards = fill(
+                labels.forwards_value,
+                &[&stats.forwards, &format!("{:.0}", stats.forward_percent())],
+            );
+            row(f, labels.forwards, &forwards)?;
+        }
+        let languages = stats.language_shares();
+        if !languages.is_empty() {
+            let languages_line = languages
+                .iter()
+                .map(|(code, share)| format!("{share:.0}% {code}"))
+                .collect::<Vec<_>>()
+                .join(" / ");
+            row(f, labels.languages, &languages_line)?;
+        }
+        if has_text {
+            let style = fill(
+                labels.style_value,
+                &[
+                    &format!("{:.0}", stats.caps_percent()),
+                    &format!("{:.2}", stats.exclamations_per_message()),
+                    &format!("{:.0}", stats.ellipsis_percent()),
+                    &format!("{:.1}", stats.sentences_per_message()),
+                ],
+            );
+            row(f, labels.style, &style)?;
+        }
+
+        if let Some(first) = stats.first_message {
+            let datetime = self.settings.time_zone.convert(first);
+            row(
+                f,
+                labels.first_message,
+                &datetime.format("%Y-%m-%d %H:%M:%S"),
+            )?;
+        }
+        if let Some(last) = stats.last_message {
+            let datetime = self.settings.time_zone.convert(last);
+            row(
+                f,
+                labels.last_message,
+                &datetime.format("%Y-%m-%d %H:%M:%S"),
+            )?;
+        }
+
+        let received = self.settings.labeled_reactions(&stats.received_reactions);
+        if self.shows(Section::Reactions) && !received.is_empty() {
+            let received = received
+                .into_iter()
+                .map(|(r, c)| format!("{r}×{c}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            row(f, labels.reactions, &received)?;
+        }
+        if stats.pinned > 0 {
+            row(
+                f,
+                labels.pinned,
+                &fill(labels.pinned_value, &[&stats.pinned]),
+            )?;
+        }
+        if !self.shows(Section::Words) {
+            return Ok(());
+        }
+
+        let top_words = stats.top_words(self.settings.max_words);
+        if !top_words.is_empty() {
+            let words_line = top_words
+                .iter()
+                .map(|(word, count)| format!("{} ({})", stats.word_label(word), count))
+                .collect::<Vec<_>>()
+                .join(", ");
+            row(f, labels.top_words, &words_line)?;
+        }
+
+        let top_entity_words = stats.top_entity_words(self.settings.max_words);
+        if !top_entity_words.is_empty() {
+            let words_line = top_entity_words
+                .iter()
+                .map(|(word, count)| format!("{word} ({count})"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            row(f, labels.entity_words, &words_line)?;
+        }
+
+        let top_phrases = stats.top_phrases(self.settings.max_phrases);
+        if !top_phrases.is_empty() {
+            let phrases_line = top_phrases
+                .iter()
+                .map(|(phrase, count)| format!("\"{}\" ({})", phrase, count))
+                .collect::<Vec<_>>()
+                .join(", ");
+            row(f, labels.top_phrases, &phrases_line)?;
+        }
+        Ok(())
+    }
+}
+
+impl Add for ChatStats {
+    type Output = ChatStats;
+
+    fn add(mut self, other: ChatStats) -> ChatStats {
+        self.merge(other);
+        self
+    }
+}
+
+impl AddAssign for ChatStats {
+    fn add_assign(&mut self, other: ChatStats) {
+        self.merge(other);
+    }
+}
+
+/// The JSON output before [`StatsReport`], which follows the internal
+/// structures and changes with them. It is kept for one more release so
+/// that scripts can move to [`ChatStats::to_report`].
+///
+/// [`StatsReport`]: crate::StatsReport
+impl Serialize for ChatStats {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // Participants in ranking order, and counters by count, so that the
+        // same input always serializes the same way.
+        let participants: IndexMap<_, _> = self
+            .ranked_participants()
+            .into_iter()
+            .map(|(id, stats)| (id, UserStatsJson::new(stats, &self.settings)))
+            .collect();
+
+        let mut state = serializer.serialize_struct("ChatStats", 28)?;
+        state.serialize_field("messages", &self.messages)?;
+        state.serialize_field("service_messages", &self.service_messages)?;
+        state.serialize_field("service_actions", &ranked(&self.service_actions))?;
+        state.serialize_field("edited", &self.edited)?;
+        state.serialize_field("excluded", &self.excluded)?;
+        state.serialize_field("duplicate_ids", &self.duplicate_ids)?;
+        state.serialize_field("undated", &self.undated)?;
+        state.serialize_field("messages_in_runs", &self.messages_in_runs)?;
+        state.serialize_field("participants", &participants)?;
+        state.serialize_field("text_entity_types", &ranked(&self.text_entity_types))?;
+        state.serialize_field("settings", &self.settings)?;
+        state.serialize_field("longest_chain", &self.longest_chain)?;
+        state.serialize_field("chats", &self.chats)?;
+        let topics: IndexMap<_, _> = self
+            .ranked_topics()
+            .into_iter()
+            .map(|(title, topic)| (title, TopicStatsJson::new(topic, &self.settings)))
+            .collect();
+        state.serialize_field("topics", &topics)?;
+        state.serialize_field("weekday_hours", &self.weekday_hours)?;
+        state.serialize_field("timeline", &self.timeline)?;
+        state.serialize_field("longest_streak", &self.longest_streak())?;
+        state.serialize_field("busiest_days", &self.busiest_days(BUSIEST_DAYS))?;
+        state.serialize_field("membership", &self.membership)?;
+        state.serialize_field("forward_sources", &ranked(&self.forward_sources))?;
+        state.serialize_field("id_gaps", &self.id_gaps)?;
+        state.serialize_field("pins", &self.pins)?;
+        state.serialize_field("milestones", &self.milestones)?;
+        state.serialize_field("title_history", &self.title_history)?;
+        let tracked: BTreeMap<_, _> = self
+            .tracked
+            .iter()
+            .map(|(keyword, users)| (keyword, ranked(users)))
+            .collect();
+        state.serialize_field("tracked", &tracked)?;
+        let mentions: Vec<_> = self
+            .mention_pairs()
+            .into_iter()
+            .map(|(from, to, count)| MentionJson { from, to, count })
+            .collect();
+        state.serialize_field("mentions", &mentions)?;
+        let months = self.months();
+        let trends = TrendsJson {
+            words: self.trend_series(&months).into_iter().collect(),
+            months,
+        };
+        state.serialize_field("trends", &trends)?;
+        state.serialize_field("reactors", &BTreeMap::from_iter(&self.reactors))?;
+        state.end()
+    }
+}
+
+/// [`UserStats`] with as much of its word and reaction maps as
+/// [`StatsSettings::json_detail`] asks for, ordered by count.
+#[derive(Serialize)]
+struct UserStatsJson<'a> {
+    #[serde(flatten)]
+    stats: &'a UserStats,
+    median_gap_seconds: Option<u64>,
+    cadence: Option<Cadence>,
+    night_percent: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    words: Option<IndexMap<&'a str, usize>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    received_reactions: Option<IndexMap<Cow<'a, str>, usize>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entity_words: Option<IndexMap<&'a str, usize>>,
+}
+
+impl<'a> UserStatsJson<'a> {
+    fn new(stats: &'a UserStats, settings: &'a StatsSettings) -> Self {
+        let max_words = match settings.json_detail {
+            JsonDetail::Summary => {
+                return Self {
+                    stats,
+                    median_gap_seconds: stats.median_gap().map(|gap| gap.as_secs()),
+                    cadence: stats.cadence(),
+                    night_percent: stats.night_percent(),
+                    words: None,
+                    received_reactions: None,
+                    entity_words: None,
+                };
+            }
+            JsonDetail::Top => settings.max_words,
+            JsonDetail::Full => usize::MAX,
+        };
+
+        let words = stats
+            .top_words(max_words)
+            .into_iter()
+            .map(|(word, &count)| (stats.word_label(word), count))
+            .collect();
+        let received_reactions = settings
+            .labeled_reactions(&stats.received_reactions)
+            .into_iter()
+            .collect();
+
+        let entity_words = (!stats.entity_words.is_empty()).then(|| {
+            stats
+                .top_entity_words(max_words)
+                .into_iter()
+                .map(|(word, &count)| (word.as_str(), count))
+                .collect()
+        });
+
+        Self {
+            stats,
+            median_gap_seconds: stats.median_gap().map(|gap| gap.as_secs()),
+            cadence: stats.cadence(),
+            night_percent: stats.night_percent(),
+            words: Some(words),
+            received_reactions: Some(received_reactions),
+            entity_words,
+        }
+    }
+}
+
+/// One edge of [`ChatStats::mention_pairs`].
+#[derive(Serialize)]
+struct MentionJson<'a> {
+    from: &'a str,
+    to: &'a str,
+    count: u64,
+}
+
+/// [`ChatStats::trends`] as one array of counts per word, aligned with
+/// `months`.
+#[derive(Serialize)]
+struct TrendsJson<'a> {
+    months: Vec<String>,
+    words: IndexMap<&'a str, Vec<u64>>,
+}
+
+/// [`TopicStats`] with as many of its words as
+/// [`StatsSettings::json_detail`] asks for, ordered by count.
+#[derive(Serialize)]
+struct TopicStatsJson<'a> {
+    #[serde(flatten)]
+    stats: &'a TopicStats,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    words: Option<IndexMap<&'a str, usize>>,
+}
+
+impl<'a> TopicStatsJson<'a> {
+    fn new(stats: &'a TopicStats, settings: &StatsSettings) -> Self {
+        let max_words = match settings.json_detail {
+            JsonDetail::Summary => None,
+            JsonDetail::Top => Some(settings.max_words),
+            JsonDetail::Full => Some(usize::MAX),
+        };
+        let words = max_words.map(|max| {
+            stats
+                .top_words(max)
+                .into_iter()
+                .map(|(word, &count)| (word.as_str(), count))
+                .collect()
+        });
+        Self { stats, words }
+    }
+}
+
+impl fmt::Display for ChatStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let combined = self.participants.values().sum::<UserStats>();
+        let labels = self.labels();
+
+        if self.shows(Section::Summary) {
+            self.display_summary(&combined, f)?;
+        }
+        self.display_charts(f)?;
+
+        if self.shows(Section::Combined) {
+            if combined.count > 0 {
+                writeln!(f, "\n{}", labels.combined)?;
+                self.display_user_stats(&combined, f)?;
+            }
+            let length = self.state.longest_chain;
+            writeln!(f, "{}", fill(labels.longest_chain, &[&length]))?;
+            for line in self.longest_chain.lines() {
+                writeln!(f, "- {line}")?;
+            }
+        }
+
+        if self.shows(Section::Participants) && !self.participants.is_empty() {
+            let max = self.settings.max_participants;
+            let rank_by = self.settings.rank_by;
+            let participants = self.ranked_participants();
+
+            let max_distinctive = if self.shows(Section::Words) {
+                self.settings.max_distinctive_words
+            } else {
+                0
+            };
+            let df = if max_distinctive > 0 {
+                self.document_frequencies()
+            } else {
+                HashMap::new()
+            };
+
+            writeln!(
+                f,
+                "\n{}",
+                fill(labels.top_participants, &[&participants.len()])
+            )?;
+            for (i, (_, stats)) in participants.iter().take(max).enumerate() {
+                let mut shares = Vec::with_capacity(3);
+                let mut keys = vec![RankBy::Messages, RankBy::Characters];
+                if !keys.contains(&rank_by) {
+                    keys.insert(0, rank_by);
+                }
+                for key in keys {
+                    // A share of nothing, such as of characters in a chat of
+                    // media only, says nothing.
+                    let total = combined.rank_value(key);
+                    if total == 0 {
+                        continue;
+                    }
+                    let share = format!("{:.0}", percent(stats.rank_value(key), total));
+                    shares.push(fill(labels.share, &[&share, &labels.rank(key)]));
+                }
+                write!(f, "\n{}. {}", i + 1, stats.name)?;
+                if !shares.is_empty() {
+                    write!(f, "  ({})", shares.join(", "))?;
+                }
+                writeln!(f)?;
+                let other_names: Vec<_> = stats.other_names().map(String::as_str).collect();
+                if !other_names.is_empty() {
+                    let names = other_names.join(", ");
+                    writeln!(f, "   {}", fill(labels.also_known_as, &[&names]))?;
+                }
+                self.display_user_stats(stats, f)?;
+
+                let distinctive = self.score_distinctive(stats, &df, max_distinctive);
+                if !distinctive.is_empty() {
+                    let line = distinctive
+                        .iter()
+                        .map(|(word, _)| *word)
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    let label = pad(labels.distinctive, labels.row_width());
+                    writeln!(f, "- {label}: {line}")?;
+                }
+            }
+            self.display_more(f, participants.len(), max)?;
+        }
+
+        if self.shows(Section::Chats) && self.chats.keys().any(|label| label != UNLABELED) {
+            writeln!(f, "\n{}", fill(labels.per_chat, &[&self.chats.len()]))?;
+            for (label, chat) in &self.chats {
+                write!(
+                    f,
+                    "- {label}: {}",
+                    fill(labels.chat_messages, &[&chat.messages])
+                )?;
+                if let (Some(first), Some(last)) = (chat.first_message, chat.last_message) {
+                    let first = self.settings.time_zone.convert(first);
+                    let last = self.settings.time_zone.convert(last);
+                    write!(
+                        f,
+                        ", {} – {}",
+                        first.format("%Y-%m-%d"),
+                        last.format("%Y-%m-%d")
+                    )?;
+                }
+                if let Some((name, count)) = chat.top_sender() {
+                    write!(f, ", {}", fill(labels.most_active, &[&name, &count]))?;
+                }
+                writeln!(f)?;
+            }
+        }
+
+        type Block = fn(&ChatStats, &mut fmt::Formatter<'_>) -> fmt::Result;
+        let blocks: [(Section, Block); 14] = [
+            (Section::Topics, Self::display_topics),
+            (Section::Mentions, Self::display_mentions),
+            (Section::Tracked, Self::display_tracked),
+            (Section::Trends, Self::display_trends),
+            (Section::Lurkers, Self::display_lurkers),
+            (Section::Services, Self::display_service_actions),
+            (Section::Heatmap, Self::display_heatmap),
+            (Section::NightOwls, Self::display_night_owls),
+            (Section::Membership, Self::display_membership),
+            (Section::Forwards, Self::display_forwards),
+            (Section::Deletions, Self::display_id_gaps),
+            (Section::Pins, Self::display_pins),
+            (Section::Milestones, Self::display_milestones),
+            (Section::Titles, Self::display_titles),
+        ];
+        for (section, display) in blocks {
+            if self.shows(section) {
+                display(self, f)?;
+            }
+        }
+
+        if !self.text_entity_types.is_empty() && self.shows(Section::Entities) {
+            writeln!(
+                f,
+                "\n{}",
+                fill(labels.entity_types, &[&self.text_entity_types.len()])
+            )?;
+            let mut entities: Vec<_> = self.text_entity_types.iter().collect();
+            entities.sort_unstable_by(by_count);
+
+            for (entity, &count) in entities {
+                writeln!(f, "- {entity:15}: {count:>4}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{analyze, message, parse, sent, service};
+
+    /// The top words of `from_id` under their display labels.
+    fn top_labels<'a>(stats: &'a ChatStats, from_id: &str) -> Vec<(&'a str, usize)> {
+        let user = &stats.participants[from_id];
+        user.top_words(usize::MAX)
+            .into_iter()
+            .map(|(word, count)| (user.word_label(word), *count))
+            .collect()
+    }
+
+    #[test]
+    fn labels_words_by_themselves_without_stem_languages() {
+        let stats = analyze(
+            StatsSettings::default(),
+            [message(1, "Alice", "runs run runs")],
+        );
+        assert_eq!(top_labels(&stats, "useralice"), [("runs", 2), ("run", 1)]);
+    }
+
+    #[cfg(feature = "stemming")]
+    #[test]
+    fn stems_each_word_with_the_language_of_its_script() {
+        let settings = StatsSettings {
+            stem_languages: vec![Language::English, Language::Russian],
+            ..Default::default()
+        };
+        let stats = analyze(
+            settings,
+            [
+                message(1, "Alice", "run runs running running"),
+                message(2, "Alice", "пошла пошли пошли"),
+            ],
+        );
+        assert_eq!(
+            top_labels(&stats, "useralice"),
+            [("running", 4), ("пошли", 3)]
+        );
+    }
+
+    #[cfg(feature = "stemming")]
+    #[test]
+    fn labels_ties_with_the_first_form_alphabetically() {
+        let settings = StatsSettings {
+            stem_languages: vec![Language::English],
+            ..Default::default()
+        };
+        for text in ["runs run", "run runs"] {
+            let stats = analyze(settings.clone(), [message(1, "Alice", text)]);
+            assert_eq!(top_labels(&stats, "useralice"), [("run", 2)]);
+        }
+    }
+
+    #[test]
+    fn distinctive_words_break_ties_by_word() {
+        for text in [
+            "apple banana apple banana common common",
+            "banana apple common banana apple common",
+        ] {
+            let stats = analyze(
+                StatsSettings::default(),
+                [message(1, "Alice", text), message(2, "Bob", "common")],
+            );
+            let words = |max| -> Vec<_> {
+                let distinctive = stats.distinctive_words("useralice", max);
+                distinctive.into_iter().map(|(word, _)| word).collect()
+            };
+            assert_eq!(words(10), ["apple", "banana"]);
+            assert_eq!(words(1), ["apple"]);
+        }
+    }
+
+    #[test]
+    fn caps_percent_leaves_out_messages_too_short_to_shout() {
+        let stats = analyze(
+            StatsSettings::default(),
+            [
+                message(1, "Alice", "HELLO THERE"),
+                message(2, "Alice", "OK"),
+                message(3, "Alice", ""),
+                message(4, "Alice", "hello there"),
+            ],
+        );
+        let alice = &stats.participants["useralice"];
+        assert_eq!((alice.caps_messages, alice.caps_eligible), (1, 2));
+        assert_eq!(alice.caps_percent(), 50.0);
+    }
+
+    #[test]
+    fn longest_chain_is_one_line_per_message() {
+        let settings = StatsSettings {
+            time_zone: TimeZone::Utc,
+            ..Default::default()
+        };
+        let mut reply = message(2, "Bob", "hello\nthere");
+        reply["reply_to_message_id"] = 1.into();
+        let stats = analyze(
+            settings,
+            [message(1, "Alice", "hi"), reply, message(3, "Carol", "hm")],
+        );
+        assert_eq!(
+            stats.longest_chain,
+            "[2024-01-01 00:01:00] Alice: hi\n[2024-01-01 00:02:00] Bob: hello there"
+        );
+        assert!(stats.to_string().contains(
+            "Longest chain: 2\n- [2024-01-01 00:01:00] Alice: hi\n- [2024-01-01 00:02:00] Bob: hello there\n"
+        ));
+    }
+
+    #[test]
+    fn token_filters_drop_short_words_numbers_links_and_mentions() {
+        let text = "a 5 1,000 https://example.com/page @bob word";
+        let words = |settings| -> Vec<_> {
+            let stats = analyze(settings, [message(1, "Alice", text)]);
+            let mut words: Vec<_> = top_labels(&stats, "useralice")
+                .into_iter()
+                .map(|(word, _)| word.to_string())
+                .collect();
+            words.sort_unstable();
+            words
+        };
+
+        let all = [
+            "1,000",
+            "5",
+            "@bob",
+            "a",
+            "https://example.com/page",
+            "word",
+        ];
+        assert_eq!(words(StatsSettings::default()), all);
+        let filtered = [
+            (
+                StatsSettings {
+                    min_word_length: 2,
+                    ..Default::default()
+                },
+                vec!["1,000", "@bob", "https://example.com/page", "word"],
+            ),
+            (
+                StatsSettings {
+                    skip_numbers: true,
+                    ..Default::default()
+                },
+                vec!["@bob", "a", "https://example.com/page", "word"],
+            ),
+            (
+                StatsSettings {
+                    skip_links: true,
+                    ..Default::default()
+                },
+                vec!["1,000", "5", "@bob", "a", "word"],
+            ),
+            (
+                StatsSettings {
+                    skip_mentions: true,
+                    ..Default::default()
+                },
+                vec!["1,000", "5", "a", "https://example.com/page", "word"],
+            ),
+            (
+                StatsSettings {
+                    min_word_length: 2,
+                    skip_numbers: true,
+                    skip_links: true,
+                    skip_mentions: true,
+                    ..Default::default()
+                },
+                vec!["word"],
+            ),
+        ];
+        for (settings, expected) in filtered {
+            assert_eq!(words(settings), expected);
+        }
+    }
+
+    #[test]
+    fn timestamps_serialize_as_iso_8601_strings() {
+        let stats = analyze(
+            StatsSettings::default(),
+            [message(1, "Alice", "hi"), message(2, "Alice", "there")],
+        );
+        let alice = &stats.participants["useralice"];
+        let mut json = serde_json::to_value(alice).unwrap();
+        assert_eq!(json["first_message"], "2024-01-01T00:01:00Z");
+        assert_eq!(json["last_message"], "2024-01-01T00:02:00Z");
+
+        let read: UserStats = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(read.first_message, alice.first_message);
+        assert_eq!(read.last_message, alice.last_message);
+
+        // Earlier versions wrote `SystemTime` as serde does by default;
+        // reading that back fails rather than losing the timestamp.
+        json["first_message"] = serde_json::json!({
+            "secs_since_epoch": 1_704_067_260,
+            "nanos_since_epoch": 0,
+        });
+        assert!(serde_json::from_value::<UserStats>(json).is_err());
+    }
+
+    #[test]
+    fn merge_keeps_the_earliest_first_and_latest_last_message() {
+        let early = || {
+            analyze(
+                StatsSettings::default(),
+                [
+                    message(1, "Alice", "a"),
+                    message(5, "Alice", "b"),
+                    message(6, "Bob", "c"),
+                ],
+            )
+        };
+        let late = || {
+            analyze(
+                StatsSettings::default(),
+                [message(3, "Alice", "x"), message(9, "Alice", "y")],
+            )
+        };
+
+        let mut merged = early();
+        merged.merge(late());
+        for stats in [merged, late() + early()] {
+            assert_eq!(stats.messages, 5);
+            let alice = &stats.participants["useralice"];
+            assert_eq!(alice.count, 4);
+            assert_eq!(alice.first_message, Some(sent(1)));
+            assert_eq!(alice.last_message, Some(sent(9)));
+            let bob = &stats.participants["userbob"];
+            assert_eq!(
+                (bob.first_message, bob.last_message),
+                (Some(sent(6)), Some(sent(6)))
+            );
+        }
+    }
+
+    #[test]
+    fn merging_undated_stats_keeps_the_dates() {
+        let mut dated = UserStats {
+            first_message: Some(sent(2)),
+            last_message: Some(sent(4)),
+            ..Default::default()
+        };
+        dated.merge(UserStats::default());
+        assert_eq!(
+            (dated.first_message, dated.last_message),
+            (Some(sent(2)), Some(sent(4)))
+        );
+
+        let mut undated = UserStats::default();
+        undated.merge(dated);
+        assert_eq!(
+            (undated.first_message, undated.last_message),
+            (Some(sent(2)), Some(sent(4)))
+        );
+    }
+
+    #[test]
+    fn date_range_includes_since_and_excludes_until() {
+        let settings = StatsSettings {
+            since: Some(sent(2).into()),
+            until: Some(sent(4).into()),
+            ..Default::default()
+        };
+        let stats = analyze(settings, (1..=5).map(|id| message(id, "Alice", "hi")));
+        assert_eq!(stats.messages, 2);
+        let alice = &stats.participants["useralice"];
+        assert_eq!(
+            (alice.first_message, alice.last_message),
+            (Some(sent(2)), Some(sent(3)))
+        );
+    }
+
+    #[test]
+    fn undated_messages_follow_the_undated_policy() {
+        let mut undated = message(3, "Alice", "when?");
+        undated["date_unixtime"] = "".into();
+        for (policy, messages) in [(UndatedPolicy::Include, 2), (UndatedPolicy::Exclude, 1)] {
+            let settings = StatsSettings {
+                since: Some(sent(1).into()),
+                undated: policy,
+                ..Default::default()
+            };
+            let stats = analyze(settings, [message(1, "Alice", "hi"), undated.clone()]);
+            assert_eq!((stats.messages, stats.undated), (messages, 1), "{policy:?}");
+        }
+    }
+
+    #[test]
+    fn replies_and_conversations_started() {
+        let reply = |id, from, to: u64| {
+            let mut message = message(id, from, "re");
+            message["reply_to_message_id"] = to.into();
+            message
+        };
+        // Message ids are minutes, so there are gaps of over an hour
+        // before messages 100 and 200.
+        let chat = || {
+            [
+                message(1, "Alice", "hi"),
+                reply(2, "Bob", 1),
+                reply(3, "Alice", 2),
+                message(100, "Bob", "anyone?"),
+                message(101, "Alice", "yes"),
+                message(200, "Alice", "news"),
+                reply(201, "Bob", 200),
+            ]
+        };
+
+        let stats = analyze(StatsSettings::default(), chat());
+        let (alice, bob) = (
+            &stats.participants["useralice"],
+            &stats.participants["userbob"],
+        );
+        assert_eq!((alice.replies, alice.reply_percent()), (1, 25.0));
+        assert_eq!((bob.replies, bob.reply_percent().round()), (2, 67.0));
+        assert_eq!(
+            (alice.conversations_started, bob.conversations_started),
+            (2, 1)
+        );
+
+        let settings = StatsSettings {
+            conversation_gap_minutes: Some(120),
+            ..Default::default()
+        };
+        let stats = analyze(settings, chat());
+        let (alice, bob) = (
+            &stats.participants["useralice"],
+            &stats.participants["userbob"],
+        );
+        assert_eq!(
+            (alice.conversations_started, bob.conversations_started),
+            (1, 0)
+        );
+    }
+
+    /// Message `id` by `from`, with a 👍 from each of `reactors`.
+    fn reacted(id: u64, from: &str, reactors: &[&str]) -> serde_json::Value {
+        let mut message = message(id, from, "look");
+        let recent: Vec<_> = reactors
+            .iter()
+            .map(|name| {
+                serde_json::json!({
+                    "from": name,
+                    "from_id": format!("user{}", name.to_lowercase()),
+                    "date": "2024-01-01T12:00:00",
+                })
+            })
+            .collect();
+        message["reactions"] = serde_json::json!([{
+            "type": "emoji",
+            "count": reactors.len(),
+            "emoji": "👍",
+            "recent": recent,
+        }]);
+        message
+    }
+
+    #[test]
+    fn excluded_senders_are_left_out_but_counted() {
+        let settings = StatsSettings {
+            // One by display name, one by sender id.
+            exclude: ["Bob".to_string(), "usercarol".to_string()].into(),
+            ..Default::default()
+        };
+        let stats = analyze(
+            settings,
+            [
+                message(1, "Alice", "hi all"),
+                message(2, "Bob", "hello"),
+                message(3, "Carol", "hey"),
+                message(4, "Alice", "anyone here?"),
+                reacted(5, "Alice", &["Bob", "Dave"]),
+            ],
+        );
+
+        assert_eq!(stats.messages, 5);
+        assert_eq!(stats.excluded, 2);
+        let counted: u64 = stats.participants.values().map(|p| p.count).sum();
+        assert_eq!(counted + stats.excluded, stats.messages);
+        assert_eq!(stats.participants.keys().collect::<Vec<_>>(), ["useralice"]);
+        // Excluded senders don't react either.
+        assert_eq!(stats.reactors.keys().collect::<Vec<_>>(), ["userdave"]);
+        assert!(stats.to_string().contains("🚫 Excluded messages  : 2"));
+    }
+
+    #[test]
+    fn bots_are_names_and_ids_ending_in_bot() {
+        let settings = StatsSettings {
+            exclude_bots: true,
+            ..Default::default()
+        };
+        let stats = analyze(
+            settings,
+            [
+                // Names whose last 3 bytes aren't whole characters.
+                message(1, "Боб", "привет"),
+                message(2, "Я", "да"),
+                message(3, "WeatherBot", "sunny"),
+                message(4, "gif_BOT", "🎞"),
+                message(5, "Abbott", "hi"),
+                reacted(6, "Боб", &["WeatherBot", "Ёжик"]),
+            ],
+        );
+
+        let mut kept: Vec<_> = stats.participants.keys().map(String::as_str).collect();
+        kept.sort_unstable();
+        assert_eq!(kept, ["userabbott", "userбоб", "userя"]);
+        assert_eq!(stats.excluded, 2);
+        assert_eq!(stats.reactors.keys().collect::<Vec<_>>(), ["userёжик"]);
+    }
+
+    /// The median gap and cadence of someone who waits `gaps` minutes
+    /// between their messages.
+    fn cadence_after(gaps: &[u64]) -> (Option<Duration>, Option<Cadence>) {
+        let mut minute = 0;
+        let mut messages = vec![message(0, "Alice", "hi")];
+        for gap in gaps {
+            minute += gap;
+            messages.push(message(minute, "Alice", "hi"));
+        }
+        let stats = analyze(StatsSettings::default(), messages);
+        let alice = &stats.participants["useralice"];
+        (alice.median_gap(), alice.cadence())
+    }
+
+    #[test]
+    fn median_gap_leaves_out_gaps_over_48_hours() {
+        const HOUR: u64 = 60;
+        let (gap, _) = cadence_after(&[10, 20, 48 * HOUR + 1, 30]);
+        assert_eq!(gap, Some(Duration::from_secs(20 * 60)));
+        // 48 hours exactly is still counted.
+        let (gap, _) = cadence_after(&[48 * HOUR, 48 * HOUR, 10]);
+        assert_eq!(gap, Some(Duration::from_secs(48 * 3600)));
+        // Of an even number of gaps, the upper middle one.
+        let (gap, _) = cadence_after(&[40, 10, 30, 20]);
+        assert_eq!(gap, Some(Duration::from_secs(30 * 60)));
+
+        let stats = analyze(
+            StatsSettings::default(),
+            [message(0, "Alice", "hi"), message(4_000, "Alice", "back")],
+        );
+        let alice = &stats.participants["useralice"];
+        assert_eq!((alice.median_gap(), alice.long_gaps), (None, 1));
+    }
+
+    #[test]
+    fn cadence_follows_the_median_gap() {
+        const HOUR: u64 = 60;
+        const DAY: u64 = 24 * HOUR;
+        let cadence = |gaps: &[u64]| cadence_after(gaps).1;
+
+        assert_eq!(cadence(&[]), None);
+        assert_eq!(cadence(&[12 * HOUR; 3]), Some(Cadence::SeveralTimesADay));
+        assert_eq!(cadence(&[12 * HOUR + 1; 3]), Some(Cadence::Daily));
+        assert_eq!(cadence(&[2 * DAY; 3]), Some(Cadence::Daily));
+        // As many gaps over 48 hours as under still go by the median.
+        assert_eq!(cadence(&[HOUR, 3 * DAY]), Some(Cadence::SeveralTimesADay));
+        // Mostly longer gaps: as many messages as weeks is weekly.
+        assert_eq!(cadence(&[7 * DAY; 4]), Some(Cadence::Weekly));
+        assert_eq!(cadence(&[7 * DAY, 14 * DAY]), Some(Cadence::Weekly));
+        assert_eq!(cadence(&[7 * DAY, 14 * DAY + 1]), Some(Cadence::Rarely));
+        assert_eq!(cadence(&[30 * DAY; 2]), Some(Cadence::Rarely));
+    }
+
+    #[test]
+    fn bursts_a_year_apart_are_judged_by_their_gaps() {
+        let mut gaps = vec![1; 9];
+        gaps.push(365 * 24 * 60);
+        gaps.extend([1; 9]);
+        let (gap, cadence) = cadence_after(&gaps);
+        assert_eq!(gap, Some(Duration::from_secs(60)));
+        assert_eq!(cadence, Some(Cadence::SeveralTimesADay));
+    }
+
+    #[test]
+    fn gaps_are_measured_within_each_chat() {
+        // Alice writes every 10 minutes in one chat and every 15 in another,
+        // the second overlapping the first.
+        let first = parse((0..4).map(|i| message(i * 10, "Alice", "hi")));
+        let second = parse((0..4).map(|i| message(5 + i * 15, "Alice", "hi")));
+        let mut stats = ChatStats::new(StatsSettings::default());
+        stats.analyze_labeled("first", &first);
+        stats.analyze_labeled("second", &second);
+        let mut merged = ChatStats::new(StatsSettings::default());
+        for (label, messages) in [("first", &first), ("second", &second)] {
+            let mut part = ChatStats::new(StatsSettings::default());
+            part.analyze_labeled(label, messages);
+            merged.merge(part);
+        }
+
+        for stats in [&stats, &merged] {
+            let alice = &stats.participants["useralice"];
+            assert_eq!(alice.gaps, BTreeMap::from([(600, 3), (900, 3)]));
+            assert_eq!(alice.long_gaps, 0);
+            assert_eq!(alice.median_gap(), Some(Duration::from_secs(900)));
+        }
+    }
+
+    #[test]
+    fn membership_credits_inviters_of_others_and_names_deleted_accounts() {
+        let members = |names: &[Option<&str>]| serde_json::json!({ "members": names });
+        let first = analyze(
+            StatsSettings::default(),
+            [
+                // Joining a public group shows up as inviting oneself.
+                service(1, "Eve", "invite_members", members(&[Some("Eve")])),
+                service(
+                    2,
+                    "Alice",
+                    "invite_members",
+                    members(&[None, Some("Frank")]),
+                ),
+                service(3, "Alice", "remove_members", members(&[None])),
+            ],
+        );
+        let membership = &first.membership;
+        assert_eq!((membership.joined, membership.left), (3, 1));
+        assert_eq!(membership.joins[DELETED_ACCOUNT], 1);
+        assert_eq!(membership.leaves[DELETED_ACCOUNT], 1);
+        assert_eq!(membership.inviters.len(), 1);
+        assert_eq!(membership.top_inviter(), Some((&"Alice".to_string(), 2)));
+
+        let second = analyze(
+            StatsSettings::default(),
+            [
+                service(1, "Bob", "invite_members", members(&[Some("Eve")])),
+                service(
+                    2,
+                    "Bob",
+                    "invite_members",
+                    members(&[Some("Gus"), Some("Hal")]),
+                ),
+                service(3, "Gus", "join_group_by_link", serde_json::json!({})),
+                service(4, "Hal", "remove_members", members(&[Some("Hal")])),
+            ],
+        );
+        let mut merged = first;
+        merged.merge(second);
+        let membership = &merged.membership;
+        assert_eq!((membership.joined, membership.left), (7, 2));
+        assert_eq!(membership.net_change(), 5);
+        assert_eq!((membership.joins["Eve"], membership.joins["Gus"]), (2, 2));
+        // Nobody is credited for a link without an inviter.
+        assert_eq!(membership.top_inviter(), Some((&"Bob".to_string(), 3)));
+        let change = membership.monthly["2024-01"];
+        assert_eq!((change.joined, change.left), (7, 2));
+    }
+
+    #[test]
+    fn pins_are_listed_by_date_with_the_messages_found_in_their_chat() {
+        let settings = StatsSettings {
+            time_zone: TimeZone::Utc,
+            aliases: [("userbob2".to_string(), "userbob".to_string())].into(),
+            ..Default::default()
+        };
+        let pin = |id, actor, message_id| {
+            service(
+                id,
+                actor,
+                "pin_message",
+                serde_json::json!({ "message_id": message_id }),
+            )
+        };
+        let long = "spoilers ahead ".repeat(6);
+        let mut stats = ChatStats::new(settings);
+        stats.analyze_labeled(
+            "books",
+            &parse([
+                message(9, "Bob", "what next?"),
+                message(10, "Carol", &long),
+                pin(11, "Bob", 10),
+            ]),
+        );
+        let mut second_account = message(3, "Bob", "see you at noon");
+        second_account["from_id"] = "userbob2".into();
+        stats.analyze_labeled(
+            "films",
+            &parse([
+                second_account,
+                pin(4, "Alice", 3),
+                // Message 10 of the other chat.
+                pin(5, "Alice", 10),
+            ]),
+        );
+
+        let pinned = |id: &str| stats.participants[id].pinned;
+        assert_eq!((pinned("userbob"), pinned("usercarol")), (1, 1));
+        let pins: Vec<_> = stats
+            .pins
+            .iter()
+            .map(|pin| {
+                (
+                    pin.message_id,
+                    pin.pinned_by.as_str(),
+                    pin.author.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            pins,
+            [
+                (3, "Alice", Some("Bob")),
+                (10, "Alice", None),
+                (10, "Bob", Some("Carol"))
+            ]
+        );
+        assert_eq!(stats.pins[2].preview.as_deref(), Some(&long[..60]));
+
+        let text = stats.to_string();
+        let section: Vec<_> = text
+            .lines()
+            .skip_while(|line| !line.starts_with("📌"))
+            .take(4)
+            .collect();
+        assert_eq!(
+            section,
+            [
+                "📌 Pins (3):",
+                "- 2024-01-01 by Alice: Bob: see you at noon",
+                "- 2024-01-01 by Alice: message #10 (not in export)",
+                &format!("- 2024-01-01 by Bob: Carol: {}", &long[..60]),
+            ]
+        );
+    }
+
+    #[test]
+    fn streaks_and_busiest_days_follow_calendar_days_in_the_time_zone() {
+        const DAY: u64 = 24 * 60;
+        let settings = |time_zone| StatsSettings {
+            time_zone,
+            ..Default::default()
+        };
+        let date = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        let days = |stats: &ChatStats| {
+            let busiest = stats.busiest_days(usize::MAX).into_iter();
+            let busiest =
+                busiest.map(|d| (d.date, d.messages, d.top_poster, d.top_poster_messages));
+            let streak = stats.longest_streak().unwrap();
+            (
+                (streak.first_day, streak.last_day, streak.days),
+                busiest.collect::<Vec<_>>(),
+            )
+        };
+
+        // Two days, a day off, then two more: of equally long streaks, the
+        // earliest, and of equally busy days, the earliest too.
+        let stats = analyze(
+            settings(TimeZone::Utc),
+            [
+                message(10, "Alice", "a"),
+                message(DAY + 10, "Bob", "b"),
+                message(DAY + 20, "Bob", "c"),
+                message(3 * DAY, "Alice", "d"),
+                message(3 * DAY + 1, "Bob", "e"),
+                message(4 * DAY + 10, "Carol", "f"),
+            ],
+        );
+        let name = |n: &str| n.to_string();
+        assert_eq!(
+            days(&stats),
+            (
+                (date(1), date(2), 2),
+                vec![
+                    (date(2), 2, name("Bob"), 2),
+                    (date(4), 2, name("Alice"), 1),
+                    (date(1), 1, name("Alice"), 1),
+                    (date(5), 1, name("Carol"), 1),
+                ]
+            )
+        );
+        // The day off was only so in UTC: it ended an hour late in UTC-1.
+        let stats = analyze(
+            settings(TimeZone::Offset(-3600)),
+            [
+                message(70, "Alice", "a"),
+                message(DAY + 70, "Bob", "b"),
+                message(3 * DAY + 30, "Alice", "c"),
+            ],
+        );
+        assert_eq!(days(&stats).0, (date(1), date(3), 3));
+
+        // Merged statistics join streaks across chats.
+        let mut merged = analyze(settings(TimeZone::Utc), [message(10, "Alice", "a")]);
+        merged.merge(analyze(
+            settings(TimeZone::Utc),
+            [message(DAY, "Bob", "b"), message(2 * DAY, "Bob", "c")],
+        ));
+        assert_eq!(days(&merged).0, (date(1), date(3), 3));
+        assert!(
+            analyze(settings(TimeZone::Utc), [])
+                .longest_streak()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn runs_are_broken_by_other_senders_in_the_same_chat_only() {
+        let settings = StatsSettings {
+            exclude: ["Carol".to_string()].into(),
+            aliases: [("userbob2".to_string(), "userbob".to_string())].into(),
+            ..Default::default()
+        };
+        let mut second_account = message(8, "Bob", "and another");
+        second_account["from_id"] = "userbob2".into();
+        let mut stats = ChatStats::new(settings);
+        stats.analyze_labeled(
+            "a",
+            &parse([
+                message(1, "Alice", "one"),
+                message(2, "Alice", "two"),
+                // Neither service messages...
+                service(
+                    3,
+                    "Bob",
+                    "pin_message",
+                    serde_json::json!({ "message_id": 1 }),
+                ),
+                message(4, "Alice", "three"),
+                message(5, "Bob", "hi"),
+                // ...nor excluded senders count, but the latter still interrupt.
+                message(6, "Carol", "hey"),
+                message(7, "Bob", "so"),
+                second_account,
+                message(9, "Bob", "anyway"),
+            ]),
+        );
+        stats.analyze_labeled(
+            "b",
+            &parse([
+                message(10, "Bob", "elsewhere"),
+                message(11, "Alice", "four"),
+                message(12, "Alice", "five"),
+                message(13, "Alice", "six"),
+            ]),
+        );
+
+        let runs = |id: &str| {
+            let user = &stats.participants[id];
+            (
+                user.count,
+                user.runs,
+                user.longest_run,
+                user.longest_run_date,
+            )
+        };
+        // Of Alice's two runs of three, the first.
+        assert_eq!(runs("useralice"), (6, 2, 3, Some(sent(1))));
+        assert_eq!(runs("userbob"), (5, 3, 3, Some(sent(7))));
+        assert_eq!(stats.participants["useralice"].avg_run_length(), 3.0);
+        assert_eq!(stats.messages_in_runs, 9);
+        assert!(stats.to_string().contains(": 82% of messages"));
+    }
+
+    #[test]
+    fn id_gaps_are_measured_within_each_chat() {
+        let mut stats = ChatStats::new(StatsSettings {
+            time_zone: TimeZone::Utc,
+            ..Default::default()
+        });
+        let chats = [
+            ("a", vec![1, 2, 5, 20]),
+            // The next month: ids are minutes since the start of the year.
+            ("b", vec![1, 44_700]),
+            // Twelve gaps of one id.
+            ("c", (1..=25).step_by(2).collect()),
+        ];
+        for (label, ids) in chats {
+            let messages = ids.into_iter().map(|id| message(id, "Alice", "hi"));
+            stats.analyze_labeled(label, &parse(messages));
+        }
+
+        let gaps = &stats.id_gaps;
+        assert_eq!((gaps.present, gaps.id_range), (19, 20 + 44_700 + 25));
+        assert_eq!(gaps.estimated_deletions, gaps.id_range - gaps.present);
+        let monthly: Vec<_> = gaps.estimated_deletions_monthly.iter().collect();
+        assert_eq!(
+            monthly,
+            [
+                (&"2024-01".to_string(), &28),
+                (&"2024-02".to_string(), &44_698)
+            ]
+        );
+        let largest: Vec<_> = gaps
+            .largest
+            .iter()
+            .map(|gap| (gap.chat.as_str(), gap.after_id, gap.missing()))
+            .collect();
+        assert_eq!(
+            largest,
+            [
+                ("b", 1, 44_698),
+                ("a", 5, 14),
+                ("a", 2, 2),
+                ("c", 1, 1),
+                ("c", 3, 1),
+                ("c", 5, 1),
+                ("c", 7, 1),
+                ("c", 9, 1),
+                ("c", 11, 1),
+                ("c", 13, 1),
+            ]
+        );
+        assert_eq!(gaps.largest[0].before_date, Some(sent(44_700)));
     }
 }
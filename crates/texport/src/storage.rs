@@ -1,86 +1,939 @@
 use std::{
-    collections::HashMap,
-    io,
+    cmp::Reverse,
+    collections::{BTreeMap, HashSet, btree_map::Entry},
+    env, fmt, io, iter,
     path::{Path, PathBuf},
+    str::FromStr,
+    sync::OnceLock,
+    time::SystemTime,
 };
 
 use directories_next::UserDirs;
 use fs_err as fs;
 use log::warn;
-use serde::Deserialize;
+use serde::{
+    Deserialize, Deserializer,
+    de::{SeqAccess, Visitor},
+};
+
+use crate::{Chat, ChatReader, ChatType, RawMessage, messages::parse_unixtime};
 
 /// Name of the directory under Downloads where Telegram exports live.
 const TG_DIRECTORY_NAME: &str = "Telegram Desktop";
 /// Filename inside each chat folder containing the JSON manifest.
-const RESULT_FILE: &str = "result.json";
+pub(crate) const RESULT_FILE: &str = "result.json";
+/// The first page of a chat exported in HTML format, which holds no
+/// `result.json`.
+pub(crate) const HTML_FILE: &str = "messages.html";
+/// How many directory levels below the root are searched by default.
+const DEFAULT_MAX_DEPTH: usize = 3;
+/// Environment variable naming the export root, overriding the default.
+pub const EXPORT_DIR_VAR: &str = "TG_EXPORT_DIR";
+
+/// Where [`Storage::new_with`] looks for exports.
+#[derive(Clone, Debug)]
+pub struct StorageConfig {
+    /// The directory holding the export root; the user's Downloads
+    /// directory when `None`.
+    pub base_dir: Option<PathBuf>,
+    /// The name of the export root under `base_dir`, `Telegram Desktop` by
+    /// default. Telegram may use a localized name on some systems.
+    pub directory_name: String,
+    /// How to search the export root.
+    pub scan: ScanOptions,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            base_dir: None,
+            directory_name: TG_DIRECTORY_NAME.to_string(),
+            scan: ScanOptions::default(),
+        }
+    }
+}
+
+impl StorageConfig {
+    /// The export root this configuration points at.
+    pub fn root(&self) -> Result<PathBuf, OpenError> {
+        let base = match &self.base_dir {
+            Some(base) => base.clone(),
+            None => UserDirs::new()
+                .ok_or(OpenError::NoHome)?
+                .download_dir()
+                .ok_or(OpenError::NoDownload)?
+                .to_path_buf(),
+        };
+        Ok(base.join(&self.directory_name))
+    }
+}
+
+/// How [`Storage::from_path_with`] looks for exports.
+#[derive(Clone, Debug)]
+pub struct ScanOptions {
+    /// How many directory levels below the root to search: 1 finds only
+    /// `<root>/<chat>/result.json`, 3 also finds
+    /// `<root>/archive/2023/<chat>/result.json`.
+    pub max_depth: usize,
+    /// What to do when several directories hold the same chat.
+    pub duplicates: DuplicatePolicy,
+    /// Whether to search directories that symbolic links below the root
+    /// point to. Each directory is then searched once, however many links
+    /// lead to it, so that links pointing back up don't loop. The root is
+    /// followed either way.
+    pub follow_symlinks: bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            duplicates: DuplicatePolicy::default(),
+            follow_symlinks: false,
+        }
+    }
+}
+
+/// A step of a scan, reported to the callback of
+/// [`Storage::from_path_with_progress`].
+#[derive(Debug)]
+pub enum ScanEvent<'a> {
+    /// A chat directory was found; its `result.json` is read next.
+    FoundDir(&'a Path),
+    /// The `result.json` of a chat was read: the chat id and the size of
+    /// the file in bytes.
+    Loaded(i64, u64),
+    /// A directory or `result.json` was skipped, and why.
+    Skipped(&'a Path, String),
+}
+
+/// What [`Storage::from_path_with`] does with a chat found in several
+/// directories.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Keep every path, to be merged by [`Storage::load_merged`]. Right for
+    /// chats exported in parts.
+    #[default]
+    KeepAll,
+    /// Keep only the `result.json` modified last. Right for re-exports
+    /// that each cover the whole chat.
+    KeepNewest,
+    /// Fail with [`OpenError::DuplicateChat`].
+    Error,
+}
+
+impl FromStr for DuplicatePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep_all" => Ok(DuplicatePolicy::KeepAll),
+            "keep_newest" => Ok(DuplicatePolicy::KeepNewest),
+            "error" => Ok(DuplicatePolicy::Error),
+            _ => Err(format!("unknown duplicate policy: {s}")),
+        }
+    }
+}
 
 /// Holds all chats discovered under a Telegram export root.
 pub struct Storage {
-    #[allow(unused)] // Keep until it's clear whether `root` is needed externally
-    root: PathBuf,
-    /// Map from Telegram `chat_id` to its on‑disk `ChatFile`.
-    pub chats: HashMap<i64, ChatFile>,
+    #[cfg_attr(not(feature = "watch"), allow(unused))] // Only read by `Storage::watch`
+    pub(crate) root: PathBuf,
+    /// Map from Telegram `chat_id` to its on‑disk `ChatFile`, ordered by id
+    /// so that chats are always visited in the same order.
+    pub chats: BTreeMap<i64, ChatFile>,
+    /// Every path of each chat found in more than one directory, whatever
+    /// [`ScanOptions::duplicates`] kept, so that callers can warn about them.
+    pub duplicates: BTreeMap<i64, Vec<PathBuf>>,
+    /// Why each directory or `result.json` that couldn't be loaded was
+    /// skipped, in the order they were found.
+    pub errors: Vec<LoadError>,
 }
 
 impl Storage {
-    /// Locate `~/Downloads/Telegram Desktop` and load all `result.json` manifests.
+    /// Locate the export root, see [`Storage::default_root`], and load all
+    /// `result.json` manifests.
     pub fn new() -> Result<Self, OpenError> {
-        let root = UserDirs::new()
-            .ok_or(OpenError::NoHome)?
-            .download_dir()
-            .ok_or(OpenError::NoDownload)?
-            .join(TG_DIRECTORY_NAME);
+        Self::from_path(Self::default_root()?)
+    }
 
-        Self::from_path(root)
+    /// Load all `result.json` manifests under the root `config` points at.
+    /// Unlike [`Storage::new`], this ignores [`EXPORT_DIR_VAR`].
+    pub fn new_with(config: StorageConfig) -> Result<Self, OpenError> {
+        Self::from_path_with(config.root()?, config.scan)
     }
 
-    /// Load all chats from the given path (each subdirectory is expected
-    /// to contain a `result.json`).
+    /// The directory named by the `TG_EXPORT_DIR` environment variable if
+    /// it is set, or else the one Telegram Desktop exports to:
+    /// `~/Downloads/Telegram Desktop`.
+    pub fn default_root() -> Result<PathBuf, OpenError> {
+        match env::var_os(EXPORT_DIR_VAR) {
+            Some(dir) if !dir.is_empty() => Ok(dir.into()),
+            _ => StorageConfig::default().root(),
+        }
+    }
+
+    /// Load all chats found under the given path, with the default
+    /// [`ScanOptions`].
     pub fn from_path(path: impl AsRef<Path>) -> Result<Self, OpenError> {
-        let chats = fs::read_dir(path.as_ref())
-            .map_err(OpenError::NoTelegram)?
-            // Skip entries we failed to read, warning on error
-            .filter_map(|e| e.inspect_err(|e| warn!("skipping entry: {e}")).ok())
-            .map(|entry| entry.path())
-            .filter(|p| p.is_dir())
-            .filter_map(try_load_chat)
-            .collect();
+        Self::from_path_with(path, ScanOptions::default())
+    }
+
+    /// Load all chats found under the given path: every directory holding
+    /// a `result.json`, up to [`ScanOptions::max_depth`] levels deep.
+    /// Hidden directories are skipped, and so are the subdirectories of a
+    /// chat, which hold its media. The path may also be a single chat's
+    /// own directory. Finding no chats at all is an error,
+    /// [`OpenError::NoChats`].
+    ///
+    /// When several directories hold the same chat, such as the parts of a
+    /// large chat exported over time, [`ScanOptions::duplicates`] decides
+    /// what is kept. By default they all end up in one [`ChatFile`]; see
+    /// [`Storage::load_merged`].
+    ///
+    /// With the `rayon` feature, the manifests are read and parsed in
+    /// parallel.
+    pub fn from_path_with(path: impl AsRef<Path>, options: ScanOptions) -> Result<Self, OpenError> {
+        let path = path.as_ref();
+        #[cfg(feature = "rayon")]
+        let (found, errors) = Self::scan(path, options.max_depth)?
+            .follow_symlinks(options.follow_symlinks)
+            .load_parallel();
+        #[cfg(not(feature = "rayon"))]
+        let (found, errors) = {
+            let mut files =
+                Self::scan(path, options.max_depth)?.follow_symlinks(options.follow_symlinks);
+            let found: Vec<_> = files.by_ref().collect();
+            (found, files.errors)
+        };
+        Self::from_found(path, found, errors, options)
+    }
+
+    /// Like [`Storage::from_path_with`], but fail with
+    /// [`OpenError::Unreadable`] if any directory or `result.json` had to
+    /// be skipped, rather than leaving the chat out.
+    pub fn from_path_strict(
+        path: impl AsRef<Path>,
+        options: ScanOptions,
+    ) -> Result<Self, OpenError> {
+        let storage = Self::from_path_with(path, options)?;
+        if storage.errors.is_empty() {
+            Ok(storage)
+        } else {
+            Err(OpenError::Unreadable(storage.errors))
+        }
+    }
+
+    /// Like [`Storage::from_path_with`], calling `progress` with every
+    /// [`ScanEvent`] as the scan goes, e.g. to drive a progress bar. A panic
+    /// in `progress` is not caught. The manifests are always read one by
+    /// one, even with the `rayon` feature, so that `progress` needn't be
+    /// `Sync` and sees the events in order.
+    pub fn from_path_with_progress(
+        path: impl AsRef<Path>,
+        options: ScanOptions,
+        mut progress: impl FnMut(ScanEvent),
+    ) -> Result<Self, OpenError> {
+        let path = path.as_ref();
+        let mut files =
+            Self::scan(path, options.max_depth)?.follow_symlinks(options.follow_symlinks);
+        let found: Vec<_> = iter::from_fn(|| files.next_with(&mut progress)).collect();
+        Self::from_found(path, found, files.errors, options)
+    }
+
+    /// Load the chats in the given `result.json` files, each a path and
+    /// its contents, e.g. fetched from object storage. Nothing is read
+    /// from disk: the paths only tell the exports apart and name them in
+    /// errors. As with [`Storage::from_path`], an entry that is not a chat
+    /// export is skipped and recorded in [`Storage::errors`], the exports
+    /// of one chat are merged, in path order, and finding no chats at all
+    /// is [`OpenError::NoChats`], with an empty path. The media of these
+    /// chats can't be verified.
+    pub fn from_entries(
+        entries: impl IntoIterator<Item = (PathBuf, Vec<u8>)>,
+    ) -> Result<Self, OpenError> {
+        let mut found = Vec::new();
+        let mut errors = Vec::new();
+        for (path, bytes) in entries {
+            match ChatFile::from_manifest(path.clone(), &bytes) {
+                Ok(mut file) => {
+                    file.source = Source::Memory(BTreeMap::from([(path, bytes)]));
+                    found.push((file.info.id, file));
+                }
+                Err(e) => {
+                    warn!("skipping: {e}");
+                    errors.push(e);
+                }
+            }
+        }
+        found.sort_by(|(_, a), (_, b)| a.paths.cmp(&b.paths));
+        Self::from_found(Path::new(""), found, errors, ScanOptions::default())
+    }
+
+    /// Collect the chats found under `path`, applying
+    /// [`ScanOptions::duplicates`].
+    pub(crate) fn from_found(
+        path: &Path,
+        found: Vec<(i64, ChatFile)>,
+        errors: Vec<LoadError>,
+        options: ScanOptions,
+    ) -> Result<Self, OpenError> {
+        let mut chats: BTreeMap<i64, ChatFile> = BTreeMap::new();
+        for (id, chat) in found {
+            match chats.get_mut(&id) {
+                Some(kept) => kept.absorb(chat),
+                None => {
+                    chats.insert(id, chat);
+                }
+            }
+        }
+        if chats.is_empty() {
+            return Err(if errors.is_empty() {
+                OpenError::NoChats {
+                    path: path.into(),
+                    max_depth: options.max_depth,
+                }
+            } else {
+                OpenError::Unreadable(errors)
+            });
+        }
+
+        let mut duplicates = BTreeMap::new();
+        for (&id, chat) in &mut chats {
+            if chat.paths.len() < 2 {
+                continue;
+            }
+            duplicates.insert(id, chat.paths.clone());
+            match options.duplicates {
+                DuplicatePolicy::KeepAll => {}
+                DuplicatePolicy::KeepNewest => {
+                    let modified = |path: &PathBuf| {
+                        fs::metadata(path)
+                            .and_then(|m| m.modified())
+                            .unwrap_or(SystemTime::UNIX_EPOCH)
+                    };
+                    // Ties go to the later path.
+                    let newest = chat.paths.iter().max_by_key(|path| modified(path));
+                    chat.paths = newest.cloned().into_iter().collect();
+                }
+                DuplicatePolicy::Error => {
+                    return Err(OpenError::DuplicateChat {
+                        id,
+                        paths: chat.paths.clone(),
+                    });
+                }
+            }
+        }
 
         Ok(Self {
-            root: path.as_ref().into(),
+            root: path.into(),
             chats,
+            duplicates,
+            errors,
         })
     }
+
+    /// Find chats under the given path lazily, reading each `result.json`
+    /// only when the iterator gets to it, so that a caller looking for one
+    /// chat can stop early. Directories are searched as by
+    /// [`Storage::from_path_with`], but duplicates are yielded as found,
+    /// once per directory. Symbolic links are not followed unless asked
+    /// to with [`ChatFiles::follow_symlinks`].
+    pub fn scan(path: impl AsRef<Path>, max_depth: usize) -> Result<ChatFiles, OpenError> {
+        let path = path.as_ref();
+        // The root must be readable; anything below it is skipped on error.
+        // `std::fs` keeps the path out of the message, as the error has it.
+        std::fs::read_dir(path).map_err(|source| OpenError::NoTelegram {
+            path: path.into(),
+            source,
+        })?;
+        Ok(ChatFiles {
+            pending: vec![(path.to_path_buf(), max_depth)],
+            errors: Vec::new(),
+            follow_symlinks: false,
+            visited: HashSet::new(),
+        })
+    }
+
+    /// The chats whose metadata satisfies `f`, in id order.
+    pub fn filter(&self, f: impl Fn(&ChatInfo) -> bool) -> impl Iterator<Item = &ChatFile> {
+        self.chats.values().filter(move |chat| f(&chat.info))
+    }
+
+    /// The chats of the given type, in id order.
+    pub fn by_type(&self, chat_type: ChatType) -> impl Iterator<Item = &ChatFile> {
+        self.filter(move |info| info.chat_type == chat_type)
+    }
+
+    /// The chats whose name contains `pattern`, ignoring case, in id order.
+    pub fn by_name_contains(&self, pattern: &str) -> impl Iterator<Item = &ChatFile> {
+        let pattern = pattern.to_lowercase();
+        self.filter(move |info| info.name.to_lowercase().contains(&pattern))
+    }
+
+    /// The chat with id `id`, if it was found.
+    pub fn chat(&self, id: i64) -> Option<&ChatFile> {
+        self.chats.get(&id)
+    }
+
+    /// The one chat whose name contains `pattern`, ignoring case. When the
+    /// names of several chats contain it, the one named exactly `pattern`,
+    /// ignoring case, is picked; otherwise the error lists them.
+    pub fn find_by_name(&self, pattern: &str) -> Result<&ChatFile, FindChatError> {
+        let candidates = |chats: Vec<&ChatFile>| {
+            chats
+                .iter()
+                .map(|chat| (chat.info.id, chat.info.name.clone()))
+                .collect()
+        };
+        let found: Vec<_> = self.by_name_contains(pattern).collect();
+        match found[..] {
+            [chat] => Ok(chat),
+            [] => Err(FindChatError::NotFound {
+                pattern: pattern.to_string(),
+                chats: candidates(self.chats.values().collect()),
+            }),
+            _ => {
+                let mut exact = found
+                    .iter()
+                    .filter(|chat| chat.info.name.to_lowercase() == pattern.to_lowercase());
+                match (exact.next(), exact.next()) {
+                    (Some(chat), None) => Ok(chat),
+                    _ => Err(FindChatError::Ambiguous {
+                        pattern: pattern.to_string(),
+                        chats: candidates(found),
+                    }),
+                }
+            }
+        }
+    }
+
+    /// Parse chat `id`. A chat found in several directories is merged as
+    /// by [`Storage::load_merged`].
+    pub fn load_chat(&self, id: i64) -> Result<Chat, LoadError> {
+        self.chats
+            .get(&id)
+            .ok_or(LoadError::UnknownChat(id))?
+            .load()
+    }
+
+    /// Parse every chat, in id order.
+    pub fn load_all(&self) -> impl Iterator<Item = Result<(i64, Chat), LoadError>> + '_ {
+        self.chats
+            .keys()
+            .map(|&id| self.load_chat(id).map(|chat| (id, chat)))
+    }
+
+    /// Parse every part of chat `id` and merge them into one [`Chat`].
+    ///
+    /// Messages are deduplicated by id and put in id order. Where parts
+    /// overlap, the part reaching the latest message wins, as it was
+    /// exported last, unless another part has a later edit of the message;
+    /// its name and type are used, too. Within a part, a message with the
+    /// same id as an earlier one is skipped with a warning, as by
+    /// [`ChatStats::push`](crate::ChatStats::push).
+    pub fn load_merged(&self, id: i64) -> Result<Chat, LoadError> {
+        self.chats
+            .get(&id)
+            .ok_or(LoadError::UnknownChat(id))?
+            .load_merged()
+    }
+
+    /// Parse every chat as [`Storage::load_merged`] does, in id order: one
+    /// [`Chat`] per id, with each message once, even when an export
+    /// repeats a message.
+    pub fn load_all_deduped(&self) -> impl Iterator<Item = Result<(i64, Chat), LoadError>> + '_ {
+        self.chats
+            .keys()
+            .map(|&id| self.load_merged(id).map(|chat| (id, chat)))
+    }
 }
 
-/// Attempt to read `chat_dir/result.json` and deserialize it.
-fn try_load_chat(chat_dir: PathBuf) -> Option<(i64, ChatFile)> {
-    let manifest = chat_dir.join(RESULT_FILE);
+/// Chats found under a directory, yielded one at a time as directories
+/// are visited, in name order. See [`Storage::scan`].
+pub struct ChatFiles {
+    /// Directories left to visit, with how many levels may still be
+    /// descended below each; the next one is on top.
+    pending: Vec<(PathBuf, usize)>,
+    errors: Vec<LoadError>,
+    follow_symlinks: bool,
+    /// The canonical paths of the directories visited so far, when
+    /// following symbolic links.
+    visited: HashSet<PathBuf>,
+}
 
-    let bytes = fs::read(&manifest)
-        .inspect_err(|e| warn!("couldn't read {:?}: {e}", manifest))
-        .ok()?;
+impl Iterator for ChatFiles {
+    type Item = (i64, ChatFile);
 
-    let info = serde_json::from_slice::<ChatInfo>(&bytes)
-        .inspect_err(|e| warn!("invalid JSON in {:?}: {e}", manifest))
-        .ok()?;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with(&mut |_| {})
+    }
+}
 
-    Some((
-        info.id,
-        ChatFile {
-            path: manifest,
-            info,
-        },
-    ))
+impl ChatFiles {
+    /// Also search the directories that symbolic links point to, see
+    /// [`ScanOptions::follow_symlinks`].
+    pub fn follow_symlinks(mut self, follow: bool) -> Self {
+        self.follow_symlinks = follow;
+        self
+    }
+
+    /// Why each directory or `result.json` skipped so far was skipped.
+    pub fn errors(&self) -> &[LoadError] {
+        &self.errors
+    }
+
+    /// The next chat, telling `progress` about every directory visited on
+    /// the way.
+    fn next_with(&mut self, progress: &mut impl FnMut(ScanEvent)) -> Option<(i64, ChatFile)> {
+        while let Some(dir) = self.next_dir(progress) {
+            match try_load_chat(dir, progress) {
+                Ok(found) => return Some(found),
+                Err(e) => self.reject(e, progress),
+            }
+        }
+        None
+    }
+
+    /// Record why something was skipped.
+    fn reject(&mut self, error: LoadError, progress: &mut impl FnMut(ScanEvent)) {
+        warn!("skipping: {error}");
+        match &error {
+            LoadError::Io { path, source } => {
+                progress(ScanEvent::Skipped(path, source.to_string()))
+            }
+            LoadError::Json { path, source } => {
+                progress(ScanEvent::Skipped(path, format!("invalid JSON: {source}")));
+            }
+            #[cfg(feature = "zip")]
+            LoadError::Zip { path, source } => {
+                progress(ScanEvent::Skipped(path, source.to_string()))
+            }
+            LoadError::Html { path } => {
+                progress(ScanEvent::Skipped(
+                    path,
+                    "HTML export, export it again as JSON".to_string(),
+                ));
+            }
+            LoadError::UnknownChat(_) => {}
+        }
+        self.errors.push(error);
+    }
+
+    /// The next directory holding a `result.json`, without reading it.
+    fn next_dir(&mut self, progress: &mut impl FnMut(ScanEvent)) -> Option<PathBuf> {
+        while let Some((dir, depth)) = self.pending.pop() {
+            if self.follow_symlinks {
+                match dir.canonicalize() {
+                    Ok(real) => {
+                        if !self.visited.insert(real) {
+                            continue;
+                        }
+                    }
+                    Err(source) => {
+                        self.reject(LoadError::Io { path: dir, source }, progress);
+                        continue;
+                    }
+                }
+            }
+            if dir.join(RESULT_FILE).is_file() {
+                return Some(dir);
+            }
+            if dir.join(HTML_FILE).is_file() {
+                self.reject(LoadError::Html { path: dir }, progress);
+                continue;
+            }
+            if depth == 0 {
+                continue;
+            }
+
+            let io_error = |source| LoadError::Io {
+                path: dir.clone(),
+                source,
+            };
+            let entries = match std::fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    self.reject(io_error(e), progress);
+                    continue;
+                }
+            };
+            let mut subdirs = Vec::new();
+            for entry in entries {
+                let entry = match entry {
+                    Ok(entry) if !entry.file_name().to_string_lossy().starts_with('.') => entry,
+                    Ok(_) => continue,
+                    Err(e) => {
+                        self.reject(io_error(e), progress);
+                        continue;
+                    }
+                };
+                // The type of the entry itself, not of what a link points to.
+                match entry.file_type() {
+                    Ok(t) if t.is_dir() => subdirs.push(entry.path()),
+                    Ok(t) if t.is_symlink() && self.follow_symlinks && entry.path().is_dir() => {
+                        subdirs.push(entry.path());
+                    }
+                    Ok(_) => {}
+                    Err(e) => self.reject(io_error(e), progress),
+                }
+            }
+            // Reversed, so that the first name is popped first.
+            subdirs.sort_by(|a, b| b.cmp(a));
+            self.pending
+                .extend(subdirs.into_iter().map(|subdir| (subdir, depth - 1)));
+        }
+        None
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl ChatFiles {
+    /// Find every remaining chat directory, then read their manifests in
+    /// parallel. The chats and errors come out in the order they were
+    /// found.
+    fn load_parallel(mut self) -> (Vec<(i64, ChatFile)>, Vec<LoadError>) {
+        use rayon::prelude::*;
+
+        let dirs: Vec<_> = iter::from_fn(|| self.next_dir(&mut |_| {})).collect();
+        let loaded: Vec<_> = dirs
+            .into_par_iter()
+            .map(|dir| try_load_chat(dir, &mut |_| {}))
+            .collect();
+        let mut found = Vec::new();
+        for chat in loaded {
+            match chat {
+                Ok(chat) => found.push(chat),
+                Err(e) => self.reject(e, &mut |_| {}),
+            }
+        }
+        (found, self.errors)
+    }
+}
+
+/// Read and deserialize a whole `result.json`.
+pub(crate) fn read_chat(path: &Path) -> Result<Chat, LoadError> {
+    parse_file(path).map_err(|e| e.with_path(path))
+}
+
+/// Read and deserialize the `result.json` at `path`.
+pub(crate) fn parse_file(path: &Path) -> Result<Chat, ParseError> {
+    match read_file(path)? {
+        // simd-json overwrites `bytes`, so errors are located in a copy
+        // read again.
+        #[cfg(feature = "simd")]
+        FileBytes::Read(mut bytes) => {
+            Chat::from_mut_slice(&mut bytes).map_err(|e| match (e, read_file(path)) {
+                (ParseError::Json(e), Ok(bytes)) => {
+                    ParseError::Json(crate::locate::locate::<Chat>(&bytes, e))
+                }
+                (e, _) => e,
+            })
+        }
+        #[cfg_attr(not(feature = "mmap"), allow(unreachable_patterns))]
+        bytes => Chat::from_slice(&bytes),
+    }
+}
+
+/// The index of the first message of the `result.json` at `path` that
+/// can't be parsed, found by reading the file again a message at a time.
+fn failing_message(path: &Path) -> Option<usize> {
+    let bytes = read_file(path).ok()?;
+    let mut messages = ChatReader::new(&bytes[..]).ok()?;
+    messages.position(|message| message.is_err())
+}
+
+/// The contents of a file, as read by [`read_file`].
+pub(crate) enum FileBytes {
+    Read(Vec<u8>),
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+}
+
+impl std::ops::Deref for FileBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            FileBytes::Read(bytes) => bytes,
+            #[cfg(feature = "mmap")]
+            FileBytes::Mapped(map) => map,
+        }
+    }
+}
+
+/// Read the whole file at `path`. With the `mmap` feature the file is
+/// memory-mapped rather than copied into memory, unless it can't be, e.g.
+/// on a filesystem that doesn't support it.
+pub(crate) fn read_file(path: &Path) -> io::Result<FileBytes> {
+    #[cfg(feature = "mmap")]
+    {
+        let file = std::fs::File::open(path)?;
+        // SAFETY: the map is only read, while it is parsed. If another
+        // process changes the file meanwhile, what is parsed is undefined,
+        // as documented on the `mmap` feature.
+        match unsafe { memmap2::Mmap::map(&file) } {
+            Ok(map) => return Ok(FileBytes::Mapped(map)),
+            Err(e) => log::debug!("couldn't map {path:?}, reading it instead: {e}"),
+        }
+    }
+    std::fs::read(path).map(FileBytes::Read)
+}
+
+/// Attempt to read `chat_dir/result.json` and deserialize its metadata.
+pub(crate) fn try_load_chat(
+    chat_dir: PathBuf,
+    progress: &mut impl FnMut(ScanEvent),
+) -> Result<(i64, ChatFile), LoadError> {
+    progress(ScanEvent::FoundDir(&chat_dir));
+    let manifest = chat_dir.join(RESULT_FILE);
+
+    let bytes = read_file(&manifest).map_err(|source| LoadError::Io {
+        path: manifest.clone(),
+        source,
+    })?;
+    let file = ChatFile::from_manifest(manifest, &bytes)?;
+    progress(ScanEvent::Loaded(file.info.id, bytes.len() as u64));
+    Ok((file.info.id, file))
 }
 
 /// A discovered chat file on disk: its path plus parsed metadata.
 #[derive(Debug)]
 pub struct ChatFile {
-    /// Filesystem path to the `result.json` we loaded.
-    pub path: PathBuf,
-    /// Parsed chat metadata.
+    /// Filesystem paths to the `result.json` of every export of this chat,
+    /// in path order. There is more than one when a chat was exported in
+    /// parts or more than once.
+    pub paths: Vec<PathBuf>,
+    /// Parsed chat metadata, from the first path.
     pub info: ChatInfo,
+    /// Cached by [`ChatFile::summary`].
+    summary: OnceLock<ExportSummary>,
+    /// Where `paths` are read from.
+    pub(crate) source: Source,
+}
+
+/// Where the `result.json` files of a [`ChatFile`] are read from.
+#[derive(Debug)]
+pub(crate) enum Source {
+    /// The filesystem.
+    Disk,
+    /// The zip archive at this path, see [`Storage::from_zip`]. The paths
+    /// of the chat start with the path of the archive, followed by the
+    /// name of each entry.
+    #[cfg(feature = "zip")]
+    Zip(PathBuf),
+    /// Memory, the contents of each path, see [`Storage::from_entries`].
+    Memory(BTreeMap<PathBuf, Vec<u8>>),
+}
+
+impl ChatFile {
+    /// A chat whose `result.json` at `path` holds `bytes`, read from disk.
+    pub(crate) fn from_manifest(path: PathBuf, bytes: &[u8]) -> Result<Self, LoadError> {
+        let info = serde_json::from_slice::<ChatInfo>(bytes).map_err(|source| LoadError::Json {
+            path: path.clone(),
+            source,
+        })?;
+        Ok(Self {
+            paths: vec![path],
+            info,
+            summary: OnceLock::new(),
+            source: Source::Disk,
+        })
+    }
+
+    /// Whether the chat was found on disk, rather than in a zip archive or
+    /// in memory, so that its media can be looked at.
+    pub(crate) fn is_on_disk(&self) -> bool {
+        matches!(self.source, Source::Disk)
+    }
+
+    /// Add the paths of `other`, another export of the same chat.
+    fn absorb(&mut self, other: ChatFile) {
+        self.paths.extend(other.paths);
+        if let (Source::Memory(contents), Source::Memory(other)) = (&mut self.source, other.source)
+        {
+            contents.extend(other);
+        }
+    }
+
+    /// The contents of `path`, one of [`ChatFile::paths`], if it is
+    /// in memory.
+    fn in_memory(&self, path: &Path) -> Option<&[u8]> {
+        match &self.source {
+            Source::Memory(contents) => contents.get(path).map(Vec::as_slice),
+            _ => None,
+        }
+    }
+
+    /// Parse the `result.json` at `path`, one of [`ChatFile::paths`].
+    pub(crate) fn read_part(&self, path: &Path) -> Result<Chat, LoadError> {
+        #[cfg(feature = "zip")]
+        if let Source::Zip(archive) = &self.source {
+            return crate::archive::read_chat(archive, path);
+        }
+        match self.in_memory(path) {
+            Some(bytes) => Chat::from_slice(bytes).map_err(|e| e.with_path(path)),
+            None => read_chat(path),
+        }
+    }
+
+    /// Parse the chat. A chat found in several directories is merged as by
+    /// [`Storage::load_merged`].
+    pub fn load(&self) -> Result<Chat, LoadError> {
+        match self.paths.as_slice() {
+            [path] => self.read_part(path),
+            _ => self.load_merged(),
+        }
+    }
+
+    /// Parse every part of the chat and merge them, see
+    /// [`Storage::load_merged`].
+    fn load_merged(&self) -> Result<Chat, LoadError> {
+        let mut parts = self
+            .paths
+            .iter()
+            .map(|path| Ok((self.read_part(path)?, path)))
+            .collect::<Result<Vec<_>, LoadError>>()?;
+        // Newest first, so that its copy of a message in several parts is
+        // kept, unless an older one was edited later.
+        parts.sort_by_key(|(part, _)| Reverse(part.messages.iter().map(RawMessage::id).max()));
+
+        let mut messages = BTreeMap::new();
+        let mut merged = None;
+        for (part, path) in parts {
+            let Chat {
+                name,
+                chat_type,
+                id,
+                messages: part_messages,
+            } = part;
+            let mut ids = HashSet::new();
+            for message in part_messages {
+                let message_id = message.id();
+                if !ids.insert(message_id) {
+                    warn!(
+                        "skipping message {message_id} of {path:?}: an earlier message has the same id"
+                    );
+                    continue;
+                }
+                match messages.entry(message_id) {
+                    Entry::Vacant(entry) => {
+                        entry.insert(message);
+                    }
+                    Entry::Occupied(mut entry) => {
+                        if message.supersedes(entry.get()) {
+                            entry.insert(message);
+                        }
+                    }
+                }
+            }
+            merged.get_or_insert((name, chat_type, id));
+        }
+        let (name, chat_type, id) = merged.ok_or(LoadError::UnknownChat(self.info.id))?;
+        Ok(Chat {
+            name,
+            chat_type,
+            id,
+            messages: messages.into_values().collect(),
+        })
+    }
+
+    /// Count the messages of the chat and find when the first and last
+    /// were sent, reading `result.json` without keeping the messages in
+    /// memory. The result is cached, so only the first call reads the file.
+    ///
+    /// The parts of a chat found in several directories are added up, so
+    /// messages that are in more than one part are counted more than once.
+    pub fn summary(&self) -> Result<ExportSummary, LoadError> {
+        if let Some(summary) = self.summary.get() {
+            return Ok(*summary);
+        }
+        let mut summary = ExportSummary::default();
+        for path in &self.paths {
+            let part = self.summarize_part(path)?;
+            summary.messages += part.messages;
+            summary.first = summary.first.into_iter().chain(part.first).min();
+            summary.last = summary.last.into_iter().chain(part.last).max();
+        }
+        Ok(*self.summary.get_or_init(|| summary))
+    }
+
+    /// Summarize the `result.json` at `path`, one of [`ChatFile::paths`].
+    fn summarize_part(&self, path: &Path) -> Result<ExportSummary, LoadError> {
+        #[cfg(feature = "zip")]
+        if let Source::Zip(archive) = &self.source {
+            return crate::archive::summarize_entry(archive, path);
+        }
+        if let Some(bytes) = self.in_memory(path) {
+            return summarize(bytes, path);
+        }
+        let file = std::fs::File::open(path).map_err(|source| LoadError::Io {
+            path: path.into(),
+            source,
+        })?;
+        summarize(io::BufReader::new(file), path)
+    }
+}
+
+/// The size and time span of a chat export, see [`ChatFile::summary`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ExportSummary {
+    /// Messages in the export, service messages included.
+    pub messages: u64,
+    /// When the earliest message was sent.
+    pub first: Option<SystemTime>,
+    /// When the latest message was sent.
+    pub last: Option<SystemTime>,
+}
+
+/// Stream the messages of the `result.json` at `path`, looking only at
+/// their dates.
+pub(crate) fn summarize(reader: impl io::Read, path: &Path) -> Result<ExportSummary, LoadError> {
+    /// The part of `result.json` needed for a summary.
+    #[derive(Deserialize)]
+    struct Manifest {
+        #[serde(deserialize_with = "summarize_messages")]
+        messages: ExportSummary,
+    }
+
+    /// The part of a message needed for a summary.
+    #[derive(Deserialize)]
+    struct MessageDate {
+        #[serde(default, deserialize_with = "crate::messages::unixtime")]
+        date_unixtime: String,
+    }
+
+    struct Messages;
+
+    impl<'de> Visitor<'de> for Messages {
+        type Value = ExportSummary;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("an array of messages")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<ExportSummary, A::Error> {
+            let mut summary = ExportSummary::default();
+            while let Some(message) = seq.next_element::<MessageDate>()? {
+                summary.messages += 1;
+                if let Some(date) = parse_unixtime(&message.date_unixtime) {
+                    summary.first = Some(summary.first.map_or(date, |first| first.min(date)));
+                    summary.last = Some(summary.last.map_or(date, |last| last.max(date)));
+                }
+            }
+            Ok(summary)
+        }
+    }
+
+    fn summarize_messages<'de, D: Deserializer<'de>>(d: D) -> Result<ExportSummary, D::Error> {
+        d.deserialize_seq(Messages)
+    }
+
+    let manifest: Manifest = serde_json::from_reader(reader).map_err(|source| LoadError::Json {
+        path: path.into(),
+        source,
+    })?;
+    Ok(manifest.messages)
 }
 
 /// A basic description of a Telegram chat, as found in `result.json`.
@@ -89,12 +942,13 @@ pub struct ChatInfo {
     /// The display name of the chat
     pub name: String,
     #[serde(rename = "type")]
-    pub chat_type: String,
+    pub chat_type: ChatType,
     pub id: i64,
 }
 
 /// An error that can occur when opening or reading Telegram exports.
 #[derive(thiserror::Error, Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum OpenError {
     /// Could not find a home directory on this OS.
     #[error("no valid home directory path could be retrieved from the operating system")]
@@ -104,7 +958,159 @@ pub enum OpenError {
     #[error("no valid download directory path could be retrieved from the operating system")]
     NoDownload,
 
-    /// The top‑level `Downloads/Telegram Desktop` directory could not be read.
-    #[error("can't open Telegram output directory: {0}")]
-    NoTelegram(io::Error),
+    /// The export root, `Downloads/Telegram Desktop` by default, could not
+    /// be read.
+    #[error("can't open Telegram output directory {path:?}: {source}")]
+    NoTelegram { path: PathBuf, source: io::Error },
+
+    /// Neither the given directory nor any below it holds a chat export.
+    #[error(
+        "no chat exports in {path:?}: neither it nor the folders up to {max_depth} levels \
+         below it contain a valid result.json"
+    )]
+    NoChats { path: PathBuf, max_depth: usize },
+
+    /// The same chat was found in several directories under
+    /// [`DuplicatePolicy::Error`].
+    #[error("chat {id} was found in several places: {paths:?}")]
+    DuplicateChat { id: i64, paths: Vec<PathBuf> },
+
+    /// Some chat exports could not be loaded, see [`Storage::from_path_strict`].
+    /// Never empty.
+    #[error("chat exports could not be loaded, the first of {}: {}", .0.len(), .0[0])]
+    Unreadable(Vec<LoadError>),
+
+    /// The zip archive given to [`Storage::from_zip`] could not be opened.
+    #[cfg(feature = "zip")]
+    #[error("can't open zip archive {path:?}: {source}")]
+    Archive {
+        path: PathBuf,
+        source: zip::result::ZipError,
+    },
+}
+
+/// An error that can occur when parsing a chat export, see
+/// [`Chat::from_slice`].
+#[derive(thiserror::Error, Debug)]
+pub enum ParseError {
+    /// The export could not be read.
+    #[error("can't read chat export: {0}")]
+    Io(#[from] io::Error),
+
+    /// The export is not valid JSON, or not a chat export.
+    #[error("invalid chat export: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+impl ParseError {
+    /// This error as a [`LoadError`] about the `result.json` at `path`.
+    pub fn with_path(self, path: impl Into<PathBuf>) -> LoadError {
+        let path = path.into();
+        match self {
+            ParseError::Io(source) => LoadError::Io { path, source },
+            ParseError::Json(source) => LoadError::Json { path, source },
+        }
+    }
+}
+
+/// An error that can occur when reading a chat export with
+/// [`Chat::from_path`].
+#[derive(thiserror::Error, Debug)]
+pub enum ChatLoadError {
+    /// The file could not be read.
+    #[error("can't read {path:?}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+
+    /// The file is not valid JSON, or not a chat export. `source` says
+    /// where, as for [`Chat::from_slice`], and `message` is the index of
+    /// the message that couldn't be parsed, if any.
+    #[error("invalid JSON in {path:?}: {source}")]
+    Json {
+        path: PathBuf,
+        message: Option<usize>,
+        source: serde_json::Error,
+    },
+}
+
+impl ChatLoadError {
+    /// `error` from parsing the `result.json` at `path`.
+    pub(crate) fn new(path: &Path, error: ParseError) -> Self {
+        match error {
+            ParseError::Io(source) => ChatLoadError::Io {
+                path: path.into(),
+                source,
+            },
+            ParseError::Json(source) => ChatLoadError::Json {
+                path: path.into(),
+                message: failing_message(path),
+                source,
+            },
+        }
+    }
+}
+
+/// A chat that [`Storage::find_by_name`] can't pick. Both variants list
+/// the chats to pick from instead, as (id, name).
+#[derive(thiserror::Error, Debug)]
+pub enum FindChatError {
+    #[error(
+        "no chat's name contains {pattern:?}; the chats are {}",
+        list_chats(chats)
+    )]
+    NotFound {
+        pattern: String,
+        chats: Vec<(i64, String)>,
+    },
+    #[error(
+        "{pattern:?} is in the names of several chats, {}; pick one by id",
+        list_chats(chats)
+    )]
+    Ambiguous {
+        pattern: String,
+        chats: Vec<(i64, String)>,
+    },
+}
+
+fn list_chats(chats: &[(i64, String)]) -> String {
+    let chats: Vec<_> = chats
+        .iter()
+        .map(|(id, name)| format!("{name:?} ({id})"))
+        .collect();
+    chats.join(", ")
+}
+
+/// An error that can occur when loading a chat found by [`Storage`].
+#[derive(thiserror::Error, Debug)]
+pub enum LoadError {
+    /// No chat with this id was found.
+    #[error("no chat with id {0}")]
+    UnknownChat(i64),
+
+    /// A `result.json`, or a directory that might hold one, could not be
+    /// read.
+    #[error("can't read {path:?}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+
+    /// A `result.json` is not a valid chat export.
+    #[error("invalid JSON in {path:?}: {source}")]
+    Json {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+
+    /// A directory holds a chat exported in HTML format, which can't be
+    /// read.
+    #[error(
+        "{path:?} is an HTML export, which can't be read; export the chat again with \
+         \"Machine-readable JSON\" as the format"
+    )]
+    Html { path: PathBuf },
+
+    /// A `result.json` in a zip archive could not be found or read.
+    #[cfg(feature = "zip")]
+    #[error("can't read {path:?}: {source}")]
+    Zip {
+        path: PathBuf,
+        source: zip::result::ZipError,
+    },
 }
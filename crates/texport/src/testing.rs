@@ -0,0 +1,85 @@
+//! Messages for the unit tests, written the way Telegram Desktop writes them.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::DateTime;
+use serde_json::{Value, json};
+
+use crate::{ChatStats, RawMessage, StatsSettings};
+
+/// When message 0 was sent: 2024-01-01 00:00 UTC. Message `id` is sent
+/// `id` minutes later.
+pub(crate) const START: u64 = 1_704_067_200;
+
+/// When message `id` was sent.
+pub(crate) fn sent(id: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(START + 60 * id)
+}
+
+/// The local date of the exporting machine for `unixtime`, in UTC.
+pub(crate) fn local_date(unixtime: u64) -> String {
+    DateTime::from_timestamp(unixtime as i64, 0)
+        .expect("timestamp in range")
+        .format("%Y-%m-%dT%H:%M:%S")
+        .to_string()
+}
+
+/// A regular message with plain `text` by `from`, whose sender id is
+/// `user` and the lowercased name.
+pub(crate) fn message(id: u64, from: &str, text: &str) -> Value {
+    let unixtime = START + 60 * id;
+    let entities = if text.is_empty() {
+        json!([])
+    } else {
+        json!([{ "type": "plain", "text": text }])
+    };
+    json!({
+        "id": id,
+        "type": "message",
+        "date": local_date(unixtime),
+        "date_unixtime": unixtime.to_string(),
+        "from": from,
+        "from_id": format!("user{}", from.to_lowercase()),
+        "text": text,
+        "text_entities": entities,
+    })
+}
+
+/// A service message by `actor` doing `action`, with the fields of the
+/// action, such as `members`, in `fields`.
+pub(crate) fn service(id: u64, actor: &str, action: &str, fields: Value) -> Value {
+    let unixtime = START + 60 * id;
+    let mut message = json!({
+        "id": id,
+        "type": "service",
+        "date": local_date(unixtime),
+        "date_unixtime": unixtime.to_string(),
+        "actor": actor,
+        "actor_id": format!("user{}", actor.to_lowercase()),
+        "action": action,
+        "text": "",
+        "text_entities": [],
+    });
+    if let (Value::Object(message), Value::Object(fields)) = (&mut message, fields) {
+        message.extend(fields);
+    }
+    message
+}
+
+/// Parse messages built by [`message`] and [`service`].
+pub(crate) fn parse(messages: impl IntoIterator<Item = Value>) -> Vec<RawMessage> {
+    messages
+        .into_iter()
+        .map(|message| serde_json::from_value(message).expect("valid message"))
+        .collect()
+}
+
+/// The statistics of `messages` with `settings`.
+pub(crate) fn analyze(
+    settings: StatsSettings,
+    messages: impl IntoIterator<Item = Value>,
+) -> ChatStats {
+    let mut stats = ChatStats::new(settings);
+    stats.analyze(parse(messages));
+    stats
+}
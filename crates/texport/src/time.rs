@@ -0,0 +1,248 @@
+use std::{str::FromStr, time::SystemTime};
+
+use chrono::{DateTime, Days, FixedOffset, Local, NaiveDate, NaiveTime, SecondsFormat, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// The time zone used for time-of-day statistics and displayed dates.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeZone {
+    /// The time zone of the machine running the analysis.
+    #[default]
+    Local,
+    Utc,
+    /// A fixed offset from UTC, in seconds east of Greenwich.
+    Offset(i32),
+}
+
+impl TimeZone {
+    /// Convert `time` to a date and time in this zone.
+    pub fn convert(self, time: SystemTime) -> DateTime<FixedOffset> {
+        match self {
+            TimeZone::Local => DateTime::<Local>::from(time).fixed_offset(),
+            TimeZone::Utc => DateTime::<Utc>::from(time).fixed_offset(),
+            // Out-of-range offsets can only be built by hand; treat them as UTC.
+            TimeZone::Offset(seconds) => match FixedOffset::east_opt(seconds) {
+                Some(offset) => DateTime::<Utc>::from(time).with_timezone(&offset),
+                None => DateTime::<Utc>::from(time).fixed_offset(),
+            },
+        }
+    }
+
+    /// Midnight at the start of `date` in this zone, or `None` if the
+    /// clocks skip it.
+    pub fn start_of_day(self, date: NaiveDate) -> Option<DateTime<Utc>> {
+        let midnight = date.and_time(NaiveTime::MIN);
+        match self {
+            TimeZone::Local => midnight
+                .and_local_timezone(Local)
+                .earliest()
+                .map(|d| d.to_utc()),
+            TimeZone::Utc => Some(midnight.and_utc()),
+            // Out-of-range offsets are treated as UTC, as in `convert`.
+            TimeZone::Offset(seconds) => match FixedOffset::east_opt(seconds) {
+                Some(offset) => midnight
+                    .and_local_timezone(offset)
+                    .single()
+                    .map(|d| d.to_utc()),
+                None => Some(midnight.and_utc()),
+            },
+        }
+    }
+
+    /// The moments a date range from `since` until `until` starts and
+    /// ends, for [`StatsSettings::since`] and [`StatsSettings::until`].
+    /// Days are taken in this zone, and a range until a day includes it.
+    /// Either bound may be left out, but a range with both must not be
+    /// empty.
+    ///
+    /// [`StatsSettings::since`]: crate::StatsSettings::since
+    /// [`StatsSettings::until`]: crate::StatsSettings::until
+    pub fn date_range(
+        self,
+        since: Option<DateBound>,
+        until: Option<DateBound>,
+    ) -> Result<DateRange, DateRangeError> {
+        let start = |date: NaiveDate| {
+            self.start_of_day(date)
+                .ok_or(DateRangeError::NoMidnight(date))
+        };
+        let since = since
+            .map(|bound| match bound {
+                DateBound::Day(date) => start(date),
+                DateBound::Moment(moment) => Ok(moment.to_utc()),
+            })
+            .transpose()?;
+        let until = until
+            .map(|bound| match bound {
+                DateBound::Day(date) => start(date + Days::new(1)),
+                DateBound::Moment(moment) => Ok(moment.to_utc()),
+            })
+            .transpose()?;
+        match (since, until) {
+            (Some(since), Some(until)) if since >= until => {
+                Err(DateRangeError::Empty { since, until })
+            }
+            _ => Ok(DateRange { since, until }),
+        }
+    }
+}
+
+impl FromStr for TimeZone {
+    type Err = String;
+
+    /// Parse `local`, `utc`, or an offset such as `+03:00` or `-0530`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "local" => Ok(TimeZone::Local),
+            "utc" | "z" => Ok(TimeZone::Utc),
+            offset => offset
+                .parse::<FixedOffset>()
+                .map(|o| TimeZone::Offset(o.local_minus_utc()))
+                .map_err(|_| format!("invalid time zone `{s}`, expected local, utc or +HH:MM")),
+        }
+    }
+}
+
+/// One end of a date range: a whole day, as in `2024-01-31`, or a moment
+/// in RFC 3339, as in `2024-01-31T18:00:00+03:00`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DateBound {
+    Day(NaiveDate),
+    Moment(DateTime<FixedOffset>),
+}
+
+impl FromStr for DateBound {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            return Ok(DateBound::Day(date));
+        }
+        DateTime::parse_from_rfc3339(s)
+            .map(DateBound::Moment)
+            .map_err(|_| {
+                format!("invalid date `{s}`, expected YYYY-MM-DD or an RFC 3339 timestamp")
+            })
+    }
+}
+
+/// The moments a date range starts and ends, made by
+/// [`TimeZone::date_range`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DateRange {
+    /// The first moment in the range.
+    pub since: Option<DateTime<Utc>>,
+    /// The first moment after the range.
+    pub until: Option<DateTime<Utc>>,
+}
+
+/// A date range that [`TimeZone::date_range`] can't build.
+#[derive(thiserror::Error, Debug)]
+pub enum DateRangeError {
+    #[error("{0} has no midnight in the time zone")]
+    NoMidnight(NaiveDate),
+    #[error("the date range is empty: it starts at {since} but ends at {until}")]
+    Empty {
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    },
+}
+
+/// The earlier of two optional timestamps, ignoring missing ones.
+pub(crate) fn earliest(a: Option<SystemTime>, b: Option<SystemTime>) -> Option<SystemTime> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        _ => a.or(b),
+    }
+}
+
+/// Format a timestamp as an RFC 3339 / ISO 8601 string in UTC,
+/// e.g. `2024-05-01T12:34:56Z`.
+pub(crate) fn to_rfc3339(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+/// Serialize a timestamp as an ISO 8601 string, for `#[serde(serialize_with)]`.
+pub(crate) fn serialize<S: Serializer>(
+    time: &SystemTime,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&to_rfc3339(*time))
+}
+
+/// Serialize an optional timestamp as an ISO 8601 string or `null`.
+pub(crate) fn serialize_opt<S: Serializer>(
+    time: &Option<SystemTime>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match time {
+        Some(time) => serialize(time, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Deserialize an optional timestamp written by [`serialize_opt`], for
+/// `#[serde(deserialize_with)]`.
+pub(crate) fn deserialize_opt<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<SystemTime>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|time| {
+            DateTime::parse_from_rfc3339(&time)
+                .map(SystemTime::from)
+                .map_err(serde::de::Error::custom)
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bound(s: &str) -> Option<DateBound> {
+        Some(s.parse().unwrap())
+    }
+
+    fn utc(s: &str) -> Option<DateTime<Utc>> {
+        Some(DateTime::parse_from_rfc3339(s).unwrap().to_utc())
+    }
+
+    #[test]
+    fn until_day_includes_the_whole_day() {
+        let range = TimeZone::Utc.date_range(bound("2024-01-01"), bound("2024-01-31"));
+        let expected = DateRange {
+            since: utc("2024-01-01T00:00:00Z"),
+            until: utc("2024-02-01T00:00:00Z"),
+        };
+        assert_eq!(range.unwrap(), expected);
+
+        let one_day = TimeZone::Utc.date_range(bound("2024-01-01"), bound("2024-01-01"));
+        assert_eq!(one_day.unwrap().until, utc("2024-01-02T00:00:00Z"));
+    }
+
+    #[test]
+    fn days_start_at_midnight_in_the_time_zone() {
+        let range = TimeZone::Offset(3 * 3600).date_range(bound("2024-01-01"), None);
+        assert_eq!(range.unwrap().since, utc("2023-12-31T21:00:00Z"));
+    }
+
+    #[test]
+    fn moments_are_taken_as_they_are() {
+        let range = TimeZone::Utc.date_range(
+            bound("2024-01-31T18:00:00+03:00"),
+            bound("2024-01-31T18:30:00+03:00"),
+        );
+        let expected = DateRange {
+            since: utc("2024-01-31T15:00:00Z"),
+            until: utc("2024-01-31T15:30:00Z"),
+        };
+        assert_eq!(range.unwrap(), expected);
+    }
+
+    #[test]
+    fn empty_range_is_an_error() {
+        let range = TimeZone::Utc.date_range(bound("2024-01-02"), bound("2024-01-01"));
+        assert!(matches!(range, Err(DateRangeError::Empty { .. })));
+    }
+}
@@ -0,0 +1,225 @@
+use std::str::FromStr;
+
+use serde::Serialize;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// How message text is split into words for word statistics.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tokenizer {
+    /// Split on whitespace, then wherever a character can't be part of a
+    /// word, so that `hello,` and `«hello»` both count as `hello`. Links,
+    /// `@mentions`, `#hashtags`, numbers such as `3.14` and words joined by
+    /// apostrophes or hyphens, such as `don't`, are kept whole. Scripts
+    /// written without spaces, such as Chinese or Thai, are only split at
+    /// their punctuation.
+    #[default]
+    Unicode,
+    /// Split on whitespace only, keeping punctuation attached to words.
+    /// This is how word statistics were computed before the Unicode tokenizer.
+    Whitespace,
+}
+
+impl Tokenizer {
+    /// Split `text` into lowercase words.
+    pub fn tokens(self, text: &str) -> Vec<String> {
+        let lowercase = text.to_lowercase();
+        match self {
+            Tokenizer::Whitespace => lowercase.split_whitespace().map(str::to_string).collect(),
+            Tokenizer::Unicode => {
+                let mut tokens = Vec::new();
+                for chunk in lowercase.split_whitespace() {
+                    split_chunk(chunk, &mut tokens);
+                }
+                tokens
+            }
+        }
+    }
+}
+
+/// Split one whitespace-delimited chunk into words.
+///
+/// Links are kept whole (minus trailing punctuation), a leading `@` or `#`
+/// is preserved so mentions and hashtags stay recognizable, and everything
+/// else is split on characters that can't be part of a word.
+fn split_chunk(chunk: &str, tokens: &mut Vec<String>) {
+    let trimmed = chunk.trim_matches(|c: char| !c.is_alphanumeric() && c != '@' && c != '#');
+    if trimmed.contains("://") || trimmed.starts_with("www.") {
+        tokens.push(
+            trimmed
+                .trim_end_matches(|c: char| !c.is_alphanumeric() && c != '/')
+                .into(),
+        );
+        return;
+    }
+
+    let mut current = String::new();
+    for c in trimmed.chars() {
+        let prefix = current.is_empty() && (c == '@' || c == '#');
+        let separator = matches!(c, '.' | ',') && current.ends_with(|p: char| p.is_numeric());
+        if c.is_alphanumeric() || is_joiner(c) || prefix || separator {
+            current.push(c);
+        } else {
+            push_word(&mut current, tokens);
+        }
+    }
+    push_word(&mut current, tokens);
+}
+
+/// Characters allowed inside a word but not at its edges: `don't`, `what's-up`.
+fn is_joiner(c: char) -> bool {
+    matches!(c, '\'' | '’' | '-' | '_' | '\u{200d}')
+}
+
+fn push_word(current: &mut String, tokens: &mut Vec<String>) {
+    let word = current.trim_end_matches(|c| is_joiner(c) || c == '.' || c == ',');
+    let word = word.trim_start_matches(is_joiner);
+    if word.chars().any(char::is_alphanumeric) {
+        tokens.push(word.to_string());
+    }
+    current.clear();
+}
+
+/// What a "character" is when measuring messages and words.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CharCount {
+    /// Unicode scalar values. Fast, but an emoji made of several code
+    /// points, such as 👨‍👩‍👧 or a flag, counts as several characters, as
+    /// does a letter with combining accents.
+    #[default]
+    Scalars,
+    /// Grapheme clusters: what a reader sees as one character. Slower.
+    Graphemes,
+}
+
+impl CharCount {
+    /// Length of `text` in characters.
+    pub fn count(self, text: &str) -> usize {
+        match self {
+            CharCount::Scalars => text.chars().count(),
+            CharCount::Graphemes => text.graphemes(true).count(),
+        }
+    }
+
+    /// The first `max` characters of `text`.
+    pub fn truncate(self, text: &str, max: usize) -> &str {
+        let end = match self {
+            CharCount::Scalars => text.char_indices().nth(max).map(|(i, _)| i),
+            CharCount::Graphemes => text.grapheme_indices(true).nth(max).map(|(i, _)| i),
+        };
+        &text[..end.unwrap_or(text.len())]
+    }
+}
+
+impl FromStr for CharCount {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "scalars" | "chars" => Ok(CharCount::Scalars),
+            "graphemes" => Ok(CharCount::Graphemes),
+            _ => Err(format!("unknown character count: {s}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The words of `text` by both tokenizers: (unicode, whitespace).
+    fn both(text: &str) -> (Vec<String>, Vec<String>) {
+        (
+            Tokenizer::Unicode.tokens(text),
+            Tokenizer::Whitespace.tokens(text),
+        )
+    }
+
+    #[test]
+    fn punctuation_is_stripped_from_words() {
+        let (unicode, whitespace) = both("Hello, world! (really?) Yes... «Привет» — „gut“");
+        assert_eq!(
+            unicode,
+            ["hello", "world", "really", "yes", "привет", "gut"]
+        );
+        assert_eq!(
+            whitespace,
+            [
+                "hello,",
+                "world!",
+                "(really?)",
+                "yes...",
+                "«привет»",
+                "—",
+                "„gut“"
+            ]
+        );
+    }
+
+    #[test]
+    fn words_are_split_at_inner_punctuation() {
+        let (unicode, whitespace) = both("cats/dogs a+b end.start");
+        assert_eq!(unicode, ["cats", "dogs", "a", "b", "end", "start"]);
+        assert_eq!(whitespace, ["cats/dogs", "a+b", "end.start"]);
+    }
+
+    #[test]
+    fn apostrophes_and_hyphens_join_words() {
+        let (unicode, _) = both("Don't stop, it’s well-known 'quoted' -dash-");
+        assert_eq!(
+            unicode,
+            ["don't", "stop", "it’s", "well-known", "quoted", "dash"]
+        );
+    }
+
+    #[test]
+    fn numbers_keep_their_decimal_separators() {
+        let (unicode, whitespace) = both("pi is 3.14, e is 2,71. Costs 1.000.000!");
+        assert_eq!(
+            unicode,
+            ["pi", "is", "3.14", "e", "is", "2,71", "costs", "1.000.000"]
+        );
+        assert_eq!(whitespace[2], "3.14,");
+    }
+
+    #[test]
+    fn links_mentions_and_hashtags_are_kept_whole() {
+        let (unicode, whitespace) =
+            both("See https://example.com/a?b=1. @Alice_Bot #Rust, (www.rust-lang.org)");
+        assert_eq!(
+            unicode,
+            [
+                "see",
+                "https://example.com/a?b=1",
+                "@alice_bot",
+                "#rust",
+                "www.rust-lang.org"
+            ]
+        );
+        assert_eq!(whitespace[1], "https://example.com/a?b=1.");
+        assert_eq!(whitespace[3], "#rust,");
+    }
+
+    #[test]
+    fn text_without_letters_or_digits_has_no_words() {
+        assert!(Tokenizer::Unicode.tokens("... — !!! 🙂").is_empty());
+        assert_eq!(Tokenizer::Whitespace.tokens("... 🙂"), ["...", "🙂"]);
+    }
+
+    #[test]
+    fn unspaced_scripts_are_split_at_punctuation_only() {
+        assert_eq!(
+            Tokenizer::Unicode.tokens("你好世界。再见"),
+            ["你好世界", "再见"]
+        );
+    }
+
+    #[test]
+    fn words_are_lowercased_beyond_ascii() {
+        assert_eq!(
+            Tokenizer::Unicode.tokens("ÉCOLE Ёлка ΣΟΦΙΑ"),
+            ["école", "ёлка", "σοφια"]
+        );
+    }
+}
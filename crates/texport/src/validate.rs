@@ -0,0 +1,267 @@
+use std::{fmt, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{
+    Chat, RawMessage, Reaction,
+    messages::{parse_local_date, parse_unixtime},
+};
+
+/// How much earlier than the message before it a message may be dated, as
+/// merged exports and Telegram's servers don't always agree to the second.
+const DATE_TOLERANCE: Duration = Duration::from_secs(60);
+
+/// How far the local date of a message may be from its Unix timestamp:
+/// every time zone is within a day of UTC.
+const LOCAL_DATE_TOLERANCE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Something in a chat export that the statistics assume doesn't happen,
+/// found by [`Chat::validate`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Anomaly {
+    /// The id of the message it was found in.
+    pub id: u64,
+    pub kind: AnomalyKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnomalyKind {
+    /// The id is not greater than the one of the message before it.
+    IdNotIncreasing { previous: u64 },
+    /// The message is dated more than a minute before the message before
+    /// it, by this many seconds.
+    DateDecreasing { previous: u64, seconds: u64 },
+    /// The message replies to a later one.
+    ReplyToLater { reply_to: u64 },
+    /// A reaction lists more recent reactors than it counts.
+    ReactionCount {
+        reaction: String,
+        count: usize,
+        recent: usize,
+    },
+    /// The local date of the message is more than a day from its Unix
+    /// timestamp.
+    DateMismatch { date: String, unixtime: String },
+    /// The texts of `text_entities` don't add up to the text.
+    EntitiesMismatch,
+}
+
+impl Chat {
+    /// Check the messages for what the statistics assume of an export:
+    /// ids increase, dates don't go back by more than a minute, replies
+    /// are to earlier messages, reactions count at least their recent
+    /// reactors, local dates agree with Unix timestamps and the texts of
+    /// `text_entities` add up to the text. Nothing is found in an export
+    /// as Telegram Desktop writes it.
+    pub fn validate(&self) -> Vec<Anomaly> {
+        let mut anomalies = Vec::new();
+        let mut previous: Option<&RawMessage> = None;
+        // The latest date so far, with the id of its message.
+        let mut latest = None;
+        for message in &self.messages {
+            let id = message.id();
+            let mut found = |kind| anomalies.push(Anomaly { id, kind });
+
+            if let Some(previous) = previous.filter(|previous| previous.id() >= id) {
+                found(AnomalyKind::IdNotIncreasing {
+                    previous: previous.id(),
+                });
+            }
+            previous = Some(message);
+
+            if let Some(date) = message.date() {
+                match latest {
+                    Some((latest_date, latest_id)) if date + DATE_TOLERANCE < latest_date => {
+                        let by = latest_date.duration_since(date).unwrap_or_default();
+                        found(AnomalyKind::DateDecreasing {
+                            previous: latest_id,
+                            seconds: by.as_secs(),
+                        });
+                    }
+                    Some((latest_date, _)) if date <= latest_date => {}
+                    _ => latest = Some((date, id)),
+                }
+            }
+
+            let (date, unixtime, text, text_entities) = match message {
+                RawMessage::Message {
+                    date,
+                    date_unixtime,
+                    text,
+                    text_entities,
+                    reply_to_message_id,
+                    reactions,
+                    ..
+                } => {
+                    if let Some(reply_to) = reply_to_message_id.filter(|&reply_to| reply_to > id) {
+                        found(AnomalyKind::ReplyToLater { reply_to });
+                    }
+                    for reaction in reactions {
+                        let (label, count) = match reaction {
+                            Reaction::Emoji { emoji, count, .. } => (emoji.clone(), *count),
+                            Reaction::CustomEmoji {
+                                document_id, count, ..
+                            } => (format!("custom_emoji:{document_id}"), *count),
+                        };
+                        if reaction.recent().len() > count {
+                            found(AnomalyKind::ReactionCount {
+                                reaction: label,
+                                count,
+                                recent: reaction.recent().len(),
+                            });
+                        }
+                    }
+                    (date, date_unixtime, text, text_entities)
+                }
+                RawMessage::Service {
+                    date,
+                    date_unixtime,
+                    text,
+                    text_entities,
+                    ..
+                } => (date, date_unixtime, text, text_entities),
+            };
+
+            if let (Some(local), Some(time)) = (parse_local_date(date), parse_unixtime(unixtime)) {
+                let offset = (local.and_utc() - DateTime::<Utc>::from(time)).abs();
+                if offset
+                    .to_std()
+                    .is_ok_and(|offset| offset > LOCAL_DATE_TOLERANCE)
+                {
+                    found(AnomalyKind::DateMismatch {
+                        date: date.clone(),
+                        unixtime: unixtime.clone(),
+                    });
+                }
+            }
+
+            let entities: String = text_entities.iter().map(|entity| &*entity.text).collect();
+            if entities != text.flatten() {
+                found(AnomalyKind::EntitiesMismatch);
+            }
+        }
+        anomalies
+    }
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "message {}: ", self.id)?;
+        match &self.kind {
+            AnomalyKind::IdNotIncreasing { previous } => {
+                write!(f, "id isn't greater than the previous one, {previous}")
+            }
+            AnomalyKind::DateDecreasing { previous, seconds } => {
+                write!(f, "sent {seconds} s before message {previous}")
+            }
+            AnomalyKind::ReplyToLater { reply_to } => {
+                write!(f, "replies to the later message {reply_to}")
+            }
+            AnomalyKind::ReactionCount {
+                reaction,
+                count,
+                recent,
+            } => write!(
+                f,
+                "reaction {reaction} counts {count} but lists {recent} recent reactors"
+            ),
+            AnomalyKind::DateMismatch { date, unixtime } => write!(
+                f,
+                "date {date} is more than a day from Unix timestamp {unixtime}"
+            ),
+            AnomalyKind::EntitiesMismatch => f.write_str("text entities don't add up to the text"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        ChatType,
+        testing::{START, message, parse, service},
+    };
+
+    fn chat(messages: impl IntoIterator<Item = serde_json::Value>) -> Chat {
+        Chat {
+            name: "Checked".to_string(),
+            chat_type: ChatType::PrivateGroup,
+            id: 1,
+            messages: parse(messages),
+        }
+    }
+
+    #[test]
+    fn exports_as_telegram_writes_them_have_no_anomalies() {
+        let exported = Chat::from_slice(include_bytes!("../tests/fixtures/service.json")).unwrap();
+        assert_eq!(exported.validate(), []);
+
+        let mut reply = message(3, "Bob", "");
+        reply["reply_to_message_id"] = 1.into();
+        // Up to a minute back, as merged exports may be.
+        let mut late = message(5, "Bob", "ok");
+        late["date_unixtime"] = (START + 60 * 4 - 60).to_string().into();
+        let built = chat([
+            service(1, "Alice", "create_group", json!({ "title": "Checked" })),
+            message(2, "Alice", "hi"),
+            reply,
+            message(4, "Alice", "how are you?"),
+            late,
+        ]);
+        assert_eq!(built.validate(), []);
+    }
+
+    #[test]
+    fn each_anomaly_is_found_at_its_message() {
+        let mut before = message(4, "Alice", "earlier");
+        before["date_unixtime"] = (START + 60).to_string().into();
+        let mut reply = message(5, "Bob", "re");
+        reply["reply_to_message_id"] = 6.into();
+        let mut reacted = message(6, "Alice", "look");
+        reacted["reactions"] = json!([{
+            "type": "emoji",
+            "count": 1,
+            "emoji": "👍",
+            "recent": [
+                { "from": "Bob", "from_id": "userbob", "date": "2024-01-01T00:07:00" },
+                { "from": "Carol", "from_id": "usercarol", "date": "2024-01-01T00:07:00" },
+            ],
+        }]);
+        let mut misdated = message(7, "Alice", "when");
+        misdated["date"] = "2024-01-03T00:07:00".into();
+        let mut mismatched = message(8, "Alice", "hello");
+        mismatched["text_entities"] = json!([{ "type": "plain", "text": "hell" }]);
+        let chat = chat([
+            message(1, "Alice", "one"),
+            message(3, "Alice", "three"),
+            message(2, "Alice", "two"),
+            before,
+            reply,
+            reacted,
+            misdated,
+            mismatched,
+        ]);
+
+        let anomalies = chat.validate();
+        let described: Vec<_> = anomalies.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            described,
+            [
+                "message 2: id isn't greater than the previous one, 3",
+                "message 4: sent 120 s before message 3",
+                "message 5: replies to the later message 6",
+                "message 6: reaction 👍 counts 1 but lists 2 recent reactors",
+                "message 7: date 2024-01-03T00:07:00 is more than a day from Unix timestamp 1704067620",
+                "message 8: text entities don't add up to the text",
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(&anomalies[1]).unwrap(),
+            json!({ "id": 4, "kind": { "type": "date_decreasing", "previous": 3, "seconds": 120 } })
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    mem,
+    path::Path,
+    sync::mpsc::{self, RecvTimeoutError},
+    time::Duration,
+};
+
+use log::warn;
+use notify::{EventKind, RecursiveMode, Watcher};
+
+use crate::storage::{ChatFile, RESULT_FILE, Storage, try_load_chat};
+
+/// How long a `result.json` must be left alone before it is read, so that
+/// one still being written isn't parsed half-way.
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// A change under the export root, reported by [`Storage::watch`].
+#[derive(Debug)]
+pub enum StorageEvent {
+    /// A chat that wasn't there before.
+    ChatAdded(ChatFile),
+    /// A new or changed `result.json` of a chat that was already there.
+    ChatUpdated(ChatFile),
+}
+
+impl Storage {
+    /// Watch the export root and call `callback` whenever a `result.json`
+    /// appears or changes, once nothing under the root has changed for a
+    /// couple of seconds. Files that can't be read then are skipped with a
+    /// warning. Blocks until the watcher fails.
+    pub fn watch(&self, mut callback: impl FnMut(StorageEvent)) -> notify::Result<()> {
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher.watch(&self.root, RecursiveMode::Recursive)?;
+
+        let mut known: HashSet<i64> = self.chats.keys().copied().collect();
+        let mut changed = BTreeSet::new();
+        loop {
+            let event = if changed.is_empty() {
+                receiver.recv().map_err(|_| RecvTimeoutError::Disconnected)
+            } else {
+                receiver.recv_timeout(DEBOUNCE)
+            };
+            match event {
+                Ok(event) => {
+                    let event = event?;
+                    if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                        changed.extend(
+                            event
+                                .paths
+                                .into_iter()
+                                .filter(|path| path.file_name() == Some(RESULT_FILE.as_ref())),
+                        );
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    for manifest in mem::take(&mut changed) {
+                        let Some(dir) = manifest.parent().filter(|_| manifest.is_file()) else {
+                            continue;
+                        };
+                        match try_load_chat(Path::to_path_buf(dir), &mut |_| {}) {
+                            Ok((id, chat)) if known.insert(id) => {
+                                callback(StorageEvent::ChatAdded(chat));
+                            }
+                            Ok((_, chat)) => callback(StorageEvent::ChatUpdated(chat)),
+                            Err(e) => warn!("skipping: {e}"),
+                        }
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+        }
+    }
+}
@@ -0,0 +1,144 @@
+mod common;
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+use texport::{
+    Chat, ChatStats, JsonDetail, Language, ScanOptions, StatsSettings, Storage, TimeZone,
+};
+
+use crate::common::fixture;
+
+/// A directory holding the fixtures `names`, each as the `result.json` of
+/// a chat export, and no cache yet.
+fn exports(test: &str, names: &[&str]) -> PathBuf {
+    let dir = env::temp_dir().join(format!("texport-cache-{test}-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    for name in names {
+        let chat = dir.join(name.trim_end_matches(".json"));
+        fs::create_dir_all(&chat).unwrap();
+        fs::write(chat.join("result.json"), fixture(name)).unwrap();
+    }
+    dir
+}
+
+/// The files cached next to the export of fixture `name` in `dir`.
+fn cached(dir: &Path, name: &str) -> usize {
+    let cache = dir
+        .join(name.trim_end_matches(".json"))
+        .join(".tg-export-cache");
+    fs::read_dir(cache).map_or(0, Iterator::count)
+}
+
+fn settings() -> StatsSettings {
+    StatsSettings {
+        max_words: 5,
+        max_participants: 10,
+        max_phrases: 3,
+        max_distinctive_words: 2,
+        json_detail: JsonDetail::Full,
+        time_zone: TimeZone::Utc,
+        track_words: vec!["dune".into()],
+        trend_words: vec!["dune".into()],
+        milestones: vec![2, 5],
+        group_by_topic: true,
+        ..Default::default()
+    }
+}
+
+/// Everything shown of `stats`: the text, the full report and the legacy
+/// JSON.
+fn shown(stats: &ChatStats) -> (String, String, String) {
+    let report = serde_json::to_string_pretty(&stats.to_report()).unwrap();
+    let legacy = serde_json::to_string_pretty(stats).unwrap();
+    (stats.to_string(), report, legacy)
+}
+
+#[test]
+fn cached_statistics_are_those_of_the_chat() {
+    let names = ["service.json", "ties.json", "charts.json"];
+    let dir = exports("round-trip", &names);
+    let storage = Storage::from_path_with(&dir, ScanOptions::default()).unwrap();
+
+    let mut expected = ChatStats::new(settings());
+    let mut computed = ChatStats::new(settings());
+    let mut read = ChatStats::new(settings());
+    for file in storage.filter(|_| true) {
+        let chat: Chat = file.load().unwrap();
+        let mut alone = ChatStats::new(settings());
+        alone.analyze_labeled(&chat.name, &chat.messages);
+        expected.analyze_labeled(&chat.name, &chat.messages);
+
+        let first = ChatStats::load_cached(file, settings()).unwrap();
+        let second = ChatStats::load_cached(file, settings()).unwrap();
+        assert_eq!(shown(&first), shown(&alone), "{}", chat.name);
+        assert_eq!(shown(&second), shown(&alone), "{}", chat.name);
+        computed.merge(first);
+        read.merge(second);
+    }
+    for name in names {
+        assert_eq!(cached(&dir, name), 1, "{name}");
+    }
+    // Merged as well, e.g. for streaks across chats.
+    assert_eq!(shown(&computed), shown(&expected));
+    assert_eq!(shown(&read), shown(&expected));
+    assert!(expected.to_string().contains("Dune club"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn settings_that_only_change_the_display_share_an_entry() {
+    let dir = exports("display", &["service.json"]);
+    let storage = Storage::from_path_with(&dir, ScanOptions::default()).unwrap();
+    let file = storage.chat(500).unwrap();
+
+    let first = ChatStats::load_cached(file, settings()).unwrap();
+    let shown_differently = StatsSettings {
+        max_words: 1,
+        max_participants: 2,
+        chart_width: 0,
+        language: Language::Russian,
+        json_detail: JsonDetail::Summary,
+        max_phrases: 1,
+        max_distinctive_words: 0,
+        min_lurker_reactions: 5,
+        ..settings()
+    };
+    let second = ChatStats::load_cached(file, shown_differently.clone()).unwrap();
+    assert_eq!(cached(&dir, "service.json"), 1);
+    // Shown with the settings asked for, not those cached with.
+    assert_eq!(second.settings.max_words, 1);
+    let mut expected = ChatStats::new(shown_differently);
+    let chat = file.load().unwrap();
+    expected.analyze_labeled(&chat.name, &chat.messages);
+    assert_eq!(shown(&second), shown(&expected));
+    assert_ne!(second.to_string(), first.to_string());
+
+    // Each of these changes the statistics.
+    let changes = [
+        StatsSettings {
+            time_zone: TimeZone::Offset(3 * 3600),
+            ..settings()
+        },
+        StatsSettings {
+            time_zone: TimeZone::Local,
+            ..settings()
+        },
+        StatsSettings {
+            max_phrases: 0,
+            ..settings()
+        },
+        StatsSettings {
+            exclude_bots: true,
+            ..settings()
+        },
+    ];
+    for (count, settings) in (2..).zip(changes) {
+        ChatStats::load_cached(file, settings).unwrap();
+        assert_eq!(cached(&dir, "service.json"), count);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,207 @@
+use std::{
+    env,
+    fs::{self, File},
+    path::{Path, PathBuf},
+    process::{self, Command, Output},
+    sync::Once,
+};
+
+fn exports() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/exports")
+}
+
+/// Run the `full` example on `tests/fixtures/exports` with `args`.
+fn full(args: &[&str]) -> Output {
+    full_in(&exports(), args)
+}
+
+/// Run the `full` example on the exports in `input` with `args`.
+fn full_in(input: &Path, args: &[&str]) -> Output {
+    static BUILD: Once = Once::new();
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    // Examples aren't built for `cargo test --test cli`, and may be stale.
+    BUILD.call_once(|| {
+        let status = Command::new(env!("CARGO"))
+            .args(["build", "--quiet", "--example", "full", "--manifest-path"])
+            .arg(manifest.join("Cargo.toml"))
+            .status()
+            .unwrap();
+        assert!(status.success(), "can't build the full example");
+    });
+    let target = manifest.join("../../target");
+    let target = env::var_os("CARGO_TARGET_DIR").map_or(target, Into::into);
+    Command::new(target.join("debug/examples/full"))
+        .arg("--input")
+        .arg(input)
+        .args(["--output", "json"])
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// The messages counted by a successful run of `full` with `args`.
+fn messages(args: &[&str]) -> u64 {
+    let output = full(args);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    report["messages"].as_u64().unwrap()
+}
+
+/// The ids and message counts of the participants reported by a successful
+/// run of `full` on `input` with `args`.
+fn participants(input: &Path, args: &[&str]) -> Vec<(String, u64)> {
+    let output = full_in(input, args);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let participants = report["participants"].as_array().unwrap().iter();
+    let mut participants: Vec<_> = participants
+        .map(|p| {
+            (
+                p["id"].as_str().unwrap().to_string(),
+                p["messages"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    participants.sort();
+    participants
+}
+
+/// The error of a failed run of `full` with `args`.
+fn failure(args: &[&str]) -> String {
+    let output = full(args);
+    assert!(!output.status.success());
+    String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+fn every_chat_is_analyzed_without_a_selector() {
+    // The two exports of Family share two of their messages.
+    assert_eq!(messages(&[]), 6 + 2 + 1 + 1);
+}
+
+#[test]
+fn chats_are_selected_by_id() {
+    assert_eq!(messages(&["--chat", "300"]), 1);
+    assert_eq!(messages(&["--chat", "100"]), 6);
+    let error = failure(&["--chat", "999"]);
+    assert!(error.contains("999"), "{error}");
+}
+
+#[test]
+fn chats_are_selected_by_name_ignoring_case() {
+    assert_eq!(messages(&["--chat-name", "NEWS"]), 1);
+    // Both Family and Extended FAMILY contain it, but one is named so.
+    assert_eq!(messages(&["--chat-name", "family"]), 6);
+    assert_eq!(messages(&["--chat-name", "extended"]), 1);
+}
+
+#[test]
+fn names_matching_several_chats_or_none_list_them() {
+    let error = failure(&["--chat-name", "fam"]);
+    assert!(error.contains(r#""Family" (100)"#), "{error}");
+    assert!(error.contains(r#""Extended FAMILY" (400)"#), "{error}");
+    assert!(!error.contains("News"), "{error}");
+
+    let error = failure(&["--chat-name", "nobody"]);
+    for chat in ["Family", "Alice", "News", "Extended FAMILY"] {
+        assert!(error.contains(&format!("{chat:?}")), "{error}");
+    }
+}
+
+#[test]
+fn users_are_picked_by_name_ignoring_case_or_by_id() {
+    let exports = exports();
+    // Alice wrote once to herself and three times in Family.
+    assert_eq!(
+        participants(&exports, &["--user", "alice"]),
+        [("user1".into(), 4)]
+    );
+    assert_eq!(
+        participants(&exports, &["--user-id", "user3"]),
+        [("user3".into(), 1)]
+    );
+    assert_eq!(
+        participants(&exports, &["--user", "BOB", "--user-id", "user3"]),
+        [("user2".into(), 4), ("user3".into(), 1)]
+    );
+
+    let error = failure(&["--user", "Nobody"]);
+    assert!(error.contains(r#"no sender is named "Nobody""#), "{error}");
+}
+
+#[test]
+fn cached_runs_look_users_up_without_parsing_chats_again() {
+    let dir = env::temp_dir().join(format!("texport-cli-cache-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    for chat in ["alice", "family_2023", "family_2024"] {
+        fs::create_dir_all(dir.join(chat)).unwrap();
+        fs::copy(
+            exports().join(chat).join("result.json"),
+            dir.join(chat).join("result.json"),
+        )
+        .unwrap();
+    }
+    let args = ["--cache", "--user", "Alice"];
+    let expected = [("user1".to_string(), 4)];
+    assert_eq!(participants(&dir, &args), expected);
+
+    // Give Alice another id in her own chat without changing its size or
+    // modification time: only a run that parses it again would notice.
+    let path = dir.join("alice/result.json");
+    let modified = fs::metadata(&path).unwrap().modified().unwrap();
+    let text = fs::read_to_string(&path).unwrap().replacen(
+        r#""from_id": "user1""#,
+        r#""from_id": "user9""#,
+        1,
+    );
+    fs::write(&path, text).unwrap();
+    File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+
+    assert_eq!(participants(&dir, &args), expected);
+    let output = full_in(&dir, &["--user", "Alice"]);
+    let error = String::from_utf8_lossy(&output.stderr);
+    assert!(error.contains("user1, user9; pick one by id"), "{error}");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn since_and_until_take_dates_in_the_time_zone_or_moments() {
+    // Every message was sent between 10:01 and 10:06 UTC on 2024-01-01.
+    let utc = "--time-zone=utc";
+    assert_eq!(messages(&[utc, "--since", "2024-01-01"]), 10);
+    // A date bound takes in the whole day...
+    assert_eq!(messages(&[utc, "--until", "2024-01-01"]), 10);
+    assert_eq!(messages(&[utc, "--until", "2023-12-31"]), 0);
+    // ...as it is in the time zone, where it ends at 11:00 UTC.
+    assert_eq!(
+        messages(&["--time-zone=-11:00", "--until", "2023-12-31"]),
+        10
+    );
+
+    // A moment is the same in any time zone, and the range is half-open.
+    assert_eq!(messages(&["--since", "2024-01-01T10:03:00Z"]), 4);
+    assert_eq!(messages(&["--until", "2024-01-01T10:03:00Z"]), 6);
+    let two_hours_east = "2024-01-01T12:03:00+02:00";
+    assert_eq!(
+        messages(&["--since", two_hours_east, "--until", "2024-01-01T10:05:00Z"]),
+        2
+    );
+
+    let error = failure(&[utc, "--since", "2024-01-02", "--until", "2024-01-01"]);
+    assert!(error.contains("the date range is empty"), "{error}");
+    let moment = "2024-01-01T10:03:00Z";
+    let error = failure(&["--since", moment, "--until", moment]);
+    assert!(error.contains("the date range is empty"), "{error}");
+    let error = failure(&["--since", "yesterday"]);
+    assert!(
+        error.contains("expected YYYY-MM-DD or an RFC 3339 timestamp"),
+        "{error}"
+    );
+}
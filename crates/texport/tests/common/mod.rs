@@ -0,0 +1,9 @@
+use std::path::Path;
+
+/// The contents of `tests/fixtures/{name}`.
+pub fn fixture(name: &str) -> Vec<u8> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    std::fs::read(&path).unwrap_or_else(|e| panic!("can't read {path:?}: {e}"))
+}
@@ -0,0 +1,146 @@
+mod common;
+
+use std::{
+    env, fs,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use texport::{Chat, ChatStats, Section, StatsSettings, TimeZone, Word};
+
+use crate::common::fixture;
+
+/// Check `actual` against `tests/golden/{name}`, or overwrite that file
+/// with it when `UPDATE_GOLDEN` is set.
+fn assert_golden(name: &str, actual: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name);
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_else(|e| panic!("can't read {path:?}: {e}"));
+    assert!(
+        actual == expected,
+        "the output differs from {name}; rerun with UPDATE_GOLDEN=1 to accept it:\n{actual}"
+    );
+}
+
+/// The text output for fixture `name`, with times in UTC.
+fn display(name: &str, settings: StatsSettings) -> String {
+    let chat = Chat::from_slice(&fixture(name)).unwrap();
+    let mut stats = ChatStats::new(StatsSettings {
+        time_zone: TimeZone::Utc,
+        ..settings
+    });
+    stats.analyze(chat.messages);
+    stats.to_string()
+}
+
+#[test]
+fn charts_are_scaled_to_the_longest_bar() {
+    let settings = StatsSettings {
+        sections: [Section::Charts, Section::Timeline].into_iter().collect(),
+        chart_width: 20,
+        max_participants: 10,
+        ..Default::default()
+    };
+    assert_golden("charts.txt", &display("charts.json", settings));
+}
+
+#[test]
+fn charts_are_left_out_at_zero_width() {
+    let settings = StatsSettings {
+        max_participants: 10,
+        ..Default::default()
+    };
+    let text = display("charts.json", settings);
+    assert!(!text.contains("Messages by participant"), "{text}");
+    assert!(!text.contains("Messages per month"), "{text}");
+}
+
+#[test]
+fn full_output() {
+    let settings = StatsSettings {
+        chart_width: 20,
+        max_participants: 3,
+        max_words: 5,
+        ..Default::default()
+    };
+    assert_golden("full.txt", &display("charts.json", settings));
+}
+
+#[test]
+fn media_only_chat_has_no_empty_values() {
+    let settings = StatsSettings {
+        chart_width: 20,
+        max_participants: 10,
+        ..Default::default()
+    };
+    let text = display("media_only.json", settings);
+    assert!(!text.contains("NaN"), "{text}");
+    for line in text.lines() {
+        assert!(!line.ends_with(": "), "empty value in {line:?}:\n{text}");
+    }
+    assert!(text.contains("Alice"), "{text}");
+}
+
+#[test]
+fn ties_come_out_the_same_on_every_run() {
+    let settings = || StatsSettings {
+        max_participants: 10,
+        max_words: 10,
+        ..Default::default()
+    };
+    let report = || {
+        let chat = Chat::from_slice(&fixture("ties.json")).unwrap();
+        let mut stats = ChatStats::new(settings());
+        stats.analyze(chat.messages);
+        serde_json::to_string(&stats.to_report()).unwrap()
+    };
+    let text = display("ties.json", settings());
+    let json = report();
+    for _ in 0..10 {
+        assert_eq!(display("ties.json", settings()), text);
+        assert_eq!(report(), json);
+    }
+    // Ties are broken by the key.
+    let alice = text.find("1. Alice").unwrap();
+    let bob = text.find("2. Bob").unwrap();
+    let carol = text.find("3. Carol").unwrap();
+    assert!(alice < bob && bob < carol, "{text}");
+    assert!(
+        text.contains("Top words      : apple (2), mango (2), zebra (2)"),
+        "{text}"
+    );
+    assert!(text.contains("Reactions      : 👍×2, 🔥×2"), "{text}");
+}
+
+#[test]
+fn large_word_maps_display_quickly() {
+    let mut stats = ChatStats::new(StatsSettings {
+        max_participants: 10,
+        max_words: 10,
+        ..Default::default()
+    });
+    // 50 participants with 10,000 different words each: 500,000 counts.
+    for user in 0..50 {
+        let participant = stats.participants.entry(format!("user{user}")).or_default();
+        participant.name = format!("User {user}");
+        participant.count = 100;
+        for i in 0..10_000 {
+            let word = Word::new(&format!("word{}", user * 2_000 + i));
+            participant.words.insert(word, i % 97 + 1);
+        }
+    }
+    stats.messages = 5_000;
+
+    let start = Instant::now();
+    let text = stats.to_string();
+    let elapsed = start.elapsed();
+    assert!(text.contains("Top words"), "{text}");
+    // Summing clones of every participant for the combined statistics
+    // took several times as long.
+    assert!(elapsed < Duration::from_secs(1), "took {elapsed:?}");
+}
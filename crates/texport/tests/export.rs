@@ -0,0 +1,99 @@
+mod common;
+
+use texport::{Chat, ExportOptions, TimeZone};
+
+use crate::common::fixture;
+
+/// Fixture `name` written as text with `options`.
+fn export(name: &str, options: ExportOptions) -> String {
+    let chat = Chat::from_slice(&fixture(name)).unwrap();
+    let mut text = Vec::new();
+    chat.write_export_with(&mut text, None, options).unwrap();
+    String::from_utf8(text).unwrap()
+}
+
+fn utc() -> ExportOptions {
+    ExportOptions {
+        time_zone: TimeZone::Utc,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn edits_are_marked_in_old_and_new_exports() {
+    for name in ["edits_2019.json", "edits_2024.json"] {
+        let text = export(name, utc());
+        let edits: Vec<_> = text.lines().filter(|l| l.contains("[edited]")).collect();
+        assert_eq!(
+            edits,
+            [
+                "  ↳ [edited] 2019-05-01 13:02:00",
+                "  ↳ [edited] 2019-05-01 13:03:00"
+            ],
+            "{name}"
+        );
+    }
+}
+
+#[test]
+fn dates_prefer_unixtime_and_fall_back_to_the_string() {
+    let lines = |options| {
+        let text = export("dates.json", options);
+        text.lines()
+            .map(|line| line.split_once(" @").unwrap().0.to_string())
+            .collect::<Vec<_>>()
+    };
+    // Dates without a Unix timestamp are the exporting machine's local
+    // time, so they are kept as they are whatever the time zone.
+    assert_eq!(
+        lines(utc()),
+        [
+            "[2019-05-01 12:01:00]",
+            "[2019-05-01 12:02:03]",
+            "[2024-01-01 12:00:00]",
+            "[yesterday]",
+        ]
+    );
+    let moscow = ExportOptions {
+        time_zone: TimeZone::Offset(3 * 3600),
+        ..Default::default()
+    };
+    assert_eq!(
+        lines(moscow),
+        [
+            "[2019-05-01 12:01:00]",
+            "[2019-05-01 12:02:03]",
+            "[2024-01-01 15:00:00]",
+            "[yesterday]",
+        ]
+    );
+}
+
+#[test]
+fn messages_outside_the_range_are_left_out_unless_undated() {
+    // Only message 3, at noon UTC, has a Unix timestamp; the other three
+    // are written whatever the range.
+    let written = |since: Option<&str>, until: Option<&str>| {
+        let range = TimeZone::Utc
+            .date_range(
+                since.map(|s| s.parse().unwrap()),
+                until.map(|u| u.parse().unwrap()),
+            )
+            .unwrap();
+        let options = ExportOptions {
+            since: range.since,
+            until: range.until,
+            ..utc()
+        };
+        let text = export("dates.json", options);
+        text.lines().filter(|line| line.contains(" @")).count()
+    };
+    assert_eq!(written(None, None), 4);
+    assert_eq!(written(Some("2024-01-01"), Some("2024-01-01")), 4);
+    assert_eq!(written(Some("2024-01-02"), None), 3);
+    assert_eq!(written(None, Some("2023-12-31")), 3);
+    // A moment bounds the range exactly, which ends just before it.
+    assert_eq!(written(Some("2024-01-01T12:00:00Z"), None), 4);
+    assert_eq!(written(None, Some("2024-01-01T12:00:00Z")), 3);
+    assert_eq!(written(None, Some("2024-01-01T12:00:01Z")), 4);
+}
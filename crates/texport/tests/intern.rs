@@ -0,0 +1,119 @@
+//! The memory held by word maps keyed by `String`, as before interning,
+//! against maps keyed by [`Word`], counted by a wrapper around the system
+//! allocator. It counts every allocation of this test binary, so this file
+//! holds a single test. Run with `--nocapture` to see the numbers.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    collections::HashMap,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use texport::Word;
+
+/// The system allocator, keeping count of the bytes and blocks allocated.
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static BLOCKS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        BLOCKS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        BLOCKS.fetch_sub(1, Ordering::Relaxed);
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// The bytes and blocks still allocated by `build` once it returns,
+/// including the words it interned. The bytes are those asked for, so the
+/// allocator's own overhead for each block comes on top.
+fn retained<T>(build: impl FnOnce() -> T) -> (T, usize, usize) {
+    let (bytes, blocks) = (
+        ALLOCATED.load(Ordering::Relaxed),
+        BLOCKS.load(Ordering::Relaxed),
+    );
+    let built = build();
+    let bytes = ALLOCATED.load(Ordering::Relaxed) - bytes;
+    (built, bytes, BLOCKS.load(Ordering::Relaxed) - blocks)
+}
+
+#[test]
+fn word_maps_hold_half_the_memory_of_string_maps_in_few_blocks() {
+    const USERS: usize = 200;
+    const WORDS_PER_USER: usize = 5_000;
+    const VOCABULARY: usize = 20_000;
+
+    // What each user wrote, drawn from a shared vocabulary by a fixed
+    // linear congruential generator.
+    let mut state = 1u64;
+    let mut next = move || {
+        state = state
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1);
+        (state >> 33) as usize % VOCABULARY
+    };
+    let corpus: Vec<Vec<String>> = (0..USERS)
+        .map(|_| {
+            (0..WORDS_PER_USER)
+                .map(|_| format!("word{}", next()))
+                .collect()
+        })
+        .collect();
+
+    let (strings, string_bytes, string_blocks) = retained(|| {
+        let maps = corpus.iter().map(|words| {
+            let mut map = HashMap::<String, usize>::new();
+            words
+                .iter()
+                .for_each(|word| *map.entry(word.clone()).or_default() += 1);
+            map
+        });
+        maps.collect::<Vec<_>>()
+    });
+    let (words, word_bytes, word_blocks) = retained(|| {
+        let maps = corpus.iter().map(|words| {
+            let mut map = HashMap::<Word, usize>::new();
+            words
+                .iter()
+                .for_each(|word| *map.entry(Word::new(word)).or_default() += 1);
+            map
+        });
+        maps.collect::<Vec<_>>()
+    });
+    println!(
+        "word maps of {USERS} users: {} KiB in {string_blocks} blocks keyed by String, \
+         {} KiB in {word_blocks} blocks keyed by Word ({}% of the bytes)",
+        string_bytes / 1024,
+        word_bytes / 1024,
+        word_bytes * 100 / string_bytes,
+    );
+
+    // The same counts either way, for under half the bytes, and a block per
+    // distinct word rather than per word of each user.
+    for (strings, words) in strings.iter().zip(&words) {
+        assert_eq!(strings.len(), words.len());
+        assert!(
+            words
+                .iter()
+                .all(|(word, count)| strings[word.as_str()] == *count)
+        );
+    }
+    assert!(
+        word_bytes * 2 < string_bytes,
+        "{word_bytes} of {string_bytes} bytes"
+    );
+    assert!(
+        word_blocks * 10 < string_blocks,
+        "{word_blocks} of {string_blocks} blocks"
+    );
+}
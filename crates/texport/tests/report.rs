@@ -0,0 +1,169 @@
+mod common;
+
+use serde_json::Value;
+use texport::{Chat, ChatStats, JsonDetail, STATS_REPORT_VERSION, StatsSettings, TimeZone};
+
+use crate::common::fixture;
+
+/// The statistics of `charts.json` with `json_detail`.
+fn stats(json_detail: JsonDetail) -> ChatStats {
+    let chat = Chat::from_slice(&fixture("charts.json")).unwrap();
+    let mut stats = ChatStats::new(StatsSettings {
+        json_detail,
+        max_words: 3,
+        max_phrases: 2,
+        max_distinctive_words: 1,
+        time_zone: TimeZone::Utc,
+        ..Default::default()
+    });
+    stats.analyze_labeled(&chat.name, &chat.messages);
+    stats
+}
+
+/// The keys of `object`, sorted.
+fn keys(object: &Value) -> Vec<&str> {
+    let mut keys: Vec<_> = object
+        .as_object()
+        .unwrap()
+        .keys()
+        .map(String::as_str)
+        .collect();
+    keys.sort_unstable();
+    keys
+}
+
+/// `keys`, sorted.
+fn sorted<const N: usize>(mut keys: [&str; N]) -> [&str; N] {
+    keys.sort_unstable();
+    keys
+}
+
+#[test]
+fn report_fields_are_stable() {
+    let report = serde_json::to_value(stats(JsonDetail::Top).to_report()).unwrap();
+
+    // Renaming or removing any of these needs a new schema version.
+    assert_eq!(STATS_REPORT_VERSION, 1);
+    assert_eq!(report["schema_version"], 1);
+    assert_eq!(
+        keys(&report),
+        sorted([
+            "schema_version",
+            "messages",
+            "service_messages",
+            "edited_messages",
+            "excluded_messages",
+            "duplicate_messages",
+            "undated_messages",
+            "messages_in_runs",
+            "first_message",
+            "last_message",
+            "participants",
+            "chats",
+            "topics",
+            "service_actions",
+            "text_entity_types",
+            "forward_sources",
+            "longest_chain",
+            "weekday_hours",
+            "timeline",
+            "longest_streak",
+            "busiest_days",
+            "membership",
+            "deletions",
+            "pins",
+            "milestones",
+            "title_changes",
+            "tracked",
+            "mentions",
+            "trends",
+            "reactors",
+            "lurkers",
+            "analysis_settings",
+        ])
+    );
+    assert_eq!(
+        keys(&report["participants"][0]),
+        sorted([
+            "id",
+            "name",
+            "other_names",
+            "messages",
+            "characters",
+            "longest_message_characters",
+            "words",
+            "unique_words",
+            "longest_word",
+            "first_message",
+            "last_message",
+            "caps_messages",
+            "caps_eligible_messages",
+            "exclamations",
+            "ellipsis_messages",
+            "sentences",
+            "single_word_messages",
+            "runs",
+            "longest_run",
+            "longest_run_start",
+            "replies",
+            "forwards",
+            "conversations_started",
+            "pinned",
+            "median_gap_seconds",
+            "cadence",
+            "night_percent",
+            "hours",
+            "languages",
+            "top_words",
+            "top_reactions",
+            "top_entity_words",
+            "top_phrases",
+            "distinctive_words",
+        ])
+    );
+    assert_eq!(
+        keys(&report["chats"][0]),
+        sorted([
+            "label",
+            "messages",
+            "first_message",
+            "last_message",
+            "senders"
+        ])
+    );
+    assert_eq!(keys(&report["timeline"][0]), ["count", "month"]);
+    // Times are ISO 8601 in UTC.
+    assert_eq!(report["first_message"], "2024-01-01T09:07:00Z");
+}
+
+#[test]
+fn detail_decides_how_much_of_each_list_is_kept() {
+    let lengths = |json_detail| {
+        let report = stats(json_detail).to_report();
+        let alice = &report.participants[0];
+        assert_eq!(alice.name, "Alice");
+        (
+            alice.top_words.len(),
+            alice.top_phrases.len(),
+            alice.distinctive_words.len(),
+        )
+    };
+    assert_eq!(lengths(JsonDetail::Summary), (0, 0, 0));
+    assert_eq!(lengths(JsonDetail::Top), (3, 2, 1));
+    let (words, phrases, distinctive) = lengths(JsonDetail::Full);
+    assert!(words > 3 && phrases > 2 && distinctive >= 1);
+}
+
+#[test]
+fn settings_can_be_left_out() {
+    let stats = stats(JsonDetail::Summary);
+    let mut report = stats.to_report();
+    let settings = serde_json::to_value(&report).unwrap()["analysis_settings"].clone();
+    assert_eq!(settings["json_detail"], "summary");
+    assert_eq!(settings["max_words"], 3);
+
+    report.analysis_settings = None;
+    let report = serde_json::to_value(&report).unwrap();
+    assert!(report.get("analysis_settings").is_none());
+    assert!(keys(&report).contains(&"lurkers"));
+}
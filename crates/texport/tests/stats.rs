@@ -0,0 +1,449 @@
+mod common;
+
+use std::{
+    collections::HashMap,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use texport::{Chat, ChatRef, ChatStats, DELETED_ACCOUNT, StatsSettings, TimeZone, Tokenizer};
+
+use crate::common::fixture;
+
+/// The statistics of fixture `name` with `settings`.
+fn analyze(name: &str, settings: StatsSettings) -> ChatStats {
+    let chat = Chat::from_slice(&fixture(name)).unwrap();
+    let mut stats = ChatStats::new(settings);
+    stats.analyze(chat.messages);
+    stats
+}
+
+#[test]
+fn duplicate_ids_are_counted_once() {
+    let bytes = fixture("duplicate_ids.json");
+
+    let chat = Chat::from_slice(&bytes).unwrap();
+    let mut stats = ChatStats::new(StatsSettings::default());
+    stats.analyze_ref(&chat.messages);
+    assert_eq!((stats.messages, stats.duplicate_ids), (3, 1));
+    assert_eq!(stats.participants["user2"].count, 1);
+    // The first copy is kept, and it wasn't edited.
+    assert_eq!(stats.edited, 0);
+    assert_eq!(stats.id_gaps.present, 3);
+
+    let chat = ChatRef::from_slice(&bytes).unwrap();
+    let mut borrowed = ChatStats::new(StatsSettings::default());
+    borrowed.analyze_ref(&chat.messages);
+    assert_eq!((borrowed.messages, borrowed.duplicate_ids), (3, 1));
+}
+
+#[test]
+fn deleted_accounts_are_named_deleted_account() {
+    let bytes = fixture("deleted_accounts.json");
+
+    let chat = Chat::from_slice(&bytes).unwrap();
+    let mut stats = ChatStats::new(StatsSettings::default());
+    stats.analyze_ref(&chat.messages);
+    assert_eq!(stats.participants["user5"].name, DELETED_ACCOUNT);
+    assert_eq!(stats.membership.joins[DELETED_ACCOUNT], 1);
+    assert_eq!(stats.membership.inviters[DELETED_ACCOUNT], 1);
+    assert_eq!(stats.title_history[0].changed_by, "Alice");
+
+    let mut text = Vec::new();
+    chat.write_export(&mut text, None).unwrap();
+    let text = String::from_utf8(text).unwrap();
+    assert!(text.contains("@Deleted Account: hello from a deleted account"));
+
+    let chat = ChatRef::from_slice(&bytes).unwrap();
+    let mut borrowed = ChatStats::new(StatsSettings::default());
+    borrowed.analyze_ref(&chat.messages);
+    assert_eq!(borrowed.participants["user5"].name, DELETED_ACCOUNT);
+}
+
+#[test]
+fn edits_are_counted_in_old_and_new_exports() {
+    // 2019 exports only have the local time of an edit, newer ones its
+    // Unix timestamp too, and some only that.
+    for name in ["edits_2019.json", "edits_2024.json"] {
+        let stats = analyze(name, StatsSettings::default());
+        assert_eq!(stats.edited, 2, "{name}");
+    }
+}
+
+#[test]
+fn participants_are_keyed_by_sender_id() {
+    let stats = analyze("renames.json", StatsSettings::default());
+    let mut ids: Vec<_> = stats.participants.keys().map(String::as_str).collect();
+    ids.sort_unstable();
+    assert_eq!(ids, ["user1", "user2", "user3", "user4"]);
+
+    // Renaming doesn't split a participant, and sharing a name doesn't
+    // merge two of them.
+    let renamed = &stats.participants["user1"];
+    assert_eq!((renamed.name.as_str(), renamed.count), ("Alice Smith", 2));
+    assert!(renamed.names.iter().eq(["Alice", "Alice Smith"]));
+    assert_eq!(stats.participants["user2"].name, "Alice");
+
+    let settings = StatsSettings {
+        max_participants: 10,
+        ..Default::default()
+    };
+    let text = analyze("renames.json", settings).to_string();
+    let lines: Vec<_> = text.lines().collect();
+    let renamed = lines.iter().position(|l| l.starts_with("1. Alice Smith "));
+    assert_eq!(
+        renamed.map(|i| lines[i + 1]),
+        Some("   also known as Alice"),
+        "{text}"
+    );
+}
+
+#[test]
+fn aliases_merge_accounts() {
+    let settings = StatsSettings {
+        aliases: [("user3".to_string(), "user4".to_string())].into(),
+        ..Default::default()
+    };
+    let stats = analyze("renames.json", settings);
+    assert!(!stats.participants.contains_key("user3"));
+    assert_eq!(stats.participants["user4"].count, 2);
+}
+
+#[test]
+fn skipping_words_leaves_word_statistics_empty() {
+    let settings = || StatsSettings {
+        max_participants: 10,
+        max_words: 10,
+        ..StatsSettings::default()
+    };
+    let full = analyze("charts.json", settings());
+    let counts = analyze(
+        "charts.json",
+        StatsSettings {
+            skip_words: true,
+            ..settings()
+        },
+    );
+
+    assert_eq!(counts.messages, full.messages);
+    for (id, participant) in &counts.participants {
+        assert_eq!(participant.count, full.participants[id].count, "{id}");
+        assert_eq!(participant.total_words, 0, "{id}");
+        assert!(participant.words.is_empty(), "{id}");
+        assert!(participant.top_words(10).is_empty(), "{id}");
+    }
+    assert!(full.participants.values().any(|p| p.total_words > 0));
+
+    let text = counts.to_string();
+    assert!(!text.contains("Top words"), "{text}");
+    assert!(!text.contains("NaN"), "{text}");
+
+    let report = counts.to_report();
+    assert!(report.participants.iter().all(|p| p.top_words.is_empty()));
+    let json: serde_json::Value = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["participants"][0]["words"], 0);
+    assert_eq!(json["participants"][0]["top_words"], serde_json::json!([]));
+}
+
+#[test]
+fn tokenizers_count_words_before_and_after() {
+    let top_words = |tokenizer| {
+        let stats = analyze(
+            "punctuation.json",
+            StatsSettings {
+                tokenizer,
+                ..StatsSettings::default()
+            },
+        );
+        let alice = &stats.participants["user1"];
+        let mut words: Vec<_> = alice
+            .top_words(usize::MAX)
+            .into_iter()
+            .map(|(word, count)| (word.to_string(), *count))
+            .collect();
+        words.sort_unstable();
+        words
+    };
+    let counts = |words: &[(&str, usize)]| {
+        let mut words: Vec<_> = words.iter().map(|&(w, c)| (w.to_string(), c)).collect();
+        words.sort_unstable();
+        words
+    };
+
+    assert_eq!(
+        top_words(Tokenizer::Unicode),
+        counts(&[
+            ("hello", 3),
+            ("world", 3),
+            ("привет", 3),
+            ("мир", 2),
+            ("как", 1),
+            ("дела", 1),
+        ])
+    );
+    // Punctuation used to stick to words, splitting their counts, and
+    // even counted as a word on its own.
+    assert_eq!(
+        top_words(Tokenizer::Whitespace),
+        counts(&[
+            ("hello", 1),
+            ("hello,", 1),
+            ("hello...", 1),
+            ("world.", 1),
+            ("(world)", 1),
+            ("world?!", 1),
+            ("привет,", 1),
+            ("«привет»", 1),
+            ("привет", 1),
+            ("мир!", 1),
+            ("мир,", 1),
+            ("как", 1),
+            ("дела?", 1),
+            ("—", 1),
+        ])
+    );
+}
+
+/// `counts` ordered by name, to compare with a literal.
+fn by_name(counts: &HashMap<String, u64>) -> Vec<(&str, u64)> {
+    let mut counts: Vec<_> = counts.iter().map(|(n, &c)| (n.as_str(), c)).collect();
+    counts.sort_unstable();
+    counts
+}
+
+#[test]
+fn members_joining_and_leaving_are_counted_with_their_inviters() {
+    let settings = StatsSettings {
+        time_zone: TimeZone::Utc,
+        ..Default::default()
+    };
+    let stats = analyze("service.json", settings);
+    let membership = &stats.membership;
+
+    // Alice creating the group with Bob isn't a join, inviting Carol is, and
+    // so is Dave following Carol's link. Bob leaving counts as he removed
+    // himself.
+    assert_eq!(by_name(&membership.joins), [("Carol", 1), ("Dave", 1)]);
+    assert_eq!(by_name(&membership.leaves), [("Bob", 1)]);
+    assert_eq!(by_name(&membership.inviters), [("Alice", 1), ("Carol", 1)]);
+    assert_eq!((membership.joined, membership.left), (2, 1));
+    assert_eq!(membership.net_change(), 1);
+    // Of inviters with as many members, the first by name.
+    assert_eq!(membership.top_inviter(), Some((&"Alice".to_string(), 1)));
+    let monthly: Vec<_> = membership
+        .monthly
+        .iter()
+        .map(|(month, change)| (month.as_str(), change.joined, change.left))
+        .collect();
+    assert_eq!(monthly, [("2024-03", 2, 1)]);
+
+    let text = stats.to_string();
+    let section: Vec<_> = text
+        .lines()
+        .skip_while(|line| !line.starts_with("👋"))
+        .take(3)
+        .collect();
+    assert_eq!(
+        section,
+        [
+            "👋 Members: 2 joined, 1 left (net +1)",
+            "- Top inviter: Alice (1)",
+            "- 2024-03: +2 / -1",
+        ]
+    );
+}
+
+#[test]
+fn missing_ids_are_shown_as_an_estimate() {
+    let settings = StatsSettings {
+        time_zone: TimeZone::Utc,
+        ..Default::default()
+    };
+    let stats = analyze("service.json", settings);
+    // Service messages have ids too, so only 13 to 15 are missing.
+    assert_eq!(stats.id_gaps.present, 16);
+    assert_eq!(stats.id_gaps.estimated_deletions, 3);
+
+    let text = stats.to_string();
+    let section: Vec<_> = text
+        .lines()
+        .skip_while(|line| !line.starts_with("🕳️"))
+        .take(4)
+        .collect();
+    assert_eq!(
+        section,
+        [
+            "🕳️ Missing message ids (an estimate of deleted messages):",
+            "- 3 of 19 ids missing (16%)",
+            // Analyzed without a label, so no chat is named.
+            "- 3 missing between #12 and #16 (2024-03-01 – 2024-03-01)",
+            "- By month: 2024-03 3",
+        ]
+    );
+}
+
+#[test]
+fn numeric_timestamps_are_read_and_unreadable_ones_counted() {
+    let message = |id: u64, date_unixtime: &str| {
+        format!(
+            r#"{{"id": {id}, "type": "message", "date": "2024-01-01T00:0{id}:00",
+                "date_unixtime": {date_unixtime}, "from": "Alice", "from_id": "user1",
+                "text": "hi", "text_entities": []}}"#
+        )
+    };
+    let edited = r#"{"id": 2, "type": "message", "date": "2024-01-01T00:02:00",
+        "date_unixtime": 1704067320, "edited": "2024-01-01T00:05:00",
+        "edited_unixtime": 1704067500, "from": "Alice", "from_id": "user1",
+        "text": "hi!", "text_entities": []}"#;
+    let messages = [
+        message(1, r#""1704067260""#),
+        edited.to_string(),
+        message(3, r#""soon""#),
+        message(4, "-5"),
+    ];
+    let export = format!(
+        r#"{{"name": "Numbers", "type": "personal_chat", "id": 9, "messages": [{}]}}"#,
+        messages.join(",")
+    );
+
+    let owned = Chat::from_slice(export.as_bytes()).unwrap();
+    let borrowed = ChatRef::from_slice(export.as_bytes()).unwrap();
+    let mut stats = ChatStats::new(StatsSettings::default());
+    stats.analyze_labeled("numbers", &owned.messages);
+    let mut from_borrowed = ChatStats::new(StatsSettings::default());
+    from_borrowed.analyze_labeled("numbers", &borrowed.messages);
+    let sent = |unixtime| UNIX_EPOCH + Duration::from_secs(unixtime);
+    assert_eq!(owned.messages[1].date(), Some(sent(1_704_067_320)));
+    assert_eq!(owned.messages[1].edited(), Some(sent(1_704_067_500)));
+    for stats in [&stats, &from_borrowed] {
+        // Undated messages still count, just not over time.
+        assert_eq!((stats.messages, stats.undated, stats.edited), (4, 2, 1));
+        let chat = &stats.chats["numbers"];
+        assert_eq!(chat.first_message, Some(sent(1_704_067_260)));
+        assert_eq!(chat.last_message, Some(sent(1_704_067_320)));
+    }
+    let text = stats.to_string();
+    let row = text.lines().find(|line| line.starts_with("⏳"));
+    assert!(row.is_some_and(|row| row.ends_with(": 2")), "{text}");
+
+    stats.merge(from_borrowed);
+    assert_eq!(stats.undated, 4);
+    let dated = analyze("ties.json", StatsSettings::default());
+    assert!(!dated.to_string().contains('⏳'));
+}
+
+/// An export of `count` messages by three senders, some replying to the one
+/// before and some with reactions, spread over several months.
+#[cfg(feature = "rayon")]
+fn large_chat(count: u64) -> Chat {
+    use serde_json::json;
+
+    let senders = [("Alice", "user1"), ("Bob", "user2"), ("Carol", "user3")];
+    let words = [
+        "tea", "coffee", "rain", "train", "late", "again", "why", "ok",
+    ];
+    let messages: Vec<_> = (1..=count)
+        .map(|id| {
+            let (from, from_id) = senders[(id * id % 7 % 3) as usize];
+            let text = (0..id % 5 + 1)
+                .map(|i| words[((id + i * 3) % 8) as usize])
+                .collect::<Vec<_>>()
+                .join(" ");
+            // Ten minutes apart, with an hour-long gap every few messages.
+            let unixtime = 1_704_067_200 + id * 600 + id / 7 * 3_600;
+            let date = chrono::DateTime::from_timestamp(unixtime as i64, 0).unwrap();
+            let mut message = json!({
+                "id": id,
+                "type": "message",
+                "date": date.naive_utc().format("%Y-%m-%dT%H:%M:%S").to_string(),
+                "date_unixtime": unixtime.to_string(),
+                "from": from,
+                "from_id": from_id,
+                "text": text,
+                "text_entities": [{"type": "plain", "text": text}],
+            });
+            if id % 4 == 0 {
+                message["reply_to_message_id"] = json!(id - 1);
+            }
+            if id % 9 == 0 {
+                message["reactions"] = json!([{
+                    "type": "emoji",
+                    "count": 2,
+                    "emoji": if id % 2 == 0 { "👍" } else { "🔥" },
+                    "recent": [{"from": "Dave", "from_id": "user4", "date": "2024-01-01T00:00:00"}],
+                }]);
+            }
+            message
+        })
+        .collect();
+    let export = json!({"name": "Big", "type": "private_group", "id": 1, "messages": messages});
+    Chat::from_slice(&serde_json::to_vec(&export).unwrap()).unwrap()
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn chunked_analysis_matches_in_order_analysis_where_order_does_not_matter() {
+    let chat = large_chat(35_000);
+    let settings = StatsSettings {
+        time_zone: TimeZone::Utc,
+        milestones: vec![1, 20_000, 30_001],
+        ..Default::default()
+    };
+    let mut sequential = ChatStats::new(settings.clone());
+    sequential.analyze_labeled(&chat.name, &chat.messages);
+    let mut chunked = ChatStats::new(settings);
+    // Four threads make chunks of 10,000 messages whatever the machine.
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(4)
+        .build()
+        .unwrap();
+    pool.install(|| chunked.analyze_parallel(&chat.name, &chat.messages));
+
+    assert_eq!(chunked.messages, 35_000);
+    assert_eq!(chunked.messages, sequential.messages);
+    assert_eq!(chunked.weekday_hours, sequential.weekday_hours);
+    assert_eq!(chunked.timeline, sequential.timeline);
+    assert_eq!(chunked.reactors.len(), 1);
+    assert_eq!(
+        chunked.reactors["user4"].reactions,
+        sequential.reactors["user4"].reactions
+    );
+    let milestones = |stats: &ChatStats| {
+        let milestones = stats.milestones.iter();
+        milestones
+            .map(|m| (m.number, m.message_id))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        milestones(&chunked),
+        [(1, 1), (20_000, 20_000), (30_001, 30_001)]
+    );
+    assert_eq!(milestones(&chunked), milestones(&sequential));
+    let counts = |stats: &ChatStats, id: &str| {
+        let user = &stats.participants[id];
+        let mut words: Vec<_> = user
+            .words
+            .iter()
+            .map(|(w, &n)| (w.to_string(), n))
+            .collect();
+        words.sort();
+        let mut reactions: Vec<_> = user.received_reactions.iter().collect();
+        reactions.sort();
+        let totals = (user.count, user.total_chars, user.total_words, user.replies);
+        (totals, user.hours, words, format!("{reactions:?}"))
+    };
+    assert_eq!(chunked.participants.len(), 3);
+    for id in sequential.participants.keys() {
+        assert_eq!(counts(&chunked, id), counts(&sequential, id), "{id}");
+    }
+
+    // It was chunked: each sender's first message in the three later chunks
+    // follows none of theirs, so three gaps between messages are missed.
+    let gaps = |stats: &ChatStats, id: &str| {
+        let user = &stats.participants[id];
+        user.gaps.values().sum::<u64>() + user.long_gaps
+    };
+    for id in sequential.participants.keys() {
+        assert_eq!(gaps(&sequential, id), sequential.participants[id].count - 1);
+        assert_eq!(gaps(&chunked, id), gaps(&sequential, id) - 3, "{id}");
+    }
+}
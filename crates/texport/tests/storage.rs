@@ -0,0 +1,418 @@
+use std::{
+    env,
+    fs::{self, File},
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, SystemTime},
+};
+
+use texport::{
+    Chat, ChatFile, ChatType, DuplicatePolicy, LoadError, OpenError, ScanOptions, Storage,
+};
+
+/// `tests/fixtures/exports`: an export root holding two exports of chat
+/// 100, `family_2023` and `family_2024`, and one of each other chat.
+fn exports() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/exports")
+}
+
+/// An empty directory for test `name` to write to.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("texport-{name}-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Copy the chat export in `exports()/{chat}` into `dir`.
+fn copy_chat(dir: &Path, chat: &str) -> PathBuf {
+    let path = dir.join(chat).join("result.json");
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::copy(exports().join(chat).join("result.json"), &path).unwrap();
+    path
+}
+
+fn scan(path: &Path, duplicates: DuplicatePolicy) -> Result<Storage, OpenError> {
+    let options = ScanOptions {
+        duplicates,
+        ..ScanOptions::default()
+    };
+    Storage::from_path_with(path, options)
+}
+
+#[test]
+fn storage_can_be_shared_between_threads() {
+    fn assert_sync<T: Send + Sync>() {}
+    assert_sync::<Storage>();
+    assert_sync::<ChatFile>();
+}
+
+#[test]
+fn duplicates_are_all_kept_by_default() {
+    let storage = scan(&exports(), DuplicatePolicy::KeepAll).unwrap();
+    let paths = [
+        exports().join("family_2023/result.json"),
+        exports().join("family_2024/result.json"),
+    ];
+    assert_eq!(storage.chats[&100].paths, paths);
+    assert_eq!(storage.duplicates.len(), 1);
+    assert_eq!(storage.duplicates[&100], paths);
+}
+
+#[test]
+fn duplicates_keep_the_newest() {
+    let dir = temp_dir("keep-newest");
+    let old = copy_chat(&dir, "family_2023");
+    let new = copy_chat(&dir, "family_2024");
+    let touch = |path: &Path, age: u64| {
+        let modified = SystemTime::now() - Duration::from_secs(age);
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    };
+
+    touch(&old, 3600);
+    touch(&new, 60);
+    let storage = scan(&dir, DuplicatePolicy::KeepNewest).unwrap();
+    assert_eq!(storage.chats[&100].paths, [new.as_path()]);
+    // Both are still reported.
+    assert_eq!(storage.duplicates[&100], [old.clone(), new.clone()]);
+
+    touch(&old, 0);
+    let storage = scan(&dir, DuplicatePolicy::KeepNewest).unwrap();
+    assert_eq!(storage.chats[&100].paths, [old]);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn duplicates_can_be_an_error() {
+    match scan(&exports(), DuplicatePolicy::Error) {
+        Err(OpenError::DuplicateChat { id, paths }) => {
+            assert_eq!(id, 100);
+            assert_eq!(paths.len(), 2);
+        }
+        other => panic!(
+            "expected a duplicate chat, got {:?}",
+            other.map(|s| s.chats)
+        ),
+    }
+}
+
+#[test]
+fn chats_are_filtered_by_type_and_name() {
+    let storage = Storage::from_path(exports()).unwrap();
+    let ids = |chats: Vec<&ChatFile>| chats.iter().map(|chat| chat.info.id).collect::<Vec<_>>();
+
+    assert_eq!(
+        ids(storage.by_type(ChatType::PrivateGroup).collect()),
+        [100]
+    );
+    assert_eq!(
+        ids(storage.by_type(ChatType::PublicChannel).collect()),
+        [300]
+    );
+    assert!(storage.by_type(ChatType::Bot).next().is_none());
+
+    assert_eq!(
+        ids(storage.by_name_contains("family").collect()),
+        [100, 400]
+    );
+    assert_eq!(ids(storage.by_name_contains("ALICE").collect()), [200]);
+    assert!(storage.by_name_contains("nobody").next().is_none());
+
+    let groups = storage.filter(|info| {
+        matches!(
+            info.chat_type,
+            ChatType::PrivateGroup | ChatType::PrivateSupergroup
+        )
+    });
+    assert_eq!(ids(groups.collect()), [100, 400]);
+}
+
+#[test]
+fn overlapping_exports_are_deduped() {
+    let storage = Storage::from_path(exports()).unwrap();
+    let parts: usize = storage.chats[&100]
+        .paths
+        .iter()
+        .map(|path| {
+            Chat::from_slice(&fs::read(path).unwrap())
+                .unwrap()
+                .messages
+                .len()
+        })
+        .sum();
+    assert_eq!(parts, 8);
+
+    let chats: Vec<_> = storage.load_all_deduped().map(Result::unwrap).collect();
+    let ids: Vec<_> = chats.iter().map(|(id, _)| *id).collect();
+    assert_eq!(ids, [100, 200, 300, 400]);
+    let family = &chats[0].1;
+    let messages: Vec<_> = family.messages.iter().map(|message| message.id()).collect();
+    assert_eq!(messages, [1, 2, 3, 4, 5, 6]);
+}
+
+/// Write an export of chat 700 named `name` with `messages` to
+/// `dir/{folder}/result.json`.
+fn write_part(dir: &Path, folder: &str, name: &str, messages: serde_json::Value) {
+    let export = serde_json::json!({
+        "name": name,
+        "type": "private_group",
+        "id": 700,
+        "messages": messages,
+    });
+    fs::create_dir_all(dir.join(folder)).unwrap();
+    fs::write(
+        dir.join(folder).join("result.json"),
+        serde_json::to_vec(&export).unwrap(),
+    )
+    .unwrap();
+}
+
+/// Message `id`, sent `id` minutes into 2024, edited `edited` minutes in.
+fn part_message(id: u64, edited: Option<u64>) -> serde_json::Value {
+    let minutes = |m: u64| (1_704_067_200 + 60 * m).to_string();
+    let mut message = serde_json::json!({
+        "id": id,
+        "type": "message",
+        "date": "2024-01-01T00:00:00",
+        "date_unixtime": minutes(id),
+        "from": "Alice",
+        "from_id": "user1",
+        "text": "hi",
+        "text_entities": [],
+    });
+    if let Some(edited) = edited {
+        message["edited"] = "2024-01-01T00:00:00".into();
+        message["edited_unixtime"] = minutes(edited).into();
+    }
+    message
+}
+
+#[test]
+fn parts_are_merged_keeping_the_latest_copy_of_each_message() {
+    let dir = temp_dir("merge-parts");
+    // Listed first, but exported last as it reaches message 4.
+    write_part(
+        &dir,
+        "a_new",
+        "New name",
+        serde_json::json!([
+            part_message(2, Some(20)),
+            part_message(3, Some(30)),
+            part_message(4, None),
+            // Skipped even though it was edited, as a repeat within a part.
+            part_message(4, Some(40)),
+        ]),
+    );
+    write_part(
+        &dir,
+        "b_old",
+        "Old name",
+        serde_json::json!([
+            part_message(1, None),
+            part_message(2, None),
+            // Edited again after the newer part was exported.
+            part_message(3, Some(50)),
+        ]),
+    );
+    let storage = Storage::from_path(&dir).unwrap();
+    assert_eq!(storage.chats[&700].paths.len(), 2);
+
+    let chat = storage.load_merged(700).unwrap();
+    assert_eq!(chat.name, "New name");
+    let minutes = |m: u64| SystemTime::UNIX_EPOCH + Duration::from_secs(1_704_067_200 + 60 * m);
+    let messages: Vec<_> = chat
+        .messages
+        .iter()
+        .map(|message| (message.id(), message.edited()))
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            (1, None),
+            (2, Some(minutes(20))),
+            (3, Some(minutes(50))),
+            (4, None),
+        ]
+    );
+    // Loading the chat merges its parts too.
+    assert_eq!(storage.load_chat(700).unwrap().messages.len(), 4);
+    assert!(matches!(
+        storage.load_merged(701),
+        Err(LoadError::UnknownChat(701))
+    ));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn strict_loading_fails_with_every_chat_left_out() {
+    let dir = temp_dir("strict");
+    copy_chat(&dir, "alice");
+    let broken = dir.join("broken/result.json");
+    fs::create_dir_all(broken.parent().unwrap()).unwrap();
+    fs::write(
+        &broken,
+        "{\n \"name\": \"Broken\",\n \"type\": \"private_group\",\n \"id\": oops\n}",
+    )
+    .unwrap();
+    fs::create_dir_all(dir.join("html")).unwrap();
+    fs::write(dir.join("html/messages.html"), "<html></html>").unwrap();
+
+    // With `rayon`, HTML exports are found before any manifest is parsed.
+    let check = |errors: &[LoadError]| {
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        let json = errors.iter().find_map(|e| match e {
+            LoadError::Json { path, source } => Some((path, source)),
+            _ => None,
+        });
+        let (path, source) = json.expect("invalid JSON");
+        assert_eq!(path, &broken);
+        assert_eq!((source.line(), source.column()), (4, 8));
+        assert!(
+            errors
+                .iter()
+                .any(|e| matches!(e, LoadError::Html { path } if path == &dir.join("html"))),
+            "{errors:?}"
+        );
+    };
+    // Left out, but listed.
+    let storage = Storage::from_path(&dir).unwrap();
+    assert_eq!(storage.chats.keys().copied().collect::<Vec<_>>(), [200]);
+    check(&storage.errors);
+    match Storage::from_path_strict(&dir, ScanOptions::default()) {
+        Err(OpenError::Unreadable(errors)) => check(&errors),
+        other => panic!(
+            "expected unreadable chats, got {:?}",
+            other.map(|s| s.chats)
+        ),
+    }
+
+    fs::remove_dir_all(dir.join("broken")).unwrap();
+    fs::remove_dir_all(dir.join("html")).unwrap();
+    let storage = Storage::from_path_strict(&dir, ScanOptions::default()).unwrap();
+    assert_eq!(storage.chats.keys().copied().collect::<Vec<_>>(), [200]);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_are_followed_once_when_asked() {
+    use std::os::unix::fs::symlink;
+
+    let dir = temp_dir("symlinks");
+    let alice = copy_chat(&dir, "alice");
+    symlink(dir.join("alice"), dir.join("alice_again")).unwrap();
+    symlink(exports().join("news"), dir.join("news")).unwrap();
+    fs::create_dir(dir.join("archive")).unwrap();
+    symlink(&dir, dir.join("archive/up")).unwrap();
+    let scan = |follow_symlinks| {
+        let options = ScanOptions {
+            follow_symlinks,
+            ..ScanOptions::default()
+        };
+        Storage::from_path_with(&dir, options).unwrap()
+    };
+
+    let storage = scan(false);
+    assert_eq!(storage.chats.keys().copied().collect::<Vec<_>>(), [200]);
+    assert_eq!(storage.chats[&200].paths, [alice.as_path()]);
+
+    let storage = scan(true);
+    assert_eq!(
+        storage.chats.keys().copied().collect::<Vec<_>>(),
+        [200, 300]
+    );
+    assert_eq!(storage.chats[&200].paths, [alice.as_path()]);
+    assert!(storage.duplicates.is_empty());
+    assert!(storage.errors.is_empty());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "zip")]
+#[test]
+fn zipped_exports_are_found_as_on_disk() {
+    use std::io::Write;
+
+    use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+    let dir = temp_dir("zip");
+    let archive = dir.join("exports.zip");
+    let fixture = |chat: &str| fs::read(exports().join(chat).join("result.json")).unwrap();
+    let entries = [
+        ("exports/family_2023/result.json", fixture("family_2023")),
+        ("exports/family_2024/result.json", fixture("family_2024")),
+        ("exports/alice/result.json", fixture("alice")),
+        // The media and subdirectories of an export aren't searched...
+        ("exports/alice/photos/photo_1.jpg", b"not a photo".to_vec()),
+        ("exports/alice/old/result.json", b"not an export".to_vec()),
+        // ...nor are hidden directories.
+        (".trash/result.json", b"not an export".to_vec()),
+        ("html/messages.html", b"<html></html>".to_vec()),
+        // Damaged below.
+        ("damaged/result.json", fixture("news")),
+    ];
+    let mut zip = ZipWriter::new(File::create(&archive).unwrap());
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    for (name, bytes) in &entries {
+        zip.start_file(*name, stored).unwrap();
+        zip.write_all(bytes).unwrap();
+    }
+    zip.finish().unwrap();
+    // Still valid JSON, but no longer what was checksummed.
+    let mut bytes = fs::read(&archive).unwrap();
+    let at = bytes.windows(8).position(|w| w == b"headline").unwrap();
+    bytes[at] = b'H';
+    fs::write(&archive, bytes).unwrap();
+
+    let storage = Storage::from_zip(&archive).unwrap();
+    assert_eq!(
+        storage.chats.keys().copied().collect::<Vec<_>>(),
+        [100, 200]
+    );
+    assert_eq!(
+        storage.chats[&200].paths,
+        [archive.join("exports/alice/result.json")]
+    );
+    let family = storage.load_merged(100).unwrap();
+    let ids: Vec<_> = family.messages.iter().map(|message| message.id()).collect();
+    assert_eq!(ids, [1, 2, 3, 4, 5, 6]);
+    assert_eq!(storage.chats[&100].summary().unwrap().messages, 8);
+
+    let skipped: Vec<_> = storage
+        .errors
+        .iter()
+        .map(|e| match e {
+            LoadError::Io { path, .. } | LoadError::Html { path } => path.clone(),
+            other => panic!("unexpected {other}"),
+        })
+        .collect();
+    assert_eq!(
+        skipped,
+        [archive.join("damaged/result.json"), archive.join("html")]
+    );
+
+    let mut zip = zip::ZipArchive::new(File::open(&archive).unwrap()).unwrap();
+    let alice = Chat::from_zip_entry(&mut zip, "exports/alice/result.json").unwrap();
+    assert_eq!(
+        alice.messages.len(),
+        storage.load_chat(200).unwrap().messages.len()
+    );
+    assert!(matches!(
+        Chat::from_zip_entry(&mut zip, "missing/result.json"),
+        Err(LoadError::Zip { .. })
+    ));
+    assert!(matches!(
+        Storage::from_zip(dir.join("missing.zip")),
+        Err(OpenError::Archive { .. })
+    ));
+
+    fs::remove_dir_all(&dir).unwrap();
+}